@@ -0,0 +1,540 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use checksum::Checksums;
+use content_length::ContentLength;
+use engine::Version;
+use histogram::Histogram;
+use random::Rng;
+use reservoir::Reservoir;
+use server_timing::ServerTiming;
+use stats::{Connections, Fact, Families, LabelStats, PoolWaits, Redirects, TargetStats, Throttling};
+
+/// How many of each of the transfer rates and throughputs are sampled to estimate their
+/// distributions.
+const TRANSFER_RATE_SAMPLE: usize = 10_000;
+
+/// How many of the sizes of the responses are sampled to estimate their distribution.
+const RESPONSE_SIZE_SAMPLE: usize = 10_000;
+
+/// Folds facts into running statistics as they arrive so that a run doesn't need to keep
+/// every fact in memory. The raw facts are only kept when the accumulator is recording.
+///
+/// Accumulators are mergeable, which lets each source of facts summarize locally and only
+/// hand over its aggregate rather than every fact.
+#[derive(Serialize, Deserialize)]
+pub struct Accumulator {
+    count: u64,
+    errors: u64,
+    failures: u64,
+    body_mismatches: u64,
+    echo_mismatches: u64,
+    length_mismatches: u64,
+    mean_ms: f64,
+    squared_distance_ms: f64,
+    min: Option<Duration>,
+    max: Option<Duration>,
+    connections: Connections,
+    families: Families,
+    /// The http versions that responses came back over, in order.
+    versions: Vec<Version>,
+    redirects: Redirects,
+    pool_waits: PoolWaits,
+    throttling: Throttling,
+    server_timing: ServerTiming,
+    content_length: ContentLength,
+    /// The bytes of the bodies of each status class, keyed by its first digit.
+    class_bytes: BTreeMap<u16, u64>,
+    status_counts: HashMap<u16, u32>,
+    labels: BTreeMap<String, LabelStats>,
+    targets: BTreeMap<usize, TargetStats>,
+    checksums: Checksums,
+    histogram: Histogram,
+    sample: Option<Reservoir<Duration>>,
+    transfer_rates: Reservoir<f64>,
+    throughputs: Reservoir<f64>,
+    response_sizes: Reservoir<u64>,
+    facts: Option<Vec<Fact>>,
+}
+
+impl Default for Accumulator {
+    fn default() -> Accumulator {
+        Accumulator::new()
+    }
+}
+
+impl Accumulator {
+    /// Creates an accumulator that only keeps the running statistics.
+    pub fn new() -> Accumulator {
+        Accumulator {
+            count: 0,
+            errors: 0,
+            failures: 0,
+            body_mismatches: 0,
+            echo_mismatches: 0,
+            length_mismatches: 0,
+            mean_ms: 0.,
+            squared_distance_ms: 0.,
+            min: None,
+            max: None,
+            connections: Connections::default(),
+            families: Families::default(),
+            versions: Vec::new(),
+            redirects: Redirects::default(),
+            pool_waits: PoolWaits::default(),
+            throttling: Throttling::default(),
+            server_timing: ServerTiming::default(),
+            content_length: ContentLength::zero(),
+            class_bytes: BTreeMap::new(),
+            status_counts: HashMap::new(),
+            labels: BTreeMap::new(),
+            targets: BTreeMap::new(),
+            checksums: Checksums::default(),
+            histogram: Histogram::new(),
+            sample: None,
+            transfer_rates: Reservoir::new(TRANSFER_RATE_SAMPLE, Rng::from_time()),
+            throughputs: Reservoir::new(TRANSFER_RATE_SAMPLE, Rng::from_time()),
+            response_sizes: Reservoir::new(RESPONSE_SIZE_SAMPLE, Rng::from_time()),
+            facts: None,
+        }
+    }
+
+    /// Keeps a random sample of at most `size` durations so that the distribution can be
+    /// charted without recording every fact.
+    pub fn with_sample(mut self, size: usize) -> Accumulator {
+        self.sample = Some(Reservoir::new(size, Rng::from_time()));
+        self
+    }
+
+    /// Creates an accumulator that also keeps every fact, pre-allocating room for the
+    /// expected number of facts.
+    pub fn recording(capacity: usize) -> Accumulator {
+        Accumulator {
+            facts: Some(Vec::with_capacity(capacity)),
+            ..Accumulator::new()
+        }
+    }
+
+    /// Folds a single fact into the statistics.
+    pub fn add(&mut self, fact: Fact) {
+        let duration = fact.duration();
+        self.count += 1;
+        if fact.is_error() {
+            self.errors += 1;
+        }
+        if !fact.is_success() {
+            self.failures += 1;
+        }
+        if fact.body_mismatch() {
+            self.body_mismatches += 1;
+        }
+        if fact.echo_mismatch() {
+            self.echo_mismatches += 1;
+        }
+        if fact.length_mismatch() {
+            self.length_mismatches += 1;
+        }
+
+        // Welford's online algorithm for the mean and variance.
+        let ms = duration.as_secs() as f64 * 1_000. + f64::from(duration.subsec_nanos()) / 1_000_000.;
+        let delta = ms - self.mean_ms;
+        self.mean_ms += delta / self.count as f64;
+        self.squared_distance_ms += delta * (ms - self.mean_ms);
+
+        self.min = Some(self.min.map_or(duration, |min| min.min(duration)));
+        self.max = Some(self.max.map_or(duration, |max| max.max(duration)));
+        self.connections.add(&fact);
+        self.families.add(&fact);
+        if let Some(version) = fact.version().filter(|version| !self.versions.contains(version)) {
+            self.versions.push(version);
+            self.versions.sort();
+        }
+        self.redirects.add(&fact);
+        self.pool_waits.add(&fact);
+        self.throttling.add(&fact);
+        self.server_timing.add(&fact);
+        self.content_length = &self.content_length + fact.content_length();
+        if let Some(status) = fact.status() {
+            *self.status_counts.entry(status).or_insert(0) += 1;
+            *self.class_bytes.entry(status / 100).or_insert(0) += fact.content_length().bytes();
+        }
+        if !fact.is_error() {
+            self.response_sizes.offer(fact.content_length().bytes());
+        }
+        for (name, value) in fact.labels() {
+            self.labels
+                .entry(format!("{}={}", name, value))
+                .or_default()
+                .add(duration);
+        }
+        if let Some(url) = fact.url() {
+            self.targets.entry(url).or_default().add(&fact);
+        }
+        if let Some(checksum) = fact.checksum() {
+            self.checksums.add(checksum);
+        }
+        self.histogram.record(duration);
+        if let Some(ref mut sample) = self.sample {
+            sample.offer(duration);
+        }
+        if let Some(rate) = fact.transfer_rate() {
+            self.transfer_rates.offer(rate);
+        }
+        if let Some(throughput) = fact.throughput() {
+            self.throughputs.offer(throughput);
+        }
+
+        if let Some(ref mut facts) = self.facts {
+            facts.push(fact);
+        }
+    }
+
+    /// Combines another accumulator into this one as if every fact it saw had been added
+    /// here. The raw facts are only kept if both accumulators were recording.
+    pub fn merge(&mut self, other: Accumulator) {
+        let count = self.count + other.count;
+        if count > 0 {
+            // Chan et al.'s parallel variant of Welford's algorithm.
+            let delta = other.mean_ms - self.mean_ms;
+            let (a, b) = (self.count as f64, other.count as f64);
+            self.mean_ms += delta * b / count as f64;
+            self.squared_distance_ms +=
+                other.squared_distance_ms + delta * delta * a * b / count as f64;
+        }
+        self.count = count;
+        self.errors += other.errors;
+        self.failures += other.failures;
+        self.body_mismatches += other.body_mismatches;
+        self.echo_mismatches += other.echo_mismatches;
+        self.length_mismatches += other.length_mismatches;
+        self.min = match (self.min, other.min) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.max = match (self.max, other.max) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        self.connections.opened += other.connections.opened;
+        self.connections.tracked += other.connections.tracked;
+        self.families.merge(&other.families);
+        for version in other.versions {
+            if !self.versions.contains(&version) {
+                self.versions.push(version);
+            }
+        }
+        self.versions.sort();
+        self.redirects.merge(&other.redirects);
+        self.pool_waits.merge(&other.pool_waits);
+        self.throttling.merge(&other.throttling);
+        self.server_timing.merge(&other.server_timing);
+        self.content_length = &self.content_length + other.content_length;
+        for (class, bytes) in other.class_bytes {
+            *self.class_bytes.entry(class).or_insert(0) += bytes;
+        }
+        for (status, count) in other.status_counts {
+            *self.status_counts.entry(status).or_insert(0) += count;
+        }
+        for (label, stats) in other.labels {
+            self.labels
+                .entry(label)
+                .or_default()
+                .merge(&stats);
+        }
+        for (url, stats) in other.targets {
+            self.targets.entry(url).or_default().merge(&stats);
+        }
+        self.checksums.merge(&other.checksums);
+        self.histogram.merge(&other.histogram);
+        self.sample = match (self.sample.take(), other.sample) {
+            (Some(mut sample), Some(other)) => {
+                sample.merge(other);
+                Some(sample)
+            }
+            _ => None,
+        };
+        self.transfer_rates.merge(other.transfer_rates);
+        self.throughputs.merge(other.throughputs);
+        self.response_sizes.merge(other.response_sizes);
+        self.facts = match (self.facts.take(), other.facts) {
+            (Some(mut facts), Some(other)) => {
+                facts.extend(other);
+                Some(facts)
+            }
+            _ => None,
+        };
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    pub fn failures(&self) -> u64 {
+        self.failures
+    }
+
+    /// The number of responses whose body failed the expected checks.
+    pub fn body_mismatches(&self) -> u64 {
+        self.body_mismatches
+    }
+
+    /// The number of responses that didn't echo the token of their request.
+    pub fn echo_mismatches(&self) -> u64 {
+        self.echo_mismatches
+    }
+
+    /// The number of responses whose body wasn't the length their content-length declared.
+    pub fn length_mismatches(&self) -> u64 {
+        self.length_mismatches
+    }
+
+    pub fn average(&self) -> Duration {
+        from_ms(self.mean_ms)
+    }
+
+    /// The sample standard deviation of the durations.
+    pub fn stddev(&self) -> Duration {
+        if self.count < 2 {
+            return Duration::new(0, 0);
+        }
+        from_ms((self.squared_distance_ms / (self.count - 1) as f64).sqrt())
+    }
+
+    pub fn min(&self) -> Option<Duration> {
+        self.min
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.max
+    }
+
+    pub fn connections(&self) -> &Connections {
+        &self.connections
+    }
+
+    pub fn families(&self) -> &Families {
+        &self.families
+    }
+
+    /// The http versions that responses came back over.
+    pub fn versions(&self) -> &[Version] {
+        &self.versions
+    }
+
+    pub fn checksums(&self) -> &Checksums {
+        &self.checksums
+    }
+
+    pub fn redirects(&self) -> &Redirects {
+        &self.redirects
+    }
+
+    pub fn pool_waits(&self) -> &PoolWaits {
+        &self.pool_waits
+    }
+
+    pub fn throttling(&self) -> &Throttling {
+        &self.throttling
+    }
+
+    pub fn server_timing(&self) -> &ServerTiming {
+        &self.server_timing
+    }
+
+    pub fn content_length(&self) -> &ContentLength {
+        &self.content_length
+    }
+
+    /// The bytes of the bodies of each status class, keyed by its first digit.
+    pub fn class_bytes(&self) -> &BTreeMap<u16, u64> {
+        &self.class_bytes
+    }
+
+    pub fn status_counts(&self) -> &HashMap<u16, u32> {
+        &self.status_counts
+    }
+
+    /// The stats of the facts that carried each label, keyed by `name=value`.
+    pub fn labels(&self) -> &BTreeMap<String, LabelStats> {
+        &self.labels
+    }
+
+    pub fn targets(&self) -> &BTreeMap<usize, TargetStats> {
+        &self.targets
+    }
+
+    pub fn histogram(&self) -> &Histogram {
+        &self.histogram
+    }
+
+    /// The sampled durations, if the accumulator is sampling.
+    pub fn sample(&self) -> Option<&[Duration]> {
+        self.sample.as_ref().map(|sample| sample.values())
+    }
+
+    /// A sample of the rates that response bodies were downloaded at.
+    pub fn transfer_rates(&self) -> &[f64] {
+        self.transfer_rates.values()
+    }
+
+    /// A sample of the rates of the bodies over the whole of their requests.
+    pub fn throughputs(&self) -> &[f64] {
+        self.throughputs.values()
+    }
+
+    /// A sample of the sizes of the bodies of the responses.
+    pub fn response_sizes(&self) -> &[u64] {
+        self.response_sizes.values()
+    }
+
+    /// The raw facts, if the accumulator is recording.
+    pub fn facts(&self) -> Option<&[Fact]> {
+        self.facts.as_deref()
+    }
+}
+
+fn from_ms(ms: f64) -> Duration {
+    Duration::from_nanos((ms * 1_000_000.) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ok_fact(ms: u64) -> Fact {
+        Fact::record(ContentLength::new(1), 200, Duration::from_millis(ms))
+    }
+
+    #[test]
+    fn folds_the_running_statistics() {
+        let mut acc = Accumulator::new();
+        for ms in &[2, 1, 4, 3] {
+            acc.add(ok_fact(*ms));
+        }
+        assert_eq!(acc.count(), 4);
+        assert_eq!(acc.average(), Duration::from_micros(2_500));
+        assert_eq!(acc.stddev().as_micros(), 1_290);
+        assert_eq!(acc.min(), Some(Duration::from_millis(1)));
+        assert_eq!(acc.max(), Some(Duration::from_millis(4)));
+        assert_eq!(acc.content_length().bytes(), 4);
+        assert_eq!(acc.status_counts().get(&200), Some(&4));
+    }
+
+    #[test]
+    fn counts_errors_and_failures() {
+        let mut acc = Accumulator::new();
+        acc.add(ok_fact(1));
+        acc.add(Fact::record(ContentLength::zero(), 503, Duration::from_millis(1)));
+        acc.add(Fact::error(Duration::from_millis(1)));
+        acc.add(ok_fact(1).with_body_mismatch(true));
+        acc.add(ok_fact(1).with_echo_mismatch(true));
+        acc.add(ok_fact(1).with_declared_length(Some(10)));
+        assert_eq!(acc.errors(), 1);
+        assert_eq!(acc.failures(), 5);
+        assert_eq!(acc.body_mismatches(), 1);
+        assert_eq!(acc.echo_mismatches(), 1);
+        assert_eq!(acc.length_mismatches(), 1);
+    }
+
+    #[test]
+    fn samples_transfer_rates_of_timed_downloads() {
+        let mut acc = Accumulator::new();
+        acc.add(ok_fact(1).with_download(Duration::from_millis(500)));
+        acc.add(ok_fact(1));
+        assert_eq!(acc.transfer_rates(), &[2.]);
+    }
+
+    #[test]
+    fn notes_each_http_version_once() {
+        let mut acc = Accumulator::new();
+        acc.add(ok_fact(1).with_version(Some(Version::Http2)));
+        acc.add(ok_fact(1).with_version(Some(Version::Http11)));
+        acc.add(ok_fact(1).with_version(Some(Version::Http2)));
+        acc.add(ok_fact(1));
+        assert_eq!(acc.versions(), &[Version::Http11, Version::Http2]);
+    }
+
+    #[test]
+    fn merging_matches_adding_everything_to_one() {
+        let mut all = Accumulator::new();
+        let mut a = Accumulator::new();
+        let mut b = Accumulator::new();
+        for ms in 1..10 {
+            all.add(ok_fact(ms));
+            if ms % 3 == 0 {
+                b.add(ok_fact(ms));
+            } else {
+                a.add(ok_fact(ms));
+            }
+        }
+        b.add(Fact::error(Duration::from_millis(20)));
+        all.add(Fact::error(Duration::from_millis(20)));
+        a.merge(b);
+
+        assert_eq!(a.count(), all.count());
+        assert_eq!(a.errors(), all.errors());
+        assert_eq!(a.failures(), all.failures());
+        assert_eq!(a.average().as_micros(), all.average().as_micros());
+        assert_eq!(a.stddev().as_micros(), all.stddev().as_micros());
+        assert_eq!(a.min(), all.min());
+        assert_eq!(a.max(), all.max());
+        assert_eq!(a.content_length(), all.content_length());
+        assert_eq!(a.status_counts(), all.status_counts());
+        assert_eq!(a.histogram(), all.histogram());
+    }
+
+    #[test]
+    fn merges_an_accumulator_read_back_from_json() {
+        let mut all = Accumulator::new().with_sample(10);
+        let mut a = Accumulator::new().with_sample(10);
+        let mut b = Accumulator::new().with_sample(10);
+        for ms in 1..6 {
+            all.add(ok_fact(ms).with_label("tier", "free"));
+            b.add(ok_fact(ms).with_label("tier", "free"));
+        }
+        all.add(ok_fact(9));
+        a.add(ok_fact(9));
+        let b: Accumulator = ::serde_json::from_str(&::serde_json::to_string(&b).unwrap()).unwrap();
+        a.merge(b);
+
+        assert_eq!(a.count(), all.count());
+        assert_eq!(a.average().as_micros(), all.average().as_micros());
+        assert_eq!(a.histogram(), all.histogram());
+        assert_eq!(a.labels(), all.labels());
+        assert_eq!(a.sample().map(<[Duration]>::len), Some(6));
+    }
+
+    #[test]
+    fn merging_into_an_empty_accumulator() {
+        let mut a = Accumulator::new();
+        let mut b = Accumulator::new();
+        b.add(ok_fact(3));
+        a.merge(b);
+        assert_eq!(a.average(), Duration::from_millis(3));
+        assert_eq!(a.min(), Some(Duration::from_millis(3)));
+    }
+
+    #[test]
+    fn only_keeps_facts_when_recording() {
+        let mut acc = Accumulator::new();
+        acc.add(ok_fact(1));
+        assert!(acc.facts().is_none());
+
+        let mut acc = Accumulator::recording(1);
+        acc.add(ok_fact(1));
+        assert_eq!(acc.facts().map(|facts| facts.len()), Some(1));
+    }
+
+    #[test]
+    fn samples_a_bounded_number_of_durations() {
+        let mut acc = Accumulator::new().with_sample(10);
+        for ms in 0..100 {
+            acc.add(ok_fact(ms));
+        }
+        assert_eq!(acc.sample().map(|sample| sample.len()), Some(10));
+        assert!(Accumulator::new().sample().is_none());
+    }
+}
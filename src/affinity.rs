@@ -0,0 +1,93 @@
+/// Which core each thread of a run is pinned to. The collector is kept on a core of its own
+/// when there's more than one, and the workers share out the rest in turn, so that the
+/// scheduler moving threads between cores doesn't add noise to the latencies.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pinning {
+    cores: Vec<usize>,
+}
+
+impl Pinning {
+    /// Pins the threads to the cores that the process is allowed to run on.
+    pub fn new() -> Result<Pinning, String> {
+        let cores = allowed_cores()?;
+        if cores.is_empty() {
+            return Err("Expected the process to be allowed to run on a core".to_string());
+        }
+        Ok(Pinning { cores })
+    }
+
+    /// The core the collector runs on.
+    pub fn collector(&self) -> usize {
+        self.cores[0]
+    }
+
+    /// The core the worker runs on, which is only shared with the collector when there's
+    /// no other.
+    pub fn worker(&self, worker: usize) -> usize {
+        match self.cores.len() {
+            1 => self.cores[0],
+            n => self.cores[1 + worker % (n - 1)],
+        }
+    }
+}
+
+/// The cores the process is allowed to run on.
+#[cfg(target_os = "linux")]
+fn allowed_cores() -> Result<Vec<usize>, String> {
+    use std::mem;
+    // The set is plain data that the call fills in, so zeroed is a valid empty set.
+    unsafe {
+        let mut set: libc::cpu_set_t = mem::zeroed();
+        if libc::sched_getaffinity(0, mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            return Err(format!(
+                "Could not read the cores the process can run on: {}",
+                ::std::io::Error::last_os_error()
+            ));
+        }
+        Ok((0..libc::CPU_SETSIZE as usize).filter(|&core| libc::CPU_ISSET(core, &set)).collect())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn allowed_cores() -> Result<Vec<usize>, String> {
+    Err("Pinning threads to cores is only supported on linux".to_string())
+}
+
+/// Pins the calling thread to the core.
+#[cfg(target_os = "linux")]
+pub fn pin(core: usize) -> Result<(), String> {
+    use std::mem;
+    unsafe {
+        let mut set: libc::cpu_set_t = mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        if libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(format!(
+                "Could not pin a thread to core {}: {}",
+                core,
+                ::std::io::Error::last_os_error()
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin(_core: usize) -> Result<(), String> {
+    Err("Pinning threads to cores is only supported on linux".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_collector_on_a_core_of_its_own() {
+        let pinning = Pinning { cores: vec![0, 2, 3] };
+        assert_eq!(pinning.collector(), 0);
+        let workers: Vec<usize> = (0..4).map(|worker| pinning.worker(worker)).collect();
+        assert_eq!(workers, vec![2, 3, 2, 3]);
+
+        let alone = Pinning { cores: vec![5] };
+        assert_eq!((alone.collector(), alone.worker(3)), (5, 5));
+    }
+}
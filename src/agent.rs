@@ -0,0 +1,295 @@
+use accumulator::Accumulator;
+use collector::Sender;
+use log;
+use message::Message;
+use plan::{self, Plan};
+use stats::Fact;
+use std::env;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// The part of a run that a controller sends to an agent, as a single line of json. The
+/// arguments are the controller's own with its share of the requests and concurrency.
+#[derive(Debug, Serialize, Deserialize)]
+struct Job {
+    token: String,
+    args: Vec<String>,
+    /// Whether to send back the statistics of the job once it's done instead of streaming
+    /// every fact.
+    #[serde(default)]
+    summarize: bool,
+}
+
+/// The line an agent sends back instead of facts when it won't run a job.
+#[derive(Debug, Serialize, Deserialize)]
+struct Rejection {
+    rejected: String,
+}
+
+/// Listens for controllers and runs the jobs they send. Each job is run by a child rench
+/// that writes its facts to stdout as lines of json, which are streamed back as they come,
+/// or that writes the statistics it folded them into as a single line once it's done.
+///
+/// Only jobs that carry the agent's token are run, and only once `check` has passed their
+/// arguments, so that a controller can't make the agent read or write its files.
+pub fn listen(addr: &str, token: &str, check: fn(&[String]) -> Result<(), String>) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    log::info(format_args!("Agent listening on {}", listener.local_addr()?));
+    for stream in listener.incoming() {
+        // A connection that fails before it's accepted only loses that controller.
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Failed to accept a controller: {}", e);
+                continue;
+            }
+        };
+        let token = token.to_string();
+        thread::spawn(move || {
+            let peer = stream.peer_addr().ok();
+            if let Err(e) = serve(stream, &token, check) {
+                eprintln!("Job from {:?} failed: {}", peer, e);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn serve(stream: TcpStream, token: &str, check: fn(&[String]) -> Result<(), String>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let job: Job = serde_json::from_str(&line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if !same(&job.token, token) {
+        return reject(&stream, "The token does not match the agent's".to_string());
+    }
+    if let Err(e) = check(&job.args) {
+        return reject(&stream, e);
+    }
+    log::info(format_args!("Running {}", job.args.join(" ")));
+
+    let mut child = Command::new(env::current_exe()?)
+        .arg(if job.summarize { "--emit-summary" } else { "--emit-facts" })
+        .args(job.args.iter().skip(1))
+        // The job would otherwise pick up the agent's own secrets from the environment.
+        .env_remove("RENCH_AGENT_TOKEN")
+        .env_remove("RENCH_OAUTH_CLIENT_SECRET")
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let copied = io::copy(child.stdout.as_mut().expect("Stdout is piped"), &mut &stream);
+    child.wait()?;
+    copied.map(|_| ())
+}
+
+/// Tells the controller why its job won't be run.
+fn reject(mut stream: &TcpStream, reason: String) -> io::Result<()> {
+    let line = serde_json::to_string(&Rejection { rejected: reason.clone() }).expect("Rejections always serialize");
+    writeln!(stream, "{}", line)?;
+    Err(io::Error::new(io::ErrorKind::PermissionDenied, reason))
+}
+
+/// Compares the tokens in the same time wherever they differ, so that how long a job takes
+/// to be refused doesn't give away how much of its token was right.
+fn same(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Writes a fact to stdout as a line of json for the agent to stream back.
+pub fn emit(fact: &Fact) {
+    let line = serde_json::to_string(fact).expect("Facts always serialize");
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let _ = writeln!(stdout, "{}", line);
+}
+
+/// Writes the statistics of a job to stdout as a line of json for the agent to send back.
+pub fn emit_summary(accumulator: &Accumulator) {
+    let line = serde_json::to_string(accumulator).expect("Accumulators always serialize");
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let _ = writeln!(stdout, "{}", line);
+}
+
+/// The remote half of a run, made by agents on other machines.
+pub struct Remote {
+    handles: Vec<thread::JoinHandle<io::Result<Option<Accumulator>>>>,
+}
+
+impl Remote {
+    /// Splits the plan between the agents and sends each its share of the requests and
+    /// concurrency. The facts they stream back are sent to the collector, followed by an
+    /// end of file for each agent. Agents that summarize send no facts, only the
+    /// statistics of their share once it's done, which `join` hands back.
+    pub fn start(
+        agents: &[String],
+        token: &str,
+        args: &[String],
+        plan: Plan,
+        summarize: bool,
+        collector: &Sender<Fact>,
+    ) -> Remote {
+        // Agents beyond the planned requests would have none to make, so they're left out
+        // as threads are.
+        let shares = Plan::new(agents.len(), plan.requests()).expect("Expected an agent to run on");
+        let requests = shares.distribute();
+        let concurrency = plan::share(plan.concurrency(), shares.threads());
+        let handles = agents
+            .iter()
+            .zip(requests.into_iter().zip(concurrency))
+            .map(|(agent, (requests, concurrency))| {
+                // Each agent keeps to the share of the rate that its share of the requests is.
+                let rate = plan
+                    .rate()
+                    .map(|rate| rate * requests as f64 / plan.requests().max(1) as f64);
+                let job = Job {
+                    token: token.to_string(),
+                    args: job_args(args, requests, concurrency, rate),
+                    summarize,
+                };
+                let agent = agent.clone();
+                let collector = collector.clone();
+                thread::spawn(move || {
+                    let result = Self::run(&agent, &job, &collector);
+                    let _ = collector.send(Message::EOF);
+                    if let Err(ref e) = result {
+                        eprintln!("Agent {} failed: {}", agent, e);
+                    }
+                    result
+                })
+            })
+            .collect();
+        Remote { handles }
+    }
+
+    /// Waits for every agent to finish. Returns how many of them failed and the statistics
+    /// that the others summarized.
+    pub fn join(self) -> (usize, Vec<Accumulator>) {
+        let mut failed = 0;
+        let mut summaries = Vec::new();
+        for handle in self.handles {
+            match handle.join() {
+                Ok(Ok(summary)) => summaries.extend(summary),
+                _ => failed += 1,
+            }
+        }
+        (failed, summaries)
+    }
+
+    fn run(agent: &str, job: &Job, collector: &Sender<Fact>) -> io::Result<Option<Accumulator>> {
+        let mut stream = TcpStream::connect(agent)?;
+        let line = serde_json::to_string(job).expect("Jobs always serialize");
+        writeln!(stream, "{}", line)?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if let Ok(rejection) = serde_json::from_str::<Rejection>(&line) {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("The agent refused the job: {}", rejection.rejected),
+                ));
+            }
+            if job.summarize {
+                let summary = serde_json::from_str(&line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                return Ok(Some(summary));
+            }
+            let fact: Fact = serde_json::from_str(&line)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            if collector.send(Message::Body(fact)).is_err() {
+                break;
+            }
+        }
+        if job.summarize {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "The agent sent no summary"));
+        }
+        Ok(None)
+    }
+}
+
+/// The controller's arguments without the agents or their token, with the requests, concurrency and rate
+/// replaced by an agent's share. The most requests an open loop keeps in flight is the
+/// concurrency it's shared out as. A `run` subcommand is left out, since the agent puts its
+/// own flags in front of the arguments.
+fn job_args(args: &[String], requests: usize, concurrency: usize, rate: Option<f64>) -> Vec<String> {
+    let mut job = Vec::with_capacity(args.len() + 4);
+    let run = args.get(1).map(String::as_str) == Some("run");
+    let mut args = args.iter().enumerate().filter(|&(n, _)| !(run && n == 1)).map(|(_, arg)| arg);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--workers-summarize" => {}
+            "--workers" | "--workers-token" | "--rate" | "--max-inflight" | "-n" | "-c" => {
+                args.next();
+            }
+            arg if ["--workers=", "--workers-token=", "--rate=", "--max-inflight="]
+                .iter()
+                .any(|flag| arg.starts_with(flag)) => {}
+            arg if arg.len() > 2 && (arg.starts_with("-n") || arg.starts_with("-c")) => {}
+            arg => job.push(arg.to_string()),
+        }
+    }
+    job.extend(vec![
+        "-n".to_string(),
+        requests.to_string(),
+        "-c".to_string(),
+        concurrency.to_string(),
+    ]);
+    if let Some(rate) = rate {
+        job.extend(vec!["--rate".to_string(), rate.to_string()]);
+    }
+    job
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn replaces_the_share_of_the_plan() {
+        assert_eq!(
+            job_args(
+                &args(&["rench", "-n", "1000", "-c8", "--workers", "a:7000,b:7000", "http://a/"]),
+                500,
+                4,
+                None
+            ),
+            args(&["rench", "http://a/", "-n", "500", "-c", "4"])
+        );
+        assert_eq!(
+            job_args(&args(&["rench", "--workers=a:7000", "http://a/"]), 1000, 1, None),
+            args(&["rench", "http://a/", "-n", "1000", "-c", "1"])
+        );
+        assert_eq!(
+            job_args(
+                &args(&["rench", "--workers-token", "secret", "--workers-token=secret", "--workers-summarize", "http://a/"]),
+                1000,
+                1,
+                None
+            ),
+            args(&["rench", "http://a/", "-n", "1000", "-c", "1"])
+        );
+        assert_eq!(
+            job_args(&args(&["rench", "--rate", "100", "http://a/"]), 500, 1, Some(50.)),
+            args(&["rench", "http://a/", "-n", "500", "-c", "1", "--rate", "50"])
+        );
+        assert_eq!(
+            job_args(&args(&["rench", "--loop", "open", "--max-inflight", "100", "http://a/"]), 500, 50, None),
+            args(&["rench", "--loop", "open", "http://a/", "-n", "500", "-c", "50"])
+        );
+        assert_eq!(
+            job_args(&args(&["rench", "run", "--label", "run", "http://a/"]), 500, 1, None),
+            args(&["rench", "--label", "run", "http://a/", "-n", "500", "-c", "1"])
+        );
+    }
+
+    #[test]
+    fn compares_tokens() {
+        assert!(same("secret", "secret"));
+        assert!(!same("secret", "secreT"));
+        assert!(!same("secret", "secrets"));
+        assert!(!same("", "secret"));
+    }
+}
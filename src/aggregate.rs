@@ -0,0 +1,174 @@
+use report::{self, Report, Results};
+use std::fs;
+
+/// Reads a run that was saved with `--output json`.
+pub fn read(path: &str) -> Result<Report, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Could not read the run '{}': {}", path, e))?;
+    Report::from_json(&contents).map_err(|e| format!("'{}' isn't a saved run: {}", path, e))
+}
+
+/// Merges the reports of runs that were made at the same time, such as from several load
+/// generators started together, into the report of one run with the merged results. The
+/// runs are taken to have overlapped, so the merged run took as long as the longest of them
+/// and its rate is of every request over that time. The host and label are those of the first
+/// run, and a tag that the runs disagree on keeps the value of the first to have it.
+pub fn merge(reports: &[Report], results: Results) -> Report {
+    let first = reports.first().expect("Expected at least one run to merge");
+    let mut run = first.run.clone();
+    run.concurrency = reports.iter().map(|report| report.run.concurrency).sum();
+    run.requests = reports.iter().map(|report| report.run.requests).sum();
+    // RFC 3339 timestamps in UTC sort in time order.
+    run.started = reports
+        .iter()
+        .map(|report| report.run.started.clone())
+        .min()
+        .expect("There is at least one run");
+    for report in reports {
+        for url in &report.run.urls {
+            if !run.urls.contains(url) {
+                run.urls.push(url.clone());
+            }
+        }
+        for (key, value) in &report.run.tags {
+            run.tags.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+    }
+
+    let mut warnings: Vec<String> = Vec::new();
+    for warning in reports.iter().flat_map(|report| &report.warnings) {
+        if !warnings.contains(warning) {
+            warnings.push(warning.clone());
+        }
+    }
+
+    let took_seconds = reports.iter().map(|report| report.took_seconds).fold(0., f64::max);
+    Report {
+        schema_version: report::SCHEMA_VERSION,
+        run,
+        warnings,
+        client_saturated: reports.iter().any(|report| report.client_saturated),
+        took_seconds,
+        setup_seconds: reports
+            .iter()
+            .filter_map(|report| report.setup_seconds)
+            .fold(None, |most: Option<f64>, setup| Some(most.map_or(setup, |most| most.max(setup)))),
+        // Each run had a collector of its own, so the deepest of their queues is kept.
+        collector: reports.iter().filter_map(|report| report.collector.as_ref()).fold(None, |all, run| {
+            Some(match all {
+                None => run.clone(),
+                Some(all) => report::Collector {
+                    sent: all.sent + run.sent,
+                    dropped: all.dropped + run.dropped,
+                    blocked_seconds: all.blocked_seconds + run.blocked_seconds,
+                    deepest_queue: all.deepest_queue.max(run.deepest_queue),
+                },
+            })
+        }),
+        preconnect: reports.iter().filter_map(|report| report.preconnect.as_ref()).fold(None, |all, run| {
+            Some(match all {
+                None => run.clone(),
+                Some(all) => {
+                    let opened = all.opened + run.opened;
+                    report::Preconnect {
+                        opened,
+                        failed: all.failed + run.failed,
+                        average_ms: if opened == 0 {
+                            0.
+                        } else {
+                            (all.average_ms * all.opened as f64 + run.average_ms * run.opened as f64) / opened as f64
+                        },
+                        longest_ms: all.longest_ms.max(run.longest_ms),
+                    }
+                }
+            })
+        }),
+        // The usage of each client is its own, so that of the busiest one is kept.
+        client_usage: reports
+            .iter()
+            .filter_map(|report| report.client_usage.as_ref())
+            .max_by(|a, b| a.busiest_cpu_percent.total_cmp(&b.busiest_cpu_percent))
+            .cloned(),
+        requests_per_second: if took_seconds > 0. {
+            f64::from(results.count) / took_seconds
+        } else {
+            0.
+        },
+        results,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use histogram::Histogram;
+    use stats::Summary;
+    use std::time::Duration;
+
+    fn run(latencies_ms: &[u64], took_seconds: f64) -> Report {
+        let mut histogram = Histogram::new();
+        for &ms in latencies_ms {
+            histogram.record(Duration::from_millis(ms));
+        }
+        let count = latencies_ms.len() as f64;
+        let average = latencies_ms.iter().sum::<u64>() as f64 / count;
+        let squared_distance: f64 = latencies_ms.iter().map(|&ms| (ms as f64 - average).powi(2)).sum();
+        let stddev = if count > 1. { (squared_distance / (count - 1.)).sqrt() } else { 0. };
+        let json = format!(
+            r#"{{
+                "schema_version": 1,
+                "run": {{
+                    "version": "0.3.0", "command": "rench", "urls": ["http://a/"], "engine": "hyper",
+                    "concurrency": 2, "requests": {count}, "started": "2026-10-16T10:00:00Z", "seed": 1,
+                    "host": {{ "name": "a", "os": "linux", "arch": "x86_64", "cpus": 4 }}
+                }},
+                "warnings": [], "client_saturated": false, "took_seconds": {took}, "setup_seconds": null,
+                "requests_per_second": 1.0,
+                "results": {{
+                    "count": {count}, "errors": 0, "failures": 0, "body_mismatches": 0, "length_mismatches": 0,
+                    "latency_ms": {{
+                        "min": {min}, "median": 0.0, "average": {average}, "max": {max}, "stddev": {stddev}
+                    }},
+                    "percentiles_ms": [], "latency_buckets_us": {buckets:?}, "content_length_bytes": 10,
+                    "status_codes": {{ "200": {count} }}, "connections": null, "redirects": null,
+                    "pool_wait": null, "transfer_rates_bytes_per_second": null, "labels": {{}},
+                    "checksums": null, "heatmap": null, "schedule": null
+                }}
+            }}"#,
+            count = latencies_ms.len(),
+            took = took_seconds,
+            min = latencies_ms.iter().min().unwrap(),
+            max = latencies_ms.iter().max().unwrap(),
+            average = average,
+            stddev = stddev,
+            buckets = histogram.buckets().iter().map(|&(v, c)| [v, c]).collect::<Vec<_>>(),
+        );
+        Report::from_json(&json).unwrap()
+    }
+
+    #[test]
+    fn merges_the_percentiles_of_every_request() {
+        let runs = vec![run(&[1, 1, 1, 1], 2.), run(&[8, 8, 8, 8, 8, 8], 4.)];
+        let results: Vec<&Results> = runs.iter().map(|run| &run.results).collect();
+        let summary = Summary::from_results(&results).unwrap();
+        let merged = merge(&runs, summary.results());
+
+        assert_eq!(merged.results.count, 10);
+        assert_eq!(merged.results.latency_ms.median, 8.);
+        assert_eq!(merged.results.latency_ms.average, 5.2);
+        assert_eq!(merged.results.percentiles_ms[30], 1.);
+        assert_eq!(merged.results.status_codes[&200], 10);
+        assert_eq!((merged.run.concurrency, merged.took_seconds), (4, 4.));
+        assert_eq!(merged.requests_per_second, 2.5);
+
+        // The stddev is that of all of the latencies together.
+        let all = run(&[1, 1, 1, 1, 8, 8, 8, 8, 8, 8], 4.);
+        assert!((merged.results.latency_ms.stddev - all.results.latency_ms.stddev).abs() < 1e-6);
+    }
+
+    #[test]
+    fn refuses_runs_without_their_histogram() {
+        let mut old = run(&[1], 1.);
+        old.results.latency_buckets_us = None;
+        assert!(Summary::from_results(&[&old.results]).is_err());
+    }
+}
@@ -1,4 +1,5 @@
-use std::time::{Duration, Instant};
+use clock::{self, Clock};
+use std::time::Duration;
 
 /// Executes a closure once and returns how long that closure took
 /// as a duration.
@@ -6,13 +7,23 @@ pub fn time_it<F, U>(f: F) -> (U, Duration)
 where
     F: FnOnce() -> U,
 {
-    let start = Instant::now();
-    (f(), start.elapsed())
+    time_on(&clock::System, f)
+}
+
+/// Like `time_it`, but timed by the clock.
+pub fn time_on<C, F, U>(clock: &C, f: F) -> (U, Duration)
+where
+    C: Clock + ?Sized,
+    F: FnOnce() -> U,
+{
+    let start = clock.now();
+    (f(), clock.elapsed(start))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use clock::Manual;
 
     #[test]
     fn reports_duration_and_response() {
@@ -20,4 +31,14 @@ mod tests {
         assert_eq!(u, 123);
         assert!(d > Duration::new(0, 0));
     }
+
+    #[test]
+    fn times_by_the_clock() {
+        let clock = Manual::new();
+        let (u, d) = time_on(&clock, || {
+            clock.advance(Duration::from_millis(250));
+            "done"
+        });
+        assert_eq!((u, d), ("done", Duration::from_millis(250)));
+    }
 }
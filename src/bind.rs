@@ -0,0 +1,145 @@
+use net2::TcpBuilder;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// The local addresses that connections are made from, each taken in turn. Spreading the
+/// connections over several source addresses gets past the limit of ephemeral ports to a
+/// single server and reaches the backends of a load balancer with source-ip affinity.
+#[derive(Clone, Debug)]
+pub struct Binds {
+    addrs: Arc<Vec<IpAddr>>,
+    next: Arc<AtomicUsize>,
+}
+
+impl Binds {
+    pub fn parse(addrs: &[&str]) -> Result<Binds, String> {
+        let addrs = addrs
+            .iter()
+            .map(|addr| {
+                addr.parse::<IpAddr>()
+                    .map_err(|_| format!("'{}' is not an ip address to bind to", addr))
+            })
+            .collect::<Result<Vec<IpAddr>, String>>()?;
+        if addrs.is_empty() {
+            return Err("No addresses to bind to".to_string());
+        }
+        Ok(Binds {
+            addrs: Arc::new(addrs),
+            next: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// The address for the next connection.
+    fn next(&self) -> IpAddr {
+        let next = self.next.fetch_add(1, Ordering::Relaxed);
+        self.addrs[next % self.addrs.len()]
+    }
+}
+
+/// The family of addresses that a connection is made over.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Family {
+    V4,
+    V6,
+}
+
+impl Family {
+    pub fn of(ip: &IpAddr) -> Family {
+        if ip.is_ipv4() {
+            Family::V4
+        } else {
+            Family::V6
+        }
+    }
+
+    /// The family of the server's end of the connection.
+    pub fn of_peer(stream: &TcpStream) -> Option<Family> {
+        stream.peer_addr().ok().map(|addr| Family::of(&addr.ip()))
+    }
+}
+
+impl fmt::Display for Family {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Family::V4 => write!(f, "IPv4"),
+            Family::V6 => write!(f, "IPv6"),
+        }
+    }
+}
+
+/// Opens a connection to the address, from the next of the local addresses if any were
+/// given. Only the remote addresses of the family asked for are tried, or of the same
+/// family as the local address, rather than whichever the host resolves to first.
+pub fn connect<A: ToSocketAddrs>(addr: A, binds: Option<&Binds>, family: Option<Family>) -> io::Result<TcpStream> {
+    let local = binds.map(Binds::next);
+    let family = local.as_ref().map(Family::of).or(family);
+    let mut last = None;
+    for remote in addr.to_socket_addrs()? {
+        if family.is_some_and(|family| Family::of(&remote.ip()) != family) {
+            continue;
+        }
+        let connected = match local {
+            Some(local) => {
+                let builder = if local.is_ipv4() {
+                    TcpBuilder::new_v4()?
+                } else {
+                    TcpBuilder::new_v6()?
+                };
+                builder.bind(SocketAddr::new(local, 0))?;
+                builder.connect(remote)
+            }
+            None => TcpStream::connect(remote),
+        };
+        match connected {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last = Some(e),
+        }
+    }
+    Err(last.unwrap_or_else(|| {
+        let message = match (local, family) {
+            (Some(local), _) => format!("The server has no address of the same family as {}", local),
+            (None, Some(family)) => format!("The server has no {} address", family),
+            (None, None) => "The server has no address".to_string(),
+        };
+        io::Error::new(io::ErrorKind::InvalidInput, message)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn cycles_through_the_addresses() {
+        let binds = Binds::parse(&["127.0.0.1", "::1"]).unwrap();
+        let next: Vec<String> = (0..3).map(|_| binds.next().to_string()).collect();
+        assert_eq!(next, vec!["127.0.0.1", "::1", "127.0.0.1"]);
+        assert!(Binds::parse(&["localhost"]).is_err());
+    }
+
+    #[test]
+    fn connects_from_the_bound_address() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let binds = Binds::parse(&["127.0.0.1"]).unwrap();
+        let stream = connect(listener.local_addr().unwrap(), Some(&binds), None).unwrap();
+        assert_eq!(stream.local_addr().unwrap().ip().to_string(), "127.0.0.1");
+
+        let binds = Binds::parse(&["::1"]).unwrap();
+        let error = connect(listener.local_addr().unwrap(), Some(&binds), None).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn connects_over_the_family_asked_for() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = connect(listener.local_addr().unwrap(), None, Some(Family::V4)).unwrap();
+        assert_eq!(Family::of_peer(&stream), Some(Family::V4));
+
+        let error = connect(listener.local_addr().unwrap(), None, Some(Family::V6)).unwrap_err();
+        assert_eq!(error.to_string(), "The server has no IPv6 address");
+    }
+}
@@ -0,0 +1,480 @@
+use report::Report;
+use stats::TimeUnit;
+use std::env;
+use std::fmt;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use {parse_duration, parse_ratio};
+
+/// How close the highest rate that met the objectives has to come to the lowest that
+/// didn't before the search stops, as a fraction of the rate that met them.
+pub const DEFAULT_PRECISION: f64 = 0.05;
+
+/// A step that made fewer requests a second than this fraction of its rate couldn't keep
+/// up with it, whatever its latency.
+const KEPT_UP: f64 = 0.95;
+
+/// The lowest rate that's tried when even the first rate misses the objectives.
+const MIN_RATE: f64 = 1.;
+
+/// A rough estimate of how far the urls are from a target rate, and with Little's law
+/// (concurrency = rate * latency) how many concurrent requests would reach it. A single run
+/// can only assume that latency holds as the rate climbs, which it rarely does near
+/// saturation. The steps of a sweep fit a line through the latency at each rate they ran,
+/// so the estimate follows how latency climbed, though it's still rough beyond the rates
+/// that were run.
+pub struct Headroom {
+    target_rps: f64,
+    observed_rps: f64,
+    /// The concurrency of a single run, or none for a sweep whose runs set their own.
+    concurrency: Option<usize>,
+    /// The latency expected at the target.
+    latency: Duration,
+    steps: usize,
+    time_unit: TimeUnit,
+}
+
+impl Headroom {
+    /// An estimate from a single run, which assumes latency holds at what the run saw.
+    pub fn from_run(target_rps: f64, observed_rps: f64, concurrency: usize, latency: Duration) -> Headroom {
+        Headroom {
+            target_rps,
+            observed_rps,
+            concurrency: Some(concurrency),
+            latency,
+            steps: 1,
+            time_unit: TimeUnit::Auto,
+        }
+    }
+
+    /// An estimate from the steps of a sweep, measured against the highest rate that met
+    /// the objectives, with the latency at the target fitted through the average latency of
+    /// each step at the rate it made.
+    pub fn from_sweep(target_rps: f64, capacity: f64, steps: &[Step]) -> Headroom {
+        let points: Vec<(f64, f64)> = steps
+            .iter()
+            .map(|step| (step.report.requests_per_second, step.report.results.latency_ms.average))
+            .collect();
+        Headroom::fitted(target_rps, capacity, &points)
+    }
+
+    fn fitted(target_rps: f64, capacity: f64, points: &[(f64, f64)]) -> Headroom {
+        Headroom {
+            target_rps,
+            observed_rps: capacity,
+            concurrency: None,
+            latency: Duration::from_secs_f64(fit(points, target_rps) / 1_000.),
+            steps: points.len(),
+            time_unit: TimeUnit::Auto,
+        }
+    }
+
+    pub fn with_time_unit(mut self, unit: TimeUnit) -> Self {
+        self.time_unit = unit;
+        self
+    }
+
+    /// How many times the observed throughput the target is.
+    pub fn ratio(&self) -> f64 {
+        if self.observed_rps > 0. {
+            self.target_rps / self.observed_rps
+        } else {
+            f64::INFINITY
+        }
+    }
+
+    /// The concurrency needed to reach the target at the latency expected there.
+    pub fn needed_concurrency(&self) -> usize {
+        (self.target_rps * self.latency.as_secs_f64()).ceil().max(1.) as usize
+    }
+}
+
+impl fmt::Display for Headroom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Capacity")?;
+        writeln!(f, "  Target:    {:.2} rps", self.target_rps)?;
+        match self.concurrency {
+            Some(concurrency) => writeln!(
+                f,
+                "  Observed:  {:.2} rps with {} concurrent requests",
+                self.observed_rps, concurrency
+            )?,
+            None => writeln!(
+                f,
+                "  Observed:  {:.2} rps, the most that met the objectives in {} steps",
+                self.observed_rps, self.steps
+            )?,
+        }
+        if self.observed_rps >= self.target_rps {
+            writeln!(
+                f,
+                "  Headroom:  {:.2} rps ({:.2}x the target was observed)",
+                self.observed_rps - self.target_rps,
+                1. / self.ratio()
+            )?;
+        } else {
+            writeln!(
+                f,
+                "  Shortfall: {:.2} rps (the target is {:.2}x what was observed)",
+                self.target_rps - self.observed_rps,
+                self.ratio()
+            )?;
+            match self.concurrency {
+                Some(_) => writeln!(
+                    f,
+                    "  Estimate:  about {} concurrent requests if latency holds at {}",
+                    self.needed_concurrency(),
+                    self.time_unit.format(self.latency)
+                )?,
+                None => writeln!(
+                    f,
+                    "  Estimate:  about {} concurrent requests with latency fitted to {} at the target",
+                    self.needed_concurrency(),
+                    self.time_unit.format(self.latency)
+                )?,
+            }
+        }
+        match self.concurrency {
+            Some(_) => writeln!(
+                f,
+                "  This extrapolates from a single run and latency usually climbs near saturation, so treat it as a lower bound."
+            ),
+            None => writeln!(
+                f,
+                "  This fits a line through the average latency of {} steps, so it's rough beyond the rates that were run.",
+                self.steps
+            ),
+        }
+    }
+}
+
+/// The latency, in milliseconds, that a line fitted through points of rate and latency
+/// gives at the rate. Latency rarely falls as the rate climbs, so the fit is kept to no
+/// less than the lowest latency seen.
+fn fit(points: &[(f64, f64)], rate: f64) -> f64 {
+    if points.is_empty() {
+        return 0.;
+    }
+    let count = points.len() as f64;
+    let mean_rate = points.iter().map(|&(rate, _)| rate).sum::<f64>() / count;
+    let mean_latency = points.iter().map(|&(_, latency)| latency).sum::<f64>() / count;
+    let spread: f64 = points.iter().map(|&(rate, _)| (rate - mean_rate).powi(2)).sum();
+    let slope = if spread > 0. {
+        points
+            .iter()
+            .map(|&(rate, latency)| (rate - mean_rate) * (latency - mean_latency))
+            .sum::<f64>()
+            / spread
+    } else {
+        0.
+    };
+    let lowest = points.iter().map(|&(_, latency)| latency).fold(f64::INFINITY, f64::min);
+    (mean_latency + slope * (rate - mean_rate)).max(lowest)
+}
+
+/// An objective that every step of the search has to meet, such as `p99<200ms` or
+/// `error-rate<1%`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slo {
+    name: String,
+    measure: Measure,
+    /// In milliseconds for a latency, or as a fraction for the error rate.
+    below: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Measure {
+    Quantile(f64),
+    Average,
+    Longest,
+    ErrorRate,
+}
+
+impl Slo {
+    pub fn parse(value: &str) -> Result<Slo, String> {
+        let invalid = || {
+            format!(
+                "Expected an objective like 'p99<200ms', 'average<50ms' or 'error-rate<1%', got '{}'",
+                value
+            )
+        };
+        let at = value.find('<').ok_or_else(invalid)?;
+        let (name, limit) = (value[..at].trim(), value[at + 1..].trim());
+        let measure = match name {
+            "median" => Measure::Quantile(0.5),
+            "average" | "avg" => Measure::Average,
+            "longest" | "max" => Measure::Longest,
+            "error-rate" => Measure::ErrorRate,
+            _ => match name.strip_prefix('p').and_then(|p| p.parse::<f64>().ok()) {
+                Some(percentile) if percentile > 0. && percentile < 100. => Measure::Quantile(percentile / 100.),
+                _ => return Err(invalid()),
+            },
+        };
+        let below = match measure {
+            Measure::ErrorRate => parse_ratio(limit).ok_or_else(invalid)?,
+            _ => parse_duration(limit).map_err(|_| invalid())?.as_secs_f64() * 1_000.,
+        };
+        Ok(Slo {
+            name: name.to_string(),
+            measure,
+            below,
+        })
+    }
+
+    /// The run's measure, if it has one.
+    fn measure(&self, report: &Report) -> Option<f64> {
+        let results = &report.results;
+        match self.measure {
+            Measure::Quantile(quantile) => results.quantile_ms(quantile),
+            Measure::Average => Some(results.latency_ms.average),
+            Measure::Longest => Some(results.latency_ms.max),
+            Measure::ErrorRate if results.count == 0 => None,
+            Measure::ErrorRate => Some(f64::from(results.failures) / f64::from(results.count)),
+        }
+    }
+
+    fn format(&self, value: f64) -> String {
+        match self.measure {
+            Measure::ErrorRate => format!("{:.2}%", value * 100.),
+            _ => format!("{:.2} ms", value),
+        }
+    }
+}
+
+impl fmt::Display for Slo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.measure {
+            Measure::ErrorRate => write!(f, "{}<{}%", self.name, self.below * 100.),
+            _ => write!(f, "{}<{}ms", self.name, self.below),
+        }
+    }
+}
+
+/// A run of the search at one rate, and whether it met every objective.
+pub struct Step {
+    pub rate: f64,
+    pub report: Report,
+    pub met: bool,
+    measured: Vec<String>,
+    kept_up: bool,
+}
+
+impl Step {
+    pub fn new(rate: f64, report: Report, slos: &[Slo]) -> Step {
+        let measures: Vec<(&Slo, Option<f64>)> = slos.iter().map(|slo| (slo, slo.measure(&report))).collect();
+        let kept_up = report.requests_per_second >= rate * KEPT_UP;
+        Step {
+            rate,
+            met: kept_up
+                && measures
+                    .iter()
+                    .all(|&(slo, value)| value.is_some_and(|value| value < slo.below)),
+            measured: measures
+                .iter()
+                .map(|&(slo, value)| match value {
+                    Some(value) => format!("{} {}", slo.name, slo.format(value)),
+                    None => format!("{} -", slo.name),
+                })
+                .collect(),
+            kept_up,
+            report,
+        }
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "  {:>10.2} requests/sec: made {:.2}, {}, {}",
+            self.rate,
+            self.report.requests_per_second,
+            self.measured.join(", "),
+            match (self.met, self.kept_up) {
+                (true, _) => "met",
+                (false, true) => "missed",
+                (false, false) => "couldn't keep up",
+            }
+        )
+    }
+}
+
+/// Searches for the highest rate that meets the objectives. The rate is doubled from the
+/// first until a step misses them, or halved until one meets them, and then the rates
+/// between the highest that met them and the lowest that missed are bisected until the
+/// two are close.
+pub struct Search {
+    start: f64,
+    max: Option<f64>,
+    precision: f64,
+    met: Option<f64>,
+    missed: Option<f64>,
+}
+
+impl Search {
+    pub fn new(start: f64, max: Option<f64>, precision: f64) -> Search {
+        Search {
+            start,
+            max,
+            precision,
+            met: None,
+            missed: None,
+        }
+    }
+
+    /// The rate to run the next step at, or none once the search is done.
+    pub fn next(&self) -> Option<f64> {
+        match (self.met, self.missed) {
+            (None, None) => Some(self.start),
+            (Some(met), None) => match self.max {
+                Some(max) if met >= max => None,
+                Some(max) => Some((met * 2.).min(max)),
+                None => Some(met * 2.),
+            },
+            (None, Some(missed)) if missed / 2. < MIN_RATE => None,
+            (None, Some(missed)) => Some(missed / 2.),
+            (Some(met), Some(missed)) if missed - met <= met * self.precision => None,
+            (Some(met), Some(missed)) => Some((met + missed) / 2.),
+        }
+    }
+
+    pub fn record(&mut self, rate: f64, met: bool) {
+        if met {
+            self.met = Some(self.met.map_or(rate, |best| best.max(rate)));
+        } else {
+            self.missed = Some(self.missed.map_or(rate, |lowest| lowest.min(rate)));
+        }
+    }
+
+    /// The highest rate that met the objectives, if any did.
+    pub fn capacity(&self) -> Option<f64> {
+        self.met
+    }
+
+    /// Whether every rate up to the most that was allowed met the objectives, so the
+    /// capacity is higher still.
+    pub fn capped(&self) -> bool {
+        self.met.is_some() && self.missed.is_none()
+    }
+}
+
+/// Checks that the arguments of the runs leave the rate, the number of requests and the
+/// output to the search.
+pub fn check_args(args: &[String]) -> Result<(), String> {
+    match args
+        .iter()
+        .find(|arg| ["-n", "--rate", "--output"].iter().any(|flag| arg.starts_with(flag)))
+    {
+        Some(arg) => Err(format!(
+            "Expected the search to choose the rate, requests and output of each run, but '{}' was given",
+            arg
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Runs a child rench with the arguments at the rate for long enough to take about the
+/// duration, and reads back its report.
+pub fn run_at(args: &[String], rate: f64, duration: Duration) -> Result<Report, String> {
+    let requests = ((rate * duration.as_secs_f64()).ceil() as usize).max(1);
+    let output = Command::new(env::current_exe().map_err(|e| e.to_string())?)
+        .arg("run")
+        .args(args)
+        .args(["--quiet", "--output", "json", "--rate"])
+        .arg(rate.to_string())
+        .arg("-n")
+        .arg(requests.to_string())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| format!("Failed to start a run: {}", e))?;
+    serde_json::from_slice(&output.stdout)
+        .map_err(|_| format!("The run at {:.2} requests/sec failed with {}", rate, output.status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_the_concurrency_for_a_higher_target() {
+        let headroom = Headroom::from_run(5_000., 2_000., 4, Duration::from_millis(2));
+        assert_eq!(headroom.ratio(), 2.5);
+        assert_eq!(headroom.needed_concurrency(), 10);
+        assert!(headroom.to_string().contains("Shortfall: 3000.00 rps"));
+    }
+
+    #[test]
+    fn reports_headroom_when_the_target_was_met() {
+        let headroom = Headroom::from_run(1_000., 2_000., 4, Duration::from_millis(2));
+        assert_eq!(headroom.ratio(), 0.5);
+        assert!(headroom.to_string().contains("Headroom:  1000.00 rps (2.00x"));
+    }
+
+    #[test]
+    fn nothing_observed_is_infinitely_short() {
+        let headroom = Headroom::from_run(1_000., 0., 1, Duration::from_millis(2));
+        assert!(headroom.ratio().is_infinite());
+    }
+
+    #[test]
+    fn fits_the_latency_of_a_sweep() {
+        // Latency climbs by a millisecond for every 100 requests a second.
+        let points = [(100., 2.), (200., 3.), (400., 5.)];
+        assert!((fit(&points, 800.) - 9.).abs() < 1e-9);
+        assert_eq!(fit(&points, 0.), 2.);
+        assert_eq!(fit(&[(100., 4.), (100., 6.)], 500.), 5.);
+
+        let headroom = Headroom::fitted(800., 400., &points);
+        assert_eq!(headroom.ratio(), 2.);
+        assert_eq!(headroom.needed_concurrency(), 8);
+        let text = headroom.to_string();
+        assert!(text.contains("400.00 rps, the most that met the objectives in 3 steps"));
+        assert!(text.contains("about 8 concurrent requests with latency fitted to"));
+    }
+
+    #[test]
+    fn parses_objectives() {
+        let p99 = Slo::parse("p99<200ms").unwrap();
+        assert_eq!(p99.measure, Measure::Quantile(0.99));
+        assert_eq!(p99.below, 200.);
+        assert_eq!(p99.to_string(), "p99<200ms");
+        let p50 = Slo::parse("p50 < 1s").unwrap();
+        assert_eq!((p50.measure, p50.below), (Measure::Quantile(0.5), 1_000.));
+        assert_eq!(Slo::parse("error-rate<1%").unwrap().below, 0.01);
+        assert_eq!(Slo::parse("average<50ms").unwrap().measure, Measure::Average);
+        assert!(Slo::parse("p100<1s").is_err());
+        assert!(Slo::parse("p99>200ms").is_err());
+        assert!(Slo::parse("error-rate<200ms").is_err());
+    }
+
+    #[test]
+    fn doubles_then_bisects_the_rate() {
+        let mut search = Search::new(100., None, 0.05);
+        let mut tried = Vec::new();
+        // The service meets the objectives up to 700 requests a second.
+        while let Some(rate) = search.next() {
+            tried.push(rate);
+            search.record(rate, rate <= 700.);
+        }
+        assert_eq!(&tried[..5], &[100., 200., 400., 800., 600.]);
+        let capacity = search.capacity().unwrap();
+        assert!((700. * 0.95..=700.).contains(&capacity));
+        assert!(!search.capped());
+    }
+
+    #[test]
+    fn stops_at_the_most_allowed_or_the_least() {
+        let mut search = Search::new(100., Some(300.), 0.05);
+        while let Some(rate) = search.next() {
+            search.record(rate, true);
+        }
+        assert_eq!(search.capacity(), Some(300.));
+        assert!(search.capped());
+
+        let mut search = Search::new(4., None, 0.05);
+        while let Some(rate) = search.next() {
+            search.record(rate, false);
+        }
+        assert_eq!(search.capacity(), None);
+    }
+}
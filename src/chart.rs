@@ -1,9 +1,17 @@
 /// A chart that can be used to render some set of data.
 pub struct Chart {
     height: u32,
+    width: u32,
     full: char,
     half_full: char,
     space: char,
+    unit: Option<String>,
+}
+
+impl Default for Chart {
+    fn default() -> Chart {
+        Chart::new()
+    }
 }
 
 impl Chart {
@@ -11,9 +19,11 @@ impl Chart {
     pub fn new() -> Chart {
         Chart {
             height: 10,
+            width: 40,
             full: '▌',
             half_full: '▖',
             space: ' ',
+            unit: None,
         }
     }
 
@@ -23,12 +33,31 @@ impl Chart {
         self
     }
 
+    /// Configure the width of the longest bar of a bar chart.
+    pub fn width(mut self, w: u32) -> Chart {
+        self.width = w;
+        self
+    }
+
+    /// Labels the axis values with a unit, rounding them to two decimal places.
+    pub fn unit(mut self, unit: &str) -> Chart {
+        self.unit = Some(unit.to_string());
+        self
+    }
+
+    fn label(&self, value: f64) -> String {
+        match self.unit {
+            Some(ref unit) => format!(" {:.2} {}", value, unit),
+            None => format!(" {}", value),
+        }
+    }
+
     /// Build the chart into a string.
     pub fn make<N>(&self, data: &[N]) -> String
     where
         N: Into<f64> + Clone,
     {
-        let data: Vec<f64> = data.into_iter().map(|d| d.clone().into()).collect();
+        let data: Vec<f64> = data.iter().map(|d| d.clone().into()).collect();
         let (min, max): (f64, f64) = data.iter().fold((0., 0.), |(min, max), datum| {
             let datum = *datum;
             (
@@ -52,24 +81,164 @@ impl Chart {
                 });
             }
             if row == 0 {
-                ret.push_str(&format!(" {}", max));
+                ret.push_str(&self.label(max));
+            }
+            if row == self.height - 1 {
+                ret.push_str(&self.label(min));
+            }
+            ret.push('\n');
+        }
+        ret
+    }
+
+    /// Build a chart of labelled horizontal bars into a string, one to a line, for data that
+    /// falls into categories rather than along an axis. The longest bar is of the largest value
+    /// and each bar ends with its value.
+    pub fn bars<L, N>(&self, data: &[(L, N)]) -> String
+    where
+        L: AsRef<str>,
+        N: Into<f64> + Clone,
+    {
+        let data: Vec<(&str, f64)> = data.iter().map(|(l, d)| (l.as_ref(), d.clone().into())).collect();
+        let max = data.iter().fold(0., |max: f64, &(_, datum)| max.max(datum));
+        let label_width = data.iter().map(|(label, _)| label.chars().count()).max().unwrap_or(0);
+        let mut ret = String::with_capacity(data.len() * (label_width + self.width as usize * 2));
+        for (label, datum) in data {
+            // Bars are drawn to the nearest half a character, and a value above zero always
+            // gets at least half of one.
+            let mut halves = if max > 0. {
+                (datum.max(0.) / max * f64::from(self.width) * 2.).round() as usize
+            } else {
+                0
+            };
+            if datum > 0. && halves == 0 {
+                halves = 1;
+            }
+            let mut bar = "█".repeat(halves / 2);
+            if halves % 2 == 1 {
+                bar.push('▌');
+            }
+            ret.push_str(&format!(
+                "{:<label_width$} │{:<bar_width$}{}\n",
+                label,
+                bar,
+                self.label(datum),
+                label_width = label_width,
+                bar_width = self.width as usize
+            ));
+        }
+        ret
+    }
+
+    /// Build a chart of layers stacked on top of each other into a string, with a legend of
+    /// the labels of the layers beneath it. Each layer is drawn with its own character and
+    /// every layer has a value for each column. The axis starts at zero.
+    pub fn stacked(&self, layers: &[(&str, char, Vec<f64>)]) -> String {
+        let columns = layers.iter().map(|(_, _, data)| data.len()).max().unwrap_or(0);
+        let totals: Vec<f64> = (0..columns)
+            .map(|column| layers.iter().filter_map(|(_, _, data)| data.get(column)).sum())
+            .collect();
+        let max = totals.iter().cloned().fold(0., f64::max);
+        let row_increment = max / f64::from(self.height);
+        let mut ret = String::with_capacity((self.height as usize + 1) * columns * 2);
+        for row in 0..self.height {
+            // A cell shows the layer that covers its middle.
+            let middle = max - (f64::from(row) + 0.5) * row_increment;
+            for column in 0..columns {
+                let mut top = 0.;
+                let mut cell = self.space;
+                for (_, fill, data) in layers {
+                    top += data.get(column).cloned().unwrap_or(0.);
+                    if top > middle {
+                        cell = *fill;
+                        break;
+                    }
+                }
+                ret.push(cell);
+            }
+            if row == 0 {
+                ret.push_str(&self.label(max));
+            }
+            if row == self.height - 1 {
+                ret.push_str(&self.label(0.));
+            }
+            ret.push('\n');
+        }
+        let legend: Vec<String> = layers.iter().map(|(label, fill, _)| format!("{} {}", fill, label)).collect();
+        ret.push_str(&legend.join("  "));
+        ret.push('\n');
+        ret
+    }
+
+    /// Build a chart of series drawn over each other on the same axis into a string, with a
+    /// legend beneath it, so that where they differ stands out. A cell under every series is
+    /// drawn with the shared character, and one under only some of them with the character
+    /// of the first of those. The axis starts at zero.
+    pub fn overlaid(&self, series: &[(&str, char, Vec<f64>)], shared: (&str, char)) -> String {
+        let columns = series.iter().map(|(_, _, data)| data.len()).max().unwrap_or(0);
+        let max = series
+            .iter()
+            .flat_map(|(_, _, data)| data.iter().cloned())
+            .fold(0., f64::max);
+        let row_increment = max / f64::from(self.height);
+        let mut ret = String::with_capacity((self.height as usize + 1) * columns * 2);
+        for row in 0..self.height {
+            // A cell is under a series that reaches its middle.
+            let middle = max - (f64::from(row) + 0.5) * row_increment;
+            for column in 0..columns {
+                let under: Vec<char> = series
+                    .iter()
+                    .filter(|(_, _, data)| data.get(column).is_some_and(|datum| *datum > middle))
+                    .map(|(_, fill, _)| *fill)
+                    .collect();
+                ret.push(match under.len() {
+                    0 => self.space,
+                    n if n == series.len() => shared.1,
+                    _ => under[0],
+                });
+            }
+            if row == 0 {
+                ret.push_str(&self.label(max));
             }
             if row == self.height - 1 {
-                ret.push_str(&format!(" {}", min));
+                ret.push_str(&self.label(0.));
             }
             ret.push('\n');
         }
+        let mut legend: Vec<String> = series.iter().map(|(label, fill, _)| format!("{} {}", fill, label)).collect();
+        legend.push(format!("{} {}", shared.1, shared.0));
+        ret.push_str(&legend.join("  "));
+        ret.push('\n');
         ret
     }
 }
 
+/// Renders the data as a single line of block characters, scaled from the smallest to the
+/// largest value, so a trend fits on the end of a line of output.
+pub fn sparkline(data: &[f64]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let min = data.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = data.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let range = max - min;
+    data.iter()
+        .map(|datum| {
+            if range > 0. {
+                let level = ((datum - min) / range * (BARS.len() - 1) as f64).round();
+                BARS[level as usize]
+            } else {
+                BARS[0]
+            }
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn it_makes_a_chart_of_default_height() {
-        let chart = Chart::new().make(&vec![1, 2, 3, 4, 3, 2, 1]);
+        let chart = Chart::new().make(&[1, 2, 3, 4, 3, 2, 1]);
         assert_eq!(
             chart,
             "   ▌    4
@@ -88,7 +257,7 @@ mod tests {
 
     #[test]
     fn it_can_change_the_height() {
-        let chart = Chart::new().height(4).make(&vec![1, 2, 3, 4, 3, 2, 1]);
+        let chart = Chart::new().height(4).make(&[1, 2, 3, 4, 3, 2, 1]);
         assert_eq!(
             chart,
             "   ▌    4
@@ -98,4 +267,43 @@ mod tests {
 "
         );
     }
+
+    #[test]
+    fn it_labels_the_axis_with_a_unit() {
+        let chart = Chart::new().height(2).unit("ms").make(&[0.5, 1.25]);
+        assert_eq!(chart, " ▌ 1.25 ms\n▌▌ 0.00 ms\n");
+    }
+
+    #[test]
+    fn it_makes_labelled_bars() {
+        let chart = Chart::new().width(4).bars(&[("200", 8), ("503", 3), ("504", 0)]);
+        assert_eq!(chart, "200 │████ 8\n503 │█▌   3\n504 │     0\n");
+        let chart = Chart::new().width(4).unit("ms").bars(&[("a".to_string(), 100.), ("b".to_string(), 1.)]);
+        assert_eq!(chart, "a │████ 100.00 ms\nb │▌    1.00 ms\n");
+    }
+
+    #[test]
+    fn it_stacks_the_layers() {
+        let chart = Chart::new().height(4).stacked(&[
+            ("low", '░', vec![2., 1., 0.]),
+            ("high", '█', vec![0., 3., 1.]),
+        ]);
+        assert_eq!(chart, " █  4\n █ \n░█ \n░░█ 0\n░ low  █ high\n");
+    }
+
+    #[test]
+    fn it_overlays_the_series() {
+        let chart = Chart::new().height(4).overlaid(
+            &[("before", '░', vec![1., 2., 4.]), ("after", '▓', vec![1., 3., 2.])],
+            ("both", '█'),
+        );
+        assert_eq!(chart, "  ░ 4\n ▓░\n ██\n███ 0\n░ before  ▓ after  █ both\n");
+    }
+
+    #[test]
+    fn it_makes_a_sparkline() {
+        assert_eq!(sparkline(&[1., 2., 3., 4., 3., 2., 1.]), "▁▃▆█▆▃▁");
+        assert_eq!(sparkline(&[5., 5.]), "▁▁");
+        assert_eq!(sparkline(&[]), "");
+    }
 }
@@ -0,0 +1,178 @@
+use accumulator::Accumulator;
+use clock::{self, Clock};
+use content_length::SizeUnit;
+use log;
+use stats::{Fact, Summary, TimeUnit};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Writes a summary of the run every interval, so that a long soak test shows how the
+/// server held up over time where the summary of the whole run would hide it. Each
+/// checkpoint covers the run so far, or only the requests since the last checkpoint when
+/// the window is reset. A checkpoint is written as the first request finishes after it's due.
+pub struct Checkpoints {
+    interval: Duration,
+    clock: Arc<dyn Clock>,
+    start: Instant,
+    /// When the next checkpoint is due, into the run.
+    due: Duration,
+    /// When the last checkpoint was written, into the run.
+    last: Duration,
+    written: u32,
+    window: Accumulator,
+    reset: bool,
+    path: Option<String>,
+    time_unit: TimeUnit,
+    size_unit: SizeUnit,
+}
+
+impl Checkpoints {
+    /// Writes a checkpoint every interval from now, alongside the progress lines unless a
+    /// file is given.
+    pub fn new(interval: Duration) -> Checkpoints {
+        Checkpoints {
+            interval,
+            clock: clock::system(),
+            start: Instant::now(),
+            due: interval,
+            last: Duration::new(0, 0),
+            written: 0,
+            window: Accumulator::new(),
+            reset: false,
+            path: None,
+            time_unit: TimeUnit::Auto,
+            size_unit: SizeUnit::Binary,
+        }
+    }
+
+    /// Times the checkpoints by the clock instead, from now by it.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.start = clock.now();
+        self.clock = clock;
+        self
+    }
+
+    /// Appends the checkpoints to the file instead, creating it if needed.
+    pub fn with_file(mut self, path: &str) -> Self {
+        self.path = Some(path.to_string());
+        self
+    }
+
+    /// Summarizes only the requests since the last checkpoint in each checkpoint.
+    pub fn with_reset_window(mut self) -> Self {
+        self.reset = true;
+        self
+    }
+
+    pub fn with_time_unit(mut self, unit: TimeUnit) -> Self {
+        self.time_unit = unit;
+        self
+    }
+
+    pub fn with_size_unit(mut self, unit: SizeUnit) -> Self {
+        self.size_unit = unit;
+        self
+    }
+
+    /// Counts a request that has just finished, writing a checkpoint if one is due.
+    pub fn record(&mut self, fact: Fact) -> io::Result<()> {
+        self.window.add(fact);
+        let elapsed = self.clock.elapsed(self.start);
+        if elapsed < self.due {
+            return Ok(());
+        }
+        let checkpoint = self.checkpoint(elapsed);
+        self.written += 1;
+        self.last = elapsed;
+        // Intervals without a request to write them are skipped rather than caught up on.
+        while self.due <= elapsed {
+            self.due += self.interval;
+        }
+        if self.reset {
+            self.window = Accumulator::new();
+        }
+        match self.path {
+            Some(ref path) => {
+                let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+                writeln!(file, "{}", checkpoint)
+            }
+            None => {
+                log::info(format_args!("{}", checkpoint));
+                Ok(())
+            }
+        }
+    }
+
+    fn checkpoint(&self, elapsed: Duration) -> String {
+        let from = if self.reset { self.last } else { Duration::new(0, 0) };
+        let seconds = (elapsed - from).as_secs_f64();
+        let summary = Summary::from_accumulator(&self.window)
+            .with_time_unit(self.time_unit)
+            .with_size_unit(self.size_unit);
+        format!(
+            "Checkpoint {} at {:.0} s, covering {:.0} s to {:.0} s\n{:.2} requests / second\n\n{}",
+            self.written + 1,
+            elapsed.as_secs_f64(),
+            from.as_secs_f64(),
+            elapsed.as_secs_f64(),
+            if seconds > 0. { self.window.count() as f64 / seconds } else { 0. },
+            summary
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clock::Manual;
+    use content_length::ContentLength;
+    use std::{env, fs, process};
+
+    fn fact(ms: u64) -> Fact {
+        Fact::record(ContentLength::new(1), 200, Duration::from_millis(ms))
+    }
+
+    #[test]
+    fn covers_the_run_so_far_or_the_last_window() {
+        let mut checkpoints = Checkpoints::new(Duration::from_secs(60));
+        checkpoints.window.add(fact(1));
+        checkpoints.window.add(fact(1));
+        let checkpoint = checkpoints.checkpoint(Duration::from_secs(60));
+        assert!(checkpoint.starts_with("Checkpoint 1 at 60 s, covering 0 s to 60 s\n0.03 requests / second"));
+
+        let mut checkpoints = Checkpoints::new(Duration::from_secs(60)).with_reset_window();
+        checkpoints.written = 2;
+        checkpoints.last = Duration::from_secs(120);
+        checkpoints.window.add(fact(1));
+        let checkpoint = checkpoints.checkpoint(Duration::from_secs(190));
+        assert!(checkpoint.starts_with("Checkpoint 3 at 190 s, covering 120 s to 190 s"));
+        assert!(checkpoint.contains("Requests:  1\n"));
+    }
+
+    #[test]
+    fn writes_once_the_first_request_after_it_is_due_finishes() {
+        let path = env::temp_dir().join(format!("rench-checkpoint-test-{}.txt", process::id()));
+        let _ = fs::remove_file(&path);
+        let clock = Manual::new();
+        let mut checkpoints = Checkpoints::new(Duration::from_secs(60))
+            .with_clock(Arc::new(clock.clone()))
+            .with_file(path.to_str().unwrap());
+        checkpoints.record(fact(1)).unwrap();
+        clock.advance(Duration::from_secs(59));
+        checkpoints.record(fact(1)).unwrap();
+        assert!(!path.exists());
+
+        // The checkpoints due at 60 s and 120 s are both written by the request at 150 s.
+        clock.advance(Duration::from_secs(91));
+        checkpoints.record(fact(1)).unwrap();
+        clock.advance(Duration::from_secs(40));
+        checkpoints.record(fact(1)).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert!(written.starts_with("Checkpoint 1 at 150 s, covering 0 s to 150 s\n0.02 requests / second"));
+        assert!(written.contains("\nCheckpoint 2 at 190 s, covering 0 s to 190 s\n"));
+        assert_eq!(written.matches("Checkpoint ").count(), 2);
+    }
+}
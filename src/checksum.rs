@@ -0,0 +1,110 @@
+use std::collections::{BTreeMap, HashMap};
+use xxhash_rust::xxh3::xxh3_64;
+
+/// The checksum of a response body and the url it came from.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Checksum {
+    pub url: usize,
+    pub hash: u64,
+}
+
+impl Checksum {
+    pub fn of(url: usize, body: &[u8]) -> Checksum {
+        Checksum {
+            url,
+            hash: xxh3_64(body),
+        }
+    }
+}
+
+/// How often each distinct body was served by each url. A url that should serve the same
+/// content every time but has several checksums is being served stale or mixed content,
+/// such as by a load balancer in front of servers on different deploys.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Checksums {
+    by_url: BTreeMap<usize, HashMap<u64, u64>>,
+}
+
+/// The bodies served by one url.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Variance {
+    pub responses: u64,
+    pub distinct: usize,
+    /// How many of the responses had the most common body.
+    pub most_common: u64,
+}
+
+impl Checksums {
+    pub fn add(&mut self, checksum: Checksum) {
+        *self
+            .by_url
+            .entry(checksum.url)
+            .or_default()
+            .entry(checksum.hash)
+            .or_insert(0) += 1;
+    }
+
+    pub fn merge(&mut self, other: &Checksums) {
+        for (&url, hashes) in &other.by_url {
+            let counts = self.by_url.entry(url).or_default();
+            for (&hash, &count) in hashes {
+                *counts.entry(hash).or_insert(0) += count;
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_url.is_empty()
+    }
+
+    /// The variance of the bodies of each url that was checksummed, by url index.
+    pub fn variances(&self) -> Vec<(usize, Variance)> {
+        self.by_url
+            .iter()
+            .map(|(&url, hashes)| {
+                let variance = Variance {
+                    responses: hashes.values().sum(),
+                    distinct: hashes.len(),
+                    most_common: hashes.values().cloned().max().unwrap_or(0),
+                };
+                (url, variance)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_distinct_bodies_per_url() {
+        let mut a = Checksums::default();
+        a.add(Checksum::of(0, b"v1"));
+        a.add(Checksum::of(0, b"v1"));
+        a.add(Checksum::of(1, b"v1"));
+        let mut b = Checksums::default();
+        b.add(Checksum::of(0, b"v2"));
+        a.merge(&b);
+
+        let variances = a.variances();
+        assert_eq!(
+            variances[0],
+            (
+                0,
+                Variance {
+                    responses: 3,
+                    distinct: 2,
+                    most_common: 2
+                }
+            )
+        );
+        assert_eq!(variances[1].1.distinct, 1);
+    }
+
+    #[test]
+    fn hashes_the_same_body_the_same_way() {
+        assert_eq!(Checksum::of(0, b"body"), Checksum::of(0, b"body"));
+        assert_ne!(Checksum::of(0, b"body"), Checksum::of(0, b"other"));
+    }
+}
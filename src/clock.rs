@@ -0,0 +1,101 @@
+use std::sync::Arc;
+#[cfg(test)]
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Tells the time and waits for whatever times, paces or checkpoints a run. Runs go by the
+/// system's clock, but a test can drive a clock of its own so that how a run is timed can
+/// be checked without waiting for it.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+
+    /// Waits for the duration to pass.
+    fn sleep(&self, duration: Duration);
+
+    /// How long it has been since the instant, which is none if it's yet to come.
+    fn elapsed(&self, since: Instant) -> Duration {
+        self.now().saturating_duration_since(since)
+    }
+}
+
+/// The system's clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct System;
+
+impl Clock for System {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration)
+    }
+}
+
+/// The system's clock, to be shared.
+pub fn system() -> Arc<dyn Clock> {
+    Arc::new(System)
+}
+
+/// A clock that only moves when it's advanced or slept on, and sleeping on it returns at
+/// once as though the time had passed. Its clones share the time.
+#[cfg(test)]
+#[derive(Clone)]
+pub struct Manual {
+    start: Instant,
+    elapsed: Arc<Mutex<Duration>>,
+}
+
+#[cfg(test)]
+impl Default for Manual {
+    fn default() -> Manual {
+        Manual::new()
+    }
+}
+
+#[cfg(test)]
+impl Manual {
+    pub fn new() -> Manual {
+        Manual {
+            start: Instant::now(),
+            elapsed: Arc::new(Mutex::new(Duration::new(0, 0))),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.elapsed.lock().expect("Expected the clock to be lockable") += by;
+    }
+
+    /// How far the clock has moved since it was made.
+    pub fn moved(&self) -> Duration {
+        *self.elapsed.lock().expect("Expected the clock to be lockable")
+    }
+}
+
+#[cfg(test)]
+impl Clock for Manual {
+    fn now(&self) -> Instant {
+        self.start + self.moved()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_manual_clock_moves_only_when_told() {
+        let clock = Manual::new();
+        let start = clock.now();
+        assert_eq!(clock.now(), start);
+        clock.advance(Duration::from_millis(5));
+        clock.clone().sleep(Duration::from_secs(60));
+        assert_eq!(clock.elapsed(start), Duration::from_millis(60_005));
+        assert_eq!(clock.elapsed(start + Duration::from_secs(120)), Duration::new(0, 0));
+    }
+}
@@ -1,48 +1,444 @@
-use std::{cmp, thread, sync::mpsc::{channel, Receiver, Sender}};
+use crossbeam_channel::{self, Receiver, SendError, TrySendError};
+use std::{cmp, fmt, thread};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use bench;
+use log;
 use message::Message;
 use plan::Plan;
+use report;
+
+/// How many requests are made between progress lines when there's no telling how many
+/// will be made.
+const UNPLANNED_PROGRESS: usize = 1000;
+
+/// How many facts a worker keeps before sending them to the collector together.
+pub const DEFAULT_BATCH: usize = 100;
+
+/// The longest a worker keeps a fact before sending it, however few it has, so that the
+/// progress and anything that stops the run keeps up with it.
+pub const DEFAULT_BATCH_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How many messages the collector's queue holds by default, each a fact or a batch of
+/// them.
+pub const DEFAULT_QUEUE: usize = 1024;
+
+/// When the queue is full and facts are sampled, one in this many waits for room and the
+/// rest are dropped.
+const SAMPLE_WHEN_FULL: u64 = 10;
+
+/// How many messages the collector's queue holds and what a worker does with its facts
+/// when it's full. The queue is bounded so that a collector that falls behind can't take
+/// all of the memory.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Queue {
+    pub size: usize,
+    pub when_full: WhenFull,
+}
+
+impl Default for Queue {
+    fn default() -> Queue {
+        Queue {
+            size: DEFAULT_QUEUE,
+            when_full: WhenFull::Block,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WhenFull {
+    /// The worker waits for room, which slows the run down to the collector's pace.
+    Block,
+    /// The facts are dropped and counted.
+    Drop,
+    /// One in ten facts waits for room and the rest are dropped and counted, so that the
+    /// results still have some of the facts made while the collector was behind.
+    Sample,
+}
 
 /// Kicks off the collector which is a background thread. The collector will capture
 /// all data sent to the sender and then will return on the handle the entire dataset.
 ///
 /// The plan is essential to pre-allocating the array.
-pub fn start<T>(plan: Plan) -> (Sender<Message<T>>, thread::JoinHandle<Vec<T>>)
+#[allow(dead_code)]
+pub fn start<T>(plan: Plan) -> (Sender<T>, thread::JoinHandle<Vec<T>>)
+where
+    T: 'static + Send,
+{
+    fold(plan, Queue::default(), Vec::with_capacity(plan.requests()), |messages, message| {
+        messages.push(message)
+    })
+}
+
+/// Kicks off a collector that folds each message into an accumulator as it arrives rather
+/// than keeping every message around. The accumulator is returned on the handle once all
+/// of the runners have finished.
+pub fn fold<T, A, F>(plan: Plan, queue: Queue, init: A, f: F) -> (Sender<T>, thread::JoinHandle<A>)
 where
     T: 'static + Send,
+    A: 'static + Send,
+    F: 'static + Send + FnMut(&mut A, T),
+{
+    fold_reporting(plan, queue, init, f, |_| None)
+}
+
+/// Like `fold`, but each progress line is followed by whatever the report returns for the
+/// accumulator at that point.
+pub fn fold_reporting<T, A, F, R>(
+    plan: Plan,
+    queue: Queue,
+    init: A,
+    f: F,
+    report: R,
+) -> (Sender<T>, thread::JoinHandle<A>)
+where
+    T: 'static + Send,
+    A: 'static + Send,
+    F: 'static + Send + FnMut(&mut A, T),
+    R: 'static + Send + FnMut(&mut A) -> Option<String>,
+{
+    let (sender, receiver) = crossbeam_channel::bounded::<Message<T>>(cmp::max(queue.size, 1));
+    let counters = Arc::new(Counters::default());
+    let sender = Sender {
+        inner: sender,
+        when_full: queue.when_full,
+        counters: counters.clone(),
+        batch: DEFAULT_BATCH,
+        batch_interval: DEFAULT_BATCH_INTERVAL,
+    };
+    (sender, thread::spawn(move || collect(&receiver, &counters, plan, init, f, report)))
+}
+
+/// The sending end of the collector's queue. It times how long the workers are held up
+/// sending their facts and keeps track of how many are waiting in the queue, since at a
+/// high enough rate the collector falls behind and becomes the bottleneck of the run.
+pub struct Sender<T: 'static + Send> {
+    inner: crossbeam_channel::Sender<Message<T>>,
+    when_full: WhenFull,
+    counters: Arc<Counters>,
+    batch: usize,
+    batch_interval: Duration,
+}
+
+#[derive(Default)]
+struct Counters {
+    queued: AtomicUsize,
+    deepest: AtomicUsize,
+    sent: AtomicU64,
+    dropped: AtomicU64,
+    /// How many messages found the queue full, to sample them by.
+    full: AtomicU64,
+    blocked_nanos: AtomicU64,
+}
+
+impl<T: 'static + Send> Clone for Sender<T> {
+    fn clone(&self) -> Sender<T> {
+        Sender {
+            inner: self.inner.clone(),
+            when_full: self.when_full,
+            counters: self.counters.clone(),
+            batch: self.batch,
+            batch_interval: self.batch_interval,
+        }
+    }
+}
+
+impl<T: 'static + Send> Sender<T> {
+    /// Has the batches of the workers send up to `size` facts at once, and keep none of
+    /// them for longer than the interval. A size of 1 sends each fact on its own.
+    pub fn with_batching(mut self, size: usize, interval: Duration) -> Sender<T> {
+        self.batch = cmp::max(size, 1);
+        self.batch_interval = interval;
+        self
+    }
+
+    pub fn send(&self, message: Message<T>) -> Result<(), SendError<Message<T>>> {
+        let counters = &self.counters;
+        let count = message.count();
+        let queued = counters.queued.fetch_add(count, Ordering::SeqCst) + count;
+        counters.deepest.fetch_max(queued, Ordering::SeqCst);
+        let (queued, took) = bench::time_it(|| self.queue(message));
+        counters.blocked_nanos.fetch_add(took.as_nanos() as u64, Ordering::Relaxed);
+        match queued {
+            Ok(true) => {
+                counters.sent.fetch_add(count as u64, Ordering::Relaxed);
+            }
+            Ok(false) => {
+                counters.queued.fetch_sub(count, Ordering::SeqCst);
+                counters.dropped.fetch_add(count as u64, Ordering::Relaxed);
+            }
+            Err(e) => {
+                counters.queued.fetch_sub(count, Ordering::SeqCst);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Puts the message on the queue, waiting for room when it's full only if the policy
+    /// says to. The end of file always waits. Returns whether it was queued rather than
+    /// dropped.
+    fn queue(&self, message: Message<T>) -> Result<bool, SendError<Message<T>>> {
+        if self.when_full == WhenFull::Block || message.count() == 0 {
+            return self.inner.send(message).map(|_| true);
+        }
+        match self.inner.try_send(message) {
+            Ok(()) => Ok(true),
+            Err(TrySendError::Disconnected(message)) => Err(SendError(message)),
+            Err(TrySendError::Full(message)) => {
+                let full = self.counters.full.fetch_add(1, Ordering::Relaxed);
+                if self.when_full == WhenFull::Sample && full.is_multiple_of(SAMPLE_WHEN_FULL) {
+                    self.inner.send(message).map(|_| true)
+                } else {
+                    Ok(false)
+                }
+            }
+        }
+    }
+
+    /// A batch for a worker to keep its facts in until there are enough to send.
+    pub fn batch(&self) -> Batch<'_, T> {
+        Batch {
+            sender: self,
+            facts: Vec::with_capacity(self.batch),
+            sent: Instant::now(),
+        }
+    }
+
+    /// How much sending to the collector has cost the workers so far.
+    pub fn overhead(&self) -> Overhead {
+        let counters = &self.counters;
+        Overhead {
+            sent: counters.sent.load(Ordering::Relaxed),
+            dropped: counters.dropped.load(Ordering::Relaxed),
+            blocked: Duration::from_nanos(counters.blocked_nanos.load(Ordering::Relaxed)),
+            deepest: counters.deepest.load(Ordering::SeqCst),
+        }
+    }
+}
+
+/// The facts a worker has made since it last sent any, which are sent together once there
+/// are enough of them or the oldest has waited long enough. At hundreds of thousands of
+/// requests a second a send for each fact is overhead enough to distort the results.
+/// Whatever is left is sent when the batch is dropped.
+pub struct Batch<'a, T: 'static + Send> {
+    sender: &'a Sender<T>,
+    facts: Vec<T>,
+    /// When the batch was last sent, which the oldest fact in it can't be older than.
+    sent: Instant,
+}
+
+impl<'a, T: 'static + Send> Batch<'a, T> {
+    pub fn push(&mut self, fact: T) -> Result<(), SendError<Message<T>>> {
+        self.facts.push(fact);
+        if self.facts.len() >= self.sender.batch || self.sent.elapsed() >= self.sender.batch_interval {
+            self.flush()
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn flush(&mut self) -> Result<(), SendError<Message<T>>> {
+        self.sent = Instant::now();
+        let message = match self.facts.len() {
+            0 => return Ok(()),
+            1 => Message::Body(self.facts.pop().expect("Has a fact")),
+            _ => Message::Batch(std::mem::replace(&mut self.facts, Vec::with_capacity(self.sender.batch))),
+        };
+        self.sender.send(message)
+    }
+}
+
+impl<'a, T: 'static + Send> Drop for Batch<'a, T> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// How many facts the workers sent the collector and how many were dropped because its
+/// queue was full, how long they were held up sending them in all, and the most facts
+/// that waited in the queue at once.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Overhead {
+    pub sent: u64,
+    pub dropped: u64,
+    pub blocked: Duration,
+    pub deepest: usize,
+}
+
+impl Overhead {
+    /// The overhead in the shape of the JSON report.
+    pub fn report(&self) -> report::Collector {
+        report::Collector {
+            sent: self.sent,
+            dropped: self.dropped,
+            blocked_seconds: self.blocked.as_secs_f64(),
+            deepest_queue: self.deepest,
+        }
+    }
+}
+
+impl fmt::Display for Overhead {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Sending {} facts to the collector held the workers up for {} seconds in all, and at most {} waited in its queue",
+            self.sent,
+            self.blocked.as_secs_f64(),
+            self.deepest
+        )?;
+        if self.dropped > 0 {
+            write!(f, "\n{} facts were dropped because the collector's queue was full", self.dropped)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs every message through an enrichment step before it's folded, which lets callers
+/// annotate or transform facts as they arrive, such as attaching a label derived from the
+/// url, so that the result flows into everything built from the accumulator.
+///
+/// ```
+/// use rench::accumulator::Accumulator;
+/// use rench::collector::{self, Queue};
+/// use rench::plan::Plan;
+/// use rench::stats::Fact;
+///
+/// let tier = |fact: Fact| {
+///     let tier = if fact.url() == Some(0) { "free" } else { "paid" };
+///     fact.with_label("tier", tier)
+/// };
+/// let plan = Plan::new(1, 100).unwrap();
+/// let fold = collector::enrich(tier, Accumulator::add);
+/// let (sender, collected) = collector::fold(plan, Queue::default(), Accumulator::new(), fold);
+/// ```
+pub fn enrich<T, A, E, F>(mut enrich: E, mut f: F) -> impl FnMut(&mut A, T)
+where
+    E: FnMut(T) -> T,
+    F: FnMut(&mut A, T),
 {
-    let (sender, receiver) = channel::<Message<T>>();
-    (sender, thread::spawn(move || collect(&receiver, plan)))
+    move |acc, message| f(acc, enrich(message))
 }
 
-fn collect<T>(receiver: &Receiver<Message<T>>, plan: Plan) -> Vec<T>
+fn collect<T, A, F, R>(
+    receiver: &Receiver<Message<T>>,
+    counters: &Counters,
+    plan: Plan,
+    mut acc: A,
+    mut f: F,
+    mut report: R,
+) -> A
 where
     T: 'static + Send,
+    F: FnMut(&mut A, T),
+    R: FnMut(&mut A) -> Option<String>,
 {
-    let chunk_size = cmp::max(plan.requests() / 10, 1);
+    // Without a planned number of requests, progress is reported every so many instead of
+    // every tenth of the way.
+    let chunk_size = match plan.requests() {
+        0 => UNPLANNED_PROGRESS,
+        requests => cmp::max(requests / 10, 1),
+    };
     let mut eof_count = 0;
-    let mut messages: Vec<T> = Vec::with_capacity(plan.requests());
+    let mut received: usize = 0;
+    let mut progress = Progress::new(plan.requests());
 
     while eof_count < plan.threads() {
-        match receiver.recv().expect("To receive correctly") {
-            Message::Body(message) => {
-                messages.push(message);
-                if (messages.len() % (chunk_size)) == 0 {
-                    println!("{} requests", messages.len());
+        let message = receiver.recv().expect("To receive correctly");
+        counters.queued.fetch_sub(message.count(), Ordering::SeqCst);
+        let mut fold = |message| {
+            progress.start(Instant::now());
+            f(&mut acc, message);
+            received += 1;
+            if received.is_multiple_of(chunk_size) {
+                let line = progress.line(received, Instant::now());
+                match report(&mut acc) {
+                    Some(report) => log::progress(format_args!("{}  {}", line, report)),
+                    None => log::progress(format_args!("{}", line)),
                 }
             }
+        };
+        match message {
+            Message::Body(message) => fold(message),
+            Message::Batch(messages) => messages.into_iter().for_each(fold),
             Message::EOF => eof_count += 1,
         }
     }
-    messages
+    acc
+}
+
+/// How far a run has got, how fast it's going and, when the number of requests is
+/// planned, how long it has left at the rate it's made them so far. The run is timed
+/// from its first fact, so the time the clients took to set up isn't counted.
+struct Progress {
+    planned: usize,
+    started: Option<Instant>,
+    /// When the last line was written and how many requests had been made by then, to
+    /// tell the current rate apart from the rate over the whole run.
+    last: Option<(Instant, usize)>,
+}
+
+impl Progress {
+    fn new(planned: usize) -> Progress {
+        Progress {
+            planned,
+            started: None,
+            last: None,
+        }
+    }
+
+    fn start(&mut self, now: Instant) {
+        self.started.get_or_insert(now);
+    }
+
+    fn line(&mut self, received: usize, now: Instant) -> String {
+        let started = *self.started.get_or_insert(now);
+        let (since, before) = self.last.unwrap_or((started, 0));
+        self.last = Some((now, received));
+        let rate = |requests: usize, took: Duration| match took.as_secs_f64() {
+            secs if secs > 0. => requests as f64 / secs,
+            _ => 0.,
+        };
+        let elapsed = now.duration_since(started);
+        let mut line = format!("{} requests", received);
+        if self.planned > 0 {
+            line.push_str(&format!("  {:>3.0}%", received as f64 * 100. / self.planned as f64));
+        }
+        line.push_str(&format!(
+            "  {:.0} req/s  elapsed {}",
+            rate(received - before, now.duration_since(since)),
+            clock(elapsed)
+        ));
+        let overall = rate(received, elapsed);
+        if self.planned > received && overall > 0. {
+            let left = Duration::from_secs_f64((self.planned - received) as f64 / overall);
+            line.push_str(&format!("  ETA {}", clock(left)));
+        }
+        line
+    }
+}
+
+/// The duration in minutes and seconds, with the hours in front once there are any.
+fn clock(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds / 3600 {
+        0 => format!("{}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("{}:{:02}:{:02}", hours, seconds / 60 % 60, seconds % 60),
+    }
 }
 
 #[cfg(test)]
 mod message_collection_tests {
     use super::*;
+    use accumulator::Accumulator;
+    use content_length::ContentLength;
+    use stats::{Fact, Summary};
+    use std::sync::mpsc::channel;
 
     #[test]
     fn it_ends_when_all_nones_are_received() {
-        let plan = Plan::new(4, 0);
+        let plan = Plan::new(4, 0).unwrap();
         let (tx, handle) = start::<usize>(plan);
         for _ in 0..4 {
             let _ = tx.send(Message::EOF);
@@ -52,7 +448,7 @@ mod message_collection_tests {
 
     #[test]
     fn it_collects_all_data_received() {
-        let plan = Plan::new(1, 0);
+        let plan = Plan::new(1, 0).unwrap();
         let (tx, handle) = start::<usize>(plan);
         for n in 0..5 {
             let _ = tx.send(Message::Body(n as usize));
@@ -60,4 +456,170 @@ mod message_collection_tests {
         let _ = tx.send(Message::EOF);
         assert_eq!(handle.join().unwrap(), vec![0, 1, 2, 3, 4]);
     }
+
+    #[test]
+    fn it_measures_the_queue() {
+        let (go, wait) = channel();
+        // The collector is held up by the first message until the rest are queued.
+        let (tx, handle) = fold(Plan::new(1, 0).unwrap(), Queue::default(), Vec::new(), move |all: &mut Vec<usize>, n| {
+            if n == 0 {
+                wait.recv().unwrap();
+            }
+            all.push(n);
+        });
+        for n in 0..5 {
+            tx.send(Message::Body(n)).unwrap();
+        }
+        go.send(()).unwrap();
+        tx.send(Message::EOF).unwrap();
+        assert_eq!(handle.join().unwrap(), vec![0, 1, 2, 3, 4]);
+
+        let overhead = tx.overhead();
+        assert_eq!(overhead.sent, 5);
+        assert!(overhead.deepest >= 4);
+        assert!(overhead.to_string().starts_with("Sending 5 facts to the collector held the workers up for "));
+    }
+
+    #[test]
+    fn it_drops_facts_when_the_queue_is_full() {
+        let (go, wait) = channel();
+        let queue = Queue {
+            size: 1,
+            when_full: WhenFull::Drop,
+        };
+        let (tx, handle) = fold(Plan::new(1, 0).unwrap(), queue, Vec::new(), move |all: &mut Vec<usize>, n| {
+            if n == 0 {
+                wait.recv().unwrap();
+            }
+            all.push(n);
+        });
+        tx.send(Message::Body(0)).unwrap();
+        // The collector is held up by the first fact once it's taken it off the queue.
+        while tx.counters.queued.load(Ordering::SeqCst) > 0 {
+            thread::yield_now();
+        }
+        for n in 1..5 {
+            tx.send(Message::Body(n)).unwrap();
+        }
+        go.send(()).unwrap();
+        tx.send(Message::EOF).unwrap();
+        assert_eq!(handle.join().unwrap(), vec![0, 1]);
+
+        let overhead = tx.overhead();
+        assert_eq!((overhead.sent, overhead.dropped), (2, 3));
+        assert!(overhead.to_string().ends_with("\n3 facts were dropped because the collector's queue was full"));
+    }
+
+    #[test]
+    fn it_sends_facts_in_batches() {
+        let (tx, handle) = start::<usize>(Plan::new(1, 0).unwrap());
+        let tx = tx.with_batching(3, Duration::from_secs(60));
+        {
+            let mut batch = tx.batch();
+            for n in 0..7 {
+                batch.push(n).unwrap();
+            }
+            assert_eq!(tx.overhead().sent, 6);
+        }
+        assert_eq!(tx.overhead().sent, 7);
+        tx.send(Message::EOF).unwrap();
+        assert_eq!(handle.join().unwrap(), vec![0, 1, 2, 3, 4, 5, 6]);
+
+        let (tx, handle) = start::<usize>(Plan::new(1, 0).unwrap());
+        let tx = tx.with_batching(100, Duration::new(0, 0));
+        tx.batch().push(1).unwrap();
+        assert_eq!(tx.overhead().sent, 1);
+        tx.send(Message::EOF).unwrap();
+        assert_eq!(handle.join().unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn it_folds_all_data_received() {
+        let plan = Plan::new(2, 0).unwrap();
+        let (tx, handle) = fold(plan, Queue::default(), 0, |sum, n: usize| *sum += n);
+        for n in 0..5 {
+            let _ = tx.send(Message::Body(n));
+        }
+        let _ = tx.send(Message::EOF);
+        let _ = tx.send(Message::EOF);
+        assert_eq!(handle.join().unwrap(), 10);
+    }
+
+    #[test]
+    fn it_reports_at_each_progress_line() {
+        let plan = Plan::new(1, 10).unwrap();
+        let (tx, handle) = fold_reporting(
+            plan,
+            Queue::default(),
+            (0, 0),
+            |&mut (ref mut sum, _), n: usize| *sum += n,
+            |&mut (_, ref mut reports)| {
+                *reports += 1;
+                None
+            },
+        );
+        for n in 0..10 {
+            let _ = tx.send(Message::Body(n));
+        }
+        let _ = tx.send(Message::EOF);
+        assert_eq!(handle.join().unwrap(), (45, 10));
+    }
+
+    #[test]
+    fn it_tells_how_long_is_left() {
+        let start = Instant::now();
+        let mut progress = Progress::new(1_000);
+        progress.start(start);
+        assert_eq!(
+            progress.line(100, start + Duration::from_secs(10)),
+            "100 requests   10%  10 req/s  elapsed 0:10  ETA 1:30"
+        );
+        assert_eq!(
+            progress.line(600, start + Duration::from_secs(20)),
+            "600 requests   60%  50 req/s  elapsed 0:20  ETA 0:13"
+        );
+        assert_eq!(progress.line(1_000, start + Duration::from_secs(30)), "1000 requests  100%  40 req/s  elapsed 0:30");
+
+        let mut unplanned = Progress::new(0);
+        unplanned.start(start);
+        assert_eq!(
+            unplanned.line(1_000, start + Duration::from_secs(4_000)),
+            "1000 requests  0 req/s  elapsed 1:06:40"
+        );
+    }
+
+    #[test]
+    fn it_enriches_data_before_folding() {
+        let plan = Plan::new(1, 0).unwrap();
+        let (tx, handle) = fold(
+            plan,
+            Queue::default(),
+            Vec::new(),
+            enrich(|n: usize| n * 10, |all: &mut Vec<usize>, n| all.push(n)),
+        );
+        for n in 0..3 {
+            let _ = tx.send(Message::Body(n));
+        }
+        let _ = tx.send(Message::EOF);
+        assert_eq!(handle.join().unwrap(), vec![0, 10, 20]);
+    }
+
+    #[test]
+    fn enriched_labels_flow_into_the_reports() {
+        let tier = |fact: Fact| {
+            let tier = if fact.url() == Some(0) { "free" } else { "paid" };
+            fact.with_label("tier", tier)
+        };
+        let plan = Plan::new(1, 3).unwrap();
+        let (tx, handle) = fold(plan, Queue::default(), Accumulator::new(), enrich(tier, Accumulator::add));
+        for url in &[0, 1, 1] {
+            let fact = Fact::record(ContentLength::zero(), 200, Duration::from_millis(10)).with_url(*url);
+            tx.send(Message::Body(fact)).unwrap();
+        }
+        tx.send(Message::EOF).unwrap();
+        let results = Summary::from_accumulator(&handle.join().unwrap()).results();
+        assert_eq!(results.labels["tier=free"].count, 1);
+        assert_eq!(results.labels["tier=paid"].count, 2);
+        assert_eq!(results.labels["tier=paid"].average_ms, 10.);
+    }
 }
@@ -0,0 +1,178 @@
+use std::time::{Duration, Instant};
+
+/// The most columns that a chart keeps. Once a run outgrows them each pair of neighbouring
+/// columns is merged, so a column covers twice the time and memory stays bounded however
+/// long the run.
+pub const MAX_COLUMNS: usize = 128;
+
+/// How much of the run each column of a chart covers to begin with.
+pub const FIRST_COLUMN_MS: u64 = 10;
+
+/// The most columns that a chart prints. The kept columns are merged down to fit.
+pub const PRINTED_COLUMNS: usize = 64;
+
+/// What's counted in a column, which can take in the counts of a neighbouring column when
+/// the columns are merged.
+pub trait Column: Clone + Default {
+    fn merge(&mut self, other: &Self);
+}
+
+/// The count of each row, where a column that has counted fewer rows has none in the rest.
+impl Column for Vec<u64> {
+    fn merge(&mut self, other: &Self) {
+        if self.len() < other.len() {
+            self.resize(other.len(), 0);
+        }
+        for (count, other) in self.iter_mut().zip(other) {
+            *count += other;
+        }
+    }
+}
+
+/// The count of each status family.
+impl Column for [u64; 6] {
+    fn merge(&mut self, other: &Self) {
+        for (count, other) in self.iter_mut().zip(other) {
+            *count += other;
+        }
+    }
+}
+
+/// A total and the count that makes it up, to be averaged.
+impl Column for (f64, u64) {
+    fn merge(&mut self, other: &Self) {
+        self.0 += other.0;
+        self.1 += other.1;
+    }
+}
+
+/// The run split into columns of time by when each request finished, which the charts that
+/// show how a run went over its course count into. The columns start out narrow and are
+/// widened by merging each pair of neighbours whenever the run outgrows them.
+#[derive(Debug, Clone)]
+pub struct Columns<T> {
+    start: Instant,
+    width: Duration,
+    most: usize,
+    columns: Vec<T>,
+}
+
+impl<T: Column> Default for Columns<T> {
+    fn default() -> Columns<T> {
+        Columns::new(Duration::from_millis(FIRST_COLUMN_MS), MAX_COLUMNS)
+    }
+}
+
+impl<T: Column> Columns<T> {
+    /// Starts columns of the width whose time starts now, keeping at most `most` of them.
+    pub fn new(width: Duration, most: usize) -> Columns<T> {
+        Columns {
+            start: Instant::now(),
+            width,
+            most,
+            columns: Vec::new(),
+        }
+    }
+
+    /// How long it's been since the columns started.
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// The column that covers the time into the run, widening the columns until one does.
+    pub fn at(&mut self, elapsed: Duration) -> &mut T {
+        let mut column = self.column_of(elapsed);
+        while column >= self.most {
+            self.columns = merge(&self.columns, 2);
+            self.width *= 2;
+            column = self.column_of(elapsed);
+        }
+        if self.columns.len() <= column {
+            self.columns.resize(column + 1, T::default());
+        }
+        &mut self.columns[column]
+    }
+
+    fn column_of(&self, elapsed: Duration) -> usize {
+        (elapsed.as_nanos() / self.width.as_nanos()) as usize
+    }
+
+    /// How much of the run each column covers.
+    pub fn width(&self) -> Duration {
+        self.width
+    }
+
+    /// The columns up to the last one that counted anything.
+    pub fn columns(&self) -> &[T] {
+        &self.columns
+    }
+
+    /// The columns merged down to the most that a chart prints, with how much of the run
+    /// they cover between them.
+    pub fn printed(&self) -> (Vec<T>, Duration) {
+        let factor = self.columns.len().div_ceil(PRINTED_COLUMNS).max(1);
+        let columns = merge(&self.columns, factor);
+        let covers = self.width * (columns.len() * factor) as u32;
+        (columns, covers)
+    }
+}
+
+/// Merges every `factor` neighbouring columns into one.
+fn merge<T: Column>(columns: &[T], factor: usize) -> Vec<T> {
+    columns
+        .chunks(factor)
+        .map(|chunk| {
+            let mut merged = chunk[0].clone();
+            for column in &chunk[1..] {
+                merged.merge(column);
+            }
+            merged
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn doubles_the_columns_once_the_run_outgrows_them() {
+        let mut columns: Columns<(f64, u64)> = Columns::default();
+        columns.at(ms(0)).1 += 1;
+        columns.at(ms(FIRST_COLUMN_MS)).1 += 1;
+        columns.at(ms(FIRST_COLUMN_MS * MAX_COLUMNS as u64)).1 += 1;
+        assert_eq!(columns.width(), ms(FIRST_COLUMN_MS * 2));
+        assert_eq!(columns.columns().len(), MAX_COLUMNS / 2 + 1);
+        assert_eq!(columns.columns()[0], (0., 2));
+        assert_eq!(columns.columns()[MAX_COLUMNS / 2], (0., 1));
+    }
+
+    #[test]
+    fn merges_down_to_the_printed_columns() {
+        let mut columns: Columns<Vec<u64>> = Columns::default();
+        assert_eq!(columns.printed(), (Vec::new(), ms(0)));
+        columns.at(ms(0)).push(1);
+        *columns.at(ms(FIRST_COLUMN_MS)) = vec![0, 2];
+        columns.at(ms(FIRST_COLUMN_MS * PRINTED_COLUMNS as u64)).push(3);
+        let (printed, covers) = columns.printed();
+        assert_eq!(printed.len(), PRINTED_COLUMNS / 2 + 1);
+        assert_eq!(printed[0], vec![1, 2]);
+        assert_eq!(printed[PRINTED_COLUMNS / 2], vec![3]);
+        assert_eq!(covers, ms(FIRST_COLUMN_MS * (PRINTED_COLUMNS as u64 + 2)));
+    }
+
+    #[test]
+    fn merges_the_counts_of_each_kind_of_column() {
+        let mut families = [1, 0, 0, 0, 2, 0];
+        families.merge(&[0, 1, 0, 0, 1, 3]);
+        assert_eq!(families, [1, 1, 0, 0, 3, 3]);
+
+        let mut average = (2.5, 1);
+        average.merge(&(1.5, 3));
+        assert_eq!(average, (4., 4));
+    }
+}
@@ -0,0 +1,248 @@
+use chart::Chart;
+use report::Report;
+use stats::{self, ChartSize, TimeUnit};
+use std::fmt;
+use std::time::Duration;
+
+/// A run side by side with a baseline, such as the same benchmark before and after a
+/// change, with how much each measure moved.
+pub struct Comparison {
+    rows: Vec<Row>,
+    /// The latency in milliseconds at each percentile from 0 to 99 of the baseline and of
+    /// the run, when both reports have them.
+    percentiles: Option<(Vec<f64>, Vec<f64>)>,
+    time_unit: TimeUnit,
+    chart_size: ChartSize,
+}
+
+struct Row {
+    name: &'static str,
+    measure: Measure,
+    baseline: Option<f64>,
+    run: Option<f64>,
+    /// Whether the run fails when the measure regresses too far.
+    gated: bool,
+}
+
+/// A measure that got worse than the baseline by more than was allowed.
+#[derive(Debug, PartialEq)]
+pub struct Regression {
+    pub name: &'static str,
+    /// How much worse it got, as a fraction of the baseline.
+    pub by: f64,
+}
+
+#[derive(Clone, Copy)]
+enum Measure {
+    /// Requests per second.
+    Rate,
+    /// A latency in milliseconds.
+    Latency,
+    /// A fraction of the requests.
+    Ratio,
+}
+
+impl Comparison {
+    pub fn new(baseline: &Report, run: &Report) -> Comparison {
+        let row = |name, measure, of: &dyn Fn(&Report) -> Option<f64>| Row {
+            name,
+            measure,
+            baseline: of(baseline),
+            run: of(run),
+            gated: false,
+        };
+        let percentiles = |report: &Report| {
+            (0..100)
+                .map(|percentile| report.results.quantile_ms(f64::from(percentile) / 100.))
+                .collect::<Option<Vec<f64>>>()
+        };
+        Comparison {
+            rows: vec![
+                Row {
+                    gated: true,
+                    ..row("Requests/sec", Measure::Rate, &|r| Some(r.requests_per_second))
+                },
+                row("Average", Measure::Latency, &|r| Some(r.results.latency_ms.average)),
+                row("Median", Measure::Latency, &|r| Some(r.results.latency_ms.median)),
+                row("p90", Measure::Latency, &|r| r.results.quantile_ms(0.9)),
+                Row {
+                    gated: true,
+                    ..row("p99", Measure::Latency, &|r| r.results.quantile_ms(0.99))
+                },
+                row("Longest", Measure::Latency, &|r| Some(r.results.latency_ms.max)),
+                row("Failures", Measure::Ratio, &|r| match r.results.count {
+                    0 => None,
+                    count => Some(f64::from(r.results.failures) / f64::from(count)),
+                }),
+            ],
+            percentiles: percentiles(baseline).and_then(|baseline| percentiles(run).map(|run| (baseline, run))),
+            time_unit: TimeUnit::Auto,
+            chart_size: ChartSize::Medium,
+        }
+    }
+
+    pub fn with_time_unit(mut self, unit: TimeUnit) -> Self {
+        self.time_unit = unit;
+        self
+    }
+
+    pub fn with_chart_size(mut self, size: ChartSize) -> Self {
+        self.chart_size = size;
+        self
+    }
+
+    /// The latency percentiles of the baseline and the run drawn over each other, so that
+    /// it shows how the whole distribution moved. Empty when either report has no
+    /// percentiles or the charts are off.
+    pub fn chart(&self) -> String {
+        let (baseline, run) = match self.percentiles {
+            Some((ref baseline, ref run)) => (baseline, run),
+            None => return String::new(),
+        };
+        let (height, scale) = match self.chart_size.dimensions() {
+            Some(dimensions) => dimensions,
+            None => return String::new(),
+        };
+        let duration = |ms: f64| Duration::from_secs_f64(ms / 1_000.);
+        let max = baseline.iter().chain(run).cloned().fold(0., f64::max);
+        let unit = self.time_unit.resolve(duration(max));
+        let curve = |percentiles: &[f64]| -> Vec<f64> {
+            stats::scale_array(percentiles, scale).into_iter().map(|ms| unit.value(duration(ms))).collect()
+        };
+        format!(
+            "Latency Percentiles ({}% of requests per column):\n{}",
+            scale,
+            Chart::new().height(height).unit(unit.symbol()).overlaid(
+                &[("Baseline", '░', curve(baseline)), ("Run", '▓', curve(run))],
+                ("Both", '█')
+            )
+        )
+    }
+
+    /// The p99 latency and the rate of requests, if either got worse than the baseline by
+    /// more than the fraction of it. A higher latency and a lower rate are worse.
+    pub fn regressions(&self, max: f64) -> Vec<Regression> {
+        self.rows
+            .iter()
+            .filter(|row| row.gated)
+            .filter_map(|row| match (row.baseline, row.run) {
+                (Some(baseline), Some(run)) if baseline > 0. => {
+                    let worse = match row.measure {
+                        Measure::Rate => baseline - run,
+                        Measure::Latency | Measure::Ratio => run - baseline,
+                    };
+                    Some(Regression {
+                        name: row.name,
+                        by: worse / baseline,
+                    })
+                }
+                _ => None,
+            })
+            .filter(|regression| regression.by > max)
+            .collect()
+    }
+
+    fn format(&self, measure: Measure, value: Option<f64>) -> String {
+        match (measure, value) {
+            (_, None) => "-".to_string(),
+            (Measure::Rate, Some(rate)) => format!("{:.2}", rate),
+            (Measure::Latency, Some(ms)) => self.time_unit.format(Duration::from_secs_f64(ms / 1_000.)),
+            (Measure::Ratio, Some(ratio)) => format!("{:.2}%", ratio * 100.),
+        }
+    }
+}
+
+/// How much the run moved from the baseline, as a percentage of the baseline.
+fn change(baseline: Option<f64>, run: Option<f64>) -> String {
+    match (baseline, run) {
+        (Some(baseline), Some(run)) if baseline > 0. => format!("{:+.2}%", (run - baseline) * 100. / baseline),
+        _ => "-".to_string(),
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "  {:<14}{:>14}{:>14}{:>12}", "", "Baseline", "Run", "Change")?;
+        for row in &self.rows {
+            writeln!(
+                f,
+                "  {:<14}{:>14}{:>14}{:>12}",
+                row.name,
+                self.format(row.measure, row.baseline),
+                self.format(row.measure, row.run),
+                change(row.baseline, row.run)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use content_length::ContentLength;
+    use metadata::Metadata;
+    use stats::{Fact, Summary};
+    use warning::Warnings;
+
+    fn report(ms: &[u64], took_seconds: f64) -> Report {
+        let facts: Vec<Fact> = ms
+            .iter()
+            .map(|&ms| Fact::record(ContentLength::zero(), 200, Duration::from_millis(ms)))
+            .collect();
+        let metadata = Metadata::new(&["rench".to_string()], vec!["http://a/".to_string()], "hyper", 1, ms.len(), 1);
+        Report::new(&metadata, &Warnings::new(), took_seconds, Summary::from_facts(&facts).results())
+    }
+
+    #[test]
+    fn puts_a_run_beside_its_baseline() {
+        let baseline = report(&[10, 10, 10, 20], 1.);
+        let run = report(&[10, 10, 20, 40], 2.);
+        let printed = Comparison::new(&baseline, &run).with_time_unit(TimeUnit::Millis).to_string();
+        assert!(printed.starts_with("                      Baseline           Run      Change\n"));
+        assert!(printed.contains("  Requests/sec            4.00          2.00     -50.00%\n"));
+        assert!(printed.contains("  Average             12.50 ms      20.00 ms     +60.00%\n"));
+        assert!(printed.contains("  Longest             20.00 ms      40.00 ms    +100.00%\n"));
+        assert!(printed.ends_with("  Failures               0.00%         0.00%           -\n"));
+    }
+
+    #[test]
+    fn overlays_the_percentiles_of_both_runs() {
+        let baseline = report(&[10; 100], 1.);
+        let slower = report(&[20; 100], 1.);
+        let chart = Comparison::new(&baseline, &slower)
+            .with_time_unit(TimeUnit::Millis)
+            .with_chart_size(ChartSize::Small)
+            .chart();
+        let lines: Vec<&str> = chart.lines().collect();
+        assert_eq!(lines[0], "Latency Percentiles (3% of requests per column):");
+        assert!(lines[1].starts_with(&"▓".repeat(34)));
+        assert!(lines[1].ends_with(" ms"));
+        assert!(lines[7].starts_with(&"█".repeat(34)));
+        assert_eq!(lines[8], "░ Baseline  ▓ Run  █ Both");
+
+        let charts_off = Comparison::new(&baseline, &slower).with_chart_size(ChartSize::None);
+        assert_eq!(charts_off.chart(), "");
+    }
+
+    #[test]
+    fn finds_the_regressions_past_the_threshold() {
+        let baseline = report(&[10; 100], 1.);
+        let slower = report(&[12; 100], 1.);
+        let comparison = Comparison::new(&baseline, &slower);
+        let regressions = comparison.regressions(0.1);
+        assert_eq!(regressions.len(), 1);
+        assert_eq!(regressions[0].name, "p99");
+        assert!((regressions[0].by - 0.2).abs() < 0.01);
+        assert_eq!(comparison.regressions(0.25), Vec::new());
+
+        let fewer = report(&[10; 100], 2.);
+        assert_eq!(
+            Comparison::new(&baseline, &fewer).regressions(0.1),
+            vec![Regression {
+                name: "Requests/sec",
+                by: 0.5
+            }]
+        );
+    }
+}
@@ -4,7 +4,7 @@ use std::fmt;
 /// Represents the content length of an http request. The ContentLength is
 /// a scalar value that represents the number of bytes (octets) in the
 /// payload of the request. This does not include header sizes.
-#[derive(Debug, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
 pub struct ContentLength(u64);
 
 impl ContentLength {
@@ -22,24 +22,66 @@ impl ContentLength {
     pub fn bytes(&self) -> u64 {
         self.0
     }
+
+    /// Parses a size given in bytes or with a unit, in the same powers of 1024 that sizes
+    /// are printed in. Example '10GB' or '512 KB'.
+    pub fn parse(value: &str) -> Result<ContentLength, String> {
+        let value = value.trim();
+        let split = value.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(value.len());
+        let (number, unit) = (&value[..split], value[split..].trim());
+        let multiplier: u64 = match unit.to_ascii_uppercase().as_str() {
+            "" | "B" => 1,
+            "KB" | "K" => 1024,
+            "MB" | "M" => 1024 * 1024,
+            "GB" | "G" => 1024 * 1024 * 1024,
+            "TB" | "T" => 1024 * 1024 * 1024 * 1024,
+            _ => return Err(format!("Unknown unit '{}' in '{}', expected B, KB, MB, GB or TB", unit, value)),
+        };
+        let number = number
+            .parse::<f64>()
+            .map_err(|_| format!("Expected a size such as '10GB', got '{}'", value))?;
+        Ok(ContentLength((number * multiplier as f64).round() as u64))
+    }
 }
 
-impl fmt::Display for ContentLength {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        const GIGS: u64 = 1024 * 1024 * 1024;
-        const MEGS: u64 = 1024 * 1024;
-        const KILO: u64 = 1024;
-
-        if self.0 > GIGS {
-            write!(f, "{:0.2} GB", self.0 as f64 / GIGS as f64)?;
-        } else if self.0 > MEGS {
-            write!(f, "{:0.2} MB", self.0 as f64 / MEGS as f64)?;
-        } else if self.0 > KILO {
-            write!(f, "{:0.2} KB", self.0 as f64 / KILO as f64)?;
+/// Whether a response to a request carries a body. Responses to head requests, informational
+/// responses, 204s and 304s never do, whatever their headers declare.
+pub fn has_body(head: bool, status: u16) -> bool {
+    !(head || status < 200 || status == 204 || status == 304)
+}
+
+/// The units that sizes are printed in. `Binary` counts in powers of 1024 and `Si` in
+/// powers of 1000, both up to gigabytes, while `Bytes` prints the exact number of bytes.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SizeUnit {
+    Binary,
+    Si,
+    Bytes,
+}
+
+impl SizeUnit {
+    pub fn format(self, length: &ContentLength) -> String {
+        let (base, units) = match self {
+            SizeUnit::Binary => (1024, ["KB", "MB", "GB"]),
+            SizeUnit::Si => (1000, ["kB", "MB", "GB"]),
+            SizeUnit::Bytes => return format!("{} B", length.0),
+        };
+        let (kilo, megs, gigs) = (base, base * base, base * base * base);
+        if length.0 > gigs {
+            format!("{:0.2} {}", length.0 as f64 / gigs as f64, units[2])
+        } else if length.0 > megs {
+            format!("{:0.2} {}", length.0 as f64 / megs as f64, units[1])
+        } else if length.0 > kilo {
+            format!("{:0.2} {}", length.0 as f64 / kilo as f64, units[0])
         } else {
-            write!(f, "{} B", self.0)?;
+            format!("{} B", length.0)
         }
-        Ok(())
+    }
+}
+
+impl fmt::Display for ContentLength {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&SizeUnit::Binary.format(self))
     }
 }
 
@@ -51,7 +93,7 @@ impl Add for ContentLength {
     }
 }
 
-impl<'a> Add for &'a ContentLength {
+impl Add for &ContentLength {
     type Output = ContentLength;
 
     fn add(self, rhs: &ContentLength) -> ContentLength {
@@ -59,7 +101,7 @@ impl<'a> Add for &'a ContentLength {
     }
 }
 
-impl<'a> Add<&'a ContentLength> for ContentLength {
+impl Add<&ContentLength> for ContentLength {
     type Output = ContentLength;
 
     fn add(self, rhs: &ContentLength) -> ContentLength {
@@ -67,7 +109,7 @@ impl<'a> Add<&'a ContentLength> for ContentLength {
     }
 }
 
-impl<'a> Add<ContentLength> for &'a ContentLength {
+impl Add<ContentLength> for &ContentLength {
     type Output = ContentLength;
 
     fn add(self, rhs: ContentLength) -> ContentLength {
@@ -77,7 +119,7 @@ impl<'a> Add<ContentLength> for &'a ContentLength {
 
 #[cfg(test)]
 mod content_length_tests {
-    use super::ContentLength;
+    use super::{ContentLength, SizeUnit};
 
     #[test]
     fn it_can_add_two_content_lengths() {
@@ -92,6 +134,15 @@ mod content_length_tests {
         assert_eq!(ContentLength::new(1).bytes(), 1)
     }
 
+    #[test]
+    fn parses_sizes_with_units() {
+        assert_eq!(ContentLength::parse("1024"), Ok(ContentLength(1024)));
+        assert_eq!(ContentLength::parse("10GB"), Ok(ContentLength(10 * 1024 * 1024 * 1024)));
+        assert_eq!(ContentLength::parse("1.5 kb"), Ok(ContentLength(1536)));
+        assert!(ContentLength::parse("10 parsecs").is_err());
+        assert!(ContentLength::parse("GB").is_err());
+    }
+
     #[test]
     fn can_pretty_print_content_length() {
         assert_eq!(format!("{}", ContentLength(500)), "500 B");
@@ -99,4 +150,13 @@ mod content_length_tests {
         assert_eq!(format!("{}", ContentLength(500_000_000)), "476.84 MB");
         assert_eq!(format!("{}", ContentLength(500_000_000_000)), "465.66 GB");
     }
+
+    #[test]
+    fn prints_sizes_in_each_unit() {
+        let length = ContentLength(1_500_000);
+        assert_eq!(SizeUnit::Binary.format(&length), "1.43 MB");
+        assert_eq!(SizeUnit::Si.format(&length), "1.50 MB");
+        assert_eq!(SizeUnit::Si.format(&ContentLength(2_000)), "2.00 kB");
+        assert_eq!(SizeUnit::Bytes.format(&length), "1500000 B");
+    }
 }
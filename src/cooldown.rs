@@ -0,0 +1,149 @@
+use engine::Engine;
+use stats::{Fact, TimeUnit};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// How many probes are used to measure the baseline before the run starts.
+const BASELINE_PROBES: usize = 5;
+
+/// A probe counts as recovered when its latency is within this multiple of the baseline.
+const RECOVERY_TOLERANCE: f64 = 1.5;
+
+/// How many consecutive probes need to be within the tolerance so one lucky probe doesn't
+/// count as a recovery.
+const RECOVERY_WINDOW: usize = 3;
+
+/// Measures how quickly a server recovers once the load stops. A handful of probes before
+/// the run establish the baseline latency, then a low rate of probes after the run show how
+/// long it takes for latency to settle back to that baseline.
+pub struct Cooldown {
+    baseline: Duration,
+    /// The time each probe was sent relative to the end of the load and its latency.
+    probes: Vec<(Duration, Duration)>,
+    time_unit: TimeUnit,
+}
+
+/// Probes the server before the run to establish a baseline latency.
+pub fn baseline(eng: &Engine, interval: Duration) -> Duration {
+    let mut latencies = Vec::with_capacity(BASELINE_PROBES);
+    eng.clone()
+        .with_interval(interval)
+        .run(BASELINE_PROBES, |fact| latencies.push(fact.duration()));
+    median(&mut latencies)
+}
+
+/// Probes the server at a low rate after the run for as long as the cooldown lasts.
+pub fn probe(eng: &Engine, baseline: Duration, length: Duration, interval: Duration) -> Cooldown {
+    let count = (length.as_millis() / interval.as_millis().max(1)).max(1) as usize;
+    let start = Instant::now();
+    let mut probes = Vec::with_capacity(count);
+    eng.clone()
+        .with_interval(interval)
+        .run(count, |fact: Fact| {
+            probes.push((start.elapsed() - fact.duration(), fact.duration()))
+        });
+    Cooldown::new(baseline, probes)
+}
+
+fn median(latencies: &mut [Duration]) -> Duration {
+    if latencies.is_empty() {
+        return Duration::new(0, 0);
+    }
+    latencies.sort();
+    latencies[latencies.len() / 2]
+}
+
+impl Cooldown {
+    pub fn new(baseline: Duration, probes: Vec<(Duration, Duration)>) -> Cooldown {
+        Cooldown {
+            baseline,
+            probes,
+            time_unit: TimeUnit::Auto,
+        }
+    }
+
+    pub fn with_time_unit(mut self, unit: TimeUnit) -> Self {
+        self.time_unit = unit;
+        self
+    }
+
+    /// How long after the load stopped the latency settled back to the baseline, or
+    /// nothing if it never did while probing.
+    pub fn recovery_time(&self) -> Option<Duration> {
+        let threshold = self.baseline.as_secs_f64() * RECOVERY_TOLERANCE;
+        let window = RECOVERY_WINDOW.min(self.probes.len());
+        if window == 0 {
+            return None;
+        }
+        self.probes
+            .windows(window)
+            .find(|probes| probes.iter().all(|&(_, l)| l.as_secs_f64() <= threshold))
+            .map(|probes| probes[0].0)
+    }
+}
+
+impl fmt::Display for Cooldown {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Cooldown")?;
+        writeln!(f, "  Baseline:  {}", self.time_unit.format(self.baseline))?;
+        writeln!(f, "  Probes:    {}", self.probes.len())?;
+        match self.recovery_time() {
+            Some(time) => writeln!(
+                f,
+                "  Recovered: {} seconds after the load stopped",
+                time.as_secs_f64()
+            )?,
+            None => writeln!(f, "  Recovered: not within the cooldown")?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn recovers_once_latency_settles_near_the_baseline() {
+        let cooldown = Cooldown::new(
+            ms(10),
+            vec![
+                (ms(0), ms(100)),
+                (ms(100), ms(80)),
+                (ms(200), ms(12)),
+                (ms(300), ms(11)),
+                (ms(400), ms(10)),
+            ],
+        );
+        assert_eq!(cooldown.recovery_time(), Some(ms(200)));
+    }
+
+    #[test]
+    fn a_single_fast_probe_is_not_a_recovery() {
+        let cooldown = Cooldown::new(
+            ms(10),
+            vec![
+                (ms(0), ms(100)),
+                (ms(100), ms(10)),
+                (ms(200), ms(100)),
+                (ms(300), ms(100)),
+            ],
+        );
+        assert_eq!(cooldown.recovery_time(), None);
+    }
+
+    #[test]
+    fn does_not_recover_without_probes() {
+        assert_eq!(Cooldown::new(ms(10), vec![]).recovery_time(), None);
+    }
+
+    #[test]
+    fn medians_are_the_middle_latency() {
+        assert_eq!(median(&mut [ms(3), ms(1), ms(2)]), ms(2));
+        assert_eq!(median(&mut []), ms(0));
+    }
+}
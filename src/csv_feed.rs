@@ -0,0 +1,325 @@
+use header;
+use middleware::{Middleware, Request};
+use params;
+use std::str;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// What a run does once every row of the feed has been used.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// Starts again from the first row.
+    Wrap,
+    /// Makes no more requests than there are rows, so each row is sent once.
+    Stop,
+}
+
+impl Mode {
+    pub fn parse(mode: &str) -> Result<Mode, String> {
+        match mode {
+            "wrap" => Ok(Mode::Wrap),
+            "stop" => Ok(Mode::Stop),
+            _ => Err(format!("Expected the feed mode to be 'wrap' or 'stop', got '{}'", mode)),
+        }
+    }
+}
+
+/// The rows of a csv file, filled into the url, headers and body of each request in place
+/// of the `{{column}}` placeholders that the header row names. The workers share the rows,
+/// so each request takes the next row whichever worker makes it, and the rows wrap around
+/// once they've all been taken. Values are percent-encoded in the url, so every engine
+/// sends the same request.
+#[derive(Debug)]
+pub struct CsvFeed {
+    columns: Vec<String>,
+    rows: Vec<Vec<String>>,
+    taken: AtomicUsize,
+}
+
+impl CsvFeed {
+    /// Parses the csv, whose first row names the columns. Blank lines are skipped.
+    pub fn parse(text: &str) -> Result<CsvFeed, String> {
+        let mut records = records(text.trim_start_matches('\u{feff}'))?.into_iter();
+        let columns: Vec<String> = match records.next() {
+            Some((_, columns)) => columns.into_iter().map(|column| column.trim().to_string()).collect(),
+            None => return Err("Expected a header row naming the columns".to_string()),
+        };
+        if let Some(column) = columns.iter().find(|column| column.is_empty()) {
+            return Err(format!("Expected every column to have a name, got '{}'", column));
+        }
+        let rows: Vec<Vec<String>> = records
+            .map(|(line, row)| {
+                if row.len() == columns.len() {
+                    Ok(row)
+                } else {
+                    Err(format!("Expected {} columns on line {}, got {}", columns.len(), line, row.len()))
+                }
+            })
+            .collect::<Result<_, String>>()?;
+        if rows.is_empty() {
+            return Err("Expected a row after the header row".to_string());
+        }
+        Ok(CsvFeed {
+            columns,
+            rows,
+            taken: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// How many rows there are.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Checks that every placeholder in the text names a column or one of the other names,
+    /// which something else fills in.
+    pub fn check(&self, text: &str, others: &[String]) -> Result<(), String> {
+        let known = |name: &str| self.columns.iter().chain(others).any(|known| known == name);
+        match placeholders(text).find(|&(_, _, name)| !known(name)) {
+            Some((_, _, name)) => Err(format!("The feed has no column '{}' for '{}'", name, text)),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the url and header values as every row fills them in, so that a row that
+    /// can't be sent is found before the run rather than by the requests that take it.
+    pub fn check_rows<F>(&self, url: &str, headers: &[(String, String)], check_url: F) -> Result<(), String>
+    where
+        F: Fn(&str) -> Result<(), String>,
+    {
+        for (at, row) in self.rows.iter().enumerate() {
+            let invalid = |e: String| format!("Row {} of the feed: {}", at + 1, e);
+            if let Some(url) = self.fill_url(url, row) {
+                check_url(&url).map_err(invalid)?;
+            }
+            for (name, value) in headers {
+                if let Some(value) = self.fill(value, row) {
+                    header::check_value(name, &value).map_err(invalid)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The text with the values of the row in place of the placeholders of its columns, or
+    /// nothing if it has none.
+    fn fill(&self, text: &str, row: &[String]) -> Option<String> {
+        fill(text, |name| self.value_of(name, row))
+    }
+
+    fn fill_url(&self, url: &str, row: &[String]) -> Option<String> {
+        fill_url(url, |name| self.value_of(name, row))
+    }
+
+    fn value_of<'a>(&self, name: &str, row: &'a [String]) -> Option<&'a str> {
+        self.columns
+            .iter()
+            .position(|column| column == name)
+            .map(|column| row[column].as_str())
+    }
+}
+
+impl Middleware for CsvFeed {
+    fn on_request(&self, request: &mut Request) {
+        let row = &self.rows[self.taken.fetch_add(1, Ordering::Relaxed) % self.rows.len()];
+        if let Some(url) = self.fill_url(request.url(), row) {
+            request.set_url(url);
+        }
+        let headers: Vec<(String, String)> = request
+            .headers()
+            .iter()
+            .map(|(name, value)| (name.clone(), self.fill(value, row).unwrap_or_else(|| value.clone())))
+            .collect();
+        if headers != request.headers() {
+            request.set_headers(headers);
+        }
+        let body = request
+            .body()
+            .and_then(|body| str::from_utf8(body).ok())
+            .and_then(|body| self.fill(body, row));
+        if let Some(body) = body {
+            request.set_body(Some(body.into_bytes()));
+        }
+    }
+}
+
+/// The text with the value of each placeholder that has one in its place, or nothing if
+/// none had a value.
+pub fn fill<'a, F>(text: &str, value_of: F) -> Option<String>
+where
+    F: Fn(&str) -> Option<&'a str>,
+{
+    fill_with(text, value_of, |filled, value| filled.push_str(value))
+}
+
+/// Like `fill`, but percent-encodes the values, which may hold anything, for a url.
+pub fn fill_url<'a, F>(url: &str, value_of: F) -> Option<String>
+where
+    F: Fn(&str) -> Option<&'a str>,
+{
+    fill_with(url, value_of, params::encode)
+}
+
+fn fill_with<'a, F>(text: &str, value_of: F, push: fn(&mut String, &str)) -> Option<String>
+where
+    F: Fn(&str) -> Option<&'a str>,
+{
+    let mut filled = String::with_capacity(text.len());
+    let mut copied = 0;
+    for (start, end, name) in placeholders(text) {
+        if let Some(value) = value_of(name) {
+            filled.push_str(&text[copied..start]);
+            push(&mut filled, value);
+            copied = end;
+        }
+    }
+    if copied == 0 {
+        return None;
+    }
+    filled.push_str(&text[copied..]);
+    Some(filled)
+}
+
+/// Where each `{{name}}` placeholder in the text starts and ends, and its trimmed name.
+fn placeholders(text: &str) -> impl Iterator<Item = (usize, usize, &str)> {
+    let mut from = 0;
+    ::std::iter::from_fn(move || {
+        let start = from + text[from..].find("{{")?;
+        let end = start + text[start..].find("}}")? + 2;
+        from = end;
+        Some((start, end, text[start + 2..end - 2].trim()))
+    })
+}
+
+/// The records of the csv with the line each starts on. Fields may be quoted, with a
+/// quote in a quoted field doubled, to hold commas, quotes and line breaks.
+fn records(text: &str) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let mut records = Vec::new();
+    let (mut record, mut field) = (Vec::new(), String::new());
+    let (mut line, mut starts_on) = (1, 1);
+    let mut chars = text.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => record.push(field.split_off(0)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' if !quoted => {
+                record.push(field.split_off(0));
+                if record.len() > 1 || !record[0].trim().is_empty() {
+                    records.push((starts_on, record.split_off(0)));
+                }
+                record.clear();
+                line += 1;
+                starts_on = line;
+            }
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    if quoted {
+        return Err(format!("Unclosed quote in the row on line {}", starts_on));
+    }
+    record.push(field);
+    if record.len() > 1 || !record[0].trim().is_empty() {
+        records.push((starts_on, record));
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_quoted_fields() {
+        let feed = CsvFeed::parse("id,name\r\n1,\"Smith, \"\"Jo\"\"\"\n\n2,\"two\nlines\"\n").unwrap();
+        assert_eq!(feed.columns(), &["id".to_string(), "name".to_string()][..]);
+        assert_eq!(
+            feed.rows,
+            vec![
+                vec!["1".to_string(), "Smith, \"Jo\"".to_string()],
+                vec!["2".to_string(), "two\nlines".to_string()],
+            ]
+        );
+        assert_eq!(
+            CsvFeed::parse("id,name\n1\n").unwrap_err(),
+            "Expected 2 columns on line 2, got 1"
+        );
+        assert!(CsvFeed::parse("id\n\"1\n").is_err());
+        assert!(CsvFeed::parse("id\n").is_err());
+    }
+
+    #[test]
+    fn fills_each_request_from_the_next_row() {
+        let feed = CsvFeed::parse("id,token\n1,a\n2,b\n").unwrap();
+        assert!(feed.check("http://a/users/{{ id }}", &[]).is_ok());
+        assert!(feed.check("http://a/users/{{user}}", &[]).is_err());
+        assert!(feed.check("http://a/users/{{user}}", &["user".to_string()]).is_ok());
+
+        let headers = vec![("authorization".to_string(), "Bearer {{token}}".to_string())];
+        let sent: Vec<(String, String, Option<Vec<u8>>)> = (0..3)
+            .map(|_| {
+                let body = Some(b"{\"id\":{{id}},\"n\":\"{{n}}\"}".to_vec());
+                let mut request = Request::new("http://a/users/{{ id }}", &headers, body);
+                feed.on_request(&mut request);
+                (
+                    request.url().to_string(),
+                    request.headers()[0].1.clone(),
+                    request.body().map(<[u8]>::to_vec),
+                )
+            })
+            .collect();
+        assert_eq!(
+            sent[1],
+            (
+                "http://a/users/2".to_string(),
+                "Bearer b".to_string(),
+                Some(b"{\"id\":2,\"n\":\"{{n}}\"}".to_vec())
+            )
+        );
+        assert_eq!(sent[2].0, "http://a/users/1");
+    }
+
+    #[test]
+    fn encodes_values_in_the_url() {
+        let feed = CsvFeed::parse("name\nfoo bar/baz\n").unwrap();
+        let headers = vec![("x-name".to_string(), "{{name}}".to_string())];
+        let mut request = Request::new("http://a/users/{{name}}?q={{name}}", &headers, None);
+        feed.on_request(&mut request);
+        assert_eq!(request.url(), "http://a/users/foo%20bar%2Fbaz?q=foo%20bar%2Fbaz");
+        assert_eq!(request.headers()[0].1, "foo bar/baz");
+    }
+
+    #[test]
+    fn checks_every_row_before_the_run() {
+        let feed = CsvFeed::parse("id,token\n1,a\n2,\"b\nc\"\n").unwrap();
+        let headers = vec![("authorization".to_string(), "Bearer {{token}}".to_string())];
+        let url_checked = |url: &str| if url.ends_with("/2") { Err(format!("Bad url '{}'", url)) } else { Ok(()) };
+        assert_eq!(
+            feed.check_rows("http://a/{{id}}", &[], url_checked),
+            Err("Row 2 of the feed: Bad url 'http://a/2'".to_string())
+        );
+        assert_eq!(
+            feed.check_rows("http://a/", &headers, url_checked),
+            Err("Row 2 of the feed: The value of the header 'authorization' can't contain control characters".to_string())
+        );
+        assert!(feed.check_rows("http://a/{{token}}", &[], url_checked).is_ok());
+    }
+}
@@ -0,0 +1,165 @@
+use bind::{self, Binds, Family};
+use native_tls::TlsConnector;
+use raw::{self, Target};
+use stats::TimeUnit;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+/// A connection that may or may not be wrapped in tls.
+trait Stream: Read + Write {}
+
+impl<S: Read + Write> Stream for S {}
+
+/// A single request sent before a run rather than as part of one, kept whole with how long
+/// each step of it took, so that the url, headers and auth can be checked before spending
+/// a run on them.
+pub struct Exchange {
+    address: SocketAddr,
+    request: Vec<u8>,
+    response: raw::Response,
+    timings: Vec<(&'static str, Duration)>,
+    time_unit: TimeUnit,
+}
+
+impl Exchange {
+    pub fn with_time_unit(mut self, unit: TimeUnit) -> Self {
+        self.time_unit = unit;
+        self
+    }
+}
+
+/// Sends the request on a connection of its own and reads the whole response, timing the
+/// lookup of the host, the connection, the tls handshake, the wait for the first byte of
+/// the response and the download of its body.
+pub fn send(
+    target: &Target,
+    request: Vec<u8>,
+    head: bool,
+    binds: Option<&Binds>,
+    family: Option<Family>,
+) -> Result<Exchange, String> {
+    let start = Instant::now();
+    let mut timings = Vec::new();
+    let mut step = |name: &'static str, since: &mut Instant| {
+        timings.push((name, since.elapsed()));
+        *since = Instant::now();
+    };
+    let mut since = start;
+
+    let address = target
+        .addr()
+        .to_socket_addrs()
+        .map_err(|e| format!("Could not resolve {}: {}", target.addr(), e))?
+        .find(|address| family.is_none_or(|family| Family::of(&address.ip()) == family))
+        .ok_or_else(|| match family {
+            Some(family) => format!("{} resolved to no {} addresses", target.addr(), family),
+            None => format!("{} resolved to no addresses", target.addr()),
+        })?;
+    step("Resolve", &mut since);
+
+    let tcp = bind::connect(address, binds, family).map_err(|e| format!("Could not connect to {}: {}", address, e))?;
+    tcp.set_nodelay(true)
+        .map_err(|e| format!("Could not set up the connection to {}: {}", address, e))?;
+    step("Connect", &mut since);
+
+    let stream: Box<dyn Stream> = if target.is_tls() {
+        let tls = TlsConnector::builder()
+            .and_then(|builder| builder.build())
+            .map_err(|e| format!("Could not set up tls: {}", e))?
+            .connect(target.domain(), tcp)
+            .map_err(|e| format!("The tls handshake with {} failed: {}", target.domain(), e))?;
+        step("TLS", &mut since);
+        Box::new(tls)
+    } else {
+        Box::new(tcp)
+    };
+
+    let mut connection = BufReader::new(stream);
+    connection
+        .get_mut()
+        .write_all(&request)
+        .map_err(|e| format!("Could not send the request: {}", e))?;
+    connection
+        .fill_buf()
+        .map_err(|e| format!("No response arrived: {}", e))?;
+    step("Waiting", &mut since);
+
+    let response = raw::read_response(&mut connection, head, true)
+        .map_err(|e| format!("Could not read the response: {}", e))?;
+    timings.push(("Download", response.download));
+    timings.push(("Total", start.elapsed()));
+
+    Ok(Exchange {
+        address,
+        request,
+        response,
+        timings,
+        time_unit: TimeUnit::Auto,
+    })
+}
+
+impl fmt::Display for Exchange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "  Connected to {}", self.address)?;
+        // The body may not be text, such as a form with files in it, so only the head is.
+        let (head, body) = match self.request.windows(4).position(|window| window == b"\r\n\r\n") {
+            Some(end) => (&self.request[..end], &self.request[end + 4..]),
+            None => (&self.request[..], &[][..]),
+        };
+        for line in String::from_utf8_lossy(head).lines() {
+            writeln!(f, "  > {}", line)?;
+        }
+        if !body.is_empty() {
+            writeln!(f, "  > ({} byte body)", body.len())?;
+        }
+        writeln!(f, "  < {}", self.response.status_line)?;
+        for (name, value) in &self.response.headers {
+            writeln!(f, "  < {}: {}", name, value)?;
+        }
+        writeln!(f, "  < ({} byte body)", self.response.body_length)?;
+        for &(name, took) in &self.timings {
+            writeln!(f, "  {:<10} {}", format!("{}:", name), self.time_unit.format(took))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn shows_what_was_sent_and_received() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/ping", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            reader.read_exact(&mut [0; 5]).unwrap();
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 4\r\n\r\npong")
+                .unwrap();
+        });
+
+        let target = Target::parse_any(&url).unwrap();
+        let headers = vec![("x-key".to_string(), "1".to_string())];
+        let request = raw::request("POST", &target, &headers, Some(b"hello"));
+        let printed = send(&target, request, false, None, None).unwrap().to_string();
+        server.join().unwrap();
+
+        assert!(printed.contains("  > POST /ping HTTP/1.1\n"));
+        assert!(printed.contains("  > x-key: 1\n  > content-length: 5\n  > (5 byte body)\n"));
+        assert!(printed.contains("  < HTTP/1.1 200 OK\n  < content-length: 4\n  < (4 byte body)\n"));
+        assert!(printed.contains("  Waiting:   "));
+        assert!(!printed.contains("TLS:"));
+    }
+}
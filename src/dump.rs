@@ -0,0 +1,111 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Writes the first few responses of a run to files, so that headers and auth can be seen
+/// to take effect without a separate request. Every worker shares the count, so only the
+/// first responses of the run as a whole are written.
+#[derive(Clone)]
+pub struct Dump {
+    dir: PathBuf,
+    max: usize,
+    taken: Arc<AtomicUsize>,
+}
+
+/// The status line and headers of a response.
+pub struct Head {
+    pub status_line: String,
+    pub headers: Vec<(String, String)>,
+}
+
+/// A response picked to be written, and where to.
+pub struct Dumped {
+    path: PathBuf,
+    head: Head,
+    body: Vec<u8>,
+}
+
+impl Dump {
+    /// Writes up to `max` responses into the directory, creating it if needed.
+    pub fn new(dir: &str, max: usize) -> io::Result<Dump> {
+        fs::create_dir_all(dir)?;
+        Ok(Dump {
+            dir: PathBuf::from(dir),
+            max,
+            taken: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// Whether more responses are still to be written. Engines only keep what's needed to
+    /// write a response while this is true.
+    pub fn wants(&self) -> bool {
+        self.taken.load(Ordering::Relaxed) < self.max
+    }
+
+    /// Picks the response to be written if there's room for it.
+    pub fn take(&self, head: Head, body: &[u8]) -> Option<Dumped> {
+        let number = self.taken.fetch_add(1, Ordering::Relaxed) + 1;
+        if number > self.max {
+            return None;
+        }
+        Some(Dumped {
+            path: self.dir.join(format!("response-{}.txt", number)),
+            head,
+            body: body.to_vec(),
+        })
+    }
+}
+
+impl Dumped {
+    /// Where the response is written, as `response-N.txt` for the Nth response.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write(&self) -> io::Result<()> {
+        fs::write(&self.path, self.to_bytes())
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = format!("{}\r\n", self.head.status_line).into_bytes();
+        for (name, value) in &self.head.headers {
+            bytes.extend(format!("{}: {}\r\n", name, value).into_bytes());
+        }
+        bytes.extend(b"\r\n");
+        bytes.extend(&self.body);
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn head() -> Head {
+        Head {
+            status_line: "HTTP/1.1 200 OK".to_string(),
+            headers: vec![("content-length".to_string(), "2".to_string())],
+        }
+    }
+
+    #[test]
+    fn only_takes_the_first_responses() {
+        let dir = env::temp_dir().join(format!("rench-dump-{}", std::process::id()));
+        let dump = Dump::new(dir.to_str().unwrap(), 2).unwrap();
+        let first = dump.take(head(), b"hi").unwrap();
+        assert!(dump.take(head(), b"hi").is_some());
+        assert!(!dump.wants());
+        assert!(dump.take(head(), b"hi").is_none());
+
+        first.write().unwrap();
+        assert_eq!(first.path(), dir.join("response-1.txt"));
+        assert_eq!(
+            fs::read_to_string(first.path()).unwrap(),
+            "HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nhi"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
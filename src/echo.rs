@@ -0,0 +1,101 @@
+use random::Rng;
+use regex::Regex;
+use std::fmt::Write;
+use std::str;
+
+/// What every token starts with, so that it can be found again in the body it was sent in.
+const TOKEN_PREFIX: &str = "rench-";
+
+/// The hex digits that follow the prefix of a token.
+const TOKEN_DIGITS: usize = 32;
+
+/// Checks that every response echoes the token that its request's body was sent with, so
+/// that a server that mixes up requests under load, such as by answering one with
+/// another's cached response, is caught rather than measured. Tokens are put in the body
+/// with the `{{token}}` placeholder.
+#[derive(Clone, Debug)]
+pub struct Echo {
+    /// Finds the echoed token in the response, as its first group. Without it the token
+    /// can be anywhere in the response.
+    regex: Option<Regex>,
+}
+
+impl Default for Echo {
+    fn default() -> Echo {
+        Echo::new()
+    }
+}
+
+impl Echo {
+    pub fn new() -> Echo {
+        Echo { regex: None }
+    }
+
+    /// Only takes the token that the first group of the regex matches in the response.
+    pub fn with_regex(mut self, pattern: &str) -> Result<Echo, String> {
+        let regex = Regex::new(pattern).map_err(|e| format!("Invalid echo regex {:?}: {}", pattern, e))?;
+        if regex.captures_len() < 2 {
+            return Err(format!("Expected a group in the echo regex {:?} to capture the token", pattern));
+        }
+        self.regex = Some(regex);
+        Ok(self)
+    }
+
+    /// Whether the response echoed the token of the request's body. A request that wasn't
+    /// sent with a token has nothing to echo.
+    pub fn check(&self, request: Option<&[u8]>, response: &[u8]) -> bool {
+        let token = match request.and_then(|body| str::from_utf8(body).ok()).and_then(token_of) {
+            Some(token) => token,
+            None => return true,
+        };
+        let response = String::from_utf8_lossy(response);
+        match self.regex {
+            Some(ref regex) => regex
+                .captures(&response)
+                .and_then(|captures| captures.get(1))
+                .is_some_and(|echoed| echoed.as_str() == token),
+            None => response.contains(token),
+        }
+    }
+}
+
+/// Writes a fresh token.
+pub fn write_token(out: &mut String, rng: &mut Rng) {
+    write!(out, "{}{:016x}{:016x}", TOKEN_PREFIX, rng.next_u64(), rng.next_u64()).expect("Writing to a string");
+}
+
+/// The first token in the body.
+fn token_of(body: &str) -> Option<&str> {
+    let start = body.find(TOKEN_PREFIX)?;
+    body.get(start..start + TOKEN_PREFIX.len() + TOKEN_DIGITS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token() -> String {
+        let mut token = String::new();
+        write_token(&mut token, &mut Rng::new(0));
+        token
+    }
+
+    #[test]
+    fn expects_the_token_anywhere_in_the_response() {
+        let request = format!("{{\"id\":\"{}\"}}", token());
+        let echo = Echo::new();
+        assert!(echo.check(Some(request.as_bytes()), format!("{{\"echo\":\"{}\"}}", token()).as_bytes()));
+        assert!(!echo.check(Some(request.as_bytes()), b"{\"echo\":\"rench-0000\"}"));
+        assert!(echo.check(Some(b"no token"), b"anything"));
+        assert!(echo.check(None, b"anything"));
+    }
+
+    #[test]
+    fn expects_the_token_where_the_regex_finds_it() {
+        let request = format!("id={}", token());
+        let echo = Echo::new().with_regex("\"id\":\"([^\"]*)\"").unwrap();
+        assert!(echo.check(Some(request.as_bytes()), format!("{{\"id\":\"{}\"}}", token()).as_bytes()));
+        assert!(!echo.check(Some(request.as_bytes()), format!("{{\"other\":\"{}\"}}", token()).as_bytes()));
+        assert!(Echo::new().with_regex("\"id\"").is_err());
+    }
+}
@@ -1,65 +1,487 @@
 use bench;
-use stats::Fact;
-use content_length::ContentLength;
+use bind::{self, Binds, Family};
+use checksum::Checksum;
+use clock::{self, Clock};
+use log;
+use mix::Mix;
+use stats::{Fact, TimeUnit};
+use content_length::{self, ContentLength};
+use dry_run::{self, Exchange};
+use dump::{Dump, Dumped, Head};
+use echo::Echo;
+use expect::{self, Expectation};
+use extract::Extractor;
+use middleware::{Middleware, Request};
+use params::{self, Params};
+use payload::Payloads;
+use pool;
+use preconnect::Preconnect;
+use raw;
+use redirect;
+use replay::Feed;
+use retry_after;
+use server_timing;
+use slow_log::SlowLog;
+use stop::Stop;
+use tcp;
+use random::Rng;
+use hyper::Uri;
+use hyper::client::Service;
+use std::{io, borrow::Cow, cell::{Cell, RefCell}, collections::HashMap, net::TcpStream, rc::Rc, time::{Duration, Instant, SystemTime}};
+use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+use tungstenite::{self, Message as WsMessage, WebSocket};
 
 /// The engine of making requests. The engine implements making the requests and producing
 /// facts for the stats collector to process.
 #[derive(Clone)]
 pub struct Engine {
     urls: Vec<String>,
+    /// Where the workers are in the urls when they walk through them together.
+    order: Option<Arc<AtomicUsize>>,
+    /// The requests to replay instead of requesting the urls.
+    feed: Option<Feed>,
     method: Method,
+    mix: Option<Mix>,
     headers: Vec<(String, String)>,
+    url_headers: Vec<Vec<(String, String)>>,
+    body: Option<Payloads>,
+    params: Option<Params>,
+    interval: Option<Duration>,
+    respect_retry_after: bool,
+    redirects: Option<u32>,
+    connection_limit: Option<pool::Limit>,
+    pool_idle_timeout: Option<Duration>,
+    expectations: Vec<Expectation>,
+    echo: Option<Echo>,
+    checksums: bool,
+    dump: Option<Dump>,
+    /// Takes values from the bodies of responses for later requests.
+    extractor: Option<Arc<Extractor>>,
+    slow_log: Option<SlowLog>,
+    stop: Option<Stop>,
+    preconnect: Option<Preconnect>,
+    captures: Vec<String>,
+    seed: Option<u64>,
+    /// The number in the run of the worker's first request, which its templates count from.
+    first_request: usize,
+    binds: Option<Binds>,
+    family: Option<Family>,
+    in_flight: usize,
+    /// Called once the client is set up, before the first request, returning when the
+    /// run started.
+    ready: Option<Arc<dyn Fn() -> Instant + Send + Sync>>,
+    /// What the requests are timed, paced and backed off by.
+    clock: Arc<dyn Clock>,
+    middleware: Vec<Arc<dyn Middleware>>,
     kind: Kind,
 }
 
-/// The methods that are supported by the current implementations. These are currently
-/// body-less methods so that we don't need to load up any additional content.
-#[derive(Clone, Copy)]
+/// The methods that are supported by the current implementations. Only posts carry a body.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Method {
     Get,
     Head,
+    Post,
 }
 const DEFAULT_METHOD: Method = Method::Get;
 
+impl Method {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+        }
+    }
+}
+
+/// The http versions that responses are told apart by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Version {
+    Http10,
+    Http11,
+    Http2,
+}
+
+impl Version {
+    /// The version that a status line starts with, such as `HTTP/1.1`.
+    pub fn parse(name: &str) -> Option<Version> {
+        match name {
+            "HTTP/1.0" => Some(Version::Http10),
+            "HTTP/1.1" => Some(Version::Http11),
+            "HTTP/2" | "HTTP/2.0" => Some(Version::Http2),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Version::Http10 => "HTTP/1.0",
+            Version::Http11 => "HTTP/1.1",
+            Version::Http2 => "HTTP/2",
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 enum Kind {
     Reqwest,
     Hyper,
+    Raw,
+    WebSocket,
+    Tcp(tcp::Reply),
 }
 const DEFAULT_KIND: Kind = Kind::Reqwest;
 
+/// The headers every engine sends unless they're given. Each http client library has its
+/// own defaults, so without these the engines would send different requests.
+const DEFAULT_HEADERS: [(&str, &str); 2] = [
+    ("user-agent", concat!("rench/", env!("CARGO_PKG_VERSION"))),
+    ("accept", "*/*"),
+];
+
+/// The message the websocket engine sends when no body is given.
+const DEFAULT_WEBSOCKET_MESSAGE: &str = "rench";
+
 impl Engine {
     /// Creates a new engine. The engine will default to using `reqwest`
     pub fn new(urls: Vec<String>, headers: Vec<(String, String)>) -> Engine {
         Engine {
+            url_headers: vec![Vec::new(); urls.len()],
             urls,
+            order: None,
+            feed: None,
             method: DEFAULT_METHOD,
+            mix: None,
             headers,
+            body: None,
+            params: None,
+            interval: None,
+            respect_retry_after: false,
+            redirects: None,
+            connection_limit: None,
+            pool_idle_timeout: None,
+            expectations: Vec::new(),
+            echo: None,
+            checksums: false,
+            dump: None,
+            extractor: None,
+            slow_log: None,
+            stop: None,
+            preconnect: None,
+            captures: Vec::new(),
+            seed: None,
+            first_request: 0,
+            binds: None,
+            family: None,
+            in_flight: 1,
+            ready: None,
+            clock: clock::system(),
+            middleware: Vec::new(),
             kind: DEFAULT_KIND,
         }
     }
 
+    /// Adds headers that are sent to every url, replacing any already set with the same
+    /// name.
+    pub fn with_headers(mut self, headers: &[(String, String)]) -> Self {
+        self.headers
+            .retain(|(name, _)| headers.iter().all(|(other, _)| other != name));
+        self.headers.extend(headers.iter().cloned());
+        self
+    }
+
+    /// Adds headers that are only sent to the given url. They replace any of the global
+    /// headers with the same name.
+    pub fn with_url_headers(mut self, url: &str, headers: Vec<(String, String)>) -> Self {
+        for (index, _) in self.urls.iter().enumerate().filter(|&(_, u)| u == url) {
+            self.url_headers[index].extend(headers.iter().cloned());
+        }
+        self
+    }
+
+    /// Sets the payloads to send as the body of each request
+    pub fn with_body(mut self, body: Payloads) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    /// Adds query parameters, rendered for every request, to the urls of the http engines.
+    pub fn with_params(mut self, params: Params) -> Self {
+        self.params = Some(params);
+        self
+    }
+
     /// Sets the method to use with the requests
     pub fn with_method(mut self, method: Method) -> Self {
         self.method = method;
         self
     }
 
+    /// Mixes the methods of the requests instead, labelling each fact with its method. Only
+    /// the reqwest, hyper and raw engines mix methods, and only posts carry the body.
+    pub fn with_mix(mut self, mix: Mix) -> Self {
+        self.mix = Some(mix);
+        self
+    }
+
+    /// Paces the requests so that each one starts at least `interval` after the last
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Times, paces and backs off the requests by the clock rather than the system's. The
+    /// hyper engine's timers wait by the system's clock whichever is given.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Checks the body of every response, counting those that fail any of the checks as
+    /// failures. The tcp engine doesn't check its replies.
+    pub fn with_expectations(mut self, expectations: Vec<Expectation>) -> Self {
+        self.expectations = expectations;
+        self
+    }
+
+    /// Checks that every response echoes the token its request's body was sent with,
+    /// counting those that don't as failures. The tcp engine doesn't check its replies.
+    pub fn with_echo(mut self, echo: Echo) -> Self {
+        self.echo = Some(echo);
+        self
+    }
+
+    /// Checksums the body of every response so that urls serving varying content can be
+    /// found. The tcp engine doesn't checksum its replies.
+    pub fn with_checksums(mut self) -> Self {
+        self.checksums = true;
+        self
+    }
+
+    /// Writes the first responses of the run out whole. The websocket and tcp engines
+    /// don't write theirs.
+    pub fn with_dump(mut self, dump: Dump) -> Self {
+        self.dump = Some(dump);
+        self
+    }
+
+    /// Takes values from the bodies of responses and fills them into later requests, after
+    /// any middleware already added. The websocket and tcp engines don't extract values.
+    pub fn with_extractor(mut self, extractor: Extractor) -> Self {
+        let extractor = Arc::new(extractor);
+        self.middleware.push(extractor.clone());
+        self.extractor = Some(extractor);
+        self
+    }
+
+    /// Logs the requests slower than the log's threshold.
+    pub fn with_slow_log(mut self, slow_log: SlowLog) -> Self {
+        self.slow_log = Some(slow_log);
+        self
+    }
+
+    /// Has every worker sharing the engine make no more requests once the run is stopped.
+    pub fn with_stop(mut self, stop: Stop) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    /// Has every worker open its connections before the run starts, so that the first
+    /// requests don't pay for the handshakes, and count how long they took.
+    pub fn with_preconnect(mut self, preconnect: Preconnect) -> Self {
+        self.preconnect = Some(preconnect);
+        self
+    }
+
+    /// Has the workers sharing the engine walk through the urls in order together, rather
+    /// than each round robining through them from the first. Every url of a long list is
+    /// then requested before any is requested again.
+    pub fn with_shared_order(mut self) -> Self {
+        self.order = Some(Arc::new(AtomicUsize::new(0)));
+        self
+    }
+
+    /// Replays the requests of the feed as they arrive rather than requesting the urls,
+    /// until the feed ends. Only the hyper engine replays requests.
+    pub fn with_feed(mut self, feed: Feed) -> Self {
+        self.feed = Some(feed);
+        self
+    }
+
+    /// Records the value of the response header with each fact, as a label named after
+    /// the header, so the summary counts each value and its latency. Such as whether a
+    /// cdn's `x-cache` was a HIT or a MISS. Only the http engines see headers.
+    pub fn with_captured_header(mut self, name: &str) -> Self {
+        self.captures.push(name.to_ascii_lowercase());
+        self
+    }
+
+    /// Seeds the randomness of the requests, such as the placeholders rendered into each
+    /// body, so that runs with the same seed send the same requests.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Gives the engine of one worker a seed of its own, so that workers don't all send
+    /// the same sequence but each sends the same one every run.
+    pub fn for_worker(mut self, worker: usize) -> Self {
+        self.seed = self.seed.map(|seed| seed.wrapping_add(worker as u64));
+        self
+    }
+
+    /// Numbers the worker's requests from `first` in their templates, so that the workers
+    /// that share a run don't fill in the same numbers.
+    pub fn with_first_request(mut self, first: usize) -> Self {
+        self.first_request = first;
+        self
+    }
+
+    /// Opens connections from the local addresses in turn. The reqwest engine can't choose
+    /// where its connections come from.
+    pub fn with_binds(mut self, binds: Binds) -> Self {
+        self.binds = Some(binds);
+        self
+    }
+
+    /// Only connects to the addresses of the family, rather than whichever the host
+    /// resolves to first. The reqwest engine can't choose the family of its connections.
+    pub fn with_family(mut self, family: Family) -> Self {
+        self.family = Some(family);
+        self
+    }
+
+    /// Keeps up to `in_flight` requests in flight at once on the worker's thread. Only the
+    /// hyper engine multiplexes requests, every other engine makes one at a time.
+    pub fn with_in_flight(mut self, in_flight: usize) -> Self {
+        self.in_flight = in_flight.max(1);
+        self
+    }
+
+    /// Calls `ready` once the engine has set up its client and is about to make its first
+    /// request, so that setting up isn't counted as part of the run. It returns when the
+    /// run started, which the times of the requests are taken from.
+    pub fn with_ready<R>(mut self, ready: R) -> Self
+    where
+        R: Fn() -> Instant + Send + Sync + 'static,
+    {
+        self.ready = Some(Arc::new(ready));
+        self
+    }
+
+    /// Runs every request through the middleware, after any middleware already added.
+    /// Only the http engines run middleware.
+    pub fn with_middleware<M>(mut self, middleware: M) -> Self
+    where
+        M: Middleware + 'static,
+    {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Whether the engine can keep more than one request in flight on a thread.
+    pub fn multiplexes(&self) -> bool {
+        matches!(self.kind, Kind::Hyper)
+    }
+
+    /// Has each worker wait as long as a response's `Retry-After` asks, up to a minute,
+    /// before its next request. Workers that keep many requests in flight don't wait.
+    pub fn with_respected_retry_after(mut self) -> Self {
+        self.respect_retry_after = true;
+        self
+    }
+
+    /// Follows up to `max` redirects. Without this no engine follows redirects, so a 3xx is
+    /// recorded as the response. Following more than `max` is recorded as an error.
+    pub fn with_redirects(mut self, max: u32) -> Self {
+        self.redirects = Some(max);
+        self
+    }
+
+    /// Limits how many requests are in flight to each host across every worker sharing
+    /// the limit. The time spent waiting for a slot is recorded with each fact.
+    pub fn with_connection_limit(mut self, limit: pool::Limit) -> Self {
+        self.connection_limit = Some(limit);
+        self
+    }
+
+    /// Closes connections that have been idle for longer than the timeout rather than
+    /// reusing them. Only the hyper and raw engines can be told to.
+    pub fn with_pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
     /// Sets the engine to be a hyper engine
     pub fn with_hyper(mut self) -> Self {
         self.kind = Kind::Hyper;
         self
     }
 
+    /// Sets the engine to be the experimental raw engine, a minimal http/1.1 client over
+    /// plain tcp that avoids the overhead of a full http client. It doesn't support tls, and
+    /// it makes its requests on blocking sockets, one at a time on each thread.
+    pub fn with_raw(mut self) -> Self {
+        self.kind = Kind::Raw;
+        self
+    }
+
+    /// Sets the engine to be a websocket engine. Each worker holds a connection open to
+    /// every url, sends the body as a message and times how long the reply takes.
+    pub fn with_websocket(mut self) -> Self {
+        self.kind = Kind::WebSocket;
+        self
+    }
+
+    /// Sets the engine to be a tcp engine for custom protocols. Each exchange writes the
+    /// body to a tcp url and reads the reply back.
+    pub fn with_tcp(mut self, reply: tcp::Reply) -> Self {
+        self.kind = Kind::Tcp(reply);
+        self
+    }
+
+    /// Sends one request to each url on a connection of its own rather than running, and
+    /// keeps each exchange whole. The requests are written as the raw engine writes them,
+    /// so the reqwest and hyper engines may send a few headers of their own on top, and
+    /// redirects aren't followed. Only the http engines make requests that can be shown.
+    pub fn dry_run(&self) -> Result<Vec<DryRun>, String> {
+        if let Kind::WebSocket | Kind::Tcp(_) = self.kind {
+            return Err("Only the hyper, reqwest and raw engines can make a dry run".to_string());
+        }
+        let mut rng = self.rng();
+        Ok(self.urls
+            .iter()
+            .enumerate()
+            .map(|(index, url)| {
+                let method = self.method_of(index);
+                let body = self.body_of(method, index, &mut rng);
+                let headers = self.headers_for(index);
+                let prepared = self.prepare(self.url_of(index, index, &mut rng), &headers, body);
+                let exchange = raw::Target::parse_any(prepared.url()).and_then(|target| {
+                    let request = raw::request(method.as_str(), &target, prepared.headers(), prepared.body());
+                    dry_run::send(&target, request, method == Method::Head, self.binds.as_ref(), self.family)
+                });
+                (format!("{} {}", method.as_str(), url), exchange)
+            })
+            .collect())
+    }
+
     /// Consumes self to start up the engine and begins making requests. It will callback
     /// to the collector to allow the caller to capture requests.
     pub fn run<F>(self, requests: usize, collect: F)
     where
         F: FnMut(Fact),
     {
+        if let Some(ref feed) = self.feed {
+            return self.run_replay(feed, collect);
+        }
         match self.kind {
             Kind::Reqwest => self.run_reqwest(requests, collect),
             Kind::Hyper => self.run_hyper(requests, collect),
+            Kind::Raw => self.run_raw(requests, collect),
+            Kind::WebSocket => self.run_websocket(requests, collect),
+            Kind::Tcp(reply) => self.run_tcp(requests, reply, collect),
         };
     }
 
@@ -67,45 +489,181 @@ impl Engine {
     where
         F: FnMut(Fact),
     {
-        use reqwest::{self, Client, Request, header};
-
-        let mut headers = header::HeaderMap::new();
-        self.headers.iter().for_each(|(k, v)| {
-            headers.insert(
-                header::HeaderName::from_lowercase(k.as_bytes()).expect("invalid header name."),
-                header::HeaderValue::from_str(&v).expect("invalid header value.")
-            );
-        });
+        use reqwest::{Client, RedirectPolicy, Request, header};
 
+        // reqwest replaces headers that share a name rather than sending each of them, so
+        // repeated headers are folded into a single comma separated value. Headers that
+        // reqwest can't send leave nothing to send.
+        let header_map = |given: &[(String, String)]| {
+            let mut headers = header::HeaderMap::new();
+            for (k, v) in given {
+                let name = header::HeaderName::from_lowercase(k.as_bytes()).ok()?;
+                let value = match headers.get(&name).and_then(|prev| prev.to_str().ok()) {
+                    Some(prev) => format!("{}, {}", prev, v),
+                    None => v.to_string(),
+                };
+                headers.insert(name, header::HeaderValue::from_str(&value).ok()?);
+            }
+            Some(headers)
+        };
+        let headers: Vec<Vec<(String, String)>> =
+            (0..self.urls.len()).map(|index| self.headers_for(index)).collect();
+        let header_maps: Vec<Option<header::HeaderMap>> = headers.iter().map(|headers| header_map(headers)).collect();
+
+        // reqwest doesn't say how many redirects it followed, so the policy keeps count.
+        let hops = Arc::new(AtomicUsize::new(0));
+        let policy = match self.redirects {
+            Some(max) => {
+                let hops = hops.clone();
+                RedirectPolicy::custom(move |attempt| {
+                    let followed = attempt.previous().len();
+                    if followed > max as usize {
+                        attempt.too_many_redirects()
+                    } else {
+                        hops.store(followed, Ordering::Relaxed);
+                        attempt.follow()
+                    }
+                })
+            }
+            None => RedirectPolicy::none(),
+        };
+        // Like the other engines, reqwest is kept from decompressing bodies, adding referers
+        // or timing out, so that every engine measures the same thing.
         let client = Client::builder()
-                    .default_headers(headers)
+                    .redirect(policy)
+                    .gzip(false)
+                    .referer(false)
+                    .timeout(None)
                     .build().expect("Failed to build reqwest client");
 
-        let method = match self.method {
-            Method::Get => reqwest::Method::GET,
-            Method::Head => reqwest::Method::HEAD,
-        };
+        if self.preconnect.is_some() {
+            for index in self.origins() {
+                let headers = match header_maps[index] {
+                    Some(ref headers) => headers.clone(),
+                    None => continue,
+                };
+                let (sent, took) = self.time_it(|| {
+                    client
+                        .head(self.urls[index].as_str())
+                        .headers(headers)
+                        .send()
+                        .and_then(|mut resp| resp.copy_to(&mut io::sink()))
+                });
+                self.preconnected(sent.is_ok(), took);
+            }
+        }
+
+        let start = self.ready();
+        let mut rng = self.rng();
 
         for n in 0..requests {
-            let url = &self.urls[n % self.urls.len()];
-
-            let request = Request::new(method.clone(), url.parse().expect("Invalid url"));
-            let mut len = 0;
-            let (resp, duration) = bench::time_it(|| {
-                let mut resp = client
-                    .execute(request)
-                    .expect("Failure to even connect is no good");
-                if let Ok(body) = resp.text() {
-                    len = body.len();
+            if self.stopped() {
+                break;
+            }
+            let lag = self.pace(start, n);
+            let index = self.url_index(n);
+            let slot = self.acquire(index);
+            let method = self.method_of(n);
+            let head = matches!(method, Method::Head);
+
+            let body = self.body_of(method, n, &mut rng);
+            let url = self.url_of(index, n, &mut rng);
+            let prepared = self.prepare(url, &headers[index], body);
+
+            let header_map = if prepared.is_changed() {
+                header_map(prepared.headers())
+            } else {
+                header_maps[index].clone()
+            };
+            let request = match (prepared.url().parse(), header_map) {
+                (Ok(url), Some(header_map)) => {
+                    let mut request = Request::new(reqwest_method(method), url);
+                    *request.headers_mut() = header_map;
+                    if let Some(body) = prepared.body() {
+                        *request.body_mut() = Some(body.to_vec().into());
+                    }
+                    request
+                }
+                _ => {
+                    let fact = self.unsent(method, &prepared);
+                    self.back_off(self.finish(start, index, prepared.url(), slot, lag, fact), &mut collect);
+                    continue;
                 }
-                resp
+            };
+            let mut body = Vec::new();
+            let mut read = false;
+            let mut download = Duration::new(0, 0);
+            hops.store(0, Ordering::Relaxed);
+            let (resp, duration) = self.time_it(|| {
+                client.execute(request).map(|mut resp| {
+                    let (copied, took) = self.time_it(|| resp.copy_to(&mut body));
+                    read = copied.is_ok();
+                    download = took;
+                    resp
+                })
             });
 
-            collect(Fact::record(
-                ContentLength::new(len as u64),
-                resp.status().as_u16(),
-                duration,
-            ));
+            // The response is only looked into once the request has been timed, so that
+            // checking and dumping it is left out of its duration.
+            let fact = match resp {
+                Ok(resp) => {
+                    let lookup = |name: &str| -> Vec<String> {
+                        resp.headers()
+                            .get_all(name)
+                            .iter()
+                            .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+                            .collect()
+                    };
+                    let mut inspection = if read {
+                        self.inspect(index, prepared.body(), &body, || {
+                            let headers = resp
+                                .headers()
+                                .iter()
+                                .map(|(name, value)| {
+                                    (name.to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned())
+                                })
+                                .collect();
+                            Some(Head {
+                                status_line: format!("{:?} {}", resp.version(), resp.status()),
+                                headers,
+                            })
+                        })
+                    } else {
+                        Inspection::default()
+                    };
+                    inspection.captured = self.capture(lookup);
+                    inspection.server_timing = server_timing::parse(&lookup("server-timing"));
+                    let retry_after = resp
+                        .headers()
+                        .get(header::RETRY_AFTER)
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(|value| retry_after::parse(value, SystemTime::now()));
+                    let status = resp.status().as_u16();
+                    let declared = resp
+                        .headers()
+                        .get(header::CONTENT_LENGTH)
+                        .and_then(|length| length.to_str().ok())
+                        .and_then(|length| length.parse::<u64>().ok())
+                        .filter(|_| content_length::has_body(head, status));
+                    let version = match resp.version() {
+                        reqwest::Version::HTTP_10 => Some(Version::Http10),
+                        reqwest::Version::HTTP_11 => Some(Version::Http11),
+                        reqwest::Version::HTTP_2 => Some(Version::Http2),
+                        _ => None,
+                    };
+                    Fact::record(ContentLength::new(body.len() as u64), status, duration)
+                        .with_version(version)
+                        .with_download(download)
+                        .with_redirects(hops.load(Ordering::Relaxed) as u32)
+                        .with_declared_length(declared)
+                        .with_retry_after(retry_after)
+                        .inspected(inspection)
+                }
+                Err(_) => Fact::error(duration),
+            };
+            let fact = self.label_method(method, fact);
+            let fact = self.responded(&prepared, fact);
+            self.back_off(self.finish(start, index, prepared.url(), slot, lag, fact), &mut collect);
         }
     }
 
@@ -113,188 +671,1498 @@ impl Engine {
     where
         F: FnMut(Fact),
     {
-        use hyper::{self, Client, Request, Uri};
-        use hyper_tls::HttpsConnector;
-        use tokio_core::reactor::Core;
-        use futures::{Future, Stream};
+        use tokio_core::reactor::{Core, Timeout};
+        use futures::{future, stream, Future, Stream};
 
         let mut core = Core::new().expect("Setting up tokio core failed");
-        let handle = core.handle();
-        let client = Client::configure()
-            .connector(HttpsConnector::new(1, &handle).expect("To set up a http connector"))
-            .build(&handle);
+        let (client, opened, families) = self.hyper_client(&core.handle());
 
-        let urls: Vec<Uri> = self.urls.iter().map(|url| url.parse().unwrap()).collect();
+        // A url that doesn't parse has its requests error without being sent.
+        let urls: Vec<Option<Uri>> = self.urls.iter().map(|url| url.parse().ok()).collect();
+        let headers: Vec<Vec<(String, String)>> =
+            (0..urls.len()).map(|index| self.headers_for(index)).collect();
 
-        let method = match self.method {
-            Method::Get => hyper::Method::Get,
-            Method::Head => hyper::Method::Head,
-        };
+        if self.preconnect.is_some() {
+            self.preconnect_hyper(&mut core, &client, &urls, &headers);
+        }
+
+        let start = self.ready();
+        let mut rng = self.rng();
+
+        if self.in_flight > 1 {
+            // Many requests share the connections of one client, so there's no telling which
+            // of them opened a connection, and they aren't limited per host. When paced, a
+            // request is late if every request in flight is still waiting when it's due.
+            let handle = core.handle();
+            let (client, urls, headers, handle, families) = (&client, &urls, &headers, &handle, &families);
+            let exchanges = stream::iter_ok::<_, ()>(0..requests)
+                .take_while(|_| Ok(!self.stopped()))
+                .map(|n| {
+                    let index = self.url_index(n);
+                    let method = self.method_of(n);
+                    let body = self.body_of(method, n, &mut rng);
+                    let url = self.url_of(index, n, &mut rng);
+                    let prepared = Rc::new(self.prepare(url, &headers[index], body));
+                    let uri = self.uri_of(&prepared, index, urls[index].as_ref());
+                    let due = self.due(start, n);
+                    future::lazy(move || {
+                        let now = self.clock.now();
+                        let lag = due.map(|due| now.saturating_duration_since(due));
+                        let wait = due.map_or(Duration::new(0, 0), |due| due.saturating_duration_since(now));
+                        Timeout::new(wait, handle)
+                            .expect("Expected to set a timer on the reactor")
+                            .then(move |_| {
+                                let start = self.clock.now();
+                                let exchange = match uri {
+                                    Some(uri) => self.hyper_exchange(client, prepared.clone(), uri, hyper_method(method)),
+                                    None => Box::new(future::err(())),
+                                };
+                                exchange.then(move |result| Ok((index, method, prepared, result, self.clock.elapsed(start), lag)))
+                            })
+                    })
+                })
+                .buffer_unordered(self.in_flight)
+                .for_each(|(index, method, prepared, result, duration, lag)| {
+                    let fact = self
+                        .hyper_fact(index, prepared.body(), result, duration)
+                        .with_family(urls[index].as_ref().and_then(|uri| families.of(uri)));
+                    let fact = self.label_method(method, fact);
+                    let fact = self.responded(&prepared, fact);
+                    collect(self.finish(start, index, prepared.url(), None, lag, fact));
+                    Ok(())
+                });
+            core.run(exchanges).expect("Exchanges never fail, their errors are facts");
+            return;
+        }
 
         for n in 0..requests {
-            let uri = &urls[n % urls.len()];
+            if self.stopped() {
+                break;
+            }
+            let lag = self.pace(start, n);
+            let index = self.url_index(n);
+            let slot = self.acquire(index);
+            let method = self.method_of(n);
+            let body = self.body_of(method, n, &mut rng);
+            let url = self.url_of(index, n, &mut rng);
+            let prepared = Rc::new(self.prepare(url, &headers[index], body));
+            let uri = match self.uri_of(&prepared, index, urls[index].as_ref()) {
+                Some(uri) => uri,
+                None => {
+                    let fact = self.unsent(method, &prepared);
+                    self.back_off(self.finish(start, index, prepared.url(), slot, lag, fact), &mut collect);
+                    continue;
+                }
+            };
+
+            let opened_before = opened.get();
+            let (result, duration) = self.time_it(|| {
+                core.run(self.hyper_exchange(&client, prepared.clone(), uri.clone(), hyper_method(method)))
+            });
+            let fact = self
+                .hyper_fact(index, prepared.body(), result, duration)
+                .with_new_connection(opened.get() > opened_before)
+                .with_family(families.of(&uri));
+            let fact = self.label_method(method, fact);
+            let fact = self.responded(&prepared, fact);
+            self.back_off(self.finish(start, index, prepared.url(), slot, lag, fact), &mut collect);
+        }
+    }
+
+    /// Opens the connections of the hyper client before the run, as many to each origin as
+    /// it keeps requests in flight, by sending a head request over each of them at once.
+    fn preconnect_hyper(&self, core: &mut tokio_core::reactor::Core, client: &HyperClient, urls: &[Option<Uri>], headers: &[Vec<(String, String)>]) {
+        use futures::{future, Future, Stream};
+
+        let heads: Vec<_> = self
+            .origins()
+            .into_iter()
+            .filter_map(|index| urls[index].as_ref().map(|uri| (index, uri)))
+            .flat_map(|origin| (0..self.in_flight).map(move |_| origin))
+            .map(|(index, uri)| {
+                future::lazy(move || {
+                    let mut request = hyper::Request::new(hyper::Method::Head, uri.clone());
+                    for (name, value) in &headers[index] {
+                        request.headers_mut().append_raw(name.to_string(), value.as_str());
+                    }
+                    let start = self.clock.now();
+                    client
+                        .request(request)
+                        .and_then(|response| response.body().for_each(|_| Ok(())))
+                        .then(move |sent| Ok::<_, ()>((sent.is_ok(), self.clock.elapsed(start))))
+                })
+            })
+            .collect();
+        let opened = core.run(future::join_all(heads)).expect("Heads never fail, their errors are counted");
+        for (opened, took) in opened {
+            self.preconnected(opened, took);
+        }
+    }
+
+    /// Builds the client of the hyper engine on the reactor, along with the count of the
+    /// connections it has opened and the family of the connections to each host.
+    fn hyper_client(&self, handle: &tokio_core::reactor::Handle) -> (HyperClient, Rc<Cell<usize>>, HostFamilies) {
+        use hyper::Client;
+        use hyper::client::HttpConnector;
+        use hyper_tls::HttpsConnector;
+        use native_tls::TlsConnector;
+
+        let opened = Rc::new(Cell::new(0));
+        let families = HostFamilies::default();
+        let mut http = HttpConnector::new(1, handle);
+        http.enforce_http(false);
+        let tls = TlsConnector::builder()
+            .and_then(|builder| builder.build())
+            .expect("To set up a tls connector");
+        let client = Client::configure()
+            .connector(CountingConnector {
+                inner: HttpsConnector::from((
+                    BindingConnector {
+                        http,
+                        binds: self.binds.clone(),
+                        family: self.family,
+                        families: families.clone(),
+                        handle: handle.clone(),
+                    },
+                    tls,
+                )),
+                opened: opened.clone(),
+            });
+        let client = match self.pool_idle_timeout {
+            Some(timeout) if timeout == Duration::new(0, 0) => client.keep_alive(false),
+            Some(timeout) => client.keep_alive_timeout(Some(timeout)),
+            None => client,
+        }
+        .build(handle);
+        (client, opened, families)
+    }
+
+    /// Replays the requests of the feed with hyper as they arrive, one at a time, until
+    /// the feed ends. Every request is sent the global headers.
+    fn run_replay<F>(&self, feed: &Feed, mut collect: F)
+    where
+        F: FnMut(Fact),
+    {
+        use tokio_core::reactor::Core;
+
+        let mut core = Core::new().expect("Setting up tokio core failed");
+        let (client, opened, families) = self.hyper_client(&core.handle());
+        let headers = self.headers_with(&[]);
+        let start = self.ready();
 
+        while let Some(entry) = feed.next() {
+            if self.stopped() {
+                break;
+            }
+            let opened_before = opened.get();
+            let prepared = Rc::new(self.prepare(entry.url.as_str().into(), &headers, entry.body.map(String::into_bytes)));
+            let (uri, method) = (entry.uri, entry.method.clone());
+            let (result, duration) =
+                self.time_it(|| core.run(self.hyper_exchange(&client, prepared.clone(), uri.clone(), method)));
+            let fact = self
+                .hyper_fact(0, prepared.body(), result, duration)
+                .with_new_connection(opened.get() > opened_before)
+                .with_family(families.of(&uri))
+                .with_started(self.clock.elapsed(start).saturating_sub(duration));
+            let fact = self.responded(&prepared, fact);
+            self.log_request(entry.method.as_ref(), &entry.url, &fact);
+            self.back_off(fact, &mut collect);
+        }
+    }
+
+    /// Sends the prepared request to the uri with hyper, following its redirects, and reads
+    /// the final response.
+    fn hyper_exchange<'a>(
+        &'a self,
+        client: &'a HyperClient,
+        prepared: Rc<Request<'a>>,
+        uri: Uri,
+        method: hyper::Method,
+    ) -> Box<dyn futures::Future<Item = HyperExchange, Error = ()> + 'a> {
+        use hyper::{self, Request, header::{ContentLength as DeclaredLength, Location}};
+        use futures::{future::{self, Loop}, Future, Stream};
+
+        let body = prepared.body().map(<[u8]>::to_vec);
+        let original = uri.to_string();
+        Box::new(future::loop_fn((uri, method, body, 0), move |(uri, method, body, redirects)| {
             let mut req = Request::new(method.clone(), uri.clone());
             {
-                let mut headers = req.headers_mut();
-                self.headers.iter().for_each(|(k,v)| {
-                    headers.set_raw(k.to_string(), v.as_str());
+                let req_headers = req.headers_mut();
+                redirect::headers_for(&original, uri.as_ref(), prepared.headers()).iter().for_each(|(k,v)| {
+                    req_headers.append_raw(k.to_string(), v.as_str());
                 });
             }
+            if let Some(ref body) = body {
+                req.set_body(body.clone());
+            }
+            let is_head = method == hyper::Method::Head;
 
-            let request = client.request(req)
-                .and_then(|response| {
+            client.request(req)
+                .and_then(move |response| {
                     let status = response.status().as_u16();
+                    let version = match response.version() {
+                        hyper::HttpVersion::Http10 => Some(Version::Http10),
+                        hyper::HttpVersion::Http11 => Some(Version::Http11),
+                        hyper::HttpVersion::H2 | hyper::HttpVersion::H2c => Some(Version::Http2),
+                        _ => None,
+                    };
+                    let location = response.headers().get::<Location>().map(|l| l.to_string());
+                    let declared_length = response
+                        .headers()
+                        .get::<DeclaredLength>()
+                        .map(|length| length.0)
+                        .filter(|_| content_length::has_body(is_head, status));
+                    // The head is only kept while responses are still to be dumped.
+                    let head = self.dump.as_ref().filter(|dump| dump.wants()).map(|_| Head {
+                        status_line: format!("{} {}", response.version(), response.status()),
+                        headers: response
+                            .headers()
+                            .iter()
+                            .map(|header| (header.name().to_string(), header.value_string()))
+                            .collect(),
+                    });
+                    let retry_after = response
+                        .headers()
+                        .get_raw("retry-after")
+                        .and_then(|raw| raw.one())
+                        .and_then(|value| ::std::str::from_utf8(value).ok())
+                        .and_then(|value| retry_after::parse(value, SystemTime::now()));
+                    let lookup = |name: &str| -> Vec<String> {
+                        response
+                            .headers()
+                            .get_raw(name)
+                            .map(|raw| raw.iter().map(|value| String::from_utf8_lossy(value).into_owned()).collect())
+                            .unwrap_or_default()
+                    };
+                    let captured = self.capture(lookup);
+                    let timing = server_timing::parse(&lookup("server-timing"));
+                    let headers_received = self.clock.now();
                     response
                         .body()
                         .concat2()
-                        .map(move |body| (status, body.len() as u64))
+                        .map(move |body| {
+                            let download = self.clock.elapsed(headers_received);
+                            let exchange = HyperExchange {
+                                status,
+                                version,
+                                content_length: body.len() as u64,
+                                declared_length,
+                                download,
+                                retry_after,
+                                redirects,
+                                captured,
+                                server_timing: timing,
+                                head,
+                                body,
+                            };
+                            (location, exchange)
+                        })
+                })
+                .map_err(|_| ())
+                .and_then(move |(location, exchange)| {
+                    let status = exchange.status;
+                    let next = location
+                        .filter(|_| redirect::is_redirect(status))
+                        .and_then(|location| redirect::resolve(uri.as_ref(), &location));
+                    match (next, self.redirects) {
+                        (Some(next), Some(max)) => {
+                            if redirects == max {
+                                return Err(());
+                            }
+                            let (method, body) = if !redirect::keeps_method(status) && method != hyper::Method::Head {
+                                (hyper::Method::Get, None)
+                            } else {
+                                (method, body)
+                            };
+                            let uri = next.parse().map_err(|_| ())?;
+                            Ok(Loop::Continue((uri, method, body, redirects + 1)))
+                        }
+                        _ => Ok(Loop::Break(exchange)),
+                    }
+                })
+        }))
+    }
+
+    fn run_raw<F>(&self, requests: usize, mut collect: F)
+    where
+        F: FnMut(Fact),
+    {
+        // A url that doesn't parse has its requests error without being sent.
+        let targets: Vec<Option<raw::Target>> = self.urls.iter().map(|url| raw::Target::parse(url).ok()).collect();
+        let headers: Vec<Vec<(String, String)>> =
+            (0..targets.len()).map(|index| self.headers_for(index)).collect();
+
+        // Without a body or a mix every request to a url is the same, so they're only
+        // written once.
+        let fixed: Vec<Option<Vec<u8>>> = match (&self.body, &self.mix, &self.params) {
+            (None, None, None) => targets
+                .iter()
+                .zip(&headers)
+                .map(|(target, headers)| {
+                    target
+                        .as_ref()
+                        .map(|target| raw::request(self.method.as_str(), target, headers, None))
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let mut client = raw::Client::new();
+        if let Some(timeout) = self.pool_idle_timeout {
+            client = client.with_idle_timeout(timeout);
+        }
+        if let Some(ref binds) = self.binds {
+            client = client.with_binds(binds.clone());
+        }
+        if let Some(family) = self.family {
+            client = client.with_family(family);
+        }
+        if self.preconnect.is_some() {
+            for index in self.origins() {
+                let target = match targets[index] {
+                    Some(ref target) => target,
+                    None => continue,
+                };
+                let request = raw::request(Method::Head.as_str(), target, &headers[index], None);
+                let (sent, took) = self.time_it(|| client.send(target, &request, true));
+                self.preconnected(sent.is_ok(), took);
+            }
+        }
+        let start = self.ready();
+        let mut rng = self.rng();
+
+        for n in 0..requests {
+            if self.stopped() {
+                break;
+            }
+            let lag = self.pace(start, n);
+            let index = self.url_index(n);
+            let slot = self.acquire(index);
+            let method = self.method_of(n);
+            let head = matches!(method, Method::Head);
+            let body = self.body_of(method, n, &mut rng);
+            let url = self.url_of(index, n, &mut rng);
+            let prepared = self.prepare(url, &headers[index], body);
+            let target = if prepared.url() == self.urls[index] {
+                targets[index].as_ref().map(Cow::Borrowed)
+            } else {
+                raw::Target::parse(prepared.url()).ok().map(Cow::Owned)
+            };
+            let target = match target {
+                Some(target) => target,
+                None => {
+                    let fact = self.unsent(method, &prepared);
+                    self.back_off(self.finish(start, index, prepared.url(), slot, lag, fact), &mut collect);
+                    continue;
+                }
+            };
+            let request = match fixed.get(index).and_then(Option::as_ref) {
+                Some(request) if !prepared.is_changed() => request.clone(),
+                _ => raw::request(method.as_str(), &target, prepared.headers(), prepared.body()),
+            };
+
+            client.keep_responses(self.reads_bodies() || !self.captures.is_empty());
+            let (result, duration) = self.time_it(|| {
+                let sent = client.send(&target, &request, head)?;
+                self.follow_raw(&mut client, method, sent, &prepared)
+            });
+            let fact = match result {
+                Ok((response, new_connection, redirects)) => Fact::record(
+                    ContentLength::new(response.body_length),
+                    response.status,
+                    duration,
+                )
+                .with_version(response.version)
+                .with_download(response.download)
+                .with_new_connection(new_connection)
+                .with_family(response.family)
+                .with_redirects(redirects)
+                .with_declared_length(response.declared_length)
+                .with_retry_after(
+                    response
+                        .retry_after
+                        .as_ref()
+                        .and_then(|value| retry_after::parse(value, SystemTime::now())),
+                )
+                .inspected(Inspection {
+                    captured: self.capture(|name| {
+                        response
+                            .headers
+                            .iter()
+                            .filter(|(header, _)| header.eq_ignore_ascii_case(name))
+                            .map(|(_, value)| value.clone())
+                            .collect()
+                    }),
+                    server_timing: server_timing::parse(&response.server_timing),
+                    ..self.inspect(index, prepared.body(), &response.body, || {
+                        Some(Head {
+                            status_line: response.status_line.clone(),
+                            headers: response.headers.clone(),
+                        })
+                    })
+                }),
+                Err(_) => Fact::error(duration),
+            };
+            let fact = self.label_method(method, fact);
+            let fact = self.responded(&prepared, fact);
+            self.back_off(self.finish(start, index, prepared.url(), slot, lag, fact), &mut collect);
+        }
+    }
+
+    /// Follows the redirects of a response from the raw engine, returning the final
+    /// response, whether any connection was opened and how many redirects were followed.
+    fn follow_raw(
+        &self,
+        client: &mut raw::Client,
+        mut method: Method,
+        sent: (raw::Response, bool),
+        prepared: &Request,
+    ) -> io::Result<(raw::Response, bool, u32)> {
+        let mut body = prepared.body();
+        let (mut response, mut new_connection) = sent;
+        let mut url = prepared.url().to_string();
+        let mut redirects = 0;
+        while let Some(max) = self.redirects {
+            let next = match response.location {
+                Some(ref location) if redirect::is_redirect(response.status) => {
+                    redirect::resolve(&url, location)
+                }
+                _ => None,
+            };
+            let next = match next {
+                Some(next) => next,
+                None => break,
+            };
+            if redirects == max {
+                return Err(io::Error::other("Too many redirects"));
+            }
+            redirects += 1;
+            if !redirect::keeps_method(response.status) && !matches!(method, Method::Head) {
+                method = Method::Get;
+                body = None;
+            }
+            let target = raw::Target::parse(&next).map_err(io::Error::other)?;
+            let headers = redirect::headers_for(prepared.url(), &next, prepared.headers());
+            let request = raw::request(method.as_str(), &target, &headers, body);
+            let (next_response, opened) =
+                client.send(&target, &request, matches!(method, Method::Head))?;
+            response = next_response;
+            new_connection |= opened;
+            url = next;
+        }
+        Ok((response, new_connection, redirects))
+    }
+
+    fn run_websocket<F>(&self, requests: usize, mut collect: F)
+    where
+        F: FnMut(Fact),
+    {
+        let mut sockets: Vec<Option<WebSocket<TcpStream>>> = self.urls.iter().map(|_| None).collect();
+        if self.preconnect.is_some() {
+            for (index, socket) in sockets.iter_mut().enumerate() {
+                let (connected, took) = self.time_it(|| self.connect_websocket(index));
+                self.preconnected(connected.is_ok(), took);
+                *socket = connected.ok();
+            }
+        }
+        let start = self.ready();
+        let mut rng = self.rng();
+
+        for n in 0..requests {
+            if self.stopped() {
+                break;
+            }
+            let lag = self.pace(start, n);
+            let index = self.url_index(n);
+            let slot = self.acquire(index);
+            let message = match self.body {
+                Some(ref body) => body.get(n, &mut rng),
+                None => DEFAULT_WEBSOCKET_MESSAGE.as_bytes().to_vec(),
+            };
+
+            let new_connection = sockets[index].is_none();
+            if new_connection {
+                let (socket, duration) = self.time_it(|| self.connect_websocket(index));
+                match socket {
+                    Ok(socket) => sockets[index] = Some(socket),
+                    Err(_) => {
+                        let fact = Fact::error(duration);
+                        collect(self.finish(start, index, &self.urls[index], slot, lag, fact));
+                        continue;
+                    }
+                }
+            }
+
+            let socket = sockets[index].as_mut().expect("Connected above");
+            let family = Family::of_peer(socket.get_ref());
+            let (reply, duration) = self.time_it(|| round_trip(socket, message.clone()));
+            let fact = match reply {
+                Ok(reply) => Fact::round_trip(ContentLength::new(reply.len() as u64), duration)
+                    .with_new_connection(new_connection)
+                    .with_family(family)
+                    .inspected(self.inspect(index, Some(&message), &reply, || None)),
+                Err(_) => {
+                    sockets[index] = None;
+                    Fact::error(duration)
+                }
+            };
+            collect(self.finish(start, index, &self.urls[index], slot, lag, fact));
+        }
+
+        for socket in sockets.iter_mut().flatten() {
+            let _ = socket.close(None);
+        }
+    }
+
+    fn run_tcp<F>(&self, requests: usize, reply: tcp::Reply, mut collect: F)
+    where
+        F: FnMut(Fact),
+    {
+        // A url that isn't an address has its requests error without being sent.
+        let addresses: Vec<Option<String>> = self.urls.iter().map(|url| tcp::address(url).ok()).collect();
+        let mut streams: Vec<Option<TcpStream>> = addresses.iter().map(|_| None).collect();
+        if self.preconnect.is_some() {
+            for (address, stream) in addresses.iter().zip(streams.iter_mut()) {
+                let address = match *address {
+                    Some(ref address) => address,
+                    None => continue,
+                };
+                let (connected, took) = self.time_it(|| {
+                    let stream = bind::connect(address.as_str(), self.binds.as_ref(), self.family)?;
+                    stream.set_nodelay(true)?;
+                    Ok::<_, io::Error>(stream)
+                });
+                self.preconnected(connected.is_ok(), took);
+                *stream = connected.ok();
+            }
+        }
+        let start = self.ready();
+        let mut rng = self.rng();
+
+        for n in 0..requests {
+            if self.stopped() {
+                break;
+            }
+            let lag = self.pace(start, n);
+            let index = self.url_index(n);
+            let slot = self.acquire(index);
+            let payload = match self.body {
+                Some(ref body) => body.get(n, &mut rng),
+                None => Vec::new(),
+            };
+
+            let new_connection = streams[index].is_none();
+            let (result, duration) = self.time_it(|| {
+                if new_connection {
+                    let address = addresses[index]
+                        .as_ref()
+                        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Not a tcp address"))?;
+                    let stream = bind::connect(address.as_str(), self.binds.as_ref(), self.family)?;
+                    stream.set_nodelay(true)?;
+                    streams[index] = Some(stream);
+                }
+                let stream = streams[index].as_mut().expect("Connected above");
+                tcp::exchange(stream, &payload, reply)
+            });
+            let fact = match result {
+                // There's no status in a tcp exchange, so a complete reply is recorded as
+                // an ok.
+                Ok(length) => Fact::round_trip(ContentLength::new(length), duration)
+                    .with_new_connection(new_connection)
+                    .with_family(streams[index].as_ref().and_then(Family::of_peer)),
+                Err(_) => Fact::error(duration),
+            };
+            if fact.is_error() || !reply.keeps_alive() {
+                streams[index] = None;
+            }
+            collect(self.finish(start, index, &self.urls[index], slot, lag, fact));
+        }
+    }
+
+    fn connect_websocket(&self, index: usize) -> tungstenite::Result<WebSocket<TcpStream>> {
+        let mut request = tungstenite::http::Request::builder().uri(self.urls[index].as_str());
+        for (name, value) in self.headers_for(index) {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        let request = request.body(()).map_err(tungstenite::Error::from)?;
+        let uri: Uri = self.urls[index].parse().map_err(|_| tungstenite::Error::Url("Invalid url".into()))?;
+        let host = uri.host().ok_or_else(|| tungstenite::Error::Url("No host in the url".into()))?;
+        let stream = bind::connect((host_name(host), uri.port().unwrap_or(80)), self.binds.as_ref(), self.family)?;
+        stream.set_nodelay(true)?;
+        tungstenite::client(request, stream)
+            .map(|(socket, _)| socket)
+            .map_err(|e| match e {
+                tungstenite::HandshakeError::Failure(e) => e,
+                tungstenite::HandshakeError::Interrupted(_) => {
+                    tungstenite::Error::Io(io::Error::new(io::ErrorKind::WouldBlock, "Handshake interrupted"))
+                }
+            })
+    }
+
+    /// The index of the first url to each origin, whose connections are opened before the
+    /// run when preconnecting.
+    fn origins(&self) -> Vec<usize> {
+        let mut seen = Vec::new();
+        (0..self.urls.len())
+            .filter(|&index| {
+                let origin = self.urls[index].parse::<Uri>().ok().map(|uri| {
+                    (uri.scheme().map(str::to_string), uri.authority().map(str::to_string))
                 });
-            let ((status, content_length), duration) =
-                bench::time_it(|| core.run(request).expect("reactor run"));
-            collect(Fact::record(
-                ContentLength::new(content_length),
-                status,
-                duration,
-            ));
+                let new = !seen.contains(&origin);
+                seen.push(origin);
+                new
+            })
+            .collect()
+    }
+
+    /// Counts a connection opened before the run, or that failed to open.
+    fn preconnected(&self, opened: bool, took: Duration) {
+        if let Some(ref preconnect) = self.preconnect {
+            preconnect.record(opened, took);
+        }
+    }
+
+    /// Waits until the run starts, returning when it started.
+    fn ready(&self) -> Instant {
+        match self.ready {
+            Some(ref ready) => ready(),
+            None => self.clock.now(),
+        }
+    }
+
+    /// Runs the closure once and returns how long it took by the engine's clock.
+    fn time_it<F, U>(&self, f: F) -> (U, Duration)
+    where
+        F: FnOnce() -> U,
+    {
+        bench::time_on(&*self.clock, f)
+    }
+
+    /// The generator for the randomness of the requests, seeded if a seed was given.
+    fn rng(&self) -> Rng {
+        match self.seed {
+            Some(seed) => Rng::new(seed),
+            None => Rng::from_time(),
+        }
+    }
+
+    /// Whether anything needs the bodies of the responses.
+    fn reads_bodies(&self) -> bool {
+        self.checksums
+            || !self.expectations.is_empty()
+            || self.echo.is_some()
+            || self.extractor.is_some()
+            || self.dump.as_ref().is_some_and(|dump| dump.wants())
+    }
+
+    /// Checks and checksums the body of a response from the url at the index to the request
+    /// sent with the body, takes the values to extract from it, and picks the response to
+    /// be dumped with its head if responses are still to be dumped.
+    fn inspect<H>(&self, index: usize, sent: Option<&[u8]>, body: &[u8], head: H) -> Inspection
+    where
+        H: FnOnce() -> Option<Head>,
+    {
+        if let Some(ref extractor) = self.extractor {
+            extractor.extract(body);
+        }
+        Inspection {
+            captured: Vec::new(),
+            server_timing: Vec::new(),
+            dumped: match self.dump {
+                Some(ref dump) if dump.wants() => head().and_then(|head| dump.take(head, body)),
+                _ => None,
+            },
+            mismatch: !expect::all(&self.expectations, body),
+            echo_mismatch: self.echo.as_ref().is_some_and(|echo| !echo.check(sent, body)),
+            checksum: if self.checksums {
+                Some(Checksum::of(index, body))
+            } else {
+                None
+            },
+        }
+    }
+
+    /// The fact of an exchange with the url at the index made by the hyper engine, whose body
+    /// is inspected here so that it's left out of the request's duration.
+    fn hyper_fact(&self, index: usize, sent: Option<&[u8]>, result: Result<HyperExchange, ()>, duration: Duration) -> Fact {
+        match result {
+            Ok(exchange) => {
+                let HyperExchange {
+                    status,
+                    version,
+                    content_length,
+                    declared_length,
+                    download,
+                    retry_after,
+                    redirects,
+                    captured,
+                    server_timing,
+                    head,
+                    body,
+                } = exchange;
+                let inspection = Inspection {
+                    captured,
+                    server_timing,
+                    ..self.inspect(index, sent, &body, || head)
+                };
+                Fact::record(ContentLength::new(content_length), status, duration)
+                    .with_version(version)
+                    .with_download(download)
+                    .with_redirects(redirects)
+                    .with_declared_length(declared_length)
+                    .with_retry_after(retry_after)
+                    .inspected(inspection)
+            }
+            Err(_) => Fact::error(duration),
+        }
+    }
+
+    /// The values of the captured headers of a response, looking up every value of each by
+    /// its lowercase name. A header the response didn't have is captured as `(none)` so
+    /// that it's still counted.
+    fn capture<L>(&self, lookup: L) -> Vec<(String, String)>
+    where
+        L: Fn(&str) -> Vec<String>,
+    {
+        self.captures
+            .iter()
+            .map(|name| {
+                let values = lookup(name);
+                let value = if values.is_empty() {
+                    "(none)".to_string()
+                } else {
+                    values.join(", ")
+                };
+                (name.clone(), value)
+            })
+            .collect()
+    }
+
+    /// Lets every middleware change the request before it's sent.
+    fn prepare<'a>(&self, url: Cow<'a, str>, headers: &'a [(String, String)], body: Option<Vec<u8>>) -> Request<'a> {
+        let mut request = Request::new(url, headers, body);
+        for middleware in &self.middleware {
+            middleware.on_request(&mut request);
+        }
+        request
+    }
+
+    /// The fact of a request that couldn't be sent, such as one whose url or headers were
+    /// made invalid by middleware, a feed or an extraction. It errors at once, and the
+    /// middleware see it as they would any other.
+    fn unsent(&self, method: Method, request: &Request) -> Fact {
+        self.responded(request, self.label_method(method, Fact::error(Duration::new(0, 0))))
+    }
+
+    /// Notes the trace id the request was sent with, then shows every middleware how the
+    /// request went.
+    fn responded(&self, request: &Request, fact: Fact) -> Fact {
+        let fact = fact.with_trace(request.trace_header().cloned());
+        for middleware in &self.middleware {
+            middleware.on_response(request, &fact);
+        }
+        fact
+    }
+
+    /// Whether the run was stopped, so no more requests should be made.
+    fn stopped(&self) -> bool {
+        self.stop.as_ref().is_some_and(Stop::is_stopped)
+    }
+
+    /// The method of the worker's nth request.
+    fn method_of(&self, n: usize) -> Method {
+        match self.mix {
+            Some(ref mix) => mix.method(n),
+            None => self.method,
+        }
+    }
+
+    /// The url of the worker's nth request to the url at the index, with its query
+    /// parameters if it has any.
+    fn url_of(&self, index: usize, n: usize, rng: &mut Rng) -> Cow<'_, str> {
+        match self.params {
+            Some(ref params) => Cow::Owned(params::append(&self.urls[index], &params.render(self.first_request + n, rng))),
+            None => Cow::Borrowed(&self.urls[index]),
+        }
+    }
+
+    /// The uri to send the prepared request to with hyper, which is only parsed again when
+    /// it isn't the url at the index, such as when it has query parameters of its own.
+    /// Nothing when it doesn't parse.
+    fn uri_of(&self, prepared: &Request, index: usize, uri: Option<&Uri>) -> Option<Uri> {
+        if prepared.url() == self.urls[index] {
+            uri.cloned()
+        } else {
+            prepared.url().parse().ok()
+        }
+    }
+
+    /// The body of the worker's nth request, if it has one.
+    fn body_of(&self, method: Method, n: usize, rng: &mut Rng) -> Option<Vec<u8>> {
+        match self.body {
+            Some(ref body) if self.mix.is_none() || method == Method::Post => Some(body.get(self.first_request + n, rng)),
+            _ => None,
+        }
+    }
+
+    /// Notes the method of the fact's request, and labels the fact with it when the methods
+    /// are mixed, so the summary breaks the requests down by method.
+    fn label_method(&self, method: Method, fact: Fact) -> Fact {
+        let fact = fact.with_method(method);
+        match self.mix {
+            Some(_) => fact.with_label("method", method.as_str()),
+            None => fact,
+        }
+    }
+
+    /// The index of the url for the worker's nth request.
+    fn url_index(&self, n: usize) -> usize {
+        match self.order {
+            Some(ref order) => order.fetch_add(1, Ordering::Relaxed) % self.urls.len(),
+            None => n % self.urls.len(),
+        }
+    }
+
+    /// Waits for a slot to the url's host if connections are limited.
+    fn acquire(&self, index: usize) -> Option<pool::Slot> {
+        self.connection_limit.as_ref().map(|limit| limit.acquire(index))
+    }
+
+    /// Notes how long the request waited for its slot, how late it started and when it
+    /// started since the run did, gives the slot back and logs the fact with the url the
+    /// request was sent to before it's collected.
+    fn finish(
+        &self,
+        start: Instant,
+        index: usize,
+        url: &str,
+        slot: Option<pool::Slot>,
+        lag: Option<Duration>,
+        fact: Fact,
+    ) -> Fact {
+        let started = self.clock.elapsed(start).saturating_sub(fact.duration());
+        let fact = fact.with_url(index).with_lag(lag).with_started(started);
+        let fact = match slot {
+            Some(slot) => fact.with_pool_wait(slot.wait()),
+            None => fact,
+        };
+        self.log(url, &fact);
+        fact
+    }
+
+    /// Collects the fact, then waits as long as its response asked with a `Retry-After`
+    /// before the next request when that's respected. The wait is cut short if the run
+    /// is stopped.
+    fn back_off<F>(&self, fact: Fact, collect: &mut F)
+    where
+        F: FnMut(Fact),
+    {
+        let backoff = fact
+            .retry_after()
+            .filter(|_| self.respect_retry_after)
+            .map(|retry_after| retry_after.min(retry_after::MAX_BACKOFF));
+        collect(fact.with_backoff(backoff));
+        if let Some(backoff) = backoff {
+            let until = self.clock.now() + backoff;
+            while !self.stopped() {
+                let now = self.clock.now();
+                if now >= until {
+                    break;
+                }
+                self.clock.sleep((until - now).min(Duration::from_millis(100)));
+            }
+        }
+    }
+
+    /// Logs a request when verbose, with the details of its connection and body when
+    /// debugging.
+    fn log(&self, url: &str, fact: &Fact) {
+        let method = fact.method().unwrap_or(self.method);
+        self.log_request(method.as_str(), url, fact);
+    }
+
+    fn log_request(&self, method: &str, url: &str, fact: &Fact) {
+        if let Some(ref slow_log) = self.slow_log {
+            slow_log.record(method, url, fact);
+        }
+        if !log::enabled(log::Level::Verbose) {
+            return;
+        }
+        let status = match fact.status() {
+            Some(status) => status.to_string(),
+            None if fact.is_error() => "error".to_string(),
+            None => "ok".to_string(),
+        };
+        log::verbose(format_args!(
+            "{} {} {} {}",
+            method,
+            url,
+            status,
+            TimeUnit::Auto.format(fact.duration())
+        ));
+        let connection = match fact.new_connection() {
+            Some(true) => "new connection",
+            Some(false) => "reused connection",
+            None => "unknown connection",
+        };
+        log::debug(format_args!(
+            "  {}, {} body, {} redirects",
+            connection,
+            fact.content_length(),
+            fact.redirects()
+        ));
+    }
+
+    /// The headers to send to the url at the index: the defaults that weren't given, the
+    /// global headers that the url doesn't override and then the url's own headers.
+    fn headers_for(&self, index: usize) -> Vec<(String, String)> {
+        self.headers_with(&self.url_headers[index])
+    }
+
+    /// The headers to send along with a url's own headers.
+    fn headers_with(&self, own: &[(String, String)]) -> Vec<(String, String)> {
+        let given = |name: &str| self.headers.iter().chain(own).any(|(given, _)| given == name);
+        DEFAULT_HEADERS
+            .iter()
+            .filter(|(name, _)| !given(name))
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .chain(
+                self.headers
+                    .iter()
+                    .filter(|(name, _)| own.iter().all(|(own, _)| own != name))
+                    .chain(own)
+                    .cloned(),
+            )
+            .collect()
+    }
+
+    /// When the nth request is due if the engine is paced.
+    fn due(&self, start: Instant, n: usize) -> Option<Instant> {
+        self.interval.map(|interval| start + interval * n as u32)
+    }
+
+    /// Sleeps until the nth request is due when the engine is paced, returning how late it
+    /// was if it was already overdue.
+    fn pace(&self, start: Instant, n: usize) -> Option<Duration> {
+        self.due(start, n).map(|due| {
+            let now = self.clock.now();
+            if due > now {
+                self.clock.sleep(due - now);
+            }
+            now.saturating_duration_since(due)
+        })
+    }
+}
+
+/// Sends a message, as text unless it isn't valid utf-8, and waits for the reply.
+fn round_trip(socket: &mut WebSocket<TcpStream>, message: Vec<u8>) -> tungstenite::Result<Vec<u8>> {
+    let message = match String::from_utf8(message) {
+        Ok(text) => WsMessage::Text(text),
+        Err(e) => WsMessage::Binary(e.into_bytes()),
+    };
+    socket.write_message(message)?;
+    loop {
+        match socket.read_message()? {
+            WsMessage::Text(reply) => return Ok(reply.into_bytes()),
+            WsMessage::Binary(reply) => return Ok(reply),
+            WsMessage::Close(_) => return Err(tungstenite::Error::ConnectionClosed),
+            WsMessage::Ping(_) | WsMessage::Pong(_) => {}
+        }
+    }
+}
+
+/// The client of the hyper engine.
+type HyperClient = hyper::Client<CountingConnector<hyper_tls::HttpsConnector<BindingConnector>>>;
+
+/// The request line of a dry run's request and its exchange, or why it couldn't be made.
+pub type DryRun = (String, Result<Exchange, String>);
+
+/// The final response of a request made by the hyper engine.
+struct HyperExchange {
+    status: u16,
+    version: Option<Version>,
+    content_length: u64,
+    declared_length: Option<u64>,
+    download: Duration,
+    retry_after: Option<Duration>,
+    redirects: u32,
+    captured: Vec<(String, String)>,
+    server_timing: Vec<(String, Duration)>,
+    /// The head of the response, if it's to be dumped.
+    head: Option<Head>,
+    /// The body, which is only inspected once the request has been timed.
+    body: hyper::Chunk,
+}
+
+fn reqwest_method(method: Method) -> reqwest::Method {
+    match method {
+        Method::Get => reqwest::Method::GET,
+        Method::Head => reqwest::Method::HEAD,
+        Method::Post => reqwest::Method::POST,
+    }
+}
+
+fn hyper_method(method: Method) -> hyper::Method {
+    match method {
+        Method::Get => hyper::Method::Get,
+        Method::Head => hyper::Method::Head,
+        Method::Post => hyper::Method::Post,
+    }
+}
+
+/// What was found in the body of a response.
+#[derive(Default)]
+struct Inspection {
+    mismatch: bool,
+    echo_mismatch: bool,
+    checksum: Option<Checksum>,
+    dumped: Option<Dumped>,
+    /// The values of the captured headers, by name.
+    captured: Vec<(String, String)>,
+    /// The phases the response reported with its `Server-Timing` headers.
+    server_timing: Vec<(String, Duration)>,
+}
+
+trait Inspected {
+    fn inspected(self, inspection: Inspection) -> Self;
+}
+
+impl Inspected for Fact {
+    /// Notes what was found in the body, writing the response out first if it was picked
+    /// to be dumped.
+    fn inspected(self, inspection: Inspection) -> Fact {
+        if let Some(dumped) = inspection.dumped {
+            match dumped.write() {
+                Ok(()) => log::verbose(format_args!("Wrote response to {}", dumped.path().display())),
+                Err(e) => eprintln!("Failed to write response to {}: {}", dumped.path().display(), e),
+            }
+        }
+        let fact = inspection
+            .captured
+            .iter()
+            .fold(self, |fact, (name, value)| fact.with_label(name, value))
+            .with_server_timing(inspection.server_timing)
+            .with_body_mismatch(inspection.mismatch)
+            .with_echo_mismatch(inspection.echo_mismatch);
+        match inspection.checksum {
+            Some(checksum) => fact.with_checksum(checksum),
+            None => fact,
+        }
+    }
+}
+
+/// Wraps a hyper connector to count how many connections it has opened, which tells us
+/// whether a request reused a kept-alive connection.
+struct CountingConnector<C> {
+    inner: C,
+    opened: Rc<Cell<usize>>,
+}
+
+impl<C> Service for CountingConnector<C>
+where
+    C: Service<Request = Uri, Error = io::Error>,
+{
+    type Request = Uri;
+    type Response = C::Response;
+    type Error = io::Error;
+    type Future = C::Future;
+
+    fn call(&self, uri: Uri) -> Self::Future {
+        self.opened.set(self.opened.get() + 1);
+        self.inner.call(uri)
+    }
+}
+
+/// The family of the last connection hyper opened to each host. Hyper doesn't tell which
+/// connection a response came over, so a request is taken to have gone over the family of
+/// its host's connections.
+#[derive(Clone, Default)]
+struct HostFamilies(Rc<RefCell<HashMap<String, Family>>>);
+
+impl HostFamilies {
+    fn record(&self, uri: &Uri, stream: &tokio_core::net::TcpStream) {
+        if let (Some(host), Ok(peer)) = (uri.host(), stream.peer_addr()) {
+            self.0.borrow_mut().insert(host.to_string(), Family::of(&peer.ip()));
         }
     }
+
+    fn of(&self, uri: &Uri) -> Option<Family> {
+        uri.host().and_then(|host| self.0.borrow().get(host).cloned())
+    }
+}
+
+/// Connects hyper from the local addresses that were given, or over the family that was
+/// asked for, which its own connector can't, and otherwise leaves connecting to it. The
+/// bound connection is opened blocking, which costs nothing since every worker waits on
+/// one request at a time anyway.
+struct BindingConnector {
+    http: hyper::client::HttpConnector,
+    binds: Option<Binds>,
+    family: Option<Family>,
+    families: HostFamilies,
+    handle: tokio_core::reactor::Handle,
+}
+
+impl Service for BindingConnector {
+    type Request = Uri;
+    type Response = tokio_core::net::TcpStream;
+    type Error = io::Error;
+    type Future = Box<dyn futures::Future<Item = Self::Response, Error = io::Error>>;
+
+    fn call(&self, uri: Uri) -> Self::Future {
+        use futures::Future;
+
+        let families = self.families.clone();
+        if self.binds.is_none() && self.family.is_none() {
+            return Box::new(self.http.call(uri.clone()).map(move |stream| {
+                families.record(&uri, &stream);
+                stream
+            }));
+        }
+        let port = uri
+            .port()
+            .unwrap_or(if uri.scheme() == Some("https") { 443 } else { 80 });
+        let connected = match uri.host() {
+            Some(host) => bind::connect((host_name(host), port), self.binds.as_ref(), self.family)
+                .and_then(|stream| tokio_core::net::TcpStream::from_stream(stream, &self.handle)),
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput, "The url has no host")),
+        };
+        if let Ok(ref stream) = connected {
+            families.record(&uri, stream);
+        }
+        Box::new(futures::future::result(connected))
+    }
+}
+
+/// The host of a url as it's resolved, without the brackets around an ipv6 address.
+fn host_name(host: &str) -> &str {
+    host.trim_start_matches('[').trim_end_matches(']')
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use stats::Summary;
+    use clock::Manual;
+    use selftest;
+
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    fn header(name: &str, value: &str) -> (String, String) {
+        (name.to_string(), value.to_string())
+    }
 
     #[test]
-    fn reqwest_engine_can_collect_facts() {
-        let eng = Engine::new(vec!["https://www.google.com".to_string()], vec![]);
-        let mut fact: Option<Fact> = None;
-        eng.run(1, |f| fact = Some(f));
-        assert!(fact.is_some());
+    fn seeds_each_worker_with_a_seed_of_its_own() {
+        let eng = Engine::new(vec!["http://a".to_string()], Vec::new()).with_seed(7);
+        let first = |eng: Engine| eng.rng().next_u64();
+        assert_eq!(first(eng.clone().for_worker(1)), Rng::new(8).next_u64());
+        assert_eq!(first(eng.clone().for_worker(1)), first(eng.clone().for_worker(1)));
+        assert_ne!(first(eng.clone().for_worker(0)), first(eng.for_worker(1)));
     }
 
     #[test]
-    fn hyper_engine_can_collect_facts() {
-        let eng = Engine::new(vec!["https://www.google.com".to_string()], vec![]).with_hyper();
-        let mut fact: Option<Fact> = None;
-        eng.run(1, |f| fact = Some(f));
-        assert!(fact.is_some());
+    fn paces_and_backs_off_by_its_clock() {
+        let ms = Duration::from_millis;
+        let clock = Manual::new();
+        let stop = Stop::new();
+        let eng = Engine::new(vec!["http://a".to_string()], Vec::new())
+            .with_interval(ms(100))
+            .with_respected_retry_after()
+            .with_stop(stop.clone())
+            .with_clock(Arc::new(clock.clone()));
+        let start = eng.ready();
+        assert_eq!(eng.pace(start, 0), Some(ms(0)));
+        // Sleeps until the request is due, or not at all if it's overdue.
+        assert_eq!(eng.pace(start, 3), Some(ms(0)));
+        assert_eq!(clock.moved(), ms(300));
+        clock.advance(ms(250));
+        assert_eq!(eng.pace(start, 4), Some(ms(150)));
+        assert_eq!(clock.moved(), ms(550));
+
+        let busy = || Fact::record(ContentLength::zero(), 503, ms(1)).with_retry_after(Some(Duration::from_secs(2)));
+        let mut collected = 0;
+        eng.back_off(busy(), &mut |_| collected += 1);
+        assert_eq!(clock.moved(), ms(2_550));
+        stop.stop("stopped".to_string());
+        eng.back_off(busy(), &mut |_| collected += 1);
+        assert_eq!((collected, clock.moved()), (2, ms(2_550)));
     }
 
     #[test]
-    fn reqwest_engine_can_pass_headers() {
-        // Request without headers first
-        let eng = Engine::new(vec!["https://httpbin.org/headers".to_string()], vec![]);
-        let mut fact: Option<Fact> = None;
-        eng.run(1, |f| fact = Some(f));
+    fn notes_when_each_request_started_by_its_clock() {
+        let ms = Duration::from_millis;
+        let clock = Manual::new();
+        let eng = Engine::new(vec!["http://a".to_string()], Vec::new()).with_clock(Arc::new(clock.clone()));
+        let start = eng.ready();
+        clock.advance(ms(500));
+        let fact = eng.finish(start, 0, "http://a", None, None, Fact::record(ContentLength::zero(), 200, ms(100)));
+        assert_eq!(fact.started(), Some(ms(400)));
+    }
 
-        let mut without_headers_size = 0;
-        if let Some(f) = fact {
-            without_headers_size = Summary::from_facts(&[f]).content_length().bytes();
-        }
+    #[test]
+    fn captures_each_header_by_its_lowercase_name() {
+        let eng = Engine::new(vec!["http://a".to_string()], Vec::new())
+            .with_captured_header("X-Cache")
+            .with_captured_header("age");
+        let captured = eng.capture(|name| match name {
+            "x-cache" => vec!["HIT".to_string(), "HIT".to_string()],
+            _ => Vec::new(),
+        });
+        assert_eq!(captured, vec![header("x-cache", "HIT, HIT"), header("age", "(none)")]);
 
-        // Request with headers
-        let (k, v) = ("key", "val");
+        let fact = Fact::record(ContentLength::zero(), 200, Duration::from_millis(1)).inspected(Inspection {
+            captured,
+            ..Inspection::default()
+        });
+        assert_eq!(fact.labels()[0], header("x-cache", "HIT, HIT"));
+    }
+
+    #[test]
+    fn added_headers_replace_those_with_the_same_name() {
         let eng = Engine::new(
-            vec!["https://httpbin.org/headers".to_string()],
-            vec![(k.to_string(), v.to_string())]
+            vec!["http://a".to_string()],
+            vec![header("user-agent", "rench"), header("x-key", "1")],
+        )
+        .with_headers(&[header("user-agent", "phone")]);
+        assert_eq!(
+            eng.headers_for(0),
+            vec![header("accept", "*/*"), header("x-key", "1"), header("user-agent", "phone")]
         );
-        let mut fact: Option<Fact> = None;
-        eng.run(1, |f| fact = Some(f));
-
-        // Sample response
-        // {
-        //   "headers": {
-        //     "Accept": "*/*",
-        //     "Connection": "close",
-        //     "Host": "httpbin.org",
-        //     "Key": "val",
-        //     "User-Agent": "curl/7.47.0"
-        //   }
-        // }
-        // The 13 bytes represent the extra characters returned by
-        // httpbin to pretty print the output of the header key and val.
-        let extra_char_bytes = 13;
-
-        if let Some(f) = fact {
-            assert_eq!(
-                Summary::from_facts(&[f]).content_length().bytes() as usize,
-                (without_headers_size as usize)+ k.as_bytes().len() + k.as_bytes().len() + extra_char_bytes
-            )
-        }
+    }
 
-        let (k, v) = ("key1", "val1");
+    #[test]
+    fn url_headers_replace_global_headers_with_the_same_name() {
         let eng = Engine::new(
-            vec!["https://httpbin.org/headers".to_string()],
-            vec![(k.to_string(), v.to_string())]
+            vec!["http://a".to_string(), "http://b".to_string()],
+            vec![header("host", "a.example.com"), header("x-key", "1")],
+        )
+        .with_url_headers("http://b", vec![header("host", "b.example.com")]);
+        let defaults: Vec<(String, String)> = DEFAULT_HEADERS
+            .iter()
+            .map(|(name, value)| header(name, value))
+            .collect();
+        assert_eq!(
+            eng.headers_for(0)[2..],
+            [header("host", "a.example.com"), header("x-key", "1")]
         );
-        let mut fact: Option<Fact> = None;
-        eng.run(1, |f| fact = Some(f));
+        assert_eq!(
+            eng.headers_for(1),
+            [&defaults[..], &[header("x-key", "1"), header("host", "b.example.com")]].concat()
+        );
+    }
 
-        if let Some(f) = fact {
-            assert_eq!(
-                Summary::from_facts(&[f]).content_length().bytes() as usize,
-                (without_headers_size as usize)+ k.as_bytes().len() + k.as_bytes().len() + extra_char_bytes
-            )
+    const OK: &str = "HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok";
+    const FOUND: &str = "HTTP/1.1 302 Found\r\nlocation: /next\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+
+    /// Makes a single request with the engine to a local server that answers each
+    /// connection with the next of the responses. Returns the fact and the names of the
+    /// headers the server received first.
+    fn request_with(engine: fn(String) -> Engine, responses: &'static [&'static str]) -> (Fact, Vec<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let mut first = None;
+            for response in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut names = Vec::new();
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                loop {
+                    line.clear();
+                    reader.read_line(&mut line).unwrap();
+                    match line.trim_end().find(':') {
+                        Some(split) => names.push(line[..split].to_lowercase()),
+                        None => break,
+                    }
+                }
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+                names.sort();
+                first = first.or(Some(names));
+            }
+            first.unwrap()
+        });
+        let mut fact = None;
+        engine(url).run(1, |f| fact = Some(f));
+        (fact.unwrap(), server.join().unwrap())
+    }
+
+    #[test]
+    fn http_engines_send_the_same_request() {
+        let engines: [fn(String) -> Engine; 3] = [
+            |url| Engine::new(vec![url], vec![header("x-key", "1")]),
+            |url| Engine::new(vec![url], vec![header("x-key", "1")]).with_hyper(),
+            |url| Engine::new(vec![url], vec![header("x-key", "1")]).with_raw(),
+        ];
+        for engine in engines.iter() {
+            let (fact, names) = request_with(*engine, &[OK]);
+            assert_eq!(names, vec!["accept", "host", "user-agent", "x-key"]);
+            assert_eq!(fact.status(), Some(200));
+            assert_eq!(fact.content_length().bytes(), 2);
+        }
+    }
+
+    static RESPONDED: AtomicUsize = AtomicUsize::new(0);
+
+    /// Signs every request and counts the responses it's shown.
+    struct Signed;
+
+    impl Middleware for Signed {
+        fn on_request(&self, request: &mut Request) {
+            let signature = request.url().to_string();
+            request.set_header("X-Signature", &signature);
+        }
+
+        fn on_response(&self, request: &Request, fact: &Fact) {
+            assert!(request.headers().iter().any(|(name, _)| name == "x-signature"));
+            assert_eq!(fact.status(), Some(200));
+            RESPONDED.fetch_add(1, Ordering::SeqCst);
         }
     }
 
     #[test]
-    fn hyper_engine_can_pass_headers() {
-        // Request without headers first
-        let eng = Engine::new(vec!["https://httpbin.org/headers".to_string()], vec![]).with_hyper();
-        let mut fact: Option<Fact> = None;
-        eng.run(1, |f| fact = Some(f));
+    fn http_engines_run_the_middleware() {
+        let engines: [fn(String) -> Engine; 3] = [
+            |url| Engine::new(vec![url], vec![]).with_middleware(Signed),
+            |url| Engine::new(vec![url], vec![]).with_hyper().with_middleware(Signed),
+            |url| Engine::new(vec![url], vec![]).with_raw().with_middleware(Signed),
+        ];
+        for engine in engines.iter() {
+            let (_, names) = request_with(*engine, &[OK]);
+            assert_eq!(names, vec!["accept", "host", "user-agent", "x-signature"]);
+        }
+        assert_eq!(RESPONDED.load(Ordering::SeqCst), 3);
+    }
+
+    /// Breaks the url of every request.
+    struct BrokenUrl;
 
-        let mut without_headers_size = 0;
-        if let Some(f) = fact {
-            without_headers_size = Summary::from_facts(&[f]).content_length().bytes();
+    impl Middleware for BrokenUrl {
+        fn on_request(&self, request: &mut Request) {
+            request.set_url("http://[broken/".to_string());
         }
+    }
 
-        // Request with headers
-        let (k, v) = ("key", "val");
-        let eng = Engine::new(
-            vec!["https://httpbin.org/headers".to_string()],
-            vec![(k.to_string(), v.to_string())]
-        ).with_hyper();
-        let mut fact: Option<Fact> = None;
-        eng.run(1, |f| fact = Some(f));
-
-        // Sample response
-        // {
-        //   "headers": {
-        //     "Accept": "*/*",
-        //     "Connection": "close",
-        //     "Host": "httpbin.org",
-        //     "Key": "val",
-        //     "User-Agent": "curl/7.47.0"
-        //   }
-        // }
-        // The 13 bytes represent the extra characters returned by
-        // httpbin to pretty print the output of the header key and val.
-        let extra_char_bytes = 13;
-
-        if let Some(f) = fact {
-            assert_eq!(
-                Summary::from_facts(&[f]).content_length().bytes() as usize,
-                (without_headers_size as usize)+ k.as_bytes().len() + k.as_bytes().len() + extra_char_bytes
-            )
+    /// Adds a header that can't be sent to every request.
+    struct BrokenHeader;
+
+    impl Middleware for BrokenHeader {
+        fn on_request(&self, request: &mut Request) {
+            request.set_header("x-broken", "a\u{1}b");
         }
+    }
 
-        let (k, v) = ("key1", "val1");
-        let eng = Engine::new(
-            vec!["https://httpbin.org/headers".to_string()],
-            vec![(k.to_string(), v.to_string())]
-        ).with_hyper();
-        let mut fact: Option<Fact> = None;
-        eng.run(1, |f| fact = Some(f));
-
-        if let Some(f) = fact {
-            assert_eq!(
-                Summary::from_facts(&[f]).content_length().bytes() as usize,
-                (without_headers_size as usize)+ k.as_bytes().len() + k.as_bytes().len() + extra_char_bytes
-            )
+    #[test]
+    fn http_engines_record_requests_they_cant_send_as_errors() {
+        let engines: [fn(String) -> Engine; 4] = [
+            |url| Engine::new(vec![url], vec![]).with_middleware(BrokenUrl),
+            |url| Engine::new(vec![url], vec![]).with_hyper().with_middleware(BrokenUrl),
+            |url| Engine::new(vec![url], vec![]).with_raw().with_middleware(BrokenUrl),
+            |url| Engine::new(vec![url], vec![]).with_middleware(BrokenHeader),
+        ];
+        for engine in engines.iter() {
+            let mut facts = Vec::new();
+            engine("http://127.0.0.1:1/".to_string()).run(2, |fact| facts.push(fact));
+            assert_eq!(facts.len(), 2);
+            assert!(facts.iter().all(Fact::is_error));
+        }
+    }
+
+    #[test]
+    fn only_http_engines_make_a_dry_run() {
+        let urls = || vec!["ws://127.0.0.1:1/".to_string()];
+        assert!(Engine::new(urls(), vec![]).with_websocket().dry_run().is_err());
+        assert!(Engine::new(urls(), vec![]).with_tcp(tcp::Reply::UntilClose).dry_run().is_err());
+    }
+
+    #[test]
+    fn http_engines_follow_redirects_the_same_way() {
+        let engines: [fn(String) -> Engine; 3] = [
+            |url| Engine::new(vec![url], vec![]),
+            |url| Engine::new(vec![url], vec![]).with_hyper(),
+            |url| Engine::new(vec![url], vec![]).with_raw(),
+        ];
+        for engine in engines.iter() {
+            let (fact, _) = request_with(*engine, &[FOUND]);
+            assert_eq!((fact.status(), fact.redirects()), (Some(302), 0));
         }
+        let following: [fn(String) -> Engine; 3] = [
+            |url| Engine::new(vec![url], vec![]).with_redirects(1),
+            |url| Engine::new(vec![url], vec![]).with_hyper().with_redirects(1),
+            |url| Engine::new(vec![url], vec![]).with_raw().with_redirects(1),
+        ];
+        for engine in following.iter() {
+            let (fact, _) = request_with(*engine, &[FOUND, OK]);
+            assert_eq!((fact.status(), fact.redirects()), (Some(200), 1));
+        }
+    }
+
+    /// The url of the path on a test server of its own.
+    fn selftest_url(path: &str) -> String {
+        format!("http://{}{}", selftest::start("127.0.0.1:0").unwrap(), path)
+    }
+
+    /// The fact of a single request with the engine to the url with the headers.
+    fn fact_with(engine: fn(Engine) -> Engine, url: &str, headers: &[(&str, &str)]) -> Fact {
+        let headers = headers.iter().map(|&(name, value)| header(name, value)).collect();
+        let mut fact = None;
+        engine(Engine::new(vec![url.to_string()], headers)).run(1, |f| fact = Some(f));
+        fact.unwrap()
+    }
+
+    #[test]
+    fn reqwest_engine_can_collect_facts() {
+        let fact = fact_with(|eng| eng, &selftest_url("/bytes/10"), &[]);
+        assert_eq!((fact.status(), fact.content_length().bytes()), (Some(200), 10));
+    }
+
+    #[test]
+    fn hyper_engine_can_collect_facts() {
+        let fact = fact_with(Engine::with_hyper, &selftest_url("/bytes/10"), &[]);
+        assert_eq!((fact.status(), fact.content_length().bytes()), (Some(200), 10));
+    }
+
+    #[test]
+    fn websocket_round_trips_have_no_status() {
+        let url = selftest_url("/ws").replace("http://", "ws://");
+        let fact = fact_with(Engine::with_websocket, &url, &[]);
+        assert_eq!(fact.status(), None);
+        assert_eq!(fact.content_length().bytes(), DEFAULT_WEBSOCKET_MESSAGE.len() as u64);
+        assert!(fact.is_success() && !fact.is_error());
+    }
+
+    #[test]
+    fn tcp_round_trips_have_no_status() {
+        let url = selftest_url("").replace("http://", "tcp://");
+        let request = b"GET /bytes/3 HTTP/1.0\r\n\r\n".to_vec();
+        let mut fact = None;
+        Engine::new(vec![url], vec![])
+            .with_tcp(tcp::Reply::UntilClose)
+            .with_body(Payloads::fixed(request))
+            .run(1, |f| fact = Some(f));
+        let fact = fact.unwrap();
+        let reply = "HTTP/1.1 200 OK\r\ncontent-length: 3\r\nconnection: close\r\n\r\nxxx";
+        assert_eq!((fact.status(), fact.content_length().bytes()), (None, reply.len() as u64));
+        assert!(fact.is_success() && !fact.is_error());
+    }
+
+    /// The test server echoes the `x-` headers of a request as lines like `x-key: val`.
+    fn passes_headers(engine: fn(Engine) -> Engine) {
+        let url = selftest_url("/headers");
+        let size = |headers: &[(&str, &str)]| fact_with(engine, &url, headers).content_length().bytes();
+        assert_eq!(size(&[]), 0);
+        assert_eq!(size(&[("x-key", "val")]), "x-key: val\n".len() as u64);
+        assert_eq!(size(&[("x-key1", "val1"), ("x-key2", "val2")]), "x-key1: val1\nx-key2: val2\n".len() as u64);
+    }
+
+    #[test]
+    fn reqwest_engine_can_pass_headers() {
+        passes_headers(|eng| eng);
+    }
+
+    #[test]
+    fn hyper_engine_can_pass_headers() {
+        passes_headers(Engine::with_hyper);
     }
 }
@@ -0,0 +1,77 @@
+use std::{error, fmt, io};
+
+/// Why rench couldn't do what it was asked. Each kind exits with a code of its own, so
+/// that a script can tell a mistyped command line from a token server that was down or a
+/// run that finished but failed its checks.
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// The run was made, but failed a check such as `--max-error-rate` or
+    /// `--max-regression`.
+    Failed(String),
+    /// An argument couldn't be understood, such as a number that isn't one.
+    Usage(String),
+    /// The arguments make sense on their own but not together, such as an open loop on an
+    /// engine that can't keep many requests in flight.
+    Plan(String),
+    /// A file couldn't be read or written.
+    Io(String),
+    /// The engine couldn't be set up, such as when no OAuth2 token was granted or the
+    /// threads couldn't be pinned.
+    Engine(String),
+}
+
+impl Error {
+    /// The code that rench exits with.
+    pub fn code(&self) -> i32 {
+        match *self {
+            Error::Failed(_) => 1,
+            Error::Usage(_) => 2,
+            Error::Plan(_) => 3,
+            Error::Io(_) => 4,
+            Error::Engine(_) => 5,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Failed(ref message)
+            | Error::Usage(ref message)
+            | Error::Plan(ref message)
+            | Error::Io(ref message)
+            | Error::Engine(ref message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exits_with_a_code_for_each_kind() {
+        let codes: Vec<i32> = [
+            Error::Failed("".to_string()),
+            Error::Usage("".to_string()),
+            Error::Plan("".to_string()),
+            Error::Io("".to_string()),
+            Error::Engine("".to_string()),
+        ]
+        .iter()
+        .map(Error::code)
+        .collect();
+        assert_eq!(codes, vec![1, 2, 3, 4, 5]);
+
+        let error = Error::from(io::Error::new(io::ErrorKind::NotFound, "No such file"));
+        assert_eq!(error, Error::Io("No such file".to_string()));
+    }
+}
@@ -0,0 +1,53 @@
+use regex::Regex;
+
+/// A check that every response body must pass for its request to count as a success, so
+/// that a 200 carrying an error payload is still a failure.
+#[derive(Clone, Debug)]
+pub enum Expectation {
+    Contains(String),
+    Matches(Regex),
+}
+
+impl Expectation {
+    /// Expects the body to match the regex.
+    pub fn regex(pattern: &str) -> Result<Expectation, String> {
+        Regex::new(pattern)
+            .map(Expectation::Matches)
+            .map_err(|e| format!("Invalid body regex {:?}: {}", pattern, e))
+    }
+
+    /// Whether the body passes the check. Bodies that aren't utf-8 are checked by their
+    /// lossy conversion.
+    pub fn check(&self, body: &[u8]) -> bool {
+        let body = String::from_utf8_lossy(body);
+        match *self {
+            Expectation::Contains(ref text) => body.contains(text.as_str()),
+            Expectation::Matches(ref regex) => regex.is_match(&body),
+        }
+    }
+}
+
+/// Whether the body passes every one of the checks.
+pub fn all(expectations: &[Expectation], body: &[u8]) -> bool {
+    expectations.iter().all(|expectation| expectation.check(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_substrings_and_regexes() {
+        let ok = Expectation::Contains("\"ok\":true".to_string());
+        assert!(ok.check(b"{\"ok\":true}"));
+        assert!(!ok.check(b"{\"ok\":false}"));
+
+        let id = Expectation::regex("\"id\":\\d+").unwrap();
+        assert!(id.check(b"{\"id\":12}"));
+        assert!(!id.check(b"{\"id\":null}"));
+        assert!(all(&[ok.clone(), id.clone()], b"{\"ok\":true,\"id\":1}"));
+        assert!(!all(&[ok, id], b"{\"ok\":true}"));
+
+        assert!(Expectation::regex("(").is_err());
+    }
+}
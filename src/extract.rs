@@ -0,0 +1,212 @@
+use csv_feed;
+use middleware::{Middleware, Request};
+use regex::Regex;
+use serde_json::{self, Value};
+use std::collections::HashMap;
+use std::str;
+use std::sync::Mutex;
+use std::thread::{self, ThreadId};
+
+/// A value to take from the body of each response, by a json pointer or a regex.
+#[derive(Debug)]
+pub struct Extraction {
+    name: String,
+    source: Source,
+}
+
+#[derive(Debug)]
+enum Source {
+    Pointer(String),
+    Regex(Regex),
+}
+
+impl Extraction {
+    /// Parses an extraction like `token=json:/access_token` or `id=regex:"id":(\d+)`.
+    pub fn parse(value: &str) -> Result<Extraction, String> {
+        let invalid = || {
+            format!(
+                "Expected an extraction like 'token=json:/access_token' or 'id=regex:\"id\":(\\d+)', got '{}'",
+                value
+            )
+        };
+        let at = value.find('=').ok_or_else(invalid)?;
+        let (name, source) = (value[..at].trim(), &value[at + 1..]);
+        if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == '{' || c == '}') {
+            return Err(invalid());
+        }
+        let source = if let Some(pointer) = source.strip_prefix("json:") {
+            if !pointer.starts_with('/') {
+                return Err(format!("Expected a json pointer starting with '/', got '{}'", pointer));
+            }
+            Source::Pointer(pointer.to_string())
+        } else if let Some(pattern) = source.strip_prefix("regex:") {
+            Source::Regex(Regex::new(pattern).map_err(|e| format!("Invalid extraction regex '{}': {}", pattern, e))?)
+        } else {
+            return Err(invalid());
+        };
+        Ok(Extraction {
+            name: name.to_string(),
+            source,
+        })
+    }
+
+    /// The value in the body, if it has one. A json string is taken without its quotes and
+    /// any other json value as it's written, and a regex takes its first group, or the
+    /// whole match if it has no groups.
+    fn find(&self, body: &[u8], json: &mut Option<Option<Value>>) -> Option<String> {
+        match self.source {
+            Source::Pointer(ref pointer) => {
+                let json = json.get_or_insert_with(|| serde_json::from_slice(body).ok());
+                match json.as_ref()?.pointer(pointer)? {
+                    Value::String(value) => Some(value.clone()),
+                    value => Some(value.to_string()),
+                }
+            }
+            Source::Regex(ref regex) => {
+                let captures = regex.captures(str::from_utf8(body).ok()?)?;
+                captures.get(1).or_else(|| captures.get(0)).map(|found| found.as_str().to_string())
+            }
+        }
+    }
+}
+
+/// Values taken from the bodies of responses and filled into the url, headers and body of
+/// later requests in place of their `{{name}}` placeholders, such as the token a login
+/// returns or the id of a resource a request created. Each worker keeps the values of its
+/// own responses, so a worker that takes the urls in turn sends what its earlier requests
+/// were given. Until a worker has a value its placeholders are sent as they are, and a
+/// response without a value leaves the last one in place.
+#[derive(Debug)]
+pub struct Extractor {
+    extractions: Vec<Extraction>,
+    values: Mutex<HashMap<ThreadId, Vec<Option<String>>>>,
+}
+
+impl Extractor {
+    pub fn new(extractions: Vec<Extraction>) -> Extractor {
+        Extractor {
+            extractions,
+            values: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The names of the values, which templates keep as placeholders to be filled in.
+    pub fn names(&self) -> Vec<String> {
+        self.extractions.iter().map(|extraction| extraction.name.clone()).collect()
+    }
+
+    /// Takes the values in the body of a response to the current worker.
+    pub fn extract(&self, body: &[u8]) {
+        let mut json = None;
+        let found: Vec<Option<String>> = self
+            .extractions
+            .iter()
+            .map(|extraction| extraction.find(body, &mut json))
+            .collect();
+        if found.iter().all(Option::is_none) {
+            return;
+        }
+        let mut values = self.values.lock().expect("Extracted values poisoned");
+        let values = values
+            .entry(thread::current().id())
+            .or_insert_with(|| vec![None; self.extractions.len()]);
+        for (value, found) in values.iter_mut().zip(found) {
+            if found.is_some() {
+                *value = found;
+            }
+        }
+    }
+}
+
+impl Middleware for Extractor {
+    fn on_request(&self, request: &mut Request) {
+        let values = match self.values.lock().expect("Extracted values poisoned").get(&thread::current().id()) {
+            Some(values) => values.clone(),
+            None => return,
+        };
+        let value_of = |name: &str| {
+            self.extractions
+                .iter()
+                .position(|extraction| extraction.name == name)
+                .and_then(|at| values[at].as_deref())
+        };
+        if let Some(url) = csv_feed::fill_url(request.url(), value_of) {
+            request.set_url(url);
+        }
+        let headers: Vec<(String, String)> = request
+            .headers()
+            .iter()
+            .map(|(name, value)| (name.clone(), csv_feed::fill(value, value_of).unwrap_or_else(|| value.clone())))
+            .collect();
+        if headers != request.headers() {
+            request.set_headers(headers);
+        }
+        let body = request
+            .body()
+            .and_then(|body| str::from_utf8(body).ok())
+            .and_then(|body| csv_feed::fill(body, value_of));
+        if let Some(body) = body {
+            request.set_body(Some(body.into_bytes()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_extractions() {
+        let token = Extraction::parse("token=json:/access_token").unwrap();
+        assert_eq!(token.name, "token");
+        let id = Extraction::parse("id=regex:\"id\":(\\d+)").unwrap();
+        assert_eq!(id.name, "id");
+        assert!(Extraction::parse("token").is_err());
+        assert!(Extraction::parse("=json:/token").is_err());
+        assert!(Extraction::parse("token=json:token").is_err());
+        assert!(Extraction::parse("token=xpath://token").is_err());
+        assert!(Extraction::parse("id=regex:(").is_err());
+    }
+
+    #[test]
+    fn finds_values_by_pointer_or_regex() {
+        let body = br#"{"access_token":"abc","user":{"id":12}}"#;
+        let find = |extraction: &str| Extraction::parse(extraction).unwrap().find(body, &mut None);
+        assert_eq!(find("token=json:/access_token"), Some("abc".to_string()));
+        assert_eq!(find("id=json:/user/id"), Some("12".to_string()));
+        assert_eq!(find("missing=json:/nope"), None);
+        assert_eq!(find("id=regex:\"id\":(\\d+)"), Some("12".to_string()));
+        assert_eq!(find("token=regex:abc"), Some("abc".to_string()));
+        assert_eq!(Extraction::parse("id=json:/id").unwrap().find(b"not json", &mut None), None);
+    }
+
+    #[test]
+    fn fills_the_workers_own_values() {
+        let extractor = Extractor::new(vec![Extraction::parse("token=json:/token").unwrap()]);
+        let headers = vec![("authorization".to_string(), "Bearer {{token}}".to_string())];
+
+        let mut request = Request::new("http://a/{{token}}", &headers, None);
+        extractor.on_request(&mut request);
+        assert!(!request.is_changed());
+
+        extractor.extract(br#"{"token":"abc"}"#);
+        extractor.extract(b"no token here");
+        let mut request = Request::new("http://a/{{token}}", &headers, Some(b"{{ token }}".to_vec()));
+        extractor.on_request(&mut request);
+        assert_eq!(request.url(), "http://a/abc");
+        assert_eq!(request.headers()[0].1, "Bearer abc");
+        assert_eq!(request.body(), Some(&b"abc"[..]));
+
+        // Another worker hasn't extracted anything yet.
+        let extractor = ::std::sync::Arc::new(extractor);
+        let other = extractor.clone();
+        let url = thread::spawn(move || {
+            let mut request = Request::new("http://a/{{token}}", &[], None);
+            other.on_request(&mut request);
+            request.url().to_string()
+        })
+        .join()
+        .unwrap();
+        assert_eq!(url, "http://a/{{token}}");
+    }
+}
@@ -0,0 +1,159 @@
+use random::Rng;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// A multipart/form-data body, such as for an upload endpoint. It's built once before the
+/// run, files and all, and the same bytes are sent with every request.
+#[derive(Debug, PartialEq)]
+pub struct Form {
+    fields: Vec<Field>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Field {
+    Text { name: String, value: String },
+    /// A file read from the path when the form is built.
+    File { name: String, path: String },
+}
+
+impl Form {
+    /// Parses fields like `name=value`, or `name=@path` to send the file at the path.
+    pub fn parse<'a, I>(args: I) -> Result<Form, String>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let fields = args
+            .into_iter()
+            .map(|arg| {
+                let split = arg
+                    .find('=')
+                    .ok_or_else(|| format!("Expected a form field like 'name=value' or 'name=@file', got '{}'", arg))?;
+                let (name, value) = (arg[..split].to_string(), &arg[split + 1..]);
+                if name.is_empty() {
+                    return Err(format!("Expected a field name before the '=' in '{}'", arg));
+                }
+                Ok(match value.strip_prefix('@') {
+                    Some(path) => Field::File {
+                        name,
+                        path: path.to_string(),
+                    },
+                    None => Field::Text {
+                        name,
+                        value: value.to_string(),
+                    },
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Form { fields })
+    }
+
+    /// Reads the files and writes the body, with the content type to send it with, which
+    /// names the boundary between the fields.
+    pub fn build(&self, rng: &mut Rng) -> io::Result<(String, Vec<u8>)> {
+        let mut parts = Vec::with_capacity(self.fields.len());
+        for field in &self.fields {
+            parts.push(match *field {
+                Field::Text { ref name, ref value } => (disposition(name, None), value.clone().into_bytes()),
+                Field::File { ref name, ref path } => {
+                    let bytes = fs::read(path)
+                        .map_err(|e| io::Error::new(e.kind(), format!("Failed to read the form file '{}': {}", path, e)))?;
+                    let filename = Path::new(path).file_name().map(|name| name.to_string_lossy().into_owned());
+                    let head = format!(
+                        "{}\r\ncontent-type: {}",
+                        disposition(name, filename.as_deref()),
+                        content_type(path)
+                    );
+                    (head, bytes)
+                }
+            });
+        }
+        // A boundary is picked again in the unlikely case that a part contains it.
+        let boundary = loop {
+            let boundary = format!("rench-{:016x}{:016x}", rng.next_u64(), rng.next_u64());
+            if !parts.iter().any(|(_, body)| contains(body, boundary.as_bytes())) {
+                break boundary;
+            }
+        };
+        let mut body = Vec::new();
+        for (head, bytes) in parts {
+            body.extend_from_slice(format!("--{}\r\n{}\r\n\r\n", boundary, head).as_bytes());
+            body.extend_from_slice(&bytes);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+        Ok((format!("multipart/form-data; boundary={}", boundary), body))
+    }
+}
+
+/// The content-disposition of a field, with the quotes and line breaks in its names
+/// escaped as browsers do.
+fn disposition(name: &str, filename: Option<&str>) -> String {
+    let escape = |name: &str| name.replace('"', "%22").replace('\r', "%0D").replace('\n', "%0A");
+    match filename {
+        Some(filename) => format!(
+            "content-disposition: form-data; name=\"{}\"; filename=\"{}\"",
+            escape(name),
+            escape(filename)
+        ),
+        None => format!("content-disposition: form-data; name=\"{}\"", escape(name)),
+    }
+}
+
+/// The content type of a file, guessed from its extension.
+fn content_type(path: &str) -> &'static str {
+    let extension = Path::new(path)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("png") => "image/png",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("svg") => "image/svg+xml",
+        Some("pdf") => "application/pdf",
+        Some("json") => "application/json",
+        Some("xml") => "application/xml",
+        Some("zip") => "application/zip",
+        Some("csv") => "text/csv",
+        Some("html") | Some("htm") => "text/html",
+        Some("txt") => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn builds_a_multipart_body() {
+        let path = env::temp_dir().join("rench-form-test.jpg");
+        fs::write(&path, [0xff, 0xd8, 0xff]).unwrap();
+        let form = Form::parse(vec!["title=A \"cat\"", &format!("file=@{}", path.display())]).unwrap();
+        let (content_type, body) = form.build(&mut Rng::new(0)).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let boundary = content_type.trim_start_matches("multipart/form-data; boundary=");
+        assert!(boundary.starts_with("rench-"));
+        let mut expected = format!(
+            "--{0}\r\ncontent-disposition: form-data; name=\"title\"\r\n\r\nA \"cat\"\r\n\
+             --{0}\r\ncontent-disposition: form-data; name=\"file\"; filename=\"rench-form-test.jpg\"\r\n\
+             content-type: image/jpeg\r\n\r\n",
+            boundary
+        )
+        .into_bytes();
+        expected.extend_from_slice(&[0xff, 0xd8, 0xff]);
+        expected.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+        assert_eq!(body, expected);
+
+        assert!(Form::parse(vec!["title"]).is_err());
+        assert!(Form::parse(vec!["=value"]).is_err());
+        assert!(Form::parse(vec!["file=@/no/such/file"]).unwrap().build(&mut Rng::new(0)).is_err());
+    }
+}
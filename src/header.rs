@@ -0,0 +1,174 @@
+use std::env;
+use std::fs;
+
+/// Parses a header argument into the headers it names. The argument is either a single
+/// header in the form `name:value` (or `name=value`), or `@path` to read one header per
+/// line from a file. Blank lines and lines starting with `#` in a file are skipped.
+///
+/// A value of `env:NAME` is read from the environment variable `NAME` so that secrets
+/// don't need to appear on the command line.
+pub fn parse(arg: &str) -> Result<Vec<(String, String)>, String> {
+    if let Some(path) = arg.strip_prefix('@') {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read headers from '{}': {}", path, e))?;
+        contents
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(parse_header)
+            .collect()
+    } else {
+        parse_header(arg).map(|header| vec![header])
+    }
+}
+
+/// Whether a header argument reads a file or the environment, which an agent won't do for
+/// the jobs that controllers send it.
+pub fn is_local(arg: &str) -> bool {
+    arg.starts_with('@') || arg.find([':', '=']).is_some_and(|split| arg[split + 1..].trim().starts_with("env:"))
+}
+
+/// The names of the headers that are given more than once.
+pub fn repeated(headers: &[(String, String)]) -> Vec<String> {
+    let mut repeated: Vec<String> = headers
+        .iter()
+        .enumerate()
+        .filter(|&(index, (name, _))| headers[..index].iter().any(|(other, _)| other == name))
+        .map(|(_, (name, _))| name.clone())
+        .collect();
+    repeated.dedup();
+    repeated
+}
+
+/// Splits a header on the first `:` or `=`, trims both sides and checks that the name is a
+/// valid token and the value has no control characters, so that mistakes are reported
+/// before the run rather than by the engine part way through.
+pub fn parse_header(header: &str) -> Result<(String, String), String> {
+    parse_header_with(header, &|var| env::var(var).ok())
+}
+
+/// Parses a header, looking the `env:NAME` values up with `var` instead of in the
+/// environment.
+fn parse_header_with(header: &str, var: &dyn Fn(&str) -> Option<String>) -> Result<(String, String), String> {
+    let split = header
+        .find([':', '='])
+        .ok_or_else(|| format!("Expected a header like 'name:value', got '{}'", header))?;
+    let name = header[..split].trim();
+    if name.is_empty() {
+        return Err(format!("Expected a header name before the ':' in '{}'", header));
+    }
+    if let Some(c) = name.chars().find(|&c| !is_token(c)) {
+        return Err(format!("The header name '{}' can't contain {:?}", name, c));
+    }
+    let value = resolve(header[split + 1..].trim(), var)?;
+    check_value(name, &value)?;
+    Ok((name.to_lowercase(), value))
+}
+
+/// Checks that a value can be sent in the header, such as one filled in from a feed.
+pub fn check_value(name: &str, value: &str) -> Result<(), String> {
+    if value.chars().any(|c| c.is_control() && c != '\t') {
+        return Err(format!("The value of the header '{}' can't contain control characters", name));
+    }
+    Ok(())
+}
+
+/// Whether a character is allowed in a header name (a token in RFC 7230).
+fn is_token(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c)
+}
+
+fn resolve(value: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String, String> {
+    match value.strip_prefix("env:") {
+        Some(var) => lookup(var).ok_or_else(|| format!("The environment variable '{}' is not set", var)),
+        None => Ok(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn knows_which_headers_are_local() {
+        assert!(is_local("@headers.txt"));
+        assert!(is_local("authorization: env:TOKEN"));
+        assert!(is_local("authorization=env:TOKEN"));
+        assert!(!is_local("x-env:prod"));
+        assert!(!is_local("user-agent:rench"));
+    }
+
+    #[test]
+    fn parses_a_single_header() {
+        assert_eq!(
+            parse("User-Agent:rust-rench"),
+            Ok(vec![("user-agent".to_string(), "rust-rench".to_string())])
+        );
+        assert_eq!(
+            parse("x-key=value"),
+            Ok(vec![("x-key".to_string(), "value".to_string())])
+        );
+        assert!(parse("no-separator").is_err());
+    }
+
+    #[test]
+    fn splits_on_the_first_separator_and_trims() {
+        assert_eq!(
+            parse(" Authorization : Basic dXNlcjpwYXNz== "),
+            Ok(vec![("authorization".to_string(), "Basic dXNlcjpwYXNz==".to_string())])
+        );
+        assert_eq!(
+            parse("x-query=a=b:c"),
+            Ok(vec![("x-query".to_string(), "a=b:c".to_string())])
+        );
+        assert_eq!(parse("x-empty:"), Ok(vec![("x-empty".to_string(), String::new())]));
+    }
+
+    #[test]
+    fn finds_repeated_headers() {
+        let headers = parse("x-a:1").unwrap().into_iter()
+            .chain(parse("x-b:1").unwrap())
+            .chain(parse("x-a:2").unwrap())
+            .chain(parse("x-a:3").unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(repeated(&headers), vec!["x-a".to_string()]);
+    }
+
+    #[test]
+    fn rejects_invalid_names_and_values() {
+        assert!(parse(":value").is_err());
+        assert!(parse("bad name:value").is_err());
+        assert!(parse("bad(name):value").is_err());
+        assert!(parse("x-key:line\nbreak").is_err());
+    }
+
+    #[test]
+    fn reads_values_from_the_environment() {
+        let var = |name: &str| Some("secret".to_string()).filter(|_| name == "API_KEY");
+        assert_eq!(
+            parse_header_with("x-api-key=env:API_KEY", &var),
+            Ok(("x-api-key".to_string(), "secret".to_string()))
+        );
+        assert_eq!(
+            parse_header_with("x-api-key:env:NOT_SET", &var),
+            Err("The environment variable 'NOT_SET' is not set".to_string())
+        );
+    }
+
+    #[test]
+    fn reads_headers_from_a_file() {
+        let path = env::temp_dir().join("rench-header-test.txt");
+        fs::write(&path, "# comment\nx-one:1\n\n  x-two: 2  \nx-one:3\n").unwrap();
+        let headers = parse(&format!("@{}", path.display())).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            headers,
+            vec![
+                ("x-one".to_string(), "1".to_string()),
+                ("x-two".to_string(), "2".to_string()),
+                ("x-one".to_string(), "3".to_string()),
+            ]
+        );
+        assert!(parse("@/does/not/exist").is_err());
+    }
+}
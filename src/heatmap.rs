@@ -0,0 +1,171 @@
+use columns::Columns;
+use report;
+use stats::TimeUnit;
+use std::fmt;
+use std::time::Duration;
+
+/// The shades of a cell from empty to the busiest cell of the map.
+const SHADES: [char; 5] = [' ', '░', '▒', '▓', '█'];
+
+/// Counts the latencies of the requests by when they finished and how long they took, so
+/// that a latency storm part way through a run stands out where the percentiles of the run
+/// as a whole would hide it. Time runs along the columns and latency up the rows, each row
+/// covering twice the latencies of the one below it.
+pub struct Heatmap {
+    /// The count of each row in each column, where row `n` counts the latencies under `2^n`
+    /// microseconds that weren't counted by a lower row.
+    columns: Columns<Vec<u64>>,
+    time_unit: TimeUnit,
+}
+
+impl Default for Heatmap {
+    fn default() -> Heatmap {
+        Heatmap::new()
+    }
+}
+
+impl Heatmap {
+    /// Starts a heatmap whose time starts now.
+    pub fn new() -> Heatmap {
+        Heatmap {
+            columns: Columns::default(),
+            time_unit: TimeUnit::Auto,
+        }
+    }
+
+    pub fn with_time_unit(mut self, unit: TimeUnit) -> Self {
+        self.time_unit = unit;
+        self
+    }
+
+    /// Counts a request that has just finished.
+    pub fn record(&mut self, duration: Duration) {
+        let elapsed = self.columns.elapsed();
+        self.record_at(elapsed, duration);
+    }
+
+    fn record_at(&mut self, elapsed: Duration, duration: Duration) {
+        let row = row_of(duration);
+        let rows = self.columns.at(elapsed);
+        if rows.len() <= row {
+            rows.resize(row + 1, 0);
+        }
+        rows[row] += 1;
+    }
+
+    /// The lowest and highest rows that counted anything.
+    fn rows(&self) -> Option<(usize, usize)> {
+        self.columns.columns().iter().fold(None, |range, rows| {
+            rows.iter()
+                .enumerate()
+                .filter(|&(_, &count)| count > 0)
+                .fold(range, |range, (row, _)| match range {
+                    Some((low, high)) => Some((usize::min(low, row), usize::max(high, row))),
+                    None => Some((row, row)),
+                })
+        })
+    }
+
+    /// The heatmap in the shape of the JSON report.
+    pub fn report(&self) -> report::Heatmap {
+        let (low, high) = self.rows().unwrap_or((0, 0));
+        report::Heatmap {
+            column_ms: self.columns.width().as_secs_f64() * 1_000.,
+            rows_under_ms: (low..=high).map(|row| upper_bound(row).as_secs_f64() * 1_000.).collect(),
+            counts: self
+                .columns
+                .columns()
+                .iter()
+                .map(|rows| (low..=high).map(|row| rows.get(row).cloned().unwrap_or(0)).collect())
+                .collect(),
+        }
+    }
+}
+
+/// The row that counts the latency, which is the number of bits in its microseconds.
+fn row_of(duration: Duration) -> usize {
+    let micros = duration.as_micros().min(u128::from(u64::MAX)) as u64;
+    (64 - micros.leading_zeros()) as usize
+}
+
+/// The latency that every latency counted by the row is under.
+fn upper_bound(row: usize) -> Duration {
+    Duration::from_micros(1u64.checked_shl(row as u32).unwrap_or(u64::MAX))
+}
+
+fn shade(count: u64, busiest: u64) -> char {
+    if count == 0 {
+        return SHADES[0];
+    }
+    let level = (count as f64 / busiest as f64 * (SHADES.len() - 1) as f64).ceil() as usize;
+    SHADES[level.clamp(1, SHADES.len() - 1)]
+}
+
+impl fmt::Display for Heatmap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (low, high) = match self.rows() {
+            Some(rows) => rows,
+            None => return Ok(()),
+        };
+        let (columns, took) = self.columns.printed();
+        let busiest = columns.iter().flatten().cloned().max().unwrap_or(0);
+        let labels: Vec<String> = (low..=high)
+            .map(|row| format!("< {}", self.time_unit.format(upper_bound(row))))
+            .collect();
+        let width = labels.iter().map(|label| label.len()).max().unwrap_or(0);
+
+        writeln!(f, "Latency heatmap (darker is more requests):")?;
+        for (row, label) in (low..high + 1).zip(&labels).rev() {
+            let cells: String = columns
+                .iter()
+                .map(|rows| shade(rows.get(row).cloned().unwrap_or(0), busiest))
+                .collect();
+            writeln!(f, "  {:>width$} │{}", label, cells, width = width)?;
+        }
+        writeln!(
+            f,
+            "  {:>width$} └ 0 s to {:.2} s",
+            "",
+            took.as_secs_f64(),
+            width = width
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use columns::{FIRST_COLUMN_MS, MAX_COLUMNS};
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn doubles_the_columns_once_the_run_outgrows_them() {
+        let mut heatmap = Heatmap::new();
+        heatmap.record_at(ms(0), ms(1));
+        heatmap.record_at(ms(FIRST_COLUMN_MS * MAX_COLUMNS as u64), ms(1));
+        assert_eq!(heatmap.columns.width(), ms(FIRST_COLUMN_MS * 2));
+        assert_eq!(heatmap.columns.columns().len(), MAX_COLUMNS / 2 + 1);
+        assert_eq!(heatmap.columns.columns()[0][row_of(ms(1))], 1);
+    }
+
+    #[test]
+    fn shades_each_cell_by_its_count() {
+        let mut heatmap = Heatmap::new().with_time_unit(TimeUnit::Millis);
+        for _ in 0..4 {
+            heatmap.record_at(ms(0), Duration::from_micros(1_500));
+        }
+        heatmap.record_at(ms(10), Duration::from_micros(1_500));
+        heatmap.record_at(ms(10), Duration::from_micros(3_000));
+        assert_eq!(
+            heatmap.to_string(),
+            "Latency heatmap (darker is more requests):\n  < 4.10 ms │ ░\n  < 2.05 ms │█░\n            └ 0 s to 0.02 s\n"
+        );
+
+        let report = heatmap.report();
+        assert_eq!(report.counts, vec![vec![4, 0], vec![1, 1]]);
+        assert_eq!(report.rows_under_ms, vec![2.048, 4.096]);
+    }
+}
@@ -0,0 +1,179 @@
+use std::time::Duration;
+
+/// The number of buckets each power of two is split into once values leave the linear
+/// range. 128 buckets keeps the error of any recorded value under 1%.
+const SUB_BUCKET_BITS: u32 = 7;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+const LINEAR_BUCKETS: u64 = SUB_BUCKETS * 2;
+
+/// A log-linear histogram of durations in the spirit of an HDR histogram. Values are
+/// recorded in microseconds into buckets that grow with the magnitude of the value, which
+/// allows quantiles to be read back without keeping every value in memory. Histograms can
+/// be merged, so they can be built up independently and combined later.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    total: u64,
+}
+
+impl Histogram {
+    /// Creates an empty histogram.
+    pub fn new() -> Histogram {
+        Histogram::default()
+    }
+
+    /// Records a single duration.
+    pub fn record(&mut self, duration: Duration) {
+        let index = index_of(micros(duration));
+        if self.counts.len() <= index {
+            self.counts.resize(index + 1, 0);
+        }
+        self.counts[index] += 1;
+        self.total += 1;
+    }
+
+    /// Adds all of the durations recorded in another histogram to this one.
+    pub fn merge(&mut self, other: &Histogram) {
+        if self.counts.len() < other.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        self.total += other.total;
+    }
+
+    /// The count of each bucket that counted anything, by the lowest value in microseconds
+    /// that the bucket holds. Recording each of those values that many times rebuilds the
+    /// histogram, which is how it's saved with a run.
+    pub fn buckets(&self) -> Vec<(u64, u64)> {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|&(_, &count)| count > 0)
+            .map(|(index, &count)| (value_of(index), count))
+            .collect()
+    }
+
+    /// Rebuilds a histogram from the counts of its buckets.
+    pub fn from_buckets(buckets: &[(u64, u64)]) -> Histogram {
+        let mut histogram = Histogram::new();
+        for &(value, count) in buckets {
+            let index = index_of(value);
+            if histogram.counts.len() <= index {
+                histogram.counts.resize(index + 1, 0);
+            }
+            histogram.counts[index] += count;
+            histogram.total += count;
+        }
+        histogram
+    }
+
+    /// The number of durations recorded.
+    pub fn count(&self) -> u64 {
+        self.total
+    }
+
+    /// Returns the duration at the given quantile, where the quantile is between 0 and 1.
+    /// The value returned is the lowest duration that falls into the same bucket.
+    pub fn value_at_quantile(&self, quantile: f64) -> Duration {
+        if self.total == 0 {
+            return Duration::new(0, 0);
+        }
+        let rank = ((quantile * self.total as f64).ceil() as u64).clamp(1, self.total);
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Duration::from_micros(value_of(index));
+            }
+        }
+        Duration::from_micros(value_of(self.counts.len() - 1))
+    }
+}
+
+fn micros(duration: Duration) -> u64 {
+    duration.as_secs() * 1_000_000 + u64::from(duration.subsec_micros())
+}
+
+fn index_of(value: u64) -> usize {
+    if value < LINEAR_BUCKETS {
+        return value as usize;
+    }
+    // Values between 2^exp and 2^(exp + 1) are split into SUB_BUCKETS buckets.
+    let exp = 63 - value.leading_zeros();
+    let shift = exp - SUB_BUCKET_BITS;
+    let offset = (value >> shift) - SUB_BUCKETS;
+    (LINEAR_BUCKETS + u64::from(exp - SUB_BUCKET_BITS - 1) * SUB_BUCKETS + offset) as usize
+}
+
+fn value_of(index: usize) -> u64 {
+    let index = index as u64;
+    if index < LINEAR_BUCKETS {
+        return index;
+    }
+    let exp = (index - LINEAR_BUCKETS) / SUB_BUCKETS + u64::from(SUB_BUCKET_BITS) + 1;
+    let offset = (index - LINEAR_BUCKETS) % SUB_BUCKETS;
+    (SUB_BUCKETS + offset) << (exp - u64::from(SUB_BUCKET_BITS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_values_are_exact() {
+        for value in 0..LINEAR_BUCKETS {
+            assert_eq!(value_of(index_of(value)), value);
+        }
+    }
+
+    #[test]
+    fn large_values_are_within_one_percent() {
+        for &value in &[256, 1_000, 12_345, 999_999, 60_000_000, u64::from(u32::MAX)] {
+            let bucketed = value_of(index_of(value));
+            assert!(bucketed <= value);
+            assert!((value - bucketed) as f64 / value as f64 <= 0.01);
+        }
+    }
+
+    #[test]
+    fn reads_back_quantiles() {
+        let mut histogram = Histogram::new();
+        for n in 1..=100 {
+            histogram.record(Duration::from_micros(n));
+        }
+        assert_eq!(histogram.value_at_quantile(0.0), Duration::from_micros(1));
+        assert_eq!(histogram.value_at_quantile(0.5), Duration::from_micros(50));
+        assert_eq!(histogram.value_at_quantile(0.99), Duration::from_micros(99));
+        assert_eq!(histogram.value_at_quantile(1.0), Duration::from_micros(100));
+    }
+
+    #[test]
+    fn merges_two_histograms() {
+        let mut a = Histogram::new();
+        let mut b = Histogram::new();
+        a.record(Duration::from_micros(10));
+        b.record(Duration::from_millis(8));
+        b.record(Duration::from_millis(8));
+        a.merge(&b);
+        assert_eq!(a.value_at_quantile(0.0), Duration::from_micros(10));
+        assert_eq!(a.value_at_quantile(0.5), Duration::from_millis(8));
+        assert_eq!(a.value_at_quantile(1.0), Duration::from_millis(8));
+    }
+
+    #[test]
+    fn rebuilds_from_its_buckets() {
+        let mut histogram = Histogram::new();
+        for &micros in &[3, 3, 700, 12_345, 12_346] {
+            histogram.record(Duration::from_micros(micros));
+        }
+        assert_eq!(histogram.buckets(), vec![(3, 2), (700, 1), (12_288, 2)]);
+        assert_eq!(Histogram::from_buckets(&histogram.buckets()), histogram);
+    }
+
+    #[test]
+    fn empty_histograms_read_back_zero() {
+        assert_eq!(Histogram::new().value_at_quantile(0.5), Duration::new(0, 0));
+    }
+}
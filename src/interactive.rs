@@ -0,0 +1,271 @@
+use compare::Comparison;
+use report::Report;
+use stats::TimeUnit;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+const HELP: &str = "\
+  c N, concurrency N  Keep N requests in flight in the next run
+  rate N              Start N requests each second in the next run
+  rate off            Stop pacing the requests
+  run                 Run again with the settings
+  runs                Show every run of the session side by side
+  compare [A B]       Compare run B with run A, or the last run with the one before
+  quit                End the session";
+
+/// What an interactive session can change from one run to the next. Everything else about
+/// the runs, from their urls to their engine, is set up once.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Settings {
+    pub concurrency: usize,
+    pub rate: Option<f64>,
+}
+
+/// A line typed at the prompt.
+#[derive(Debug, PartialEq)]
+enum Command {
+    Concurrency(usize),
+    Rate(Option<f64>),
+    Run,
+    Runs,
+    /// The runs to compare, numbered from one, if they were named.
+    Compare(Option<(usize, usize)>),
+    Help,
+    Quit,
+}
+
+impl Command {
+    fn parse(line: &str) -> Result<Command, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let number = |word: &str, what: &str| {
+            word.parse::<usize>()
+                .ok()
+                .filter(|n| *n > 0)
+                .ok_or_else(|| format!("Expected {} to be a number above zero, got '{}'", what, word))
+        };
+        match words[..] {
+            ["c", concurrency] | ["concurrency", concurrency] => {
+                Ok(Command::Concurrency(number(concurrency, "the concurrency")?))
+            }
+            ["rate", "off"] => Ok(Command::Rate(None)),
+            ["rate", rate] => match rate.parse::<f64>() {
+                Ok(rate) if rate > 0. && rate.is_finite() => Ok(Command::Rate(Some(rate))),
+                _ => Err(format!("Expected the rate to be above zero, got '{}'", rate)),
+            },
+            ["run"] => Ok(Command::Run),
+            ["runs"] => Ok(Command::Runs),
+            ["compare"] => Ok(Command::Compare(None)),
+            ["compare", baseline, run] => Ok(Command::Compare(Some((
+                number(baseline, "the baseline")?,
+                number(run, "the run")?,
+            )))),
+            ["help"] => Ok(Command::Help),
+            ["quit"] | ["exit"] => Ok(Command::Quit),
+            _ => Err(format!("Unknown command '{}', 'help' lists them", line.trim())),
+        }
+    }
+}
+
+/// The runs of an interactive session, each with the settings it was run with, kept so
+/// that each run can be compared with the ones before it.
+pub struct Session {
+    runs: Vec<(Settings, Report)>,
+    time_unit: TimeUnit,
+}
+
+impl Default for Session {
+    fn default() -> Session {
+        Session::new()
+    }
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session {
+            runs: Vec::new(),
+            time_unit: TimeUnit::Auto,
+        }
+    }
+
+    pub fn with_time_unit(mut self, unit: TimeUnit) -> Self {
+        self.time_unit = unit;
+        self
+    }
+
+    pub fn record(&mut self, settings: Settings, report: Report) {
+        self.runs.push((settings, report));
+    }
+
+    /// Reads commands until the next run or the end of the session, and returns the
+    /// settings of the next run or nothing once the session is over. Each setting is
+    /// checked as it's typed, so that a run never starts with settings it can't run with.
+    pub fn prompt<R, W, C>(&self, settings: Settings, input: &mut R, out: &mut W, check: C) -> io::Result<Option<Settings>>
+    where
+        R: BufRead,
+        W: Write,
+        C: Fn(Settings) -> Result<(), String>,
+    {
+        let mut next = settings;
+        if self.runs.len() == 1 {
+            writeln!(out, "Type 'run' to run again, or 'help' for the other commands")?;
+        }
+        loop {
+            write!(out, "rench> ")?;
+            out.flush()?;
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                writeln!(out)?;
+                return Ok(None);
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            let changed = match Command::parse(&line) {
+                Ok(Command::Concurrency(concurrency)) => Settings { concurrency, ..next },
+                Ok(Command::Rate(rate)) => Settings { rate, ..next },
+                Ok(Command::Run) => return Ok(Some(next)),
+                Ok(Command::Runs) => {
+                    write!(out, "{}", self)?;
+                    continue;
+                }
+                Ok(Command::Compare(runs)) => {
+                    match self.compare(runs) {
+                        Ok(comparison) => write!(out, "{}", comparison)?,
+                        Err(e) => writeln!(out, "{}", e)?,
+                    }
+                    continue;
+                }
+                Ok(Command::Help) => {
+                    writeln!(out, "{}", HELP)?;
+                    continue;
+                }
+                Ok(Command::Quit) => return Ok(None),
+                Err(e) => {
+                    writeln!(out, "{}", e)?;
+                    continue;
+                }
+            };
+            match check(changed) {
+                Ok(()) => next = changed,
+                Err(e) => writeln!(out, "{}", e)?,
+            }
+        }
+    }
+
+    fn compare(&self, runs: Option<(usize, usize)>) -> Result<Comparison, String> {
+        let (baseline, run) = match runs {
+            Some(runs) => runs,
+            None if self.runs.len() < 2 => return Err("Expected two runs to compare".to_string()),
+            None => (self.runs.len() - 1, self.runs.len()),
+        };
+        let report = |n: usize| {
+            self.runs
+                .get(n - 1)
+                .map(|(_, report)| report)
+                .ok_or_else(|| format!("Expected a run from 1 to {}, got {}", self.runs.len(), n))
+        };
+        Ok(Comparison::new(report(baseline)?, report(run)?).with_time_unit(self.time_unit))
+    }
+}
+
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "  {:>4}{:>13}{:>10}{:>14}{:>12}{:>12}{:>10}",
+            "Run", "Concurrency", "Rate", "Requests/sec", "Median", "p99", "Failures"
+        )?;
+        let latency = |ms: Option<f64>| match ms {
+            Some(ms) => self.time_unit.format(Duration::from_secs_f64(ms / 1_000.)),
+            None => "-".to_string(),
+        };
+        for (n, (settings, report)) in self.runs.iter().enumerate() {
+            let results = &report.results;
+            let failures = match results.count {
+                0 => "-".to_string(),
+                count => format!("{:.2}%", f64::from(results.failures) * 100. / f64::from(count)),
+            };
+            writeln!(
+                f,
+                "  {:>4}{:>13}{:>10}{:>14.2}{:>12}{:>12}{:>10}",
+                n + 1,
+                settings.concurrency,
+                settings.rate.map_or("-".to_string(), |rate| format!("{:.2}", rate)),
+                report.requests_per_second,
+                latency(Some(results.latency_ms.median)),
+                latency(results.quantile_ms(0.99)),
+                failures
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use content_length::ContentLength;
+    use metadata::Metadata;
+    use stats::{Fact, Summary};
+    use warning::Warnings;
+
+    fn report(ms: u64, took_seconds: f64) -> Report {
+        let facts = vec![Fact::record(ContentLength::zero(), 200, Duration::from_millis(ms)); 10];
+        let metadata = Metadata::new(&["rench".to_string()], vec!["http://a/".to_string()], "hyper", 1, 10, 1);
+        Report::new(&metadata, &Warnings::new(), took_seconds, Summary::from_facts(&facts).results())
+    }
+
+    #[test]
+    fn reads_the_commands() {
+        assert_eq!(Command::parse("c 50\n"), Ok(Command::Concurrency(50)));
+        assert_eq!(Command::parse(" rate  2.5 "), Ok(Command::Rate(Some(2.5))));
+        assert_eq!(Command::parse("rate off"), Ok(Command::Rate(None)));
+        assert_eq!(Command::parse("compare 1 3"), Ok(Command::Compare(Some((1, 3)))));
+        assert!(Command::parse("c 0").is_err());
+        assert!(Command::parse("rate -1").is_err());
+        assert!(Command::parse("compare 1").is_err());
+    }
+
+    #[test]
+    fn changes_the_settings_until_the_next_run() {
+        let mut session = Session::new().with_time_unit(TimeUnit::Millis);
+        let settings = Settings {
+            concurrency: 10,
+            rate: None,
+        };
+        session.record(settings, report(10, 1.));
+        session.record(
+            Settings {
+                concurrency: 20,
+                rate: Some(5.),
+            },
+            report(20, 2.),
+        );
+        let check = |settings: Settings| {
+            if settings.concurrency > 100 {
+                Err("Too many".to_string())
+            } else {
+                Ok(())
+            }
+        };
+        let mut out = Vec::new();
+        let input = "c 500\nc 50\nrate 100\nruns\ncompare\ncompare 1 9\nrun\n";
+        let next = session.prompt(settings, &mut input.as_bytes(), &mut out, check).unwrap();
+        assert_eq!(
+            next,
+            Some(Settings {
+                concurrency: 50,
+                rate: Some(100.),
+            })
+        );
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("rench> Too many\n"));
+        assert!(out.contains("     2           20      5.00          5.00    20.00 ms    19.97 ms     0.00%\n"));
+        assert!(out.contains("  Requests/sec           10.00          5.00     -50.00%\n"));
+        assert!(out.contains("Expected a run from 1 to 2, got 9\n"));
+
+        let mut out = Vec::new();
+        assert_eq!(session.prompt(settings, &mut "".as_bytes(), &mut out, check).unwrap(), None);
+    }
+}
@@ -0,0 +1,40 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set when interim results have been asked for and not yet printed.
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Asks for interim results whenever the process is sent SIGUSR1, so that a long run can be
+/// looked in on without stopping it. Signals are only handled on unix.
+#[cfg(unix)]
+pub fn listen() {
+    extern "C" fn request(_: libc::c_int) {
+        REQUESTED.store(true, Ordering::Relaxed);
+    }
+    // Storing to an atomic is all the handler does, which is safe to do in a signal handler.
+    unsafe {
+        libc::signal(libc::SIGUSR1, request as *const () as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn listen() {}
+
+/// Whether interim results were asked for since this was last called.
+pub fn take() -> bool {
+    REQUESTED.swap(false, Ordering::Relaxed)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_signal_asks_for_results_once() {
+        listen();
+        unsafe {
+            libc::raise(libc::SIGUSR1);
+        }
+        assert!(take());
+        assert!(!take());
+    }
+}
@@ -0,0 +1,216 @@
+//! Benchmarks http servers. The binary is the usual way in, but the engine, its middleware
+//! and the statistics it gathers can also be driven from code: build an [`Engine`] with the
+//! urls to request, hook into every request it makes with a [`Middleware`], and fold the
+//! [`Fact`] of each request into the statistics of the run.
+//!
+//! [`Engine`]: engine/struct.Engine.html
+//! [`Middleware`]: middleware/trait.Middleware.html
+//! [`Fact`]: stats/struct.Fact.html
+
+extern crate clap;
+extern crate crossbeam_channel;
+extern crate futures;
+extern crate hyper;
+extern crate hyper_tls;
+#[cfg(unix)]
+extern crate libc;
+extern crate native_tls;
+extern crate net2;
+extern crate regex;
+extern crate reqwest;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate tokio_core;
+extern crate toml;
+extern crate tungstenite;
+extern crate xxhash_rust;
+
+// Only the engine, the middleware, the statistics, the collector that folds them, the
+// reporters that write them out and the versioned report are meant to be used as a
+// library. The other modules are public for the binary and may change in any release.
+pub mod accumulator;
+#[doc(hidden)]
+pub mod affinity;
+#[doc(hidden)]
+pub mod agent;
+#[doc(hidden)]
+pub mod aggregate;
+#[doc(hidden)]
+pub mod bench;
+#[doc(hidden)]
+pub mod bind;
+#[doc(hidden)]
+pub mod capacity;
+#[doc(hidden)]
+pub mod chart;
+#[doc(hidden)]
+pub mod checkpoint;
+#[doc(hidden)]
+pub mod checksum;
+#[doc(hidden)]
+pub mod clock;
+pub mod collector;
+#[doc(hidden)]
+pub mod columns;
+#[doc(hidden)]
+pub mod compare;
+#[doc(hidden)]
+pub mod content_length;
+#[doc(hidden)]
+pub mod cooldown;
+#[doc(hidden)]
+pub mod csv_feed;
+#[doc(hidden)]
+pub mod dry_run;
+#[doc(hidden)]
+pub mod dump;
+#[doc(hidden)]
+pub mod echo;
+pub mod engine;
+#[doc(hidden)]
+pub mod error;
+#[doc(hidden)]
+pub mod expect;
+#[doc(hidden)]
+pub mod extract;
+#[doc(hidden)]
+pub mod form;
+#[doc(hidden)]
+pub mod header;
+#[doc(hidden)]
+pub mod heatmap;
+#[doc(hidden)]
+pub mod histogram;
+#[doc(hidden)]
+pub mod interactive;
+#[doc(hidden)]
+pub mod interim;
+#[doc(hidden)]
+pub mod log;
+#[doc(hidden)]
+pub mod message;
+#[doc(hidden)]
+pub mod metadata;
+pub mod middleware;
+#[doc(hidden)]
+pub mod mix;
+#[doc(hidden)]
+pub mod oauth;
+pub mod output;
+#[doc(hidden)]
+pub mod params;
+#[doc(hidden)]
+pub mod payload;
+#[doc(hidden)]
+pub mod periodicity;
+pub mod plan;
+#[doc(hidden)]
+pub mod pool;
+#[doc(hidden)]
+pub mod preconnect;
+#[doc(hidden)]
+pub mod preset;
+#[doc(hidden)]
+pub mod profile;
+#[doc(hidden)]
+pub mod random;
+#[doc(hidden)]
+pub mod raw;
+#[doc(hidden)]
+pub mod redirect;
+#[doc(hidden)]
+pub mod replay;
+pub mod report;
+#[doc(hidden)]
+pub mod reservoir;
+#[doc(hidden)]
+pub mod retry_after;
+#[doc(hidden)]
+pub mod runner;
+#[doc(hidden)]
+pub mod schedule;
+#[doc(hidden)]
+pub mod selftest;
+#[doc(hidden)]
+pub mod server_timing;
+#[doc(hidden)]
+pub mod size_latency;
+#[doc(hidden)]
+pub mod slow_log;
+pub mod stats;
+#[doc(hidden)]
+pub mod status_chart;
+#[doc(hidden)]
+pub mod stop;
+#[doc(hidden)]
+pub mod tcp;
+#[doc(hidden)]
+pub mod template;
+#[doc(hidden)]
+pub mod trace;
+#[doc(hidden)]
+pub mod trend;
+#[doc(hidden)]
+pub mod url_file;
+#[doc(hidden)]
+pub mod usage;
+#[doc(hidden)]
+pub mod validate;
+#[doc(hidden)]
+pub mod warning;
+#[doc(hidden)]
+pub mod watchdog;
+
+pub use output::Reporter;
+pub use report::Report;
+pub use stats::Summary;
+
+use std::time::Duration;
+
+/// Parses a ratio given either as a fraction ("0.005") or a percentage ("0.5%").
+pub fn parse_ratio(value: &str) -> Option<f64> {
+    let ratio = if let Some(percent) = value.strip_suffix('%') {
+        percent.trim().parse::<f64>().ok()? / 100.
+    } else {
+        value.trim().parse::<f64>().ok()?
+    };
+    if (0. ..=1.).contains(&ratio) {
+        Some(ratio)
+    } else {
+        None
+    }
+}
+
+/// Parses a duration such as "500ms", "250us" or "60s". A bare number is in milliseconds.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let (number, scale) = if let Some(number) = value.strip_suffix("us") {
+        (number, 1e-6)
+    } else if let Some(number) = value.strip_suffix("ms") {
+        (number, 1e-3)
+    } else if let Some(number) = value.strip_suffix('s') {
+        (number, 1.)
+    } else {
+        (value, 1e-3)
+    };
+    match number.trim().parse::<f64>() {
+        Ok(number) if number >= 0. => Ok(Duration::from_secs_f64(number * scale)),
+        _ => Err(format!("Expected a duration like '500ms' or '60s', got '{}'", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations_in_any_unit() {
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("250us"), Ok(Duration::from_micros(250)));
+        assert_eq!(parse_duration("1.5s"), Ok(Duration::from_millis(1_500)));
+        assert_eq!(parse_duration("20"), Ok(Duration::from_millis(20)));
+        assert!(parse_duration("soon").is_err());
+        assert!(parse_duration("-1ms").is_err());
+    }
+}
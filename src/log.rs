@@ -0,0 +1,116 @@
+use std::fmt;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// How much is logged while running. The summary is always printed.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Level {
+    /// Nothing but the summary.
+    Quiet = 0,
+    /// The banner and progress lines.
+    Normal = 1,
+    /// Every request as it completes.
+    Verbose = 2,
+    /// Every request with the details of its connection and body.
+    Debug = 3,
+}
+
+static LEVEL: AtomicUsize = AtomicUsize::new(Level::Normal as usize);
+static INFO_TO_STDERR: AtomicBool = AtomicBool::new(false);
+/// Whether the last thing logged was a progress line that's waiting to be rewritten.
+static PROGRESS_OPEN: AtomicBool = AtomicBool::new(false);
+
+/// Sets the level for the whole process.
+pub fn set_level(level: Level) {
+    LEVEL.store(level as usize, Ordering::Relaxed);
+}
+
+/// Whether messages at the level are logged.
+pub fn enabled(level: Level) -> bool {
+    LEVEL.load(Ordering::Relaxed) >= level as usize
+}
+
+/// Sends the banner and progress to stderr too, leaving stdout to a report that's read by
+/// another program.
+pub fn info_to_stderr() {
+    INFO_TO_STDERR.store(true, Ordering::Relaxed);
+}
+
+/// Logs the banner and progress of a run to stdout, unless quiet.
+pub fn info(args: fmt::Arguments) {
+    if !enabled(Level::Normal) {
+        return;
+    }
+    end_progress();
+    if INFO_TO_STDERR.load(Ordering::Relaxed) {
+        eprintln!("{}", args);
+    } else {
+        println!("{}", args);
+    }
+}
+
+/// Logs the progress of a run where the banner goes. A terminal shows a single line that
+/// each progress line rewrites in place, while anything else, such as the log of a CI job,
+/// gets a line for each so that it isn't filled with carriage returns.
+pub fn progress(args: fmt::Arguments) {
+    if !enabled(Level::Normal) {
+        return;
+    }
+    let to_stderr = INFO_TO_STDERR.load(Ordering::Relaxed);
+    let terminal = if to_stderr {
+        io::stderr().is_terminal()
+    } else {
+        io::stdout().is_terminal()
+    };
+    if !terminal {
+        return info(args);
+    }
+    let line = format!("\r{}\x1b[K", args);
+    let _ = if to_stderr {
+        io::stderr().write_all(line.as_bytes())
+    } else {
+        let mut stdout = io::stdout();
+        stdout.write_all(line.as_bytes()).and_then(|_| stdout.flush())
+    };
+    PROGRESS_OPEN.store(true, Ordering::Relaxed);
+}
+
+/// Moves on from the progress line if one is waiting to be rewritten, so that what's logged
+/// next starts on a line of its own.
+pub fn end_progress() {
+    if PROGRESS_OPEN.swap(false, Ordering::Relaxed) {
+        if INFO_TO_STDERR.load(Ordering::Relaxed) {
+            eprintln!();
+        } else {
+            println!();
+        }
+    }
+}
+
+/// Logs a request to stderr so it doesn't mix with the summary.
+pub fn verbose(args: fmt::Arguments) {
+    if enabled(Level::Verbose) {
+        eprintln!("{}", args);
+    }
+}
+
+/// Logs the details of a request to stderr.
+pub fn debug(args: fmt::Arguments) {
+    if enabled(Level::Debug) {
+        eprintln!("{}", args);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_include_everything_below_them() {
+        set_level(Level::Verbose);
+        assert!(enabled(Level::Normal));
+        assert!(enabled(Level::Verbose));
+        assert!(!enabled(Level::Debug));
+        set_level(Level::Normal);
+    }
+}
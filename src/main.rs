@@ -1,44 +1,1646 @@
 extern crate clap;
-extern crate futures;
-extern crate hyper;
-extern crate hyper_tls;
-extern crate reqwest;
-extern crate tokio_core;
-
-use clap::{App, Arg};
-
-mod bench;
-mod chart;
-mod collector;
-mod content_length;
-mod engine;
-mod message;
-mod plan;
-mod runner;
-mod stats;
-use stats::{ChartSize, Fact, Summary};
+extern crate rench;
+
+use clap::{App, AppSettings, Arg, ArgMatches, Shell, SubCommand};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rench::{
+    accumulator,
+    affinity,
+    agent,
+    aggregate,
+    bench,
+    bind,
+    capacity,
+    checkpoint,
+    clock,
+    collector,
+    compare,
+    content_length,
+    cooldown,
+    csv_feed,
+    dump,
+    echo,
+    engine,
+    error,
+    expect,
+    extract,
+    form,
+    header,
+    heatmap,
+    interactive,
+    interim,
+    log,
+    metadata,
+    mix,
+    oauth,
+    output,
+    params,
+    payload,
+    periodicity,
+    plan,
+    pool,
+    preconnect,
+    preset,
+    profile,
+    random,
+    redirect,
+    replay,
+    report,
+    runner,
+    schedule,
+    selftest,
+    size_latency,
+    slow_log,
+    stats,
+    status_chart,
+    stop,
+    tcp,
+    template,
+    trace,
+    trend,
+    url_file,
+    usage,
+    validate,
+    warning,
+    watchdog,
+    parse_duration,
+    parse_ratio,
+};
+use accumulator::Accumulator;
+use compare::Comparison;
+use error::Error;
+use content_length::{ContentLength, SizeUnit};
+use expect::Expectation;
+use heatmap::Heatmap;
+use payload::Payloads;
+use periodicity::Periodicity;
+use random::Rng;
+use stats::{ChartSize, Fact, Summary, TimeUnit};
+use template::Template;
 use plan::Plan;
+use preset::Presets;
+use profile::Profiles;
 use runner::Runner;
+use schedule::Schedule;
+use size_latency::SizeLatency;
+use status_chart::StatusChart;
+use trend::Trend;
+use warning::{Warning, Warnings};
+
+/// The most requests an open loop keeps in flight unless told otherwise.
+const DEFAULT_MAX_INFLIGHT: usize = 1000;
+
+/// The most threads per cpu that the engines making one request at a time are run with
+/// unless forced, past which they spend more time switching than requesting.
+const MAX_THREADS_PER_CPU: usize = 8;
+
+/// What the collector folds the facts of a run into: the totals, the trend for the progress
+/// line and each of the charts that were asked for.
+type Collected = (
+    Accumulator,
+    Trend,
+    Option<Heatmap>,
+    Option<Schedule>,
+    Option<StatusChart>,
+    Option<SizeLatency>,
+    Periodicity,
+);
 
 fn main() {
-    let matches = App::new("Git Release Names")
-        .author("Kevin Choubacha <chewbacha@gmail.com>")
+    if let Err(e) = run() {
+        log::end_progress();
+        eprintln!("error: {}", e);
+        process::exit(e.code());
+    }
+}
+
+fn run() -> Result<(), Error> {
+    let args = preset::expand(env::args().collect(), || Presets::load(&presets_path()?))
+        .map_err(|e| Error::Usage(format!("Invalid preset. {}", e)))?;
+
+    let matches = match app().get_matches_from_safe(args.clone()) {
+        Ok(matches) => matches,
+        Err(ref e) if !e.use_stderr() => e.exit(),
+        Err(e) => return Err(Error::Usage(e.message.trim_start_matches("error: ").to_string())),
+    };
+
+    let emits = matches.is_present("emit-facts") || matches.is_present("emit-summary");
+    if emits && matches.subcommand_name().is_some_and(|name| name != "run") {
+        return Err(Error::Usage("Only runs can emit facts".to_string()));
+    }
+    if let Some(matches) = matches.subcommand_matches("preset") {
+        return manage_presets(matches);
+    }
+    if let Some(matches) = matches.subcommand_matches("aggregate") {
+        return aggregate_runs(matches);
+    }
+    if let Some(matches) = matches.subcommand_matches("completions") {
+        let shell = match matches.value_of("SHELL").expect("A shell is required") {
+            "bash" => Shell::Bash,
+            "zsh" => Shell::Zsh,
+            "fish" => Shell::Fish,
+            _ => unreachable!(),
+        };
+        app().gen_completions_to(env!("CARGO_PKG_NAME"), shell, &mut std::io::stdout());
+        return Ok(());
+    }
+    if let Some(matches) = matches.subcommand_matches("report") {
+        return report_run(matches);
+    }
+    if let Some(matches) = matches.subcommand_matches("compare") {
+        return compare_runs(matches);
+    }
+    if let Some(matches) = matches.subcommand_matches("find-capacity") {
+        return find_capacity(matches);
+    }
+    if let Some(matches) = matches.subcommand_matches("agent") {
+        let addr = matches.value_of("listen").expect("The address has a default");
+        let token = matches.value_of("token").expect("The token is required");
+        if token.is_empty() {
+            return Err(Error::Usage("Expected a token that isn't empty".to_string()));
+        }
+        return agent::listen(addr, token, check_job).map_err(|e| Error::Engine(format!("The agent stopped: {}", e)));
+    }
+    if let Some(matches) = matches.subcommand_matches("selftest-server") {
+        let addr = matches.value_of("listen").expect("The address has a default");
+        return selftest::listen(addr).map_err(|e| Error::Engine(format!("The test server stopped: {}", e)));
+    }
+    let matches = matches.subcommand_matches("run").cloned().unwrap_or(matches);
+    let emit_facts = matches.is_present("emit-facts");
+    let emit_summary = matches.is_present("emit-summary");
+
+    log::set_level(match (matches.is_present("quiet") || emit_facts || emit_summary, matches.occurrences_of("verbose")) {
+        (true, _) => log::Level::Quiet,
+        (false, 0) => log::Level::Normal,
+        (false, 1) => log::Level::Verbose,
+        (false, _) => log::Level::Debug,
+    });
+
+    let mut urls: Vec<String> = matches
+        .values_of("URL")
+        .into_iter()
+        .flatten()
+        .map(|v| v.to_string())
+        .collect();
+    if let Some(path) = matches.value_of("url-file") {
+        urls.extend(url_file::read(path).map_err(Error::Io)?);
+    }
+
+    let extractor = match matches.values_of("extract") {
+        Some(values) => {
+            let extractions = values.map(extract::Extraction::parse).collect::<Result<_, _>>().map_err(Error::Usage)?;
+            Some(extract::Extractor::new(extractions))
+        }
+        None => None,
+    };
+    let extracted = extractor.as_ref().map(extract::Extractor::names).unwrap_or_default();
+
+    let feed = match matches.value_of("feed") {
+        Some(path) => {
+            let text = fs::read_to_string(path).map_err(|e| Error::Io(format!("Failed to read the feed '{}': {}", path, e)))?;
+            let feed = csv_feed::CsvFeed::parse(&text).map_err(|e| Error::Usage(format!("Invalid feed '{}'. {}", path, e)))?;
+            for url in &urls {
+                feed.check(url, &extracted).map_err(Error::Usage)?;
+            }
+            Some(feed)
+        }
+        None => None,
+    };
+    let feed_mode = csv_feed::Mode::parse(matches.value_of("feed-mode").unwrap_or("wrap")).map_err(Error::Usage)?;
+    let columns: Vec<String> = feed
+        .iter()
+        .flat_map(|feed| feed.columns().to_vec())
+        .chain(extracted.iter().cloned())
+        .collect();
+
+    // Every url is checked before anything is started, so that each bad one is reported
+    // rather than every request to it failing once the run has begun.
+    let engine_name = matches.value_of("engine").unwrap_or("hyper");
+    let invalid: Vec<String> = urls
+        .iter()
+        .filter_map(|url| validate::url(engine_name, url).err())
+        .collect();
+    if !invalid.is_empty() {
+        return Err(Error::Usage(invalid.join("\n")));
+    }
+
+    let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let concurrency = match matches.value_of("concurrency").unwrap_or("1") {
+        "auto" => cpus,
+        concurrency => number(concurrency, "concurrency")?,
+    };
+
+    // An open loop is bounded by the most requests it keeps in flight rather than by its
+    // concurrency. An agent is given its share of that bound as its concurrency.
+    let open_loop = matches.value_of("loop") == Some("open");
+    if open_loop && !matches.is_present("rate") {
+        return Err(Error::Plan(
+            "An open loop starts the requests on the --rate schedule, so it needs a --rate".to_string(),
+        ));
+    }
+    let concurrency = match matches.value_of("max-inflight") {
+        Some(max) if open_loop => number(max, "the most requests in flight")?,
+        None if open_loop && !matches.is_present("concurrency") => DEFAULT_MAX_INFLIGHT,
+        _ => concurrency,
+    };
+    if concurrency == 0 {
+        return Err(Error::Usage("Expected at least one request in flight, got a concurrency of 0".to_string()));
+    }
+
+    // An agent is given its share of the concurrency but the controller's threads, which
+    // can be more than it needs.
+    let threads_arg = matches
+        .value_of("threads")
+        .map(|threads| number::<usize>(threads, "threads"))
+        .transpose()?;
+    let threads_for = |concurrency: usize| {
+        let default_threads = if open_loop {
+            cpus
+        } else {
+            concurrency
+        };
+        threads_arg.unwrap_or(default_threads).min(concurrency)
+    };
+    let threads = threads_for(concurrency);
+
+    // A replayed run makes as many requests as it reads, so none are planned.
+    let replay = matches.is_present("stdin");
+    let requests = if replay {
+        0
+    } else {
+        number(matches.value_of("requests").unwrap_or("1000"), "number of requests")?
+    };
+    // A feed that stops sends each of its rows once at most.
+    let requests = match feed {
+        Some(ref feed) if feed_mode == csv_feed::Mode::Stop => match matches.value_of("requests") {
+            Some(_) => requests.min(feed.len()),
+            None => feed.len(),
+        },
+        _ => requests,
+    };
+
+    let chart_size = parse_chart_size(matches.value_of("chart-size"));
+
+    let sinks: Vec<output::Sink> = match matches.values_of("output") {
+        Some(values) => values.map(output::Sink::parse).collect::<Result<_, _>>().map_err(Error::Usage)?,
+        None => vec![output::Sink::parse("human").expect("'human' is an output")],
+    };
+    if sinks.iter().filter(|sink| sink.is_stdout()).count() > 1 {
+        return Err(Error::Usage(
+            "Expected at most one --output without a file, since they would share stdout".to_string(),
+        ));
+    }
+    let json = sinks.iter().any(output::Sink::takes_stdout);
+    if json {
+        log::info_to_stderr();
+    }
+
+    let time_unit = parse_time_unit(matches.value_of("time-unit"));
+
+    let size_unit = match matches.value_of("size-unit") {
+        _ if matches.is_present("bytes") => SizeUnit::Bytes,
+        Some("si") => SizeUnit::Si,
+        Some("bytes") => SizeUnit::Bytes,
+        _ => SizeUnit::Binary,
+    };
+
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for value in matches.values_of("header").unwrap_or_default() {
+        headers.extend(header::parse(value).map_err(invalid_header)?);
+    }
+
+    // Agents that summarize only send back statistics, so a run made by them streams.
+    let summarize = matches.is_present("workers-summarize");
+    let stream = matches.is_present("stream") || summarize;
+    let record = !stream || matches.is_present("record");
+    if !record && sinks.iter().any(output::Sink::needs_facts) {
+        return Err(Error::Usage("Expected --record with --stream to write every request as csv".to_string()));
+    }
+
+    let sample_size = number(matches.value_of("sample-size").unwrap_or("10000"), "the sample size")?;
+
+    let batch = match matches.value_of("batch") {
+        Some(batch) => number(batch, "facts in a batch")?,
+        None => collector::DEFAULT_BATCH,
+    };
+    let batch_interval = match matches.value_of("batch-interval") {
+        Some(interval) => parse_duration(interval).map_err(Error::Usage)?,
+        None => collector::DEFAULT_BATCH_INTERVAL,
+    };
+
+    let queue = collector::Queue {
+        size: match matches.value_of("queue-size") {
+            Some(size) => number(size, "messages in the queue")?,
+            None => collector::DEFAULT_QUEUE,
+        },
+        when_full: match matches.value_of("when-full") {
+            Some("drop") => collector::WhenFull::Drop,
+            Some("sample") => collector::WhenFull::Sample,
+            _ => collector::WhenFull::Block,
+        },
+    };
+
+    let cooldown = matches
+        .value_of("cooldown")
+        .map(|v| number(v, "seconds of the cooldown").map(Duration::from_secs))
+        .transpose()?;
+
+    let cooldown_interval = Duration::from_millis(number(
+        matches.value_of("cooldown-interval").unwrap_or("250"),
+        "milliseconds of the cooldown interval",
+    )?);
+
+    let target_rps = matches
+        .value_of("target-rps")
+        .map(|v| number::<f64>(v, "the target requests per second"))
+        .transpose()?;
+
+    let fail_on_errors = matches.is_present("fail-on-errors");
+
+    let max_error_rate = matches
+        .value_of("max-error-rate")
+        .map(|v| ratio(v, "max error rate"))
+        .transpose()?;
+
+    let trim_outliers = matches
+        .value_of("trim-outliers")
+        .map(|v| ratio(v, "--trim-outliers"))
+        .transpose()?;
+
+    let baseline_run = matches
+        .value_of("baseline")
+        .map(aggregate::read)
+        .transpose()
+        .map_err(Error::Io)?;
+    let max_regression = matches
+        .value_of("max-regression")
+        .map(|v| ratio(v, "the max regression"))
+        .transpose()?;
+
+    // The watchdog of each run is set up as it starts, but its rule is checked beforehand.
+    if let Some(rule) = matches.value_of("abort-on") {
+        watchdog::Watchdog::parse(rule).map_err(Error::Usage)?;
+    }
+
+    let max_bytes = matches
+        .value_of("max-bytes")
+        .map(ContentLength::parse)
+        .transpose()
+        .map_err(Error::Usage)?;
+
+    let rate = match matches.value_of("rate") {
+        Some(v) => {
+            let rate: f64 = number(v, "the rate")?;
+            if rate.is_nan() || rate <= 0. {
+                return Err(Error::Usage(format!("Expected a rate above zero, got '{}'", v)));
+            }
+            Some(rate)
+        }
+        None => None,
+    };
+
+    let plan = Plan::new(threads, requests).map_err(Error::Plan)?.with_concurrency(concurrency);
+    let plan = match rate {
+        Some(rate) => plan.with_rate(rate),
+        None => plan,
+    };
+
+    // Every header that may be sent, for the rows of a feed to be checked against.
+    let mut every_header = headers.clone();
+    let mut profiles = Profiles::new();
+    if let Some(values) = matches.values_of("profile") {
+        let values: Vec<&str> = values.collect();
+        for pair in values.chunks(2) {
+            let headers = header::parse(pair[1]).map_err(invalid_header)?;
+            every_header.extend(headers.iter().cloned());
+            profiles.add_headers(pair[0], headers);
+        }
+    }
+    if let Some(values) = matches.values_of("profile-weight") {
+        let values: Vec<&str> = values.collect();
+        for pair in values.chunks(2) {
+            let weight = number(pair[1], "the profile weight")?;
+            profiles.set_weight(pair[0], weight).map_err(Error::Usage)?;
+        }
+    }
+
+    let mut warnings = Warnings::new();
+    let repeated = header::repeated(&headers);
+    if matches.value_of("engine") == Some("reqwest") && !repeated.is_empty() {
+        warnings.push(Warning::FoldedHeaders(repeated));
+    }
+    if engine_name == "reqwest" {
+        warnings.push(Warning::UntrackedConnections(engine_name.to_string()));
+    }
+
+    let eng = match engine_name {
+        "hyper" => engine::Engine::new(urls.clone(), headers).with_hyper(),
+        "raw" => engine::Engine::new(urls.clone(), headers).with_raw(),
+        "websocket" => engine::Engine::new(urls.clone(), headers).with_websocket(),
+        "tcp" => {
+            let reply = match matches.value_of("read-bytes") {
+                Some(bytes) => tcp::Reply::Bytes(number(bytes, "bytes to read")?),
+                None => tcp::Reply::UntilClose,
+            };
+            engine::Engine::new(urls.clone(), headers).with_tcp(reply)
+        }
+        _ => engine::Engine::new(urls.clone(), headers),
+    };
+
+    if open_loop && !eng.multiplexes() {
+        return Err(Error::Plan(format!(
+            "Only the hyper engine can run an open loop, since it's the only one that keeps many requests in flight on a thread, got the {} engine",
+            matches.value_of("engine").unwrap_or("hyper")
+        )));
+    }
+    // The threads are checked again for each run of an interactive session, so that its
+    // concurrency can't be changed to one the engine can't run.
+    let multiplexes = eng.multiplexes();
+    let check_threads = |concurrency: usize, threads: usize| -> Result<Option<Warning>, Error> {
+        if threads < concurrency && !multiplexes {
+            return Err(Error::Plan(format!(
+                "Only the hyper engine can keep more than one request in flight on a thread, so --threads can't be less than the concurrency with the {} engine",
+                matches.value_of("engine").unwrap_or("hyper")
+            )));
+        }
+        if !multiplexes && !matches.is_present("workers") && threads > cpus * MAX_THREADS_PER_CPU {
+            if !matches.is_present("force") {
+                return Err(Error::Plan(format!(
+                    "A concurrency of {} makes {} threads on {} cpus, so the {} engine would mostly measure switching between them. Use the hyper engine with fewer --threads, or --force to run anyway",
+                    concurrency,
+                    threads,
+                    cpus,
+                    matches.value_of("engine").unwrap_or("hyper")
+                )));
+            }
+            return Ok(Some(Warning::Oversubscribed { threads, cpus }));
+        }
+        Ok(None)
+    };
+    check_threads(concurrency, threads)?;
+
+    let eng = if replay {
+        let engine = matches.value_of("engine").unwrap_or("hyper");
+        if engine != "hyper" {
+            return Err(Error::Plan(format!(
+                "Only the hyper engine can replay requests from stdin, not the {} engine",
+                engine
+            )));
+        }
+        eng.with_feed(replay::Feed::read(std::io::BufReader::new(std::io::stdin())))
+    } else {
+        eng
+    };
+
+    let mut eng = eng;
+    let header_for: Vec<&str> = matches.values_of("header-for").into_iter().flatten().collect();
+    for pair in header_for.chunks(2) {
+        if !urls.iter().any(|url| url == pair[0]) {
+            return Err(Error::Usage(format!(
+                "The url '{}' given to --header-for is not one of the urls",
+                pair[0]
+            )));
+        }
+        let headers = header::parse(pair[1]).map_err(invalid_header)?;
+        every_header.extend(headers.iter().cloned());
+        eng = eng.with_url_headers(pair[0], headers);
+    }
+
+    let family = match (matches.is_present("ipv4"), matches.is_present("ipv6")) {
+        (true, _) => Some(bind::Family::V4),
+        (_, true) => Some(bind::Family::V6),
+        _ => None,
+    };
+    let eng = match family {
+        Some(family) => {
+            let engine = matches.value_of("engine").unwrap_or("hyper");
+            if engine == "reqwest" {
+                warnings.push(Warning::IgnoredOption {
+                    option: format!("--{}", if family == bind::Family::V4 { "ipv4" } else { "ipv6" }),
+                    engine: engine.to_string(),
+                });
+            }
+            eng.with_family(family)
+        }
+        None => eng,
+    };
+
+    let eng = match matches.values_of("bind") {
+        Some(addrs) => {
+            let addrs: Vec<&str> = addrs.collect();
+            let binds = bind::Binds::parse(&addrs).map_err(Error::Usage)?;
+            if let Some(family) = family {
+                if let Some(addr) = addrs.iter().find(|addr| addr.parse().ok().map(|ip| bind::Family::of(&ip)) != Some(family)) {
+                    return Err(Error::Plan(format!("Expected every --bind address to be {}, but {} isn't", family, addr)));
+                }
+            }
+            let engine = matches.value_of("engine").unwrap_or("hyper");
+            if engine == "reqwest" {
+                warnings.push(Warning::IgnoredOption {
+                    option: "--bind".to_string(),
+                    engine: engine.to_string(),
+                });
+            }
+            eng.with_binds(binds)
+        }
+        None => eng,
+    };
+
+    let eng = if matches.is_present("sequential") {
+        eng.with_shared_order()
+    } else {
+        eng
+    };
+
+    let eng = match matches.value_of("max-connections-per-host") {
+        Some(max) => {
+            let max = number(max, "the connections per host")?;
+            eng.with_connection_limit(pool::Limit::new(max, &urls))
+        }
+        None => eng,
+    };
+
+    let eng = match matches.value_of("pool-idle-timeout") {
+        Some(timeout) => {
+            let timeout: f64 = number(timeout, "seconds of the pool idle timeout")?;
+            let engine = matches.value_of("engine").unwrap_or("hyper");
+            if engine != "hyper" && engine != "raw" {
+                warnings.push(Warning::IgnoredOption {
+                    option: "--pool-idle-timeout".to_string(),
+                    engine: engine.to_string(),
+                });
+            }
+            eng.with_pool_idle_timeout(Duration::from_secs_f64(timeout))
+        }
+        None => eng,
+    };
+
+    // Every worker opens its connections before the start line, so the run is timed from
+    // once they're all open.
+    let preconnect = if matches.is_present("preconnect") {
+        Some(preconnect::Preconnect::new())
+    } else {
+        None
+    };
+    let eng = match preconnect {
+        Some(ref preconnect) => eng.with_preconnect(preconnect.clone()),
+        None => eng,
+    };
+
+    let eng = if matches.is_present("follow-redirects") {
+        let max = match matches.value_of("follow-redirects") {
+            Some(max) => number(max, "the most redirects to follow")?,
+            None => redirect::DEFAULT_MAX,
+        };
+        eng.with_redirects(max)
+    } else {
+        eng
+    };
+
+    let mut expectations: Vec<Expectation> = matches
+        .values_of("expect-body")
+        .into_iter()
+        .flatten()
+        .map(|text| Expectation::Contains(text.to_string()))
+        .collect();
+    for pattern in matches.values_of("expect-body-regex").into_iter().flatten() {
+        expectations.push(Expectation::regex(pattern).map_err(Error::Usage)?);
+    }
+    let eng = if expectations.is_empty() {
+        eng
+    } else {
+        let engine = matches.value_of("engine").unwrap_or("hyper");
+        if engine == "tcp" {
+            warnings.push(Warning::IgnoredOption {
+                option: "--expect-body".to_string(),
+                engine: engine.to_string(),
+            });
+        }
+        eng.with_expectations(expectations)
+    };
+
+    let eng = if matches.is_present("checksum") {
+        let engine = matches.value_of("engine").unwrap_or("hyper");
+        if engine == "tcp" {
+            warnings.push(Warning::IgnoredOption {
+                option: "--checksum".to_string(),
+                engine: engine.to_string(),
+            });
+        }
+        eng.with_checksums()
+    } else {
+        eng
+    };
+
+    let captures: Vec<&str> = matches.values_of("capture-header").into_iter().flatten().collect();
+    let eng = if captures.is_empty() {
+        eng
+    } else {
+        let engine = matches.value_of("engine").unwrap_or("hyper");
+        if engine == "websocket" || engine == "tcp" {
+            warnings.push(Warning::IgnoredOption {
+                option: "--capture-header".to_string(),
+                engine: engine.to_string(),
+            });
+        }
+        captures.iter().fold(eng, |eng, name| eng.with_captured_header(name))
+    };
+
+    let seed = match matches.value_of("seed") {
+        Some(seed) => number(seed, "the seed")?,
+        None => Rng::from_time().next_u64(),
+    };
+    let eng = eng.with_seed(seed);
+
+    let eng = match matches.value_of("dump-responses") {
+        Some(count) => {
+            let count = number(count, "responses to dump")?;
+            let dir = matches.value_of("dump-dir").unwrap_or("rench-responses");
+            let engine = matches.value_of("engine").unwrap_or("hyper");
+            if engine == "websocket" || engine == "tcp" {
+                warnings.push(Warning::IgnoredOption {
+                    option: "--dump-responses".to_string(),
+                    engine: engine.to_string(),
+                });
+            }
+            let dump = dump::Dump::new(dir, count)
+                .map_err(|e| Error::Io(format!("Failed to create the dump directory '{}': {}", dir, e)))?;
+            eng.with_dump(dump)
+        }
+        None => eng,
+    };
+
+    let eng = match matches.values_of("log-slow") {
+        Some(mut values) => {
+            let threshold = values.next().expect("Expected a threshold and a file");
+            let path = values.next().expect("Expected a threshold and a file");
+            let threshold = parse_duration(threshold).map_err(Error::Usage)?;
+            let slow_log = slow_log::SlowLog::open(threshold, path)
+                .map_err(|e| Error::Io(format!("Failed to open the slow log '{}': {}", path, e)))?;
+            eng.with_slow_log(slow_log)
+        }
+        None => eng,
+    };
+
+    // The feed fills in each request before the other middleware sees it, so that a trace
+    // header or a token is added to the request as it's sent.
+    let eng = match feed {
+        Some(feed) => {
+            let engine = matches.value_of("engine").unwrap_or("hyper");
+            if engine == "websocket" || engine == "tcp" {
+                return Err(Error::Plan(format!(
+                    "Only the hyper, reqwest and raw engines can fill requests from a feed, not the {} engine",
+                    engine
+                )));
+            }
+            for url in &urls {
+                feed.check_rows(url, &every_header, |url| validate::url(engine, url))
+                    .map_err(Error::Usage)?;
+            }
+            eng.with_middleware(feed)
+        }
+        None => eng,
+    };
+
+    let eng = match extractor {
+        Some(extractor) => {
+            let engine = matches.value_of("engine").unwrap_or("hyper");
+            if engine == "websocket" || engine == "tcp" {
+                return Err(Error::Plan(format!(
+                    "Only the hyper, reqwest and raw engines can fill requests with extracted values, not the {} engine",
+                    engine
+                )));
+            }
+            eng.with_extractor(extractor)
+        }
+        None => eng,
+    };
+
+    let eng = match matches.value_of("trace-header") {
+        Some(trace_header) => {
+            let engine = matches.value_of("engine").unwrap_or("hyper");
+            if engine == "websocket" || engine == "tcp" {
+                warnings.push(Warning::IgnoredOption {
+                    option: "--trace-header".to_string(),
+                    engine: engine.to_string(),
+                });
+            }
+            eng.with_middleware(trace::TraceHeader::parse(trace_header).map_err(invalid_header)?)
+        }
+        None => eng,
+    };
+
+    let eng = match matches.value_of("oauth-token-url") {
+        Some(token_url) => {
+            let engine = matches.value_of("engine").unwrap_or("hyper");
+            if engine == "websocket" || engine == "tcp" {
+                warnings.push(Warning::IgnoredOption {
+                    option: "--oauth-token-url".to_string(),
+                    engine: engine.to_string(),
+                });
+            }
+            let credentials = oauth::Credentials {
+                token_url: token_url.to_string(),
+                client_id: matches.value_of("oauth-client-id").unwrap_or_default().to_string(),
+                client_secret: matches.value_of("oauth-client-secret").unwrap_or_default().to_string(),
+                scope: matches.value_of("oauth-scope").map(str::to_string),
+            };
+            let provider = oauth::TokenProvider::fetch(credentials)
+                .map_err(|e| Error::Engine(format!("Failed to fetch the OAuth2 token: {}", e)))?;
+            eng.with_middleware(provider)
+        }
+        None => eng,
+    };
+
+    // The collector stops the run when it has gone wrong.
+    let stop = stop::Stop::new();
+    let eng = eng.with_stop(stop.clone());
+
+    // Everything that times the run goes by the same clock.
+    let clock = clock::system();
+    let eng = eng.with_clock(clock.clone());
+
+    let eng = if matches.is_present("respect-retry-after") {
+        eng.with_respected_retry_after()
+    } else {
+        eng
+    };
+
+    let eng = if matches.is_present("head-requests") {
+        eng.with_method(engine::Method::Head)
+    } else {
+        eng
+    };
+
+    let eng = match matches.value_of("mix") {
+        Some(mix) => {
+            let engine = matches.value_of("engine").unwrap_or("hyper");
+            if engine == "websocket" || engine == "tcp" {
+                return Err(Error::Plan(format!(
+                    "Only the hyper, reqwest and raw engines can mix methods, not the {} engine",
+                    engine
+                )));
+            }
+            eng.with_mix(mix::Mix::parse(mix).map_err(Error::Usage)?)
+        }
+        None => eng,
+    };
+
+    let eng = match matches.value_of("body") {
+        Some(body) => {
+            let template = Template::parse_with_columns(body, &columns).map_err(|e| Error::Usage(format!("Invalid body template. {}", e)))?;
+            let echo = matches.is_present("echo");
+            if echo && !template.has_token() {
+                return Err(Error::Usage("Expected a {{token}} in the --body for the responses to echo".to_string()));
+            }
+            // A body that's echoed has a token of its own, so that a response to another
+            // request can't pass.
+            let payloads = if matches.is_present("unique-bodies") || echo {
+                Payloads::per_request(template)
+            } else {
+                let size = number(matches.value_of("body-pool").unwrap_or("1000"), "the body pool")?;
+                Payloads::pool(&template, size, &mut Rng::new(seed))
+            };
+            eng.with_method(engine::Method::Post).with_body(payloads)
+        }
+        None => eng,
+    };
+
+    let eng = match matches.values_of("param") {
+        Some(params) => {
+            let engine = matches.value_of("engine").unwrap_or("hyper");
+            if engine == "websocket" || engine == "tcp" {
+                warnings.push(Warning::IgnoredOption {
+                    option: "--param".to_string(),
+                    engine: engine.to_string(),
+                });
+            }
+            eng.with_params(params::Params::parse(params).map_err(|e| Error::Usage(format!("Invalid parameter. {}", e)))?)
+        }
+        None => eng,
+    };
+
+    let eng = match matches.values_of("form") {
+        Some(fields) => {
+            let engine = matches.value_of("engine").unwrap_or("hyper");
+            if engine == "websocket" || engine == "tcp" {
+                return Err(Error::Plan(format!(
+                    "Only the hyper, reqwest and raw engines can post forms, not the {} engine",
+                    engine
+                )));
+            }
+            let form = form::Form::parse(fields).map_err(|e| Error::Usage(format!("Invalid form. {}", e)))?;
+            let (content_type, body) = form.build(&mut Rng::new(seed))?;
+            eng.with_method(engine::Method::Post)
+                .with_body(Payloads::fixed(body))
+                .with_headers(&[("content-type".to_string(), content_type)])
+        }
+        None => eng,
+    };
+
+    let eng = if matches.is_present("echo") {
+        let engine = matches.value_of("engine").unwrap_or("hyper");
+        if engine == "tcp" {
+            warnings.push(Warning::IgnoredOption {
+                option: "--echo".to_string(),
+                engine: engine.to_string(),
+            });
+        }
+        let echo = match matches.value_of("echo-regex") {
+            Some(pattern) => echo::Echo::new().with_regex(pattern).map_err(Error::Usage)?,
+            None => echo::Echo::new(),
+        };
+        eng.with_echo(echo)
+    } else {
+        eng
+    };
+
+    if matches.is_present("dry-run") {
+        let exchanges = eng.dry_run().map_err(Error::Usage)?;
+        let (total, mut failed) = (exchanges.len(), 0);
+        for (request, exchange) in exchanges {
+            println!("{}", request);
+            match exchange {
+                Ok(exchange) => println!("{}", exchange.with_time_unit(time_unit)),
+                Err(e) => {
+                    println!("  Failed: {}\n", e);
+                    failed += 1;
+                }
+            }
+        }
+        if failed > 0 {
+            return Err(Error::Failed(format!("{} of {} requests failed", failed, total)));
+        }
+        return Ok(());
+    }
+
+    let pinning = if matches.is_present("pin-cores") {
+        Some(affinity::Pinning::new().map_err(Error::Engine)?)
+    } else {
+        None
+    };
+
+    if emit_facts {
+        let (collector, handle) = collector::fold(plan, queue, (), |_, fact: Fact| agent::emit(&fact));
+        let collector = collector.with_batching(batch, batch_interval);
+        let runner = Runner::start(plan, &eng, &profiles, pinning.as_ref(), &collector);
+        warn_unpinned(&runner);
+        runner.join();
+        handle.join().expect("Emitting thread to finish");
+        return Ok(());
+    }
+    if emit_summary {
+        let accumulator = if sample_size > 0 {
+            Accumulator::new().with_sample(sample_size)
+        } else {
+            Accumulator::new()
+        };
+        let (collector, handle) = collector::fold(plan, queue, accumulator, |accumulator, fact: Fact| accumulator.add(fact));
+        let collector = collector.with_batching(batch, batch_interval);
+        let runner = Runner::start(plan, &eng, &profiles, pinning.as_ref(), &collector);
+        warn_unpinned(&runner);
+        runner.join();
+        agent::emit_summary(&handle.join().expect("Summarizing thread to finish"));
+        return Ok(());
+    }
+    let agents: Vec<String> = matches
+        .values_of("workers")
+        .map(|agents| agents.map(|agent| agent.to_string()).collect())
+        .unwrap_or_default();
+    if !agents.is_empty() && concurrency < agents.len() {
+        return Err(Error::Plan(format!(
+            "Expected at least one concurrent request for each of the {} workers",
+            agents.len()
+        )));
+    }
+
+    let baseline = cooldown.map(|_| {
+        log::info(format_args!("Probing baseline latency"));
+        cooldown::baseline(&eng, cooldown_interval)
+    });
+
+    let interactive = matches.is_present("interactive");
+    let mut session = interactive::Session::new().with_time_unit(time_unit);
+    let mut settings = interactive::Settings { concurrency, rate };
+    // A session's settings are checked as they're typed, against what the first run was
+    // checked against.
+    let check = |settings: interactive::Settings| {
+        if open_loop && settings.rate.is_none() {
+            return Err("An open loop starts the requests on the rate's schedule, so it needs a rate".to_string());
+        }
+        check_threads(settings.concurrency, threads_for(settings.concurrency))
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    };
+    let engine_warnings = warnings;
+    // An interactive session runs again for as long as it's asked to, with everything but
+    // its settings set up once for the first run.
+    let (summary, regressions, cut_short) = loop {
+        let interactive::Settings { concurrency, rate } = settings;
+        let threads = threads_for(concurrency);
+        let plan = Plan::new(threads, requests).map_err(Error::Plan)?.with_concurrency(concurrency);
+        let plan = match rate {
+            Some(rate) => plan.with_rate(rate),
+            None => plan,
+        };
+        let mut warnings = engine_warnings.clone();
+        if let Some(warning) = check_threads(concurrency, threads)? {
+            warnings.push(warning);
+        }
+        let mut watchdog = matches
+            .value_of("abort-on")
+            .map(|rule| watchdog::Watchdog::parse(rule).map(|watchdog| watchdog.with_clock(clock.clone())))
+            .transpose()
+            .map_err(Error::Usage)?;
+        let max_bytes = max_bytes.clone();
+
+        let accumulator = if record {
+            Accumulator::recording(requests)
+        } else if sample_size > 0 {
+            Accumulator::new().with_sample(sample_size)
+        } else {
+            Accumulator::new()
+        };
+
+        let metadata = metadata::Metadata::new(
+            &args,
+            urls.clone(),
+            matches.value_of("engine").unwrap_or("hyper"),
+            concurrency,
+            requests,
+            seed,
+        );
+        let metadata = match matches.value_of("label") {
+            Some(label) => metadata.with_label(label),
+            None => metadata,
+        };
+        let mut metadata = metadata;
+        for tag in matches.values_of("tag").into_iter().flatten() {
+            let (key, value) = metadata::parse_tag(tag).map_err(Error::Usage)?;
+            metadata = metadata.with_tag(&key, &value);
+        }
+        // The collector waits for each source of facts to finish, which is every agent when the
+        // run is remote.
+        let sources = if agents.is_empty() { plan.threads() } else { agents.len() };
+        let heatmap = if matches.is_present("heatmap") {
+            Some(Heatmap::new().with_time_unit(time_unit))
+        } else {
+            None
+        };
+        let schedule = rate.map(|rate| Schedule::new(rate).with_time_unit(time_unit).with_clock(clock.clone()));
+        let status_chart = if matches.is_present("status-chart") {
+            Some(StatusChart::new())
+        } else {
+            None
+        };
+        let size_latency = if matches.is_present("by-size") {
+            Some(SizeLatency::new().with_time_unit(time_unit).with_size_unit(size_unit))
+        } else {
+            None
+        };
+        let report_interval = matches
+            .value_of("report-interval")
+            .map(parse_duration)
+            .transpose()
+            .map_err(Error::Usage)?;
+        if report_interval == Some(Duration::new(0, 0)) {
+            return Err(Error::Usage("Expected a report interval above zero".to_string()));
+        }
+        let mut checkpoints = report_interval.map(|interval| {
+            let checkpoints = checkpoint::Checkpoints::new(interval)
+                .with_time_unit(time_unit)
+                .with_size_unit(size_unit)
+                .with_clock(clock.clone());
+            let checkpoints = match matches.value_of("report-file") {
+                Some(path) => checkpoints.with_file(path),
+                None => checkpoints,
+            };
+            if matches.is_present("reset-window") {
+                checkpoints.with_reset_window()
+            } else {
+                checkpoints
+            }
+        });
+        // Interim results are printed as the next request finishes after SIGUSR1 arrives.
+        interim::listen();
+        let interim_urls = urls.clone();
+        let collector_stop = stop.clone();
+        // The collector pins itself as the first fact arrives, since it runs on its own thread,
+        // and leaves why it couldn't to be warned about with the workers.
+        let mut collector_core = pinning.as_ref().map(affinity::Pinning::collector);
+        let collector_unpinned = Arc::new(Mutex::new(None));
+        let unpinned = collector_unpinned.clone();
+        let (collector, rec_handle) = collector::fold_reporting(
+            Plan::new(sources, requests).map_err(Error::Plan)?,
+            queue,
+            (
+                accumulator,
+                Trend::new().with_time_unit(time_unit),
+                heatmap,
+                schedule,
+                status_chart,
+                size_latency,
+                Periodicity::new(),
+            ),
+            move |(accumulator, trend, heatmap, schedule, status_chart, size_latency, periodicity): &mut Collected,
+                  fact: Fact| {
+                if let Some(Err(e)) = collector_core.take().map(affinity::pin) {
+                    *unpinned.lock().expect("Expected the unpinned collector to be lockable") = Some(e);
+                }
+                trend.record(fact.duration());
+                periodicity.record(&fact);
+                if let Some(ref mut heatmap) = *heatmap {
+                    heatmap.record(fact.duration());
+                }
+                if let Some(ref mut schedule) = *schedule {
+                    schedule.record(fact.lag());
+                }
+                if let Some(ref mut status_chart) = *status_chart {
+                    // Round trips have no status family to be charted in.
+                    if fact.status().is_some() || fact.is_error() {
+                        status_chart.record(fact.status());
+                    }
+                }
+                if let Some(ref mut size_latency) = *size_latency {
+                    size_latency.record(&fact);
+                }
+                if let Some(ref mut watchdog) = watchdog {
+                    if let Some(reason) = watchdog.record(&fact) {
+                        collector_stop.abort(reason);
+                    }
+                }
+                if let Some(ref mut checkpoints) = checkpoints {
+                    if let Err(e) = checkpoints.record(fact.clone()) {
+                        eprintln!("Failed to write a checkpoint: {}", e);
+                    }
+                }
+                accumulator.add(fact);
+                if let Some(ref budget) = max_bytes {
+                    if accumulator.content_length() >= budget && !collector_stop.is_stopped() {
+                        collector_stop.stop(format!(
+                            "{} were downloaded, reaching the --max-bytes budget of {}",
+                            size_unit.format(accumulator.content_length()),
+                            size_unit.format(budget)
+                        ));
+                    }
+                }
+                if interim::take() {
+                    let summary = Summary::from_accumulator(accumulator)
+                        .with_chart_size(chart_size)
+                        .with_time_unit(time_unit)
+                        .with_size_unit(size_unit)
+                        .with_urls(&interim_urls);
+                    let interim = format!("Interim results after {} requests\n\n{}", accumulator.count(), summary);
+                    log::end_progress();
+                    if json {
+                        eprintln!("{}", interim);
+                    } else {
+                        println!("{}", interim);
+                    }
+                }
+            },
+            |(_, trend, _, _, _, _, _)| Some(trend.tick()),
+        );
+        let collector = collector.with_batching(batch, batch_interval);
+        log::info(format_args!("Beginning requests"));
+        let mut setup = None;
+        let mut client_usage = None;
+        let mut unpinned = Vec::new();
+        let ((failed, summaries), duration) = if agents.is_empty() {
+            // The run is timed from when every thread has set up its client.
+            let runner = Runner::start(plan, &eng, &profiles, pinning.as_ref(), &collector);
+            setup = Some(runner.ready());
+            unpinned = runner.unpinned();
+            let sampler = usage::Sampler::start(metadata.host.cpus);
+            let timed = bench::time_it(|| (runner.join(), Vec::new()));
+            client_usage = sampler.finish().map(|usage| usage.with_size_unit(size_unit));
+            timed
+        } else {
+            let token = matches.value_of("workers-token").expect("The workers require a token");
+            let remote = agent::Remote::start(&agents, token, &args, plan, summarize, &collector);
+            bench::time_it(|| remote.join())
+        };
+        let (mut accumulator, _, heatmap, schedule, status_chart, size_latency, periodicity) = rec_handle.join().expect("Receiving thread to finish");
+        for summary in summaries {
+            accumulator.merge(summary);
+        }
+        let seconds =
+            duration.as_secs() as f64 + (f64::from(duration.subsec_nanos()) / 1_000_000_000f64);
+        let schedule = schedule.map(|schedule| schedule.with_achieved_rps(accumulator.count() as f64 / seconds));
+
+        log::info(format_args!("Finished!\n"));
+        unpinned.extend(collector_unpinned.lock().expect("Expected the unpinned collector to be lockable").take());
+        if !unpinned.is_empty() {
+            warnings.push(Warning::Unpinned(unpinned));
+        }
+        if failed > 0 {
+            warnings.push(Warning::WorkersStopped {
+                stopped: failed,
+                workers: sources,
+            });
+        }
+        if let Some(reason) = stop.reason() {
+            warnings.push(Warning::Stopped(reason));
+        }
+        let overhead = collector.overhead();
+        if overhead.dropped > 0 {
+            warnings.push(Warning::DroppedFacts {
+                dropped: overhead.dropped,
+                total: overhead.sent + overhead.dropped,
+            });
+        }
+        // The dropped requests were made, they just aren't in the results.
+        let completed = accumulator.count() + overhead.dropped;
+        warnings.check_counts(completed, requests as u64, accumulator.errors());
+        // Why the run made fewer requests than it was asked to, unless it was stopped.
+        let cut_short = if failed > 0 {
+            Some(format!("{} of {} workers stopped early", failed, sources))
+        } else if completed < requests as u64 && stop.reason().is_none() {
+            Some(format!("Only {} of {} requests were made", completed, requests))
+        } else {
+            None
+        };
+        if let Some(ref schedule) = schedule {
+            warnings.check_rate(schedule.achieved_rps(), schedule.target_rps());
+        }
+        let versions: Vec<&str> = accumulator.versions().iter().map(|version| version.as_str()).collect();
+        warnings.check_versions(&versions);
+        // The agents make the requests of a remote run, so only local runs can saturate.
+        if let Some(ref client_usage) = client_usage {
+            let cpus = metadata.host.cpus;
+            warnings.check_saturation(usage::cpu_utilization(client_usage.cpu(), duration, cpus, plan.threads()));
+        }
+
+        // The statistics were folded as the facts arrived, so only the charts are left to draw.
+        let summary = Summary::from_accumulator(&accumulator)
+            .with_chart_size(chart_size)
+            .with_time_unit(time_unit)
+            .with_size_unit(size_unit)
+            .with_urls(&urls);
+        let summary = match trim_outliers {
+            Some(fraction) => summary.with_trimmed_outliers(fraction),
+            None => summary,
+        };
+
+        if let Some(path) = matches.value_of("chart-data") {
+            std::fs::write(path, summary.chart_data())
+                .map_err(|e| Error::Io(format!("Failed to write the chart data to {}: {}", path, e)))?;
+        }
+
+        let run = output::Run {
+            metadata: &metadata,
+            warnings: &warnings,
+            seconds,
+            setup,
+            collector: Some(overhead),
+            // Agents open their own connections, which aren't counted here.
+            preconnect: preconnect.as_ref().filter(|_| agents.is_empty()).map(|preconnect| preconnect.take()),
+            client_usage,
+            summary: &summary,
+            heatmap: heatmap.as_ref(),
+            status_chart: status_chart.as_ref(),
+            size_latency: size_latency.as_ref(),
+            periodicity: Some(&periodicity),
+            schedule: schedule.as_ref(),
+            facts: accumulator.facts(),
+        };
+        for sink in &sinks {
+            sink.write(&run).map_err(|e| Error::Io(format!("Failed to write the results: {}", e)))?;
+        }
+
+        // The capacity and cooldown sections aren't part of the report so they go to stderr
+        // when stdout is taken by one.
+        let section = |section: &dyn std::fmt::Display| {
+            if json {
+                eprintln!("{}", section);
+            } else {
+                println!("{}", section);
+            }
+        };
+
+        if let Some(target_rps) = target_rps {
+            let headroom =
+                capacity::Headroom::from_run(target_rps, accumulator.count() as f64 / seconds, concurrency, summary.average());
+            section(&headroom.with_time_unit(time_unit));
+        }
+
+        if let (Some(length), Some(baseline)) = (cooldown, baseline) {
+            log::info(format_args!("Probing cooldown for {} seconds\n", length.as_secs()));
+            let cooldown = cooldown::probe(&eng, baseline, length, cooldown_interval);
+            section(&cooldown.with_time_unit(time_unit));
+        }
+
+        let regressions = baseline_run.as_ref().map(|baseline_run| {
+            let report = report::Report::new(&metadata, &warnings, seconds, summary.results());
+            let comparison = Comparison::new(baseline_run, &report).with_time_unit(time_unit);
+            section(&format_args!("Compared with the baseline\n{}", comparison));
+            comparison.regressions(max_regression.unwrap_or(f64::INFINITY))
+        });
+
+        if !interactive {
+            break (summary, regressions, cut_short);
+        }
+        session.record(settings, report::Report::new(&metadata, &warnings, seconds, summary.results()));
+        let stdin = io::stdin();
+        let mut out: Box<dyn Write> = if json {
+            Box::new(io::stderr())
+        } else {
+            Box::new(io::stdout())
+        };
+        let next = session
+            .prompt(settings, &mut stdin.lock(), &mut out, check)
+            .map_err(|e| Error::Io(format!("Failed to read the next command: {}", e)))?;
+        match next {
+            Some(next) => {
+                stop.reset();
+                settings = next;
+            }
+            None => break (summary, regressions, cut_short),
+        }
+    };
+
+    if let (true, Some(reason)) = (stop.is_aborted(), stop.reason()) {
+        return Err(Error::Failed(format!("The run was aborted: {}", reason)));
+    }
+    if let Some(reason) = cut_short {
+        return Err(Error::Engine(reason));
+    }
+    if fail_on_errors && summary.errors() > 0 {
+        return Err(Error::Failed(format!("{} requests errored", summary.errors())));
+    }
+    if let Some(max_error_rate) = max_error_rate {
+        if summary.failure_rate() > max_error_rate {
+            return Err(Error::Failed(format!(
+                "{:.2}% of requests failed, more than the allowed {:.2}%",
+                summary.failure_rate() * 100.,
+                max_error_rate * 100.
+            )));
+        }
+    }
+    if let (Some(regressions), Some(max_regression)) = (regressions, max_regression) {
+        if !regressions.is_empty() {
+            let regressed: Vec<String> = regressions
+                .iter()
+                .map(|regression| {
+                    format!(
+                        "{} regressed {:.2}% from the baseline, more than the allowed {:.2}%",
+                        regression.name,
+                        regression.by * 100.,
+                        max_regression * 100.
+                    )
+                })
+                .collect();
+            return Err(Error::Failed(regressed.join("\n")));
+        }
+    }
+    Ok(())
+}
+
+/// The command line of rench. A bare `rench URL` runs just as `rench run URL` does, so the
+/// arguments of a run are taken at the top as well as by the subcommand.
+fn app<'a, 'b>() -> App<'a, 'b> {
+    with_run_args(App::new(env!("CARGO_PKG_NAME")))
+        .version(env!("CARGO_PKG_VERSION"))
+        .about(env!("CARGO_PKG_DESCRIPTION"))
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .subcommand(with_run_args(
+            SubCommand::with_name("run").about("Benchmark the urls. 'rench URL' is the same as 'rench run URL'"),
+        ))
+        .subcommand(
+            SubCommand::with_name("report")
+                .about("Print a run saved with --output json as it was printed when it ran. Example 'rench report run.json'")
+                .arg(Arg::with_name("RUN").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("compare")
+                .about("Compare a run saved with --output json against a baseline saved the same way, side by side. Example 'rench compare before.json after.json'")
+                .arg(Arg::with_name("BASELINE").required(true))
+                .arg(Arg::with_name("RUN").required(true))
+                .arg(
+                    Arg::with_name("time-unit")
+                        .long("time-unit")
+                        .takes_value(true)
+                        .possible_values(&["auto", "us", "ms", "s"])
+                        .help("The unit to print latencies in [default: auto]"),
+                )
+                .arg(
+                    Arg::with_name("chart-size")
+                        .long("chart-size")
+                        .takes_value(true)
+                        .possible_values(&["none", "n", "small", "s", "medium", "m", "large", "l"])
+                        .help("The size of the chart of both runs' latency percentiles drawn over each other [default: medium]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("find-capacity")
+                .about("Find the highest rate the urls can be run at while meeting the objectives, by doubling the rate of a run until it misses them and then narrowing in. Example 'rench find-capacity --slo p99<200ms -- -c 50 http://localhost/'")
+                .setting(AppSettings::TrailingVarArg)
+                .arg(
+                    Arg::with_name("slo")
+                        .long("slo")
+                        .takes_value(true)
+                        .required(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("An objective every run has to meet: a percentile such as 'p99', 'median', 'average' or 'longest' below a latency, or 'error-rate' below a ratio. Can be given more than once. Example '--slo p99<200ms --slo error-rate<1%'"),
+                )
+                .arg(
+                    Arg::with_name("start-rate")
+                        .long("start-rate")
+                        .takes_value(true)
+                        .validator(validate::number)
+                        .help("The requests per second of the first run [default: 10]"),
+                )
+                .arg(
+                    Arg::with_name("max-rate")
+                        .long("max-rate")
+                        .takes_value(true)
+                        .validator(validate::number)
+                        .help("The most requests per second to try"),
+                )
+                .arg(
+                    Arg::with_name("step-duration")
+                        .long("step-duration")
+                        .takes_value(true)
+                        .help("About how long each run takes [default: 10s]"),
+                )
+                .arg(
+                    Arg::with_name("precision")
+                        .long("precision")
+                        .takes_value(true)
+                        .help("How close the highest rate that met the objectives has to come to the lowest that missed them, as a fraction or a percentage [default: 5%]"),
+                )
+                .arg(
+                    Arg::with_name("target-rps")
+                        .long("target-rps")
+                        .takes_value(true)
+                        .validator(validate::number)
+                        .help("Estimate the headroom or shortfall against this many requests per second, with the latency at it fitted across the runs"),
+                )
+                .arg(
+                    Arg::with_name("ARGS")
+                        .required(true)
+                        .multiple(true)
+                        .allow_hyphen_values(true)
+                        .help("The arguments of each run, without its -n, --rate or --output"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("agent")
+                .about("Wait for a controller started with --workers to send part of a run. Only listen on networks that you trust")
+                .arg(
+                    Arg::with_name("listen")
+                        .long("listen")
+                        .takes_value(true)
+                        .default_value("127.0.0.1:7000")
+                        .help("The address to listen on. Controllers on other machines can only reach an agent listening on a public address, such as 0.0.0.0:7000"),
+                )
+                .arg(
+                    Arg::with_name("token")
+                        .long("token")
+                        .takes_value(true)
+                        .value_name("TOKEN")
+                        .required(true)
+                        .env("RENCH_AGENT_TOKEN")
+                        .hide_env_values(true)
+                        .help("The secret that a controller has to send with --workers-token for the agent to run its job, best given in the environment"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("selftest-server")
+                .about("Serve the routes that the engines are tested against, on the address printed once it's listening")
+                .setting(AppSettings::Hidden)
+                .arg(
+                    Arg::with_name("listen")
+                        .long("listen")
+                        .takes_value(true)
+                        .default_value("127.0.0.1:0")
+                        .help("The address to listen on. Port 0 picks a free port"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("aggregate")
+                .about("Merge runs saved with --output json, such as from several machines started together, into the results of one run. Example 'rench aggregate a.json b.json'")
+                .arg(Arg::with_name("RUNS").required(true).multiple(true))
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .takes_value(true)
+                        .possible_values(&["human", "json"])
+                        .help("How to print the merged results. 'json' prints a versioned report [default: human]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("preset")
+                .about("Manage the presets saved in the user config directory")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(SubCommand::with_name("list").about("List the saved presets"))
+                .subcommand(
+                    SubCommand::with_name("show")
+                        .about("Show the arguments saved in a preset")
+                        .arg(Arg::with_name("NAME").required(true)),
+                )
+                .subcommand(
+                    SubCommand::with_name("save")
+                        .about("Save arguments as a preset. Example 'rench preset save smoke -- -c 4 http://localhost/'")
+                        .setting(AppSettings::TrailingVarArg)
+                        .arg(Arg::with_name("NAME").required(true))
+                        .arg(
+                            Arg::with_name("ARGS")
+                                .required(true)
+                                .multiple(true)
+                                .allow_hyphen_values(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("completions")
+                .about("Print the completions of rench for a shell. Example 'rench completions bash > /etc/bash_completion.d/rench'")
+                .arg(
+                    Arg::with_name("SHELL")
+                        .required(true)
+                        .possible_values(&["bash", "zsh", "fish"]),
+                ),
+        )
+}
+
+/// Adds the arguments of a run to the app or subcommand.
+fn with_run_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    app
+        .arg(
+            Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .conflicts_with("verbose")
+                .help("Only print the results, without the banner and progress lines"),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .multiple(true)
+                .help("Log each request to stderr. Use -vv to also log its connection and body size"),
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .conflicts_with_all(&["workers", "stdin"])
+                .help("Send one request to each url and print the request sent, the response and how long each step took, then exit without running"),
+        )
+        .arg(
+            Arg::with_name("interactive")
+                .long("interactive")
+                .conflicts_with_all(&["workers", "stdin", "dry-run", "emit-facts"])
+                .help("After the summary, prompt for the concurrency and rate of another run against the same setup, and keep every run of the session to compare them side by side"),
+        )
         .arg(
             Arg::with_name("URL")
-                .required(true)
+                .required_unless_one(&["url-file", "stdin"])
                 .multiple(true)
                 .help("Each url specified will be round robined."),
         )
+        .arg(
+            Arg::with_name("url-file")
+                .long("url-file")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Read more urls from the file, one per line. Blank lines and lines starting with '#' are skipped"),
+        )
+        .arg(
+            Arg::with_name("feed")
+                .long("feed")
+                .takes_value(true)
+                .value_name("FILE")
+                .conflicts_with("stdin")
+                .help("Fill the {{column}} placeholders in the urls, headers and --body of each request from the next row of the csv file, whose header row names the columns. Example '--feed users.csv http://localhost/users/{{id}}'"),
+        )
+        .arg(
+            Arg::with_name("feed-mode")
+                .long("feed-mode")
+                .takes_value(true)
+                .possible_values(&["wrap", "stop"])
+                .requires("feed")
+                .help("Start again from the first row once every row of the --feed is used, or stop, making no more requests than there are rows [default: wrap]"),
+        )
+        .arg(
+            Arg::with_name("extract")
+                .long("extract")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("NAME=SOURCE")
+                .conflicts_with("stdin")
+                .help("Take a value from the body of each response, by a json pointer or the first group of a regex, and fill it into the {{NAME}} placeholders in the urls, headers and --body of the worker's later requests. Can be given more than once. Example '--extract token=json:/access_token -H \"authorization: Bearer {{token}}\"'"),
+        )
+        .arg(
+            Arg::with_name("stdin")
+                .long("stdin")
+                .conflicts_with_all(&[
+                    "URL",
+                    "url-file",
+                    "sequential",
+                    "requests",
+                    "threads",
+                    "workers",
+                    "head-requests",
+                    "body",
+                    "checksum",
+                    "max-connections-per-host",
+                    "cooldown",
+                    "rate",
+                ])
+                .help("Replay the requests read from stdin as they arrive until it ends, such as an access log. Each line is a url or a json object like {\"method\":\"POST\",\"url\":\"...\",\"body\":\"...\"}. Only the hyper engine replays requests"),
+        )
+        .arg(
+            Arg::with_name("sequential")
+                .long("sequential")
+                .help("Walk through the urls in order across all workers, instead of each worker round robining through them from the first. With as many requests as urls each url is requested once"),
+        )
+        .arg(
+            Arg::with_name("workers")
+                .long("workers")
+                .takes_value(true)
+                .use_delimiter(true)
+                .value_name("HOST:PORT")
+                .requires("workers-token")
+                .help("Split the run between agents on other machines, started with 'rench agent', and combine the facts they stream back. Example '--workers host1:7000,host2:7000'"),
+        )
+        .arg(
+            Arg::with_name("workers-token")
+                .long("workers-token")
+                .takes_value(true)
+                .value_name("TOKEN")
+                .env("RENCH_AGENT_TOKEN")
+                .hide_env_values(true)
+                .help("The token that the --workers agents were started with, best given in the environment"),
+        )
+        .arg(
+            Arg::with_name("workers-summarize")
+                .long("workers-summarize")
+                .requires("workers")
+                .conflicts_with_all(&["record", "heatmap", "status-chart", "by-size", "report-interval"])
+                .help("Have the --workers agents send back the statistics of their share once it's done instead of streaming every fact, which keeps the controller's traffic small on long runs. The charts and reports that need each request aren't available"),
+        )
+        .arg(
+            Arg::with_name("emit-facts")
+                .long("emit-facts")
+                .hidden(true)
+                .help("Write each fact to stdout as json instead of summarizing, for an agent to stream back"),
+        )
+        .arg(
+            Arg::with_name("emit-summary")
+                .long("emit-summary")
+                .hidden(true)
+                .conflicts_with("emit-facts")
+                .help("Write the statistics of the run to stdout as json once it's done, for an agent to send back"),
+        )
         .arg(
             Arg::with_name("concurrency")
                 .short("c")
                 .takes_value(true)
-                .help("The number of concurrent requests to make"),
+                .validator(validate::concurrency)
+                .help("The number of concurrent requests to make, or 'auto' for one per cpu"),
+        )
+        .arg(
+            Arg::with_name("force")
+                .long("force")
+                .help("Run with more threads than the engines that make one request at a time can keep busy on this machine's cpus, which mostly measures switching between them"),
+        )
+        .arg(
+            Arg::with_name("threads")
+                .long("threads")
+                .takes_value(true)
+                .validator(validate::whole_number)
+                .conflicts_with("max-connections-per-host")
+                .help("The number of threads to make the concurrent requests on, each keeping its share of them in flight. Only the hyper engine can have more than one request in flight on a thread [default: the concurrency, or the cpus in an open loop]"),
+        )
+        .arg(
+            Arg::with_name("loop")
+                .long("loop")
+                .takes_value(true)
+                .possible_values(&["closed", "open"])
+                .help("'closed' waits for a request to finish before its thread starts another. 'open' starts the requests on the --rate schedule whatever is still in flight, which shows how the server copes with load it can't keep up with. Only the hyper engine runs an open loop [default: closed]"),
+        )
+        .arg(
+            Arg::with_name("max-inflight")
+                .long("max-inflight")
+                .takes_value(true)
+                .validator(validate::whole_number)
+                .requires("loop")
+                .conflicts_with("concurrency")
+                .help("The most requests an open loop keeps in flight, after which requests start late rather than exhaust the client [default: 1000]"),
         )
         .arg(
             Arg::with_name("requests")
                 .short("n")
                 .takes_value(true)
+                .validator(validate::whole_number)
                 .help("The number of requests in total to make"),
         )
         .arg(
@@ -47,14 +1649,28 @@ fn main() {
                 .long("head")
                 .help("The issue head requests instead of get"),
         )
+        .arg(
+            Arg::with_name("mix")
+                .long("mix")
+                .takes_value(true)
+                .conflicts_with_all(&["head-requests", "stdin"])
+                .help("Mix the methods of the requests by weight and break the summary down by method. Only posts carry the --body, and only the hyper, reqwest and raw engines mix methods. Example '--mix GET=90,HEAD=10'"),
+        )
         .arg(
             Arg::with_name("engine")
                 .long("engine")
                 .short("e")
                 .takes_value(true)
-                .possible_values(&["hyper", "reqwest"])
+                .possible_values(&["hyper", "reqwest", "raw", "websocket", "tcp"])
                 .default_value("hyper")
-                .help("The engine to use"),
+                .help("The engine to use. 'raw' is an experimental minimal http/1.1 client for plain http urls, making one request at a time on each thread over blocking sockets. 'websocket' times the reply to a message sent over ws urls. 'tcp' writes the body to tcp://host:port urls and times the reply"),
+        )
+        .arg(
+            Arg::with_name("read-bytes")
+                .long("read-bytes")
+                .takes_value(true)
+                .validator(validate::whole_number)
+                .help("With the tcp engine, read this many bytes of reply and reuse the connection. Without it each reply is read until the server closes the connection"),
         )
         .arg(
             Arg::with_name("header")
@@ -62,7 +1678,246 @@ fn main() {
                 .multiple(true)
                 .takes_value(true)
                 .number_of_values(1)
-                .help("Headers to inject in the request. Example '--header user-agent:rust-rench'"),
+                .help("Headers to inject in the request. Example '--header user-agent:rust-rench'. Use '@file' to read headers from a file, one per line, and 'name:env:VAR' to read a value from the environment"),
+        )
+        .arg(
+            Arg::with_name("header-for")
+                .long("header-for")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(2)
+                .value_names(&["URL", "HEADER"])
+                .help("Headers to send only to one of the urls, replacing global headers of the same name. Example '--header-for http://0.0.0.0/ host:tenant.example.com'"),
+        )
+        .arg(
+            Arg::with_name("profile")
+                .long("profile")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(2)
+                .value_names(&["NAME", "HEADER"])
+                .help("Add a header to a named profile. Each worker is assigned one profile for the whole run and the results are broken down by profile. Example '--profile mobile user-agent:iPhone'"),
+        )
+        .arg(
+            Arg::with_name("profile-weight")
+                .long("profile-weight")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(2)
+                .value_names(&["NAME", "WEIGHT"])
+                .requires("profile")
+                .help("How many workers a profile gets relative to the others [default: 1]"),
+        )
+        .arg(
+            Arg::with_name("bind")
+                .long("bind")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(1)
+                .value_name("IP")
+                .help("Open connections from this local address. Given more than once, connections take the addresses in turn, to get past the ephemeral port limit or spread over a source-ip affinity load balancer"),
+        )
+        .arg(
+            Arg::with_name("ipv4")
+                .long("ipv4")
+                .short("4")
+                .conflicts_with("ipv6")
+                .help("Only connect to the IPv4 addresses of the hosts, to measure one side of a dual-stack service. Not supported by the reqwest engine"),
+        )
+        .arg(
+            Arg::with_name("ipv6")
+                .long("ipv6")
+                .short("6")
+                .help("Only connect to the IPv6 addresses of the hosts, to measure one side of a dual-stack service. Not supported by the reqwest engine"),
+        )
+        .arg(
+            Arg::with_name("max-connections-per-host")
+                .long("max-connections-per-host")
+                .takes_value(true)
+                .validator(validate::whole_number)
+                .help("Allow at most this many requests in flight to each host across all workers, as if they shared one pool of connections. Time spent waiting for a slot is reported as the pool wait"),
+        )
+        .arg(
+            Arg::with_name("pool-idle-timeout")
+                .long("pool-idle-timeout")
+                .takes_value(true)
+                .validator(validate::number)
+                .help("Close connections that have been idle for this many seconds instead of reusing them, 0 to never reuse them. Only the hyper and raw engines support it"),
+        )
+        .arg(
+            Arg::with_name("preconnect")
+                .long("preconnect")
+                .conflicts_with("stdin")
+                .help("Open and check every connection before the run starts, and report how long they took to open on their own, so that the handshakes aren't part of the latency of the first requests. The http engines check a connection with a HEAD request to the url, which isn't counted"),
+        )
+        .arg(
+            Arg::with_name("follow-redirects")
+                .long("follow-redirects")
+                .takes_value(true)
+                .validator(validate::whole_number)
+                .min_values(0)
+                .max_values(1)
+                .require_equals(true)
+                .value_name("MAX")
+                .help("Follow up to MAX redirects, given as '--follow-redirects=MAX', and count how many each request followed. Without this no engine follows redirects [default MAX: 10]"),
+        )
+        .arg(
+            Arg::with_name("respect-retry-after")
+                .long("respect-retry-after")
+                .help("Have each thread wait as long as a response's Retry-After asks, up to a minute, before its next request. Threads that keep many requests in flight don't wait"),
+        )
+        .arg(
+            Arg::with_name("expect-body")
+                .long("expect-body")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(1)
+                .help("Count a response as a failure unless its body contains this text, whatever its status. Can be given more than once"),
+        )
+        .arg(
+            Arg::with_name("expect-body-regex")
+                .long("expect-body-regex")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(1)
+                .help("Count a response as a failure unless its body matches this regex, whatever its status. Can be given more than once"),
+        )
+        .arg(
+            Arg::with_name("checksum")
+                .long("checksum")
+                .help("Checksum every response body and report how many distinct bodies each url served, which catches stale or mixed content"),
+        )
+        .arg(
+            Arg::with_name("capture-header")
+                .long("capture-header")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(1)
+                .value_name("NAME")
+                .help("Count each value of the response header, with the average latency of each, such as a cdn's x-cache HIT or MISS. Responses without it are counted as (none)"),
+        )
+        .arg(
+            Arg::with_name("dump-responses")
+                .long("dump-responses")
+                .takes_value(true)
+                .validator(validate::whole_number)
+                .value_name("N")
+                .help("Write the first N responses, with their status line and headers, to files in the dump directory before carrying on with the run"),
+        )
+        .arg(
+            Arg::with_name("dump-dir")
+                .long("dump-dir")
+                .takes_value(true)
+                .requires("dump-responses")
+                .help("The directory to write dumped responses to [default: rench-responses]"),
+        )
+        .arg(
+            Arg::with_name("log-slow")
+                .long("log-slow")
+                .takes_value(true)
+                .number_of_values(2)
+                .value_names(&["THRESHOLD", "FILE"])
+                .help("Append a line with the time, url, status, duration and captured headers of every request slower than the threshold to the file. Example '--log-slow 500ms slow.log'"),
+        )
+        .arg(
+            Arg::with_name("trace-header")
+                .long("trace-header")
+                .takes_value(true)
+                .value_name("NAME=uuid")
+                .help("Send every request with a uuid of its own in the header, and name it in the slow log and the emitted facts so that they can be matched with the server's traces. Example '--trace-header x-request-id=uuid'"),
+        )
+        .arg(
+            Arg::with_name("oauth-token-url")
+                .long("oauth-token-url")
+                .takes_value(true)
+                .value_name("URL")
+                .requires_all(&["oauth-client-id", "oauth-client-secret"])
+                .help("Send every request with a bearer token from the OAuth2 token url, granted to the client credentials before the run and again whenever it's near its expiry"),
+        )
+        .arg(
+            Arg::with_name("oauth-client-id")
+                .long("oauth-client-id")
+                .takes_value(true)
+                .value_name("ID")
+                .requires("oauth-token-url")
+                .help("The client id to fetch the OAuth2 token with"),
+        )
+        .arg(
+            Arg::with_name("oauth-client-secret")
+                .long("oauth-client-secret")
+                .takes_value(true)
+                .value_name("SECRET")
+                .env("RENCH_OAUTH_CLIENT_SECRET")
+                .hide_env_values(true)
+                .help("The client secret to fetch the OAuth2 token with, best given in the environment"),
+        )
+        .arg(
+            Arg::with_name("oauth-scope")
+                .long("oauth-scope")
+                .takes_value(true)
+                .requires("oauth-token-url")
+                .help("The scope to ask for with the OAuth2 token"),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .takes_value(true)
+                .validator(validate::whole_number)
+                .help("Seed the randomness of the requests, such as body placeholders, so that a run can be reproduced. Every run prints its seed [default: random]"),
+        )
+        .arg(
+            Arg::with_name("body")
+                .long("body")
+                .takes_value(true)
+                .conflicts_with("head-requests")
+                .help("Post this body with each request, or send it as the message with the websocket and tcp engines. Supports the placeholders {{n}}, {{uuid}}, {{rand_int(low,high)}} and {{token}}"),
+        )
+        .arg(
+            Arg::with_name("param")
+                .long("param")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("NAME=VALUE")
+                .help("Add a query parameter to the url of every request, rendered for each one. The value is a placeholder of the --body without its braces, a template, or else sent as it is. Can be given more than once. Example '--param page=rand_int(1,100) --param cachebust=uuid'"),
+        )
+        .arg(
+            Arg::with_name("form")
+                .long("form")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("NAME=VALUE")
+                .conflicts_with_all(&["body", "head-requests"])
+                .help("Post a multipart/form-data body with this field, or the file at the path after an '@'. Can be given more than once, and the body is built once before the run. Example '--form title=cat --form file=@cat.jpg'"),
+        )
+        .arg(
+            Arg::with_name("echo")
+                .long("echo")
+                .requires("body")
+                .help("Count a response as a failure unless it echoes the {{token}} that its request's --body was sent with, to test that the server answers every request with its own response under load. Every body is rendered fresh"),
+        )
+        .arg(
+            Arg::with_name("echo-regex")
+                .long("echo-regex")
+                .takes_value(true)
+                .requires("echo")
+                .help("Only take the token that the first group of this regex matches in the response, rather than the token anywhere in it. Example '--echo-regex \"id\":\"([^\"]*)\"'"),
+        )
+        .arg(
+            Arg::with_name("body-pool")
+                .long("body-pool")
+                .takes_value(true)
+                .validator(validate::whole_number)
+                .requires("body")
+                .help("The number of bodies to render before the run and cycle through [default: 1000]"),
+        )
+        .arg(
+            Arg::with_name("unique-bodies")
+                .long("unique-bodies")
+                .requires("body")
+                .conflicts_with("body-pool")
+                .help("Render a fresh body for every request instead of cycling through a pool"),
         )
         .arg(
             Arg::with_name("chart-size")
@@ -71,77 +1926,516 @@ fn main() {
                 .possible_values(&["none", "n", "small", "s", "medium", "m", "large", "l"])
                 .help("The size of the chart to render"),
         )
-        .get_matches();
+        .arg(
+            Arg::with_name("chart-data")
+                .long("chart-data")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Write the percentiles and latency histogram to the file as columns of numbers, for plotting with gnuplot or a spreadsheet"),
+        )
+        .arg(
+            Arg::with_name("heatmap")
+                .long("heatmap")
+                .help("Chart how many requests took how long as the run went on, which shows latency storms that the percentiles of the whole run hide"),
+        )
+        .arg(
+            Arg::with_name("pin-cores")
+                .long("pin-cores")
+                .help("Pin each thread to a core, keeping the collector on a core of its own, so that the scheduler moving threads around adds less noise to the numbers. Only supported on linux"),
+        )
+        .arg(
+            Arg::with_name("batch")
+                .long("batch")
+                .takes_value(true)
+                .value_name("FACTS")
+                .help("How many facts each thread keeps before sending them to the collector together, 1 to send each on its own [default: 100]"),
+        )
+        .arg(
+            Arg::with_name("queue-size")
+                .long("queue-size")
+                .takes_value(true)
+                .value_name("MESSAGES")
+                .help("How many facts or batches of them the collector's queue holds before --when-full kicks in [default: 1024]"),
+        )
+        .arg(
+            Arg::with_name("when-full")
+                .long("when-full")
+                .takes_value(true)
+                .possible_values(&["block", "drop", "sample"])
+                .help("What a thread does with its facts when the collector's queue is full: wait for room, drop them, or keep one in ten and drop the rest. Dropped facts are counted and warned about [default: block]"),
+        )
+        .arg(
+            Arg::with_name("batch-interval")
+                .long("batch-interval")
+                .takes_value(true)
+                .value_name("DURATION")
+                .help("The longest a thread keeps a fact before sending it to the collector, such as 5ms [default: 10ms]"),
+        )
+        .arg(
+            Arg::with_name("by-size")
+                .long("by-size")
+                .help("Group the latencies of the responses by the size of their bodies, which shows whether the slow responses are just the big ones"),
+        )
+        .arg(
+            Arg::with_name("status-chart")
+                .long("status-chart")
+                .help("Chart the status codes of the responses as the run went on, which shows bursts of server errors that the counts of the whole run hide"),
+        )
+        .arg(
+            Arg::with_name("time-unit")
+                .long("time-unit")
+                .takes_value(true)
+                .possible_values(&["auto", "us", "ms", "s"])
+                .help("The unit to print latencies in. 'auto' picks one based on each latency [default: auto]"),
+        )
+        .arg(
+            Arg::with_name("trim-outliers")
+                .long("trim-outliers")
+                .takes_value(true)
+                .value_name("FRACTION")
+                .help("Also show the latency without this fraction of the slowest requests, so that a few pathological ones don't hide how the rest went. Example '--trim-outliers 0.1%'"),
+        )
+        .arg(
+            Arg::with_name("size-unit")
+                .long("size-unit")
+                .takes_value(true)
+                .possible_values(&["binary", "si", "bytes"])
+                .help("The unit to print sizes in. 'binary' counts in 1024s, 'si' in 1000s and 'bytes' prints them exactly [default: binary]"),
+        )
+        .arg(
+            Arg::with_name("bytes")
+                .long("bytes")
+                .conflicts_with("size-unit")
+                .help("Print sizes as exact numbers of bytes, the same as --size-unit bytes"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("FORMAT[=FILE]")
+                .help("How to write the results, to stdout or to the file given: 'human', its 'text' or 'charts' alone, 'markdown' tables, 'html' for a page of them, 'json' for a versioned report or 'csv' for a line per request. Can be given more than once, but only one can take stdout, and everything else goes to stderr when it's json or csv. Example '--output human --output json=results.json' [default: human]"),
+        )
+        .arg(
+            Arg::with_name("label")
+                .long("label")
+                .takes_value(true)
+                .help("Name the run in its results, such as the release it was made against. Example '--label release-1.42-canary'"),
+        )
+        .arg(
+            Arg::with_name("tag")
+                .long("tag")
+                .multiple(true)
+                .number_of_values(1)
+                .takes_value(true)
+                .value_name("KEY=VALUE")
+                .help("Tag the run's results with a value, so they can be joined with other data such as deployments. Example '--tag region=eu-west-1'"),
+        )
+        .arg(
+            Arg::with_name("report-interval")
+                .long("report-interval")
+                .takes_value(true)
+                .help("Summarize the run so far every interval, such as '60s', to see how a long soak test held up over time"),
+        )
+        .arg(
+            Arg::with_name("report-file")
+                .long("report-file")
+                .takes_value(true)
+                .value_name("FILE")
+                .requires("report-interval")
+                .help("Append the summaries of --report-interval to the file instead of printing them"),
+        )
+        .arg(
+            Arg::with_name("reset-window")
+                .long("reset-window")
+                .requires("report-interval")
+                .help("Summarize only the requests since the last --report-interval summary in each one"),
+        )
+        .arg(
+            Arg::with_name("stream")
+                .long("stream")
+                .help("Fold facts into running statistics instead of keeping every fact in memory"),
+        )
+        .arg(
+            Arg::with_name("record")
+                .long("record")
+                .requires("stream")
+                .help("Keep the raw facts while streaming so that charts can be drawn"),
+        )
+        .arg(
+            Arg::with_name("sample-size")
+                .long("sample-size")
+                .takes_value(true)
+                .validator(validate::whole_number)
+                .requires("stream")
+                .help("The number of durations to sample for charts while streaming, 0 to disable [default: 10000]"),
+        )
+        .arg(
+            Arg::with_name("cooldown")
+                .long("cooldown")
+                .takes_value(true)
+                .validator(validate::whole_number)
+                .help("Probe the server for this many seconds after the run to measure how long latency takes to recover"),
+        )
+        .arg(
+            Arg::with_name("cooldown-interval")
+                .long("cooldown-interval")
+                .takes_value(true)
+                .validator(validate::whole_number)
+                .requires("cooldown")
+                .help("The milliseconds between cooldown probes [default: 250]"),
+        )
+        .arg(
+            Arg::with_name("rate")
+                .long("rate")
+                .takes_value(true)
+                .validator(validate::number)
+                .help("Pace the requests so this many start each second over all of the threads, and report whether the client kept up"),
+        )
+        .arg(
+            Arg::with_name("target-rps")
+                .long("target-rps")
+                .takes_value(true)
+                .validator(validate::number)
+                .help("Estimate the headroom or shortfall against this many requests per second"),
+        )
+        .arg(
+            Arg::with_name("fail-on-errors")
+                .long("fail-on-errors")
+                .help("Exit with a non-zero code if any request errored"),
+        )
+        .arg(
+            Arg::with_name("max-error-rate")
+                .long("max-error-rate")
+                .takes_value(true)
+                .help("Exit with a non-zero code if more than this fraction of responses are non-2xx. Example '--max-error-rate 0.5%'"),
+        )
+        .arg(
+            Arg::with_name("baseline")
+                .long("baseline")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Compare the run with a run saved with --output json, such as one kept in the repo, and print them side by side. Example '--baseline baseline.json'"),
+        )
+        .arg(
+            Arg::with_name("max-regression")
+                .long("max-regression")
+                .takes_value(true)
+                .requires("baseline")
+                .help("Exit with a non-zero code if the p99 latency rose or the requests per second fell from the --baseline by more than this fraction. Example '--max-regression 10%'"),
+        )
+        .arg(
+            Arg::with_name("max-bytes")
+                .long("max-bytes")
+                .takes_value(true)
+                .value_name("SIZE")
+                .conflicts_with("workers")
+                .help("Stop the run once the bodies of the responses add up to this much, for endpoints that bill by the byte. The requests in flight finish, so a little more may be downloaded. Example '--max-bytes 10GB'"),
+        )
+        .arg(
+            Arg::with_name("abort-on")
+                .long("abort-on")
+                .takes_value(true)
+                .value_name("RULE")
+                .conflicts_with("workers")
+                .help("Stop the run and exit with a non-zero code once the error rate of the requests has stayed above a threshold for a while, such as when every request is refused. Example '--abort-on \"error-rate>50% for 10s\"'"),
+        )
+        .arg(
+            Arg::with_name("preset")
+                .long("preset")
+                .takes_value(true)
+                .help("Use the arguments saved in a preset. Arguments given alongside are added to the preset's"),
+        )
+}
 
-    let urls: Vec<String> = matches
-        .values_of("URL")
-        .expect("URLs are required")
-        .map(|v| v.to_string())
+/// Where presets are read from and saved to.
+fn presets_path() -> Result<std::path::PathBuf, String> {
+    preset::default_path().ok_or_else(|| "Expected a home directory to store presets in".to_string())
+}
+
+fn report_run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = matches.value_of("RUN").expect("A run is required");
+    let report = aggregate::read(path).map_err(Error::Io)?;
+    let summary = Summary::from_results(&[&report.results]).map_err(Error::Usage)?;
+    println!("Run of {} at {}", report.run.command, report.run.started);
+    print_report(&report, summary);
+    Ok(())
+}
+
+fn compare_runs(matches: &ArgMatches) -> Result<(), Error> {
+    let read = |name: &str| {
+        let path = matches.value_of(name).expect("Both runs are required");
+        aggregate::read(path).map_err(Error::Io)
+    };
+    let comparison = Comparison::new(&read("BASELINE")?, &read("RUN")?)
+        .with_time_unit(parse_time_unit(matches.value_of("time-unit")))
+        .with_chart_size(parse_chart_size(matches.value_of("chart-size")));
+    print!("{}", comparison);
+    let chart = comparison.chart();
+    if !chart.is_empty() {
+        println!();
+        print!("{}", chart);
+    }
+    Ok(())
+}
+
+fn find_capacity(matches: &ArgMatches) -> Result<(), Error> {
+    let slos: Vec<capacity::Slo> = matches
+        .values_of("slo")
+        .expect("An objective is required")
+        .map(capacity::Slo::parse)
+        .collect::<Result<_, _>>()
+        .map_err(Error::Usage)?;
+    let args: Vec<String> = matches
+        .values_of("ARGS")
+        .expect("The arguments of the runs are required")
+        .map(String::from)
         .collect();
+    capacity::check_args(&args).map_err(Error::Usage)?;
+    let start = number(matches.value_of("start-rate").unwrap_or("10"), "the start rate")?;
+    let max = matches
+        .value_of("max-rate")
+        .map(|max| number::<f64>(max, "the max rate"))
+        .transpose()?;
+    let duration = parse_duration(matches.value_of("step-duration").unwrap_or("10s")).map_err(Error::Usage)?;
+    let precision = match matches.value_of("precision") {
+        Some(precision) => ratio(precision, "the precision")?,
+        None => capacity::DEFAULT_PRECISION,
+    };
+    let target_rps = matches
+        .value_of("target-rps")
+        .map(|target| number::<f64>(target, "the target requests per second"))
+        .transpose()?;
+    let objectives: Vec<String> = slos.iter().map(|slo| slo.to_string()).collect();
+    let objectives = objectives.join(" and ");
 
-    let threads = matches
-        .value_of("concurrency")
-        .unwrap_or("1")
-        .parse::<usize>()
-        .expect("Expected valid number for threads");
+    println!("Finding the capacity with {}", objectives);
+    let mut search = capacity::Search::new(start, max, precision);
+    let mut steps = Vec::new();
+    while let Some(rate) = search.next() {
+        let report = capacity::run_at(&args, rate, duration).map_err(Error::Failed)?;
+        let step = capacity::Step::new(rate, report, &slos);
+        println!("{}", step);
+        search.record(step.rate, step.met);
+        steps.push(step);
+    }
+    let capacity = match search.capacity() {
+        Some(capacity) if search.capped() => {
+            println!("\nCapacity: at least {:.2} requests/sec with {}", capacity, objectives);
+            capacity
+        }
+        Some(capacity) => {
+            println!("\nCapacity: about {:.2} requests/sec with {}", capacity, objectives);
+            capacity
+        }
+        None => return Err(Error::Failed(format!("No rate that was tried met {}", objectives))),
+    };
+    if let Some(target_rps) = target_rps {
+        println!();
+        print!("{}", capacity::Headroom::from_sweep(target_rps, capacity, &steps));
+    }
+    Ok(())
+}
 
-    let requests = matches
-        .value_of("requests")
-        .unwrap_or("1000")
-        .parse::<usize>()
-        .expect("Expected valid number for number of requests");
+fn aggregate_runs(matches: &ArgMatches) -> Result<(), Error> {
+    let reports: Vec<report::Report> = matches
+        .values_of("RUNS")
+        .expect("Runs are required")
+        .map(aggregate::read)
+        .collect::<Result<_, _>>()
+        .map_err(Error::Io)?;
+    let results: Vec<&report::Results> = reports.iter().map(|report| &report.results).collect();
+    let summary = Summary::from_results(&results).map_err(Error::Usage)?;
+    let report = aggregate::merge(&reports, summary.results());
+    if matches.value_of("output") == Some("json") {
+        println!("{}", report.to_json());
+    } else {
+        println!("Merged {} runs", reports.len());
+        print_report(&report, summary);
+    }
+    Ok(())
+}
 
-    let chart_size = match matches.value_of("chart-size").unwrap_or("medium") {
+/// Prints a saved or merged report the way a run prints its results.
+fn print_report(report: &report::Report, summary: Summary) {
+    for warning in &report.warnings {
+        println!("  ! {}", warning);
+    }
+    println!("Took {} seconds", report.took_seconds);
+    println!("{} requests / second", report.requests_per_second);
+    println!();
+    println!("{}", summary.with_urls(&report.run.urls));
+}
+
+/// Warns on stderr about the threads that couldn't be pinned, for the runs that hand their
+/// results to a controller instead of printing them.
+fn warn_unpinned(runner: &Runner) {
+    runner.ready();
+    let unpinned = runner.unpinned();
+    if !unpinned.is_empty() {
+        eprintln!("Warning: {}", Warning::Unpinned(unpinned));
+    }
+}
+
+fn manage_presets(matches: &ArgMatches) -> Result<(), Error> {
+    let path = presets_path().map_err(Error::Io)?;
+    let mut presets = Presets::load(&path).map_err(Error::Io)?;
+    match matches.subcommand() {
+        ("list", _) => {
+            for name in presets.names() {
+                println!("{}", name);
+            }
+        }
+        ("show", Some(matches)) => {
+            let name = matches.value_of("NAME").expect("A preset name is required");
+            match presets.get(name) {
+                Some(args) => println!("{}", args.join(" ")),
+                None => return Err(Error::Usage(format!("There is no preset named '{}'", name))),
+            }
+        }
+        ("save", Some(matches)) => {
+            let name = matches.value_of("NAME").expect("A preset name is required");
+            let args = matches
+                .values_of("ARGS")
+                .expect("Arguments are required")
+                .map(|v| v.to_string())
+                .collect();
+            presets.insert(name, args);
+            presets.save(&path).map_err(Error::Io)?;
+            println!("Saved preset '{}' to {}", name, path.display());
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+/// The size of a `--chart-size`, which clap has already checked is one of them.
+fn parse_chart_size(value: Option<&str>) -> ChartSize {
+    match value.unwrap_or("medium") {
         "none" | "n" => ChartSize::None,
         "small" | "s" => ChartSize::Small,
         "medium" | "m" => ChartSize::Medium,
         "large" | "l" => ChartSize::Large,
         _ => unreachable!(),
-    };
+    }
+}
 
-    let headers: Vec<(String, String)> = matches
-        .values_of("header")
-        .unwrap_or(Default::default())
-        .map(|v| {
-            let m = v.splitn(2, ":").collect::<Vec<&str>>();
-            if m.len() != 2 {
-                panic!("Invalid header.");
-            }
-            let k = m[0].to_string().to_lowercase();
-            let v = m[1].to_string();
-            (k, v)
-        })
-        .collect();
+/// The unit of a `--time-unit`, which clap has already checked is one of them.
+fn parse_time_unit(value: Option<&str>) -> TimeUnit {
+    match value.unwrap_or("auto") {
+        "auto" => TimeUnit::Auto,
+        "us" => TimeUnit::Micros,
+        "ms" => TimeUnit::Millis,
+        "s" => TimeUnit::Seconds,
+        _ => unreachable!(),
+    }
+}
 
-    let plan = Plan::new(threads, requests);
+/// Parses the number given for an option, which `what` describes in the error.
+fn number<T: std::str::FromStr>(value: &str, what: &str) -> Result<T, Error> {
+    value
+        .trim()
+        .parse::<T>()
+        .map_err(|_| Error::Usage(format!("Expected valid number for {}, got '{}'", what, value)))
+}
 
-    let eng = match matches.value_of("engine").unwrap_or("hyper") {
-        "hyper" => engine::Engine::new(urls.clone(), headers).with_hyper(),
-        "reqwest" | _ => engine::Engine::new(urls.clone(), headers),
-    };
+/// Parses the ratio given for an option, which `what` describes in the error.
+fn ratio(value: &str, what: &str) -> Result<f64, Error> {
+    parse_ratio(value).ok_or_else(|| Error::Usage(format!("Expected valid ratio for {}, got '{}'", what, value)))
+}
 
-    let eng = if matches.is_present("head-requests") {
-        eng.with_method(engine::Method::Head)
-    } else {
-        eng
+/// Checks the arguments of a job that a controller sent to an agent. Only runs are made, and
+/// none of the options that read or write the agent's files, read its environment or stdin,
+/// or hand the job on to other agents.
+fn check_job(args: &[String]) -> Result<(), String> {
+    const REFUSED: &[&str] = &[
+        "url-file",
+        "feed",
+        "stdin",
+        "workers",
+        "interactive",
+        "log-slow",
+        "dump-responses",
+        "dump-dir",
+        "chart-data",
+        "report-file",
+        "baseline",
+        "preset",
+    ];
+    // Presets are expanded before the arguments are parsed, wherever they appear.
+    if args.iter().any(|arg| arg == "--preset" || arg.starts_with("--preset=")) {
+        return Err("A job can't use the agent's presets".to_string());
+    }
+    let matches = app()
+        .get_matches_from_safe(args)
+        .map_err(|e| e.message.trim_start_matches("error: ").to_string())?;
+    let matches = match matches.subcommand() {
+        (_, None) => &matches,
+        ("run", Some(run)) => run,
+        (name, Some(_)) => return Err(format!("A job can only be a run, not '{}'", name)),
     };
+    if let Some(option) = REFUSED.iter().find(|option| matches.is_present(option)) {
+        return Err(format!("A job can't use --{}", option));
+    }
+    let values = |name| matches.values_of(name).into_iter().flatten();
+    if values("header").chain(values("header-for")).chain(values("profile")).any(header::is_local) {
+        return Err("A job can't read headers from the agent's files or environment".to_string());
+    }
+    if values("form").any(|field| field.split_once('=').is_some_and(|(_, value)| value.starts_with('@'))) {
+        return Err("A job can't post the agent's files".to_string());
+    }
+    if values("output").any(|output| output.contains('=')) {
+        return Err("A job can't write its output to the agent's files".to_string());
+    }
+    Ok(())
+}
 
-    let (collector, rec_handle) = collector::start::<Fact>(plan);
-    let runner = Runner::start(plan, &eng, &collector);
+fn invalid_header(e: String) -> Error {
+    Error::Usage(format!("Invalid header. {}", e))
+}
 
-    println!("Beginning requests");
-    let ((), duration) = bench::time_it(|| runner.join());
-    let facts = rec_handle.join().expect("Receiving thread to finish");
-    let seconds =
-        duration.as_secs() as f64 + (f64::from(duration.subsec_nanos()) / 1_000_000_000f64);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    println!("Finished!");
-    println!();
-    println!("Took {} seconds", seconds);
-    println!("{} requests / second", requests as f64 / seconds);
-    println!();
-    println!(
-        "{}",
-        Summary::from_facts(&facts).with_chart_size(chart_size)
-    );
+    #[test]
+    fn only_runs_jobs_that_stay_off_the_agents_files() {
+        let job = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            check_job(&args)
+        };
+        assert_eq!(job(&["rench", "--header", "x-one:1", "--output", "json", "http://a/", "-n", "10"]), Ok(()));
+        assert_eq!(job(&["rench", "run", "--form", "title=cat", "http://a/"]), Ok(()));
+        assert!(job(&["rench", "--url-file", "/etc/passwd"]).is_err());
+        assert!(job(&["rench", "--feed", "users.csv", "http://a/"]).is_err());
+        assert!(job(&["rench", "--dump-responses", "1", "http://a/"]).is_err());
+        assert!(job(&["rench", "--header", "@/etc/passwd", "http://a/"]).is_err());
+        assert!(job(&["rench", "--header", "authorization:env:SECRET", "http://a/"]).is_err());
+        assert!(job(&["rench", "--form", "file=@/etc/passwd", "http://a/"]).is_err());
+        assert!(job(&["rench", "--output", "json=/tmp/results.json", "http://a/"]).is_err());
+        assert!(job(&["rench", "--label", "x", "--preset", "mine", "http://a/"]).is_err());
+        assert!(job(&["rench", "preset", "save", "mine", "http://a/"]).is_err());
+        assert!(job(&["rench", "aggregate", "/etc/passwd"]).is_err());
+    }
+
+    #[test]
+    fn parses_ratios_as_fractions_or_percentages() {
+        assert_eq!(parse_ratio("0.5"), Some(0.5));
+        assert_eq!(parse_ratio("50%"), Some(0.5));
+        assert_eq!(parse_ratio("0.5%"), Some(0.005));
+        assert_eq!(parse_ratio("150%"), None);
+        assert_eq!(parse_ratio("abc"), None);
+    }
+
+    #[test]
+    fn completes_the_flags_of_runs_and_subcommands() {
+        for &shell in &[Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let mut completions = Vec::new();
+            app().gen_completions_to("rench", shell, &mut completions);
+            let completions = String::from_utf8(completions).unwrap();
+            assert!(completions.contains("max-error-rate"), "{} doesn't complete the flags", shell);
+            assert!(completions.contains("aggregate"), "{} doesn't complete the subcommands", shell);
+        }
+    }
 }
@@ -1,10 +1,27 @@
 /// Represents a message that can be sent along the channel from a runner
 /// to a collector. It needs to be returning via a join handle so the data
 /// is static.
+#[allow(clippy::upper_case_acronyms)]
 pub enum Message<T>
 where
     T: 'static + Send,
 {
     Body(T),
+    /// Many bodies at once, which a worker sends to spare the channel a send for each.
+    Batch(Vec<T>),
     EOF,
 }
+
+impl<T> Message<T>
+where
+    T: 'static + Send,
+{
+    /// How many bodies the message carries.
+    pub fn count(&self) -> usize {
+        match *self {
+            Message::Body(_) => 1,
+            Message::Batch(ref bodies) => bodies.len(),
+            Message::EOF => 0,
+        }
+    }
+}
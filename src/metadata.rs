@@ -0,0 +1,265 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Describes a run so that saved results make sense when they're read long after: what
+/// was run, against what, from where and when.
+pub struct Metadata {
+    pub version: String,
+    pub command: String,
+    pub urls: Vec<String>,
+    pub engine: String,
+    pub concurrency: usize,
+    pub requests: usize,
+    pub started: SystemTime,
+    /// The seed of the requests' randomness, which reproduces them when given again.
+    pub seed: u64,
+    pub host: Host,
+    /// A name for the run, such as the release it was made against.
+    pub label: Option<String>,
+    /// Key value pairs that are carried into the results, so they can be joined with other
+    /// data such as deployments.
+    pub tags: BTreeMap<String, String>,
+}
+
+/// The machine the run was made from.
+pub struct Host {
+    pub name: String,
+    pub os: String,
+    pub arch: String,
+    pub cpus: usize,
+}
+
+impl Host {
+    /// Describes the current machine.
+    pub fn current() -> Host {
+        let name = env::var("HOSTNAME")
+            .ok()
+            .or_else(|| fs::read_to_string("/etc/hostname").ok())
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "unknown".to_string());
+        Host {
+            name,
+            os: env::consts::OS.to_string(),
+            arch: env::consts::ARCH.to_string(),
+            cpus: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+}
+
+impl Metadata {
+    /// Describes a run that's starting now on this machine. Header values are left out of
+    /// the command line since they often hold secrets.
+    pub fn new(
+        args: &[String],
+        urls: Vec<String>,
+        engine: &str,
+        concurrency: usize,
+        requests: usize,
+        seed: u64,
+    ) -> Metadata {
+        Metadata {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            command: redact(args),
+            urls,
+            engine: engine.to_string(),
+            concurrency,
+            requests,
+            started: SystemTime::now(),
+            seed,
+            host: Host::current(),
+            label: None,
+            tags: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_label(mut self, label: &str) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    /// Tags the run with a value, replacing any value the key was already tagged with.
+    pub fn with_tag(mut self, key: &str, value: &str) -> Self {
+        self.tags.insert(key.to_string(), value.to_string());
+        self
+    }
+}
+
+/// Reads a tag given as `key=value`.
+pub fn parse_tag(tag: &str) -> Result<(String, String), String> {
+    match tag.find('=') {
+        Some(split) if split > 0 => Ok((tag[..split].to_string(), tag[split + 1..].to_string())),
+        _ => Err(format!("Expected a tag like 'key=value', got '{}'", tag)),
+    }
+}
+
+/// Joins the arguments into a command line with the values of headers and secrets hidden.
+fn redact(args: &[String]) -> String {
+    const SECRETS: &[&str] = &["--workers-token", "--oauth-client-secret"];
+    let mut redacted = Vec::with_capacity(args.len());
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if let Some(secret) = SECRETS.iter().find(|secret| arg.strip_prefix(*secret).is_some_and(|rest| rest.starts_with('='))) {
+            redacted.push(format!("{}=***", secret));
+            continue;
+        }
+        redacted.push(arg.clone());
+        let skip = match arg.as_str() {
+            "--header" => 0,
+            "--header-for" => 1,
+            secret if SECRETS.contains(&secret) => {
+                if args.next().is_some() {
+                    redacted.push("***".to_string());
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        redacted.extend(args.by_ref().take(skip).cloned());
+        if let Some(header) = args.next() {
+            redacted.push(match header.find([':', '=']) {
+                Some(split) if !header.starts_with('@') => format!("{}:***", &header[..split]),
+                _ => header.clone(),
+            });
+        }
+    }
+    redacted.join(" ")
+}
+
+/// Formats a time as an RFC 3339 timestamp in UTC.
+pub fn timestamp(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rest) = (seconds / 86_400, seconds % 86_400);
+    // Howard Hinnant's days to civil date algorithm.
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rest / 3_600,
+        rest % 3_600 / 60,
+        rest % 60
+    )
+}
+
+/// The most urls named in the banner. A run over a long list names the first of them and
+/// how many more there are.
+const NAMED_TARGETS: usize = 3;
+
+fn targets(urls: &[String]) -> String {
+    if urls.is_empty() {
+        return "the urls read from stdin".to_string();
+    }
+    if urls.len() <= NAMED_TARGETS {
+        return urls.join(", ");
+    }
+    format!("{} and {} more", urls[..NAMED_TARGETS].join(", "), urls.len() - NAMED_TARGETS)
+}
+
+impl fmt::Display for Metadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Run")?;
+        writeln!(f, "  Version:   rench {}", self.version)?;
+        writeln!(f, "  Command:   {}", self.command)?;
+        if let Some(ref label) = self.label {
+            writeln!(f, "  Label:     {}", label)?;
+        }
+        if !self.tags.is_empty() {
+            let tags: Vec<String> = self.tags.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+            writeln!(f, "  Tags:      {}", tags.join(", "))?;
+        }
+        writeln!(f, "  Targets:   {}", targets(&self.urls))?;
+        // A run without a planned number of requests replays them as it reads them.
+        if self.requests == 0 {
+            writeln!(
+                f,
+                "  Engine:    {} replaying requests from stdin, {} at a time",
+                self.engine, self.concurrency
+            )?;
+        } else {
+            writeln!(
+                f,
+                "  Engine:    {} making {} requests, {} at a time",
+                self.engine, self.requests, self.concurrency
+            )?;
+        }
+        writeln!(f, "  Started:   {}", timestamp(self.started))?;
+        writeln!(f, "  Seed:      {}", self.seed)?;
+        writeln!(
+            f,
+            "  Host:      {} ({} {}, {} cpus)",
+            self.host.name, self.host.os, self.host.arch, self.host.cpus
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn formats_timestamps_in_utc() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            timestamp(UNIX_EPOCH + Duration::from_secs(951_827_696)),
+            "2000-02-29T12:34:56Z"
+        );
+    }
+
+    #[test]
+    fn reads_tags_as_key_value_pairs() {
+        assert_eq!(parse_tag("env=prod"), Ok(("env".to_string(), "prod".to_string())));
+        assert_eq!(parse_tag("query=a=b"), Ok(("query".to_string(), "a=b".to_string())));
+        assert!(parse_tag("=prod").is_err());
+        assert!(parse_tag("env").is_err());
+    }
+
+    #[test]
+    fn names_only_the_first_of_many_targets() {
+        assert_eq!(targets(&args(&["http://a/", "http://b/"])), "http://a/, http://b/");
+        assert_eq!(
+            targets(&args(&["http://a/", "http://b/", "http://c/", "http://d/", "http://e/"])),
+            "http://a/, http://b/, http://c/ and 2 more"
+        );
+    }
+
+    #[test]
+    fn hides_header_values_in_the_command() {
+        assert_eq!(
+            redact(&args(&[
+                "rench",
+                "--header",
+                "authorization:Bearer secret",
+                "--header-for",
+                "http://a/",
+                "x-key=secret",
+                "--header",
+                "@headers.txt",
+                "http://a/",
+            ])),
+            "rench --header authorization:*** --header-for http://a/ x-key:*** --header @headers.txt http://a/"
+        );
+        assert_eq!(
+            redact(&args(&["rench", "--workers-token", "secret", "--oauth-client-secret=secret", "http://a/"])),
+            "rench --workers-token *** --oauth-client-secret=*** http://a/"
+        );
+    }
+}
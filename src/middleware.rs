@@ -0,0 +1,136 @@
+use stats::Fact;
+use std::borrow::Cow;
+
+/// Hooks into every request the http engines make, to change each request before it's
+/// sent, such as to sign it or give it a trace id, and to look at how it went once it's
+/// been measured. The workers share the middleware, so it's called from many threads at
+/// once. The time spent in `on_request` isn't counted as part of the request.
+///
+/// ```
+/// use rench::engine::Engine;
+/// use rench::middleware::{Middleware, Request};
+/// # fn sign(url: &str, body: Option<&[u8]>) -> String {
+/// #     format!("{:x}", url.len() + body.map_or(0, |body| body.len()))
+/// # }
+///
+/// struct Signed;
+///
+/// impl Middleware for Signed {
+///     fn on_request(&self, request: &mut Request) {
+///         let signature = sign(request.url(), request.body());
+///         request.set_header("x-signature", &signature);
+///     }
+/// }
+///
+/// let urls = vec!["http://localhost:8080/".to_string()];
+/// let engine = Engine::new(urls, vec![]).with_middleware(Signed);
+/// ```
+pub trait Middleware: Send + Sync {
+    /// Changes the request before it's sent. A request left with a url or headers that the
+    /// engine can't send isn't sent, and its fact is an error.
+    fn on_request(&self, _request: &mut Request) {}
+
+    /// Looks at the fact of the request once it's been measured, before it's collected.
+    fn on_response(&self, _request: &Request, _fact: &Fact) {}
+}
+
+/// A request that's about to be sent. Only its url, headers and body can be changed, and
+/// the requests of the redirects it's followed through are sent the same headers and body.
+#[derive(Debug)]
+pub struct Request<'a> {
+    url: Cow<'a, str>,
+    headers: Cow<'a, [(String, String)]>,
+    body: Option<Vec<u8>>,
+    trace: Option<(String, String)>,
+    changed: bool,
+}
+
+impl<'a> Request<'a> {
+    pub fn new<U>(url: U, headers: &'a [(String, String)], body: Option<Vec<u8>>) -> Request<'a>
+    where
+        U: Into<Cow<'a, str>>,
+    {
+        Request {
+            url: url.into(),
+            headers: Cow::Borrowed(headers),
+            body,
+            trace: None,
+            changed: false,
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    pub fn body(&self) -> Option<&[u8]> {
+        self.body.as_deref()
+    }
+
+    pub fn set_url(&mut self, url: String) {
+        self.url = Cow::Owned(url);
+        self.changed = true;
+    }
+
+    /// Replaces every header.
+    pub fn set_headers(&mut self, headers: Vec<(String, String)>) {
+        self.headers = Cow::Owned(headers);
+        self.changed = true;
+    }
+
+    /// Sets the header, replacing every header already set with the same name.
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        let name = name.to_ascii_lowercase();
+        let headers = self.headers.to_mut();
+        headers.retain(|(given, _)| *given != name);
+        headers.push((name, value.to_string()));
+        self.changed = true;
+    }
+
+    /// Sets the header and notes its value as the request's trace id, which is kept with
+    /// the request's fact.
+    pub fn set_trace_header(&mut self, name: &str, value: &str) {
+        self.set_header(name, value);
+        self.trace = Some((name.to_ascii_lowercase(), value.to_string()));
+    }
+
+    /// The name and value of the header that carries the request's trace id, if it has one.
+    pub fn trace_header(&self) -> Option<&(String, String)> {
+        self.trace.as_ref()
+    }
+
+    pub fn set_body(&mut self, body: Option<Vec<u8>>) {
+        self.body = body;
+        self.changed = true;
+    }
+
+    /// Whether any middleware changed the request, so that it has to be written again
+    /// rather than sent as it was written before the run.
+    pub fn is_changed(&self) -> bool {
+        self.changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_headers_with_the_same_name() {
+        let headers = vec![("x-key".to_string(), "1".to_string()), ("accept".to_string(), "*/*".to_string())];
+        let mut request = Request::new("http://a/", &headers, None);
+        assert!(!request.is_changed());
+
+        request.set_header("X-Key", "2");
+        assert!(request.is_changed());
+        assert_eq!(
+            request.headers(),
+            &[("accept".to_string(), "*/*".to_string()), ("x-key".to_string(), "2".to_string())][..]
+        );
+        assert_eq!(headers.len(), 2);
+    }
+}
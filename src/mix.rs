@@ -0,0 +1,80 @@
+use engine::Method;
+
+/// The methods of a run that mixes them, such as mostly gets with a few heads, each with its
+/// weight. Every worker spreads its requests over the methods in proportion to the weights.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mix {
+    methods: Vec<(Method, u32)>,
+    total: u32,
+}
+
+impl Mix {
+    /// Parses a mix given as methods and their weights. Example 'GET=90,HEAD=10'.
+    pub fn parse(value: &str) -> Result<Mix, String> {
+        let mut methods: Vec<(Method, u32)> = Vec::new();
+        for part in value.split(',') {
+            let mut pair = part.splitn(2, '=');
+            let name = pair.next().unwrap_or("").trim();
+            let method = match name.to_ascii_uppercase().as_str() {
+                "GET" => Method::Get,
+                "HEAD" => Method::Head,
+                "POST" => Method::Post,
+                _ => return Err(format!("Unknown method '{}' in the mix, expected GET, HEAD or POST", name)),
+            };
+            let weight = pair
+                .next()
+                .and_then(|weight| weight.trim().parse::<u32>().ok())
+                .ok_or_else(|| format!("Expected a weight for {} in the mix, such as '{}=10'", name, name))?;
+            if methods.iter().any(|&(other, _)| other == method) {
+                return Err(format!("{} is in the mix more than once", name));
+            }
+            if weight > 0 {
+                methods.push((method, weight));
+            }
+        }
+        let total = methods.iter().map(|&(_, weight)| weight).sum();
+        if total == 0 {
+            return Err("Expected a method with a weight above zero in the mix".to_string());
+        }
+        Ok(Mix { methods, total })
+    }
+
+    /// The method of a worker's nth request. The requests are placed along the weights by
+    /// the bit reversal of their number, so that any run of requests from the first one
+    /// mixes the methods in close to the right proportions rather than in blocks.
+    pub fn method(&self, n: usize) -> Method {
+        let point = (n as u64).reverse_bits() as f64 / 2f64.powi(64) * f64::from(self.total);
+        let mut cumulative = 0.;
+        for &(method, weight) in &self.methods {
+            cumulative += f64::from(weight);
+            if point < cumulative {
+                return method;
+            }
+        }
+        self.methods.last().expect("A mix always has a method").0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_methods_and_their_weights() {
+        let mix = Mix::parse("GET=90, head=10").unwrap();
+        assert_eq!(mix.methods, vec![(Method::Get, 90), (Method::Head, 10)]);
+        assert!(Mix::parse("GET").is_err());
+        assert!(Mix::parse("PUT=1").is_err());
+        assert!(Mix::parse("GET=1,GET=2").is_err());
+        assert!(Mix::parse("GET=0").is_err());
+    }
+
+    #[test]
+    fn spreads_the_requests_in_proportion_to_the_weights() {
+        let mix = Mix::parse("GET=75,HEAD=25").unwrap();
+        let heads = |requests: usize| (0..requests).filter(|&n| mix.method(n) == Method::Head).count();
+        assert_eq!(heads(4), 1);
+        assert_eq!(heads(8), 2);
+        assert_eq!(heads(1_024), 256);
+    }
+}
@@ -0,0 +1,180 @@
+use middleware::{Middleware, Request};
+use reqwest;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Refreshes a token once no more than this much of its life is left, or a tenth of its
+/// life when that's shorter, so that no request is sent with a token that expires on
+/// the way.
+const REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// How long to wait after a token couldn't be refreshed before trying again, so that a
+/// token server that's down isn't asked by every request.
+const RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// The client of an OAuth2 token server, which grants tokens for its id and secret.
+pub struct Credentials {
+    pub token_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub scope: Option<String>,
+}
+
+/// Sends every request with a bearer token from the client credentials grant. The token
+/// is fetched before the run and fetched again when it's near its expiry, so that long
+/// runs don't start failing once it expires. Every worker shares the token.
+pub struct TokenProvider {
+    credentials: Credentials,
+    client: reqwest::Client,
+    token: Mutex<Token>,
+}
+
+struct Token {
+    value: String,
+    /// When to fetch another token, or never if the server didn't say when it expires.
+    refresh_at: Option<Instant>,
+}
+
+#[derive(Deserialize)]
+struct Grant {
+    access_token: String,
+    expires_in: Option<u64>,
+}
+
+impl TokenProvider {
+    /// Fetches the first token, failing if the token server won't grant one.
+    pub fn fetch(credentials: Credentials) -> Result<TokenProvider, String> {
+        let client = reqwest::Client::new();
+        let token = request_token(&client, &credentials)?;
+        Ok(TokenProvider {
+            credentials,
+            client,
+            token: Mutex::new(token),
+        })
+    }
+
+    /// The token to send, fetching another first if it's near its expiry. A token that
+    /// can't be refreshed is kept until it can be, since the server may still take it.
+    fn token(&self) -> String {
+        let mut token = self.token.lock().expect("Expected the token not to be poisoned");
+        let now = Instant::now();
+        if token.refresh_at.is_some_and(|at| at <= now) {
+            match request_token(&self.client, &self.credentials) {
+                Ok(fresh) => *token = fresh,
+                Err(e) => {
+                    eprintln!("Failed to refresh the OAuth2 token: {}", e);
+                    token.refresh_at = Some(now + RETRY_AFTER);
+                }
+            }
+        }
+        token.value.clone()
+    }
+}
+
+impl Middleware for TokenProvider {
+    fn on_request(&self, request: &mut Request) {
+        request.set_header("authorization", &format!("Bearer {}", self.token()));
+    }
+}
+
+/// Asks the token server for a token with the client credentials grant, authenticating
+/// the client with basic auth.
+fn request_token(client: &reqwest::Client, credentials: &Credentials) -> Result<Token, String> {
+    let mut form = vec![("grant_type", "client_credentials")];
+    if let Some(ref scope) = credentials.scope {
+        form.push(("scope", scope));
+    }
+    let requested = Instant::now();
+    let mut response = client
+        .post(&credentials.token_url)
+        .basic_auth(&credentials.client_id, Some(&credentials.client_secret))
+        .form(&form)
+        .send()
+        .map_err(|e| format!("Could not reach the token url '{}': {}", credentials.token_url, e))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "The token url '{}' answered {}: {}",
+            credentials.token_url,
+            response.status(),
+            response.text().unwrap_or_default()
+        ));
+    }
+    let grant: Grant = response
+        .json()
+        .map_err(|e| format!("Expected a token from '{}': {}", credentials.token_url, e))?;
+    Ok(Token {
+        value: grant.access_token,
+        refresh_at: grant.expires_in.map(|expires_in| {
+            let life = Duration::from_secs(expires_in);
+            requested + life - REFRESH_MARGIN.min(life / 10)
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Answers each connection with the next of the grants, returning the first request.
+    fn token_server(grants: &'static [&'static str]) -> (String, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/token", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let mut first = None;
+            for grant in grants {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let (mut request, mut line, mut length) = (String::new(), String::new(), 0);
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    if line.to_lowercase().starts_with("content-length:") {
+                        length = line[15..].trim().parse().unwrap();
+                    }
+                    request.push_str(&line);
+                    line.clear();
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                request.push_str(&String::from_utf8(body).unwrap());
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    grant.len(),
+                    grant
+                );
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+                first = first.or(Some(request));
+            }
+            first.unwrap()
+        });
+        (url, server)
+    }
+
+    #[test]
+    fn refreshes_a_token_near_its_expiry() {
+        let (url, server) = token_server(&[
+            r#"{"access_token":"first","token_type":"bearer","expires_in":0}"#,
+            r#"{"access_token":"second","token_type":"bearer","expires_in":3600}"#,
+        ]);
+        let provider = TokenProvider::fetch(Credentials {
+            token_url: url,
+            client_id: "rench".to_string(),
+            client_secret: "secret".to_string(),
+            scope: Some("read".to_string()),
+        })
+        .unwrap();
+
+        let headers = Vec::new();
+        let mut request = Request::new("http://a/", &headers, None);
+        provider.on_request(&mut request);
+        assert_eq!(request.headers(), &[("authorization".to_string(), "Bearer second".to_string())][..]);
+        provider.on_request(&mut request);
+        assert_eq!(request.headers()[0].1, "Bearer second");
+
+        let first = server.join().unwrap();
+        assert!(first.starts_with("POST /token HTTP/1.1\r\n"));
+        assert!(first.contains(": Basic cmVuY2g6c2VjcmV0\r\n"));
+        assert!(first.ends_with("grant_type=client_credentials&scope=read"));
+    }
+}
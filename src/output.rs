@@ -0,0 +1,498 @@
+use collector::Overhead;
+use heatmap::Heatmap;
+use metadata::Metadata;
+use periodicity::Periodicity;
+use preconnect::Connections;
+use usage::Usage;
+use report;
+use schedule::Schedule;
+use size_latency::SizeLatency;
+use stats::{ChartSize, Fact, Summary};
+use status_chart::StatusChart;
+use std::fs::File;
+use std::io::{self, Write};
+use std::time::Duration;
+use warning::Warnings;
+
+/// A finished run, as it's given to every sink.
+pub struct Run<'a> {
+    pub metadata: &'a Metadata,
+    pub warnings: &'a Warnings,
+    pub seconds: f64,
+    /// How long the clients took to set up beforehand, when the run was local.
+    pub setup: Option<Duration>,
+    /// How much sending the facts to the collector held the workers up.
+    pub collector: Option<Overhead>,
+    /// The connections opened before the run, when it was local and preconnected.
+    pub preconnect: Option<Connections>,
+    /// The cpu and memory the client used, when the run was local and they were sampled.
+    pub client_usage: Option<Usage>,
+    pub summary: &'a Summary,
+    pub heatmap: Option<&'a Heatmap>,
+    pub status_chart: Option<&'a StatusChart>,
+    pub size_latency: Option<&'a SizeLatency>,
+    pub periodicity: Option<&'a Periodicity>,
+    pub schedule: Option<&'a Schedule>,
+    /// Every fact of the run, unless they were streamed into the summary.
+    pub facts: Option<&'a [Fact]>,
+}
+
+impl<'a> Run<'a> {
+    pub fn requests_per_second(&self) -> f64 {
+        if self.seconds > 0. {
+            f64::from(self.summary.count()) / self.seconds
+        } else {
+            0.
+        }
+    }
+
+    /// The versioned report of the run, with the charts that were asked for.
+    pub fn report(&self) -> report::Report {
+        let mut results = self.summary.results();
+        results.heatmap = self.heatmap.map(Heatmap::report);
+        results.size_latency = self.size_latency.map(SizeLatency::report);
+        results.periodicity = self.periodicity.and_then(Periodicity::report);
+        results.schedule = self.schedule.map(Schedule::report);
+        let mut report = report::Report::new(self.metadata, self.warnings, self.seconds, results);
+        report.setup_seconds = self.setup.map(|setup| setup.as_secs_f64());
+        report.collector = self.collector.map(|collector| collector.report());
+        report.preconnect = self.preconnect.map(|preconnect| preconnect.report());
+        report.client_usage = self.client_usage.map(|client_usage| client_usage.report());
+        report
+    }
+}
+
+/// Writes a finished run in some format. Reporters are given the run as a whole, so a new
+/// format only needs a new reporter rather than changes to how the run is summarized. A
+/// reporter of its own can read the run's `Summary` or its versioned `Report` rather than
+/// the text that rench prints.
+///
+/// ```
+/// use rench::output::{Reporter, Run};
+/// use std::io::{self, Write};
+///
+/// /// The request rate and the p99 latency, for a status line.
+/// struct StatusLine;
+///
+/// impl Reporter for StatusLine {
+///     fn report(&self, run: &Run, out: &mut dyn Write) -> io::Result<()> {
+///         let report = run.report();
+///         let p99 = report.results.quantile_ms(0.99).unwrap_or_default();
+///         writeln!(out, "{:.0} rps, p99 {:.2} ms", report.requests_per_second, p99)
+///     }
+/// }
+/// ```
+pub trait Reporter {
+    fn report(&self, run: &Run, out: &mut dyn Write) -> io::Result<()>;
+
+    /// Whether the reporter writes the facts themselves, so they can't be streamed.
+    fn needs_facts(&self) -> bool {
+        false
+    }
+}
+
+/// The text of the run laid out for a terminal, followed by its charts.
+pub struct Human;
+
+impl Reporter for Human {
+    fn report(&self, run: &Run, out: &mut dyn Write) -> io::Result<()> {
+        Text.report(run, out)?;
+        Charts.report(run, out)
+    }
+}
+
+/// The numbers of the run laid out for a terminal, without any charts.
+pub struct Text;
+
+impl Reporter for Text {
+    fn report(&self, run: &Run, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "{}", run.metadata)?;
+        if !run.warnings.is_empty() {
+            writeln!(out, "{}", run.warnings)?;
+        }
+        writeln!(out, "Took {} seconds", run.seconds)?;
+        if let Some(setup) = run.setup {
+            writeln!(out, "Setting up the clients took {} seconds beforehand", setup.as_secs_f64())?;
+        }
+        if let Some(preconnect) = run.preconnect {
+            writeln!(out, "{}", preconnect)?;
+        }
+        if let Some(collector) = run.collector {
+            writeln!(out, "{}", collector)?;
+        }
+        writeln!(out, "{} requests / second", run.requests_per_second())?;
+        writeln!(out)?;
+        write!(out, "{}", run.summary.text())?;
+        if let Some(size_latency) = run.size_latency {
+            writeln!(out)?;
+            write!(out, "{}", size_latency)?;
+        }
+        if let Some(schedule) = run.schedule {
+            writeln!(out)?;
+            write!(out, "{}", schedule)?;
+        }
+        if let Some(client_usage) = run.client_usage {
+            writeln!(out)?;
+            write!(out, "{}", client_usage)?;
+        }
+        Ok(())
+    }
+}
+
+/// The charts of the run that were asked for.
+pub struct Charts;
+
+impl Reporter for Charts {
+    fn report(&self, run: &Run, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "{}", run.summary.charts())?;
+        if let Some(heatmap) = run.heatmap {
+            writeln!(out, "{}", heatmap)?;
+        }
+        if let Some(status_chart) = run.status_chart {
+            writeln!(out, "{}", status_chart)?;
+        }
+        // Repeating spikes are only looked for in the large report.
+        if let (Some(periodicity), ChartSize::Large) = (run.periodicity, run.summary.chart_size()) {
+            write!(out, "{}", periodicity)?;
+        }
+        Ok(())
+    }
+}
+
+/// The run as Markdown tables, to paste into an issue or a pull request. It's written from
+/// the results of the JSON report, so its latencies are in milliseconds.
+pub struct Markdown;
+
+impl Reporter for Markdown {
+    fn report(&self, run: &Run, out: &mut dyn Write) -> io::Result<()> {
+        let results = run.summary.results();
+        let metadata = run.metadata;
+        match metadata.label {
+            Some(ref label) => writeln!(out, "## rench run: {}", label)?,
+            None => writeln!(out, "## rench run")?,
+        }
+        writeln!(out)?;
+        // Warnings come first so that they're read before the numbers they're about.
+        if !run.warnings.is_empty() {
+            for warning in run.warnings.iter() {
+                writeln!(out, "> **Warning:** {}", warning)?;
+            }
+            writeln!(out)?;
+        }
+        writeln!(out, "| | |")?;
+        writeln!(out, "|---|---|")?;
+        writeln!(out, "| Targets | {} |", metadata.urls.join(", "))?;
+        writeln!(out, "| Engine | {} |", metadata.engine)?;
+        writeln!(out, "| Concurrency | {} |", metadata.concurrency)?;
+        writeln!(out, "| Requests | {} |", results.count)?;
+        writeln!(out, "| Errors | {} |", results.errors)?;
+        writeln!(out, "| Failures | {} |", results.failures)?;
+        writeln!(out, "| Took | {:.2} s |", run.seconds)?;
+        writeln!(out, "| Rate | {:.2} requests / second |", run.requests_per_second())?;
+        writeln!(out)?;
+        writeln!(out, "| Latency | ms |")?;
+        writeln!(out, "|---|---:|")?;
+        let latency = &results.latency_ms;
+        writeln!(out, "| Average | {:.2} |", latency.average)?;
+        writeln!(out, "| Std dev | {:.2} |", latency.stddev)?;
+        writeln!(out, "| Shortest | {:.2} |", latency.min)?;
+        writeln!(out, "| Median | {:.2} |", latency.median)?;
+        for &percentile in &[90, 99] {
+            if let Some(ms) = results.percentiles_ms.get(percentile) {
+                writeln!(out, "| {}% | {:.2} |", percentile, ms)?;
+            }
+        }
+        writeln!(out, "| Longest | {:.2} |", latency.max)?;
+        writeln!(out)?;
+        writeln!(out, "| Status | Responses |")?;
+        writeln!(out, "|---|---:|")?;
+        for (status, count) in &results.status_codes {
+            writeln!(out, "| {} | {} |", status, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// The run as a page of its own, to open in a browser or attach to a ticket. Like the
+/// Markdown it's written from the results of the JSON report.
+pub struct Html;
+
+impl Reporter for Html {
+    fn report(&self, run: &Run, out: &mut dyn Write) -> io::Result<()> {
+        let results = run.summary.results();
+        let metadata = run.metadata;
+        let title = match metadata.label {
+            Some(ref label) => format!("rench run: {}", label),
+            None => "rench run".to_string(),
+        };
+        writeln!(out, "<!DOCTYPE html>")?;
+        writeln!(out, "<html>")?;
+        writeln!(out, "<head><meta charset=\"utf-8\"><title>{}</title></head>", escape(&title))?;
+        writeln!(out, "<body>")?;
+        writeln!(out, "<h1>{}</h1>", escape(&title))?;
+        // Warnings come first so that they're read before the numbers they're about.
+        if !run.warnings.is_empty() {
+            writeln!(out, "<ul class=\"warnings\" style=\"color: #b00; font-weight: bold\">")?;
+            for warning in run.warnings.iter() {
+                writeln!(out, "<li>Warning: {}</li>", escape(&warning.to_string()))?;
+            }
+            writeln!(out, "</ul>")?;
+        }
+        let row = |out: &mut dyn Write, name: &str, value: &str| writeln!(out, "<tr><th>{}</th><td>{}</td></tr>", name, escape(value));
+        writeln!(out, "<table>")?;
+        row(out, "Targets", &metadata.urls.join(", "))?;
+        row(out, "Engine", &metadata.engine)?;
+        row(out, "Concurrency", &metadata.concurrency.to_string())?;
+        row(out, "Requests", &results.count.to_string())?;
+        row(out, "Errors", &results.errors.to_string())?;
+        row(out, "Failures", &results.failures.to_string())?;
+        row(out, "Took", &format!("{:.2} s", run.seconds))?;
+        row(out, "Rate", &format!("{:.2} requests / second", run.requests_per_second()))?;
+        writeln!(out, "</table>")?;
+        writeln!(out, "<h2>Latency (ms)</h2>")?;
+        writeln!(out, "<table>")?;
+        let latency = &results.latency_ms;
+        row(out, "Average", &format!("{:.2}", latency.average))?;
+        row(out, "Std dev", &format!("{:.2}", latency.stddev))?;
+        row(out, "Shortest", &format!("{:.2}", latency.min))?;
+        row(out, "Median", &format!("{:.2}", latency.median))?;
+        for &percentile in &[90, 99] {
+            if let Some(ms) = results.percentiles_ms.get(percentile) {
+                row(out, &format!("{}%", percentile), &format!("{:.2}", ms))?;
+            }
+        }
+        row(out, "Longest", &format!("{:.2}", latency.max))?;
+        writeln!(out, "</table>")?;
+        writeln!(out, "<h2>Status codes</h2>")?;
+        writeln!(out, "<table>")?;
+        for (status, count) in &results.status_codes {
+            row(out, &status.to_string(), &count.to_string())?;
+        }
+        writeln!(out, "</table>")?;
+        writeln!(out, "</body>")?;
+        writeln!(out, "</html>")
+    }
+}
+
+/// Escapes the text for the body of an html element.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// The versioned report of the run.
+pub struct Json;
+
+impl Reporter for Json {
+    fn report(&self, run: &Run, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "{}", run.report().to_json())
+    }
+}
+
+/// A line for each fact of the run in the order they were collected, for loading into a
+/// spreadsheet or a notebook.
+pub struct Csv;
+
+impl Reporter for Csv {
+    fn report(&self, run: &Run, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "status,duration_ms,bytes,new_connection,lag_ms,started_ms,ended_ms")?;
+        for fact in run.facts.unwrap_or_default() {
+            let ms = |duration: Duration| format!("{:.3}", duration.as_secs_f64() * 1_000.);
+            let optional = |value: Option<String>| value.unwrap_or_default();
+            writeln!(
+                out,
+                "{},{},{},{},{},{},{}",
+                optional(match fact.status() {
+                    Some(status) => Some(status.to_string()),
+                    None if fact.is_error() => None,
+                    None => Some("ok".to_string()),
+                }),
+                ms(fact.duration()),
+                fact.content_length().bytes(),
+                optional(fact.new_connection().map(|new| new.to_string())),
+                optional(fact.lag().map(ms)),
+                optional(fact.started().map(ms)),
+                optional(fact.ended().map(ms))
+            )?;
+        }
+        Ok(())
+    }
+
+    fn needs_facts(&self) -> bool {
+        true
+    }
+}
+
+/// A reporter and where it writes to.
+pub struct Sink {
+    format: String,
+    reporter: Box<dyn Reporter>,
+    path: Option<String>,
+}
+
+impl Sink {
+    /// Parses a sink given as a format, to write to stdout, or as `FORMAT=FILE`. Example
+    /// 'json=results.json'.
+    pub fn parse(value: &str) -> Result<Sink, String> {
+        let (format, path) = match value.find('=') {
+            Some(at) => (&value[..at], Some(value[at + 1..].to_string())),
+            None => (value, None),
+        };
+        if path.as_ref().is_some_and(|path| path.is_empty()) {
+            return Err(format!("Expected a file to write the {} output to", format));
+        }
+        let reporter: Box<dyn Reporter> = match format {
+            "human" => Box::new(Human),
+            "text" => Box::new(Text),
+            "charts" => Box::new(Charts),
+            "markdown" => Box::new(Markdown),
+            "html" => Box::new(Html),
+            "json" => Box::new(Json),
+            "csv" => Box::new(Csv),
+            _ => {
+                return Err(format!(
+                    "Unknown output '{}', expected human, text, charts, markdown, html, json or csv, optionally followed by =FILE",
+                    format
+                ))
+            }
+        };
+        Ok(Sink {
+            format: format.to_string(),
+            reporter,
+            path,
+        })
+    }
+
+    /// Whether the sink writes to stdout rather than a file.
+    pub fn is_stdout(&self) -> bool {
+        self.path.is_none()
+    }
+
+    /// Whether the sink writes a machine readable format to stdout, so that everything else
+    /// has to be written to stderr.
+    pub fn takes_stdout(&self) -> bool {
+        self.is_stdout() && (self.format == "json" || self.format == "csv")
+    }
+
+    pub fn needs_facts(&self) -> bool {
+        self.reporter.needs_facts()
+    }
+
+    /// Writes the run to the sink, replacing the file if there is one.
+    pub fn write(&self, run: &Run) -> io::Result<()> {
+        match self.path {
+            Some(ref path) => {
+                let mut file = File::create(path)
+                    .map_err(|e| io::Error::new(e.kind(), format!("Could not create {}: {}", path, e)))?;
+                self.reporter.report(run, &mut file)
+            }
+            None => self.reporter.report(run, &mut io::stdout().lock()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use content_length::ContentLength;
+
+    #[test]
+    fn parses_a_format_and_where_to_write_it() {
+        let sink = Sink::parse("json=results.json").unwrap();
+        assert_eq!(sink.path, Some("results.json".to_string()));
+        assert!(Sink::parse("human").unwrap().is_stdout());
+        assert!(!Sink::parse("human").unwrap().takes_stdout());
+        assert!(Sink::parse("json").unwrap().takes_stdout());
+        assert!(Sink::parse("csv").unwrap().needs_facts());
+        assert!(Sink::parse("csv=").is_err());
+        assert!(Sink::parse("xml").is_err());
+    }
+
+    fn facts() -> Vec<Fact> {
+        vec![
+            Fact::record(ContentLength::new(10), 200, Duration::from_millis(5))
+                .with_new_connection(true)
+                .with_started(Duration::from_millis(20)),
+            Fact::error(Duration::from_micros(1_500)),
+        ]
+    }
+
+    fn written<R: Reporter>(reporter: R, facts: &[Fact]) -> String {
+        written_with(reporter, facts, &Warnings::new())
+    }
+
+    fn written_with<R: Reporter>(reporter: R, facts: &[Fact], warnings: &Warnings) -> String {
+        let metadata = Metadata::new(&["rench".to_string()], vec!["http://a/".to_string()], "hyper", 1, 2, 1);
+        let summary = Summary::from_facts(facts);
+        let run = Run {
+            metadata: &metadata,
+            warnings,
+            seconds: 1.,
+            setup: None,
+            collector: None,
+            preconnect: None,
+            client_usage: None,
+            summary: &summary,
+            heatmap: None,
+            status_chart: None,
+            size_latency: None,
+            periodicity: None,
+            schedule: None,
+            facts: Some(facts),
+        };
+        let mut out = Vec::new();
+        reporter.report(&run, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn writes_a_line_for_each_fact() {
+        assert_eq!(
+            written(Csv, &facts()),
+            "status,duration_ms,bytes,new_connection,lag_ms,started_ms,ended_ms\n200,5.000,10,true,,20.000,25.000\n,1.500,0,,,,\n"
+        );
+    }
+
+    #[test]
+    fn writes_markdown_tables() {
+        let markdown = written(Markdown, &facts());
+        assert!(markdown.starts_with("## rench run\n\n| | |\n|---|---|\n| Targets | http://a/ |\n"));
+        assert!(markdown.contains("| Rate | 2.00 requests / second |\n"));
+        assert!(markdown.contains("| Longest | 5.00 |\n"));
+        assert!(markdown.ends_with("| Status | Responses |\n|---|---:|\n| 200 | 1 |\n"));
+    }
+
+    #[test]
+    fn leads_with_the_warnings() {
+        let mut warnings = Warnings::new();
+        warnings.check_versions(&["HTTP/1.1", "HTTP/2"]);
+        let markdown = written_with(Markdown, &facts(), &warnings);
+        assert!(markdown.starts_with(
+            "## rench run\n\n> **Warning:** The responses came back over more than one http version: HTTP/1.1, HTTP/2\n\n| | |\n"
+        ));
+        let html = written_with(Html, &facts(), &warnings);
+        let warned = html.find("<li>Warning: The responses came back over more than one http version").unwrap();
+        assert!(warned < html.find("<table>").unwrap());
+    }
+
+    #[test]
+    fn writes_an_html_page() {
+        let html = written(Html, &facts());
+        assert!(html.starts_with("<!DOCTYPE html>\n"));
+        assert!(!html.contains("warnings"));
+        assert!(html.contains("<tr><th>Targets</th><td>http://a/</td></tr>\n"));
+        assert!(html.contains("<tr><th>Longest</th><td>5.00</td></tr>\n"));
+        assert!(html.ends_with("<tr><th>200</th><td>1</td></tr>\n</table>\n</body>\n</html>\n"));
+        assert_eq!(escape("<a & \"b\">"), "&lt;a &amp; &quot;b&quot;&gt;");
+    }
+
+    #[test]
+    fn splits_the_human_output_into_text_and_charts() {
+        let human = written(Human, &facts());
+        let text = written(Text, &facts());
+        let charts = written(Charts, &facts());
+        assert_eq!(human, format!("{}{}", text, charts));
+        assert!(text.contains("Status codes:"));
+        assert!(!text.contains("Latency Histogram"));
+        assert!(charts.contains("Latency Histogram"));
+    }
+}
@@ -0,0 +1,112 @@
+use random::Rng;
+use std::fmt::Write;
+use template::Template;
+
+/// Query parameters that are rendered for every request and added to its url, such as a
+/// random page to exercise pagination or a fresh value to get past caches. A value is
+/// either a single placeholder of the body templates without its braces, like
+/// `rand_int(1,100)` or `uuid`, or a template like `user-{{n}}`, or else taken as it is.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Params {
+    params: Vec<(String, Template)>,
+}
+
+impl Params {
+    /// Parses parameters like `page=rand_int(1,100)`.
+    pub fn parse<'a, I>(args: I) -> Result<Params, String>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let params = args
+            .into_iter()
+            .map(|arg| {
+                let split = arg
+                    .find('=')
+                    .ok_or_else(|| format!("Expected a parameter like 'name=value', got '{}'", arg))?;
+                let (name, value) = (&arg[..split], &arg[split + 1..]);
+                if name.is_empty() {
+                    return Err(format!("Expected a parameter name before the '=' in '{}'", arg));
+                }
+                Ok((name.to_string(), template(value)?))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Params { params })
+    }
+
+    /// The encoded query of the parameters for the nth request.
+    pub fn render(&self, n: usize, rng: &mut Rng) -> String {
+        let mut query = String::new();
+        for (name, value) in &self.params {
+            if !query.is_empty() {
+                query.push('&');
+            }
+            encode(&mut query, name);
+            query.push('=');
+            encode(&mut query, &value.render(n, rng));
+        }
+        query
+    }
+}
+
+/// A bare placeholder is rendered as one, but a value that's no placeholder, like `en`,
+/// is sent as it is. One that looks like a call is expected to be one, so that a typo in
+/// `rand_int(1,100)` isn't sent as it is.
+fn template(value: &str) -> Result<Template, String> {
+    if value.contains("{{") {
+        return Template::parse(value);
+    }
+    match Template::parse(&format!("{{{{{}}}}}", value)) {
+        Ok(template) => Ok(template),
+        Err(e) if value.contains('(') => Err(e),
+        Err(_) => Template::parse(value),
+    }
+}
+
+/// Percent-encodes everything but the unreserved characters of RFC 3986.
+pub fn encode(out: &mut String, value: &str) {
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => out.push(byte as char),
+            _ => write!(out, "%{:02X}", byte).expect("Writing to a string"),
+        }
+    }
+}
+
+/// Adds the query to the url, after any query it already has and before its fragment.
+pub fn append(url: &str, query: &str) -> String {
+    let (url, fragment) = match url.find('#') {
+        Some(at) => url.split_at(at),
+        None => (url, ""),
+    };
+    let separator = match url.find('?') {
+        Some(at) if at + 1 == url.len() || url.ends_with('&') => "",
+        Some(_) => "&",
+        None => "?",
+    };
+    format!("{}{}{}{}", url, separator, query, fragment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_the_parameters_of_each_request() {
+        let params = Params::parse(vec!["page=rand_int(3,3)", "n={{n}}", "q=a b&c", "id=uuid"]).unwrap();
+        let query = params.render(7, &mut Rng::new(0));
+        assert!(query.starts_with("page=3&n=7&q=a%20b%26c&id="));
+        assert_eq!(query.len(), "page=3&n=7&q=a%20b%26c&id=".len() + 36);
+
+        assert!(Params::parse(vec!["page=rand_int(1)"]).is_err());
+        assert!(Params::parse(vec!["page"]).is_err());
+        assert!(Params::parse(vec!["=1"]).is_err());
+    }
+
+    #[test]
+    fn appends_to_the_query_of_the_url() {
+        assert_eq!(append("http://a/", "x=1"), "http://a/?x=1");
+        assert_eq!(append("http://a/?y=2", "x=1"), "http://a/?y=2&x=1");
+        assert_eq!(append("http://a/?", "x=1"), "http://a/?x=1");
+        assert_eq!(append("http://a/#top", "x=1"), "http://a/?x=1#top");
+    }
+}
@@ -0,0 +1,75 @@
+use random::Rng;
+use std::sync::Arc;
+use template::Template;
+
+/// The bodies to send with each request. Templated bodies are either rendered ahead of
+/// time into a pool that requests cycle through, which keeps formatting out of the hot
+/// path, or rendered for every request when each body needs to be unique. A body that
+/// isn't a template, such as a form with files in it, is built once and sent as it is.
+#[derive(Clone, Debug)]
+pub enum Payloads {
+    Pool(Arc<Vec<String>>),
+    PerRequest(Template),
+    Fixed(Arc<Vec<u8>>),
+}
+
+impl Payloads {
+    /// Renders a pool of payloads up front. A template without placeholders only ever needs
+    /// a single payload.
+    pub fn pool(template: &Template, size: usize, rng: &mut Rng) -> Payloads {
+        let size = if template.is_static() { 1 } else { size.max(1) };
+        Payloads::Pool(Arc::new(
+            (0..size).map(|n| template.render(n, rng)).collect(),
+        ))
+    }
+
+    /// Renders a fresh payload for every request.
+    pub fn per_request(template: Template) -> Payloads {
+        Payloads::PerRequest(template)
+    }
+
+    /// Sends the same bytes with every request.
+    pub fn fixed(body: Vec<u8>) -> Payloads {
+        Payloads::Fixed(Arc::new(body))
+    }
+
+    /// The payload for the nth request.
+    pub fn get(&self, n: usize, rng: &mut Rng) -> Vec<u8> {
+        match *self {
+            Payloads::Pool(ref pool) => pool[n % pool.len()].clone().into_bytes(),
+            Payloads::PerRequest(ref template) => template.render(n, rng).into_bytes(),
+            Payloads::Fixed(ref body) => body.to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycles_through_the_pool() {
+        let template = Template::parse("{{n}}").unwrap();
+        let mut rng = Rng::new(0);
+        let payloads = Payloads::pool(&template, 3, &mut rng);
+        let bodies: Vec<Vec<u8>> = (0..5).map(|n| payloads.get(n, &mut rng)).collect();
+        assert_eq!(bodies, vec![b"0", b"1", b"2", b"0", b"1"]);
+    }
+
+    #[test]
+    fn static_templates_only_render_once() {
+        let template = Template::parse("hello").unwrap();
+        match Payloads::pool(&template, 100, &mut Rng::new(0)) {
+            Payloads::Pool(pool) => assert_eq!(pool.len(), 1),
+            _ => panic!("Expected a pool"),
+        }
+    }
+
+    #[test]
+    fn renders_per_request_when_unique() {
+        let template = Template::parse("{{n}}").unwrap();
+        let payloads = Payloads::per_request(template);
+        let mut rng = Rng::new(0);
+        assert_eq!(payloads.get(1000, &mut rng), b"1000");
+    }
+}
@@ -0,0 +1,174 @@
+use columns::Columns;
+use report;
+use stats::Fact;
+use std::fmt;
+use std::time::Duration;
+
+/// The most buckets that are kept, many more than the columns of a chart so that a short
+/// period still spans a few buckets late in a long run.
+const MAX_BUCKETS: usize = 1024;
+
+/// How much of the run each bucket covers to begin with.
+const FIRST_BUCKET_MS: u64 = 100;
+
+/// How many times a period has to repeat within the run to be reported.
+const MIN_REPEATS: usize = 3;
+
+/// How strongly the latency has to correlate with itself a period later to count as
+/// repeating.
+const MIN_AUTOCORRELATION: f64 = 0.3;
+
+/// Looks for latency spikes that repeat on a schedule, such as every 30 seconds, which
+/// usually come from a cron job or garbage collection on the server rather than from the
+/// load. The average latency of each bucket of the run is correlated with itself shifted
+/// by every lag, and the shortest lag that correlates about as well as the best one is the
+/// period, since every multiple of a period correlates too.
+pub struct Periodicity {
+    /// The total latency in milliseconds and the count of the requests that finished in
+    /// each bucket.
+    buckets: Columns<(f64, u64)>,
+}
+
+/// A period that the latency repeats on, with how strongly it repeats from 0 to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Period {
+    pub every: Duration,
+    pub autocorrelation: f64,
+}
+
+impl Default for Periodicity {
+    fn default() -> Periodicity {
+        Periodicity::new()
+    }
+}
+
+impl Periodicity {
+    /// Starts looking for a period from now.
+    pub fn new() -> Periodicity {
+        Periodicity {
+            buckets: Columns::new(Duration::from_millis(FIRST_BUCKET_MS), MAX_BUCKETS),
+        }
+    }
+
+    /// Counts a request that has just finished, at the time it ended if the engine timed
+    /// it and otherwise now.
+    pub fn record(&mut self, fact: &Fact) {
+        let ended = fact.ended().unwrap_or_else(|| self.buckets.elapsed());
+        self.record_at(ended, fact.duration());
+    }
+
+    fn record_at(&mut self, elapsed: Duration, duration: Duration) {
+        let bucket = self.buckets.at(elapsed);
+        bucket.0 += duration.as_secs_f64() * 1_000.;
+        bucket.1 += 1;
+    }
+
+    /// The period the latency repeats on, if it repeats often enough within the run.
+    pub fn period(&self) -> Option<Period> {
+        let buckets = self.buckets.columns();
+        let (total_ms, total) = buckets
+            .iter()
+            .fold((0., 0), |(ms, count), &(m, c)| (ms + m, count + c));
+        if total == 0 || buckets.len() < MIN_REPEATS * 2 {
+            return None;
+        }
+        // Buckets that no request finished in are taken to be average.
+        let mean = total_ms / total as f64;
+        let series: Vec<f64> = buckets
+            .iter()
+            .map(|&(ms, count)| if count == 0 { 0. } else { ms / count as f64 - mean })
+            .collect();
+        let variance: f64 = series.iter().map(|x| x * x).sum();
+        if variance <= 0. {
+            return None;
+        }
+        let autocorrelation =
+            |lag: usize| series.iter().zip(&series[lag..]).map(|(a, b)| a * b).sum::<f64>() / variance;
+        // A lag of one bucket only says that neighbouring buckets are alike.
+        let lags: Vec<(usize, f64)> = (2..=series.len() / MIN_REPEATS + 1)
+            .map(|lag| (lag, autocorrelation(lag)))
+            .collect();
+        let peaks: Vec<(usize, f64)> = lags
+            .iter()
+            .enumerate()
+            .filter(|&(i, &(_, r))| {
+                let before = if i == 0 { autocorrelation(1) } else { lags[i - 1].1 };
+                let after = lags.get(i + 1).map_or(f64::MIN, |&(_, r)| r);
+                r >= before && r >= after
+            })
+            .map(|(_, &peak)| peak)
+            .filter(|&(lag, _)| lag * MIN_REPEATS <= series.len())
+            .collect();
+        let best = peaks.iter().map(|&(_, r)| r).fold(f64::MIN, f64::max);
+        if best < MIN_AUTOCORRELATION {
+            return None;
+        }
+        peaks
+            .into_iter()
+            .find(|&(_, r)| r >= best * 0.9)
+            .map(|(lag, r)| Period {
+                every: self.buckets.width() * lag as u32,
+                autocorrelation: r,
+            })
+    }
+
+    /// The period in the shape of the JSON report, if there is one.
+    pub fn report(&self) -> Option<report::Periodicity> {
+        self.period().map(|period| report::Periodicity {
+            period_seconds: period.every.as_secs_f64(),
+            autocorrelation: period.autocorrelation,
+        })
+    }
+}
+
+impl fmt::Display for Periodicity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.period() {
+            Some(period) => writeln!(
+                f,
+                "Latency spikes repeat about every {:.2} s (autocorrelation {:.2}), which may line up with a cron job or garbage collection on the server",
+                period.every.as_secs_f64(),
+                period.autocorrelation
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use random::Rng;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn finds_spikes_that_repeat() {
+        let mut periodicity = Periodicity::new();
+        let mut rng = Rng::new(7);
+        // A request every 10ms for a minute, with a spike of half a second every 5s.
+        for n in 0..6_000 {
+            let at = ms(n * 10);
+            let spike = at.as_millis() % 5_000 < 500;
+            let latency = if spike { 40 + rng.range(0, 10) } else { 2 + rng.range(0, 2) };
+            periodicity.record_at(at, ms(latency as u64));
+        }
+        let period = periodicity.period().unwrap();
+        assert_eq!(period.every, ms(5_000));
+        assert!(period.autocorrelation > 0.5);
+        assert!(periodicity.to_string().starts_with("Latency spikes repeat about every 5.00 s"));
+    }
+
+    #[test]
+    fn finds_nothing_in_noise() {
+        let mut periodicity = Periodicity::new();
+        let mut rng = Rng::new(7);
+        for n in 0..6_000 {
+            periodicity.record_at(ms(n * 10), ms(rng.range(1, 20) as u64));
+        }
+        assert_eq!(periodicity.period(), None);
+        assert_eq!(periodicity.to_string(), "");
+    }
+}
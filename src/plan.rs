@@ -1,53 +1,138 @@
+use std::time::Duration;
+
 #[derive(Clone, Copy)]
 pub struct Plan {
     threads: usize,
     requests: usize,
+    concurrency: usize,
+    rate: Option<f64>,
 }
 
 impl Plan {
-    pub fn new(threads: usize, requests: usize) -> Self {
-        Self { threads, requests }
+    /// Plans the requests over the threads, each making one request at a time. A plan of
+    /// fewer requests than threads runs on only as many threads as there are requests,
+    /// since the rest would have none to make. No requests means they aren't planned, and
+    /// every thread makes as many as it's given.
+    pub fn new(threads: usize, requests: usize) -> Result<Self, String> {
+        if threads == 0 {
+            return Err("Expected at least one thread to make the requests on, got 0".to_string());
+        }
+        let threads = match requests {
+            0 => threads,
+            requests => threads.min(requests),
+        };
+        Ok(Self {
+            threads,
+            requests,
+            concurrency: threads,
+            rate: None,
+        })
+    }
+
+    /// Keeps `concurrency` requests in flight in all, shared out between the threads.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Paces the requests so that `rate` of them start each second over all of the threads.
+    pub fn with_rate(mut self, rate: f64) -> Self {
+        self.rate = Some(rate);
+        self
+    }
+
+    /// How many requests start each second over all of the threads, if they're paced.
+    pub fn rate(&self) -> Option<f64> {
+        self.rate
+    }
+
+    /// How long each thread leaves between starting its requests so that together they keep
+    /// to the rate, if they're paced.
+    pub fn interval(&self) -> Option<Duration> {
+        self.rate
+            .map(|rate| Duration::from_secs_f64(self.threads as f64 / rate))
     }
 
     pub fn threads(&self) -> usize {
         self.threads
     }
 
+    /// How many requests are in flight at once over all of the threads.
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    /// How many requests each thread keeps in flight, shared out as evenly as the
+    /// requests are.
+    pub fn in_flight(&self) -> Vec<usize> {
+        share(self.concurrency, self.threads)
+    }
+
     pub fn requests(&self) -> usize {
         self.requests
     }
 
     pub fn distribute(&self) -> Vec<usize> {
-        // Every thread should get even work:
-        let base_work = self.requests / self.threads;
-        let remaining_work = self.requests % self.threads;
-
-        (0..self.threads)
-            .map(|thread| {
-                // The remainder means that we don't have enough for
-                // every thread to get 1. So we just add one until
-                // we've used up the entire remainder
-                if thread < remaining_work {
-                    base_work + 1
-                } else {
-                    base_work
-                }
-            })
-            .collect()
+        share(self.requests, self.threads)
     }
 }
 
+/// Shares the work out between the parts as evenly as it goes, the first parts taking one
+/// more each until the remainder is used up.
+pub fn share(work: usize, parts: usize) -> Vec<usize> {
+    if parts == 0 {
+        return Vec::new();
+    }
+    // Every part should get even work:
+    let base_work = work / parts;
+    let remaining_work = work % parts;
+
+    (0..parts)
+        .map(|part| {
+            // The remainder means that we don't have enough for
+            // every part to get 1. So we just add one until
+            // we've used up the entire remainder
+            if part < remaining_work {
+                base_work + 1
+            } else {
+                base_work
+            }
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn it_can_distribute_all_work_as_evenly_as_possible() {
-        assert_eq!(Plan::new(3, 1000).distribute(), vec![334, 333, 333]);
-        assert_eq!(Plan::new(2, 1000).distribute(), vec![500, 500]);
+        assert_eq!(Plan::new(3, 1000).unwrap().distribute(), vec![334, 333, 333]);
+        assert_eq!(Plan::new(2, 1000).unwrap().distribute(), vec![500, 500]);
         assert_eq!(
-            Plan::new(20, 39).distribute(),
+            Plan::new(20, 39).unwrap().distribute(),
             vec![2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1]
         );
+        assert_eq!(share(5, 0), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn runs_on_no_more_threads_than_there_are_requests() {
+        assert!(Plan::new(0, 1000).is_err());
+        let plan = Plan::new(8, 3).unwrap();
+        assert_eq!((plan.threads(), plan.distribute()), (3, vec![1, 1, 1]));
+        assert_eq!(Plan::new(8, 0).unwrap().threads(), 8);
+    }
+
+    #[test]
+    fn shares_the_concurrency_between_the_threads() {
+        assert_eq!(Plan::new(3, 1000).unwrap().in_flight(), vec![1, 1, 1]);
+        assert_eq!(Plan::new(3, 1000).unwrap().with_concurrency(1000).in_flight(), vec![334, 333, 333]);
+    }
+
+    #[test]
+    fn shares_the_rate_between_the_threads() {
+        assert_eq!(Plan::new(4, 1000).unwrap().interval(), None);
+        assert_eq!(Plan::new(4, 1000).unwrap().with_rate(100.).interval(), Some(Duration::from_millis(40)));
     }
 }
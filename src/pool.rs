@@ -0,0 +1,117 @@
+use hyper::Uri;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// A limit on how many requests are in flight to each host at once. Every worker has its
+/// own client, so the limit is shared between them the way a single pool of connections
+/// would be, and the time spent waiting for a slot is what that pool's exhaustion costs.
+#[derive(Clone)]
+pub struct Limit {
+    max: usize,
+    /// The host of each url, as an index into the counts.
+    hosts: Arc<Vec<usize>>,
+    in_flight: Arc<(Mutex<Vec<usize>>, Condvar)>,
+}
+
+/// A slot taken from a limit, given back when dropped.
+pub struct Slot {
+    limit: Limit,
+    host: usize,
+    wait: Duration,
+}
+
+impl Limit {
+    /// Limits the requests to each of the urls' hosts. Urls that can't be parsed are
+    /// treated as their own host.
+    pub fn new(max: usize, urls: &[String]) -> Limit {
+        let mut names: Vec<String> = Vec::new();
+        let hosts = urls
+            .iter()
+            .map(|url| {
+                let name = url
+                    .parse::<Uri>()
+                    .ok()
+                    .and_then(|uri| uri.authority().map(|a| a.to_string()))
+                    .unwrap_or_else(|| url.clone());
+                match names.iter().position(|n| *n == name) {
+                    Some(host) => host,
+                    None => {
+                        names.push(name);
+                        names.len() - 1
+                    }
+                }
+            })
+            .collect();
+        Limit {
+            max: max.max(1),
+            hosts: Arc::new(hosts),
+            in_flight: Arc::new((Mutex::new(vec![0; names.len()]), Condvar::new())),
+        }
+    }
+
+    /// Waits for a free slot to the host of the url at the index.
+    pub fn acquire(&self, url: usize) -> Slot {
+        let host = self.hosts[url];
+        let start = Instant::now();
+        let (ref counts, ref freed) = *self.in_flight;
+        let mut counts = counts.lock().expect("Pool lock poisoned");
+        let mut blocked = false;
+        while counts[host] >= self.max {
+            blocked = true;
+            counts = freed.wait(counts).expect("Pool lock poisoned");
+        }
+        counts[host] += 1;
+        Slot {
+            limit: self.clone(),
+            host,
+            wait: if blocked { start.elapsed() } else { Duration::new(0, 0) },
+        }
+    }
+}
+
+impl Slot {
+    /// How long it took to get the slot.
+    pub fn wait(&self) -> Duration {
+        self.wait
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        let (ref counts, ref freed) = *self.limit.in_flight;
+        if let Ok(mut counts) = counts.lock() {
+            counts[self.host] -= 1;
+        }
+        freed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn urls(urls: &[&str]) -> Vec<String> {
+        urls.iter().map(|url| url.to_string()).collect()
+    }
+
+    #[test]
+    fn shares_slots_between_urls_to_the_same_host() {
+        let limit = Limit::new(1, &urls(&["http://a/x", "http://a/y", "http://b/"]));
+        assert_eq!(*limit.hosts, vec![0, 0, 1]);
+    }
+
+    #[test]
+    fn waits_for_a_slot_to_be_given_back() {
+        let limit = Limit::new(1, &urls(&["http://a/"]));
+        let slot = limit.acquire(0);
+        let waiting = {
+            let limit = limit.clone();
+            thread::spawn(move || limit.acquire(0).wait())
+        };
+        thread::sleep(Duration::from_millis(20));
+        drop(slot);
+        assert!(waiting.join().unwrap() >= Duration::from_millis(20));
+        assert_eq!(limit.acquire(0).wait(), Duration::new(0, 0));
+    }
+}
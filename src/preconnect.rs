@@ -0,0 +1,118 @@
+use report;
+use stats::ToMilliseconds;
+use std::fmt;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The connections the workers opened before the run, so that the handshakes of the first
+/// requests aren't part of their latency. The workers share it, and count each connection
+/// once it has been opened and has answered, or has failed to.
+#[derive(Clone, Debug, Default)]
+pub struct Preconnect {
+    connections: Arc<Mutex<Connections>>,
+}
+
+impl Preconnect {
+    pub fn new() -> Preconnect {
+        Preconnect::default()
+    }
+
+    /// Counts a connection that took so long to open, or failed to.
+    pub fn record(&self, opened: bool, took: Duration) {
+        let mut connections = self.connections.lock().expect("Expected the preconnected connections to be lockable");
+        if opened {
+            connections.opened += 1;
+            connections.total += took;
+            connections.longest = connections.longest.max(took);
+        } else {
+            connections.failed += 1;
+        }
+    }
+
+    /// The connections that have been opened since they were last taken, so that each run
+    /// of an interactive session counts its own.
+    pub fn take(&self) -> Connections {
+        let mut connections = self.connections.lock().expect("Expected the preconnected connections to be lockable");
+        mem::take(&mut *connections)
+    }
+}
+
+/// How many connections were opened before the run, how long they took and how many
+/// failed to open.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Connections {
+    pub opened: u64,
+    pub failed: u64,
+    total: Duration,
+    longest: Duration,
+}
+
+impl Connections {
+    pub fn average(&self) -> Duration {
+        if self.opened == 0 {
+            Duration::new(0, 0)
+        } else {
+            self.total / self.opened as u32
+        }
+    }
+
+    /// The connections in the shape of the JSON report.
+    pub fn report(&self) -> report::Preconnect {
+        report::Preconnect {
+            opened: self.opened,
+            failed: self.failed,
+            average_ms: self.average().to_ms(),
+            longest_ms: self.longest.to_ms(),
+        }
+    }
+}
+
+impl fmt::Display for Connections {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Opening {} connections took {:.3}ms on average and {:.3}ms at the longest beforehand",
+            self.opened,
+            self.average().to_ms(),
+            self.longest.to_ms()
+        )?;
+        if self.failed > 0 {
+            write!(f, "\n{} connections failed to open beforehand", self.failed)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_the_connections_of_every_worker() {
+        let preconnect = Preconnect::new();
+        let worker = preconnect.clone();
+        worker.record(true, Duration::from_millis(10));
+        worker.record(true, Duration::from_millis(30));
+        preconnect.record(false, Duration::from_millis(1_000));
+
+        let connections = preconnect.take();
+        assert_eq!((connections.opened, connections.failed), (2, 1));
+        assert_eq!(preconnect.take(), Connections::default());
+        assert_eq!(connections.average(), Duration::from_millis(20));
+        assert_eq!(
+            connections.report(),
+            report::Preconnect {
+                opened: 2,
+                failed: 1,
+                average_ms: 20.,
+                longest_ms: 30.,
+            }
+        );
+        assert_eq!(
+            connections.to_string(),
+            "Opening 2 connections took 20.000ms on average and 30.000ms at the longest beforehand\n\
+             1 connections failed to open beforehand"
+        );
+    }
+}
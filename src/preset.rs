@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use toml::Value;
+
+/// Named sets of command line arguments saved in the user's config directory so that a
+/// run can be repeated with `--preset NAME` instead of a long one-liner. The file is toml
+/// with a table per preset:
+///
+/// ```toml
+/// [api-smoke]
+/// args = ["-c", "4", "-n", "1000", "--max-error-rate", "1%", "https://api.example.com/"]
+/// ```
+#[derive(Debug, Default, PartialEq)]
+pub struct Presets {
+    presets: BTreeMap<String, Vec<String>>,
+}
+
+/// Where presets are stored, `$XDG_CONFIG_HOME/rench/presets.toml` falling back to
+/// `~/.config/rench/presets.toml`.
+pub fn default_path() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("rench").join("presets.toml"))
+}
+
+impl Presets {
+    /// Reads the presets from a file. A missing file has no presets.
+    pub fn load(path: &Path) -> Result<Presets, String> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Presets::parse(&contents)
+                .map_err(|e| format!("Invalid presets in '{}': {}", path.display(), e)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(Presets::default()),
+            Err(e) => Err(format!("Could not read presets from '{}': {}", path.display(), e)),
+        }
+    }
+
+    /// Writes the presets to a file, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Could not create '{}': {}", dir.display(), e))?;
+        }
+        fs::write(path, self.to_string())
+            .map_err(|e| format!("Could not write presets to '{}': {}", path.display(), e))
+    }
+
+    fn parse(contents: &str) -> Result<Presets, String> {
+        let value = contents.parse::<Value>().map_err(|e| e.to_string())?;
+        let table = value.as_table().ok_or("Expected a table of presets")?;
+        let mut presets = BTreeMap::new();
+        for (name, preset) in table {
+            let args = preset
+                .get("args")
+                .and_then(|args| args.as_array())
+                .and_then(|args| args.iter().map(|arg| arg.as_str()).collect::<Option<Vec<_>>>())
+                .ok_or_else(|| format!("Expected '{}' to have a list of string args", name))?;
+            presets.insert(
+                name.to_string(),
+                args.into_iter().map(|arg| arg.to_string()).collect(),
+            );
+        }
+        Ok(Presets { presets })
+    }
+
+    /// The arguments saved under a name.
+    pub fn get(&self, name: &str) -> Option<&[String]> {
+        self.presets.get(name).map(|args| args.as_slice())
+    }
+
+    /// Saves the arguments under a name, replacing any preset already there.
+    pub fn insert(&mut self, name: &str, args: Vec<String>) {
+        self.presets.insert(name.to_string(), args);
+    }
+
+    /// The names of every preset in order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.presets.keys().map(|name| name.as_str())
+    }
+}
+
+impl fmt::Display for Presets {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut table = BTreeMap::new();
+        for (name, args) in &self.presets {
+            let mut preset = BTreeMap::new();
+            preset.insert(
+                "args".to_string(),
+                Value::Array(args.iter().map(|arg| Value::String(arg.clone())).collect()),
+            );
+            table.insert(name.clone(), Value::Table(preset));
+        }
+        write!(f, "{}", Value::Table(table))
+    }
+}
+
+/// Replaces every `--preset NAME` (or `--preset=NAME`) in the arguments with the arguments
+/// saved under that name. Presets are only loaded when one is asked for.
+pub fn expand<F>(args: Vec<String>, load: F) -> Result<Vec<String>, String>
+where
+    F: FnOnce() -> Result<Presets, String>,
+{
+    if !args.iter().any(|arg| arg == "--preset" || arg.starts_with("--preset=")) {
+        return Ok(args);
+    }
+    let presets = load()?;
+    let mut expanded = Vec::with_capacity(args.len());
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let name = if arg == "--preset" {
+            args.next().ok_or("Expected a name after --preset")?
+        } else if let Some(name) = arg.strip_prefix("--preset=") {
+            name.to_string()
+        } else {
+            expanded.push(arg);
+            continue;
+        };
+        let preset = presets
+            .get(&name)
+            .ok_or_else(|| format!("There is no preset named '{}'", name))?;
+        expanded.extend(preset.iter().cloned());
+    }
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn smoke() -> Presets {
+        let mut presets = Presets::default();
+        presets.insert("smoke", args(&["-c", "4", "http://localhost/"]));
+        presets
+    }
+
+    #[test]
+    fn expands_presets_in_place() {
+        assert_eq!(
+            expand(args(&["rench", "--preset", "smoke", "-n", "10"]), || Ok(smoke())),
+            Ok(args(&["rench", "-c", "4", "http://localhost/", "-n", "10"]))
+        );
+        assert_eq!(
+            expand(args(&["rench", "--preset=smoke"]), || Ok(smoke())),
+            Ok(args(&["rench", "-c", "4", "http://localhost/"]))
+        );
+        assert!(expand(args(&["rench", "--preset", "nope"]), || Ok(smoke())).is_err());
+        assert!(expand(args(&["rench", "--preset"]), || Ok(smoke())).is_err());
+    }
+
+    #[test]
+    fn only_loads_presets_when_asked() {
+        assert_eq!(
+            expand(args(&["rench", "http://localhost/"]), || Err("loaded".to_string())),
+            Ok(args(&["rench", "http://localhost/"]))
+        );
+    }
+
+    #[test]
+    fn round_trips_through_toml() {
+        let presets = smoke();
+        assert_eq!(Presets::parse(&presets.to_string()), Ok(presets));
+        assert!(Presets::parse("[smoke]\nargs = [1]").is_err());
+        assert!(Presets::parse("[smoke]").is_err());
+    }
+
+    #[test]
+    fn saves_and_loads_files() {
+        let path = env::temp_dir().join("rench-preset-test").join("presets.toml");
+        smoke().save(&path).unwrap();
+        let loaded = Presets::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, smoke());
+        assert_eq!(Presets::load(&path), Ok(Presets::default()));
+    }
+}
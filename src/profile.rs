@@ -0,0 +1,124 @@
+/// A named set of headers that models one population of clients, such as mobile apps or
+/// crawlers. Each worker is assigned a single profile for the whole run, and profiles are
+/// assigned in proportion to their weights.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+    name: String,
+    weight: u32,
+    headers: Vec<(String, String)>,
+}
+
+impl Profile {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+}
+
+/// The profiles for a run, in the order they were first given.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profiles {
+    profiles: Vec<Profile>,
+}
+
+impl Profiles {
+    pub fn new() -> Profiles {
+        Profiles::default()
+    }
+
+    /// Adds headers to the named profile, creating it with a weight of 1 if it's new.
+    pub fn add_headers(&mut self, name: &str, headers: Vec<(String, String)>) {
+        self.profile(name).headers.extend(headers);
+    }
+
+    /// Sets the weight of the named profile.
+    pub fn set_weight(&mut self, name: &str, weight: u32) -> Result<(), String> {
+        if weight == 0 {
+            return Err(format!("The weight of the profile '{}' must be at least 1", name));
+        }
+        self.profile(name).weight = weight;
+        Ok(())
+    }
+
+    fn profile(&mut self, name: &str) -> &mut Profile {
+        match self.profiles.iter().position(|p| p.name == name) {
+            Some(index) => &mut self.profiles[index],
+            None => {
+                self.profiles.push(Profile {
+                    name: name.to_string(),
+                    weight: 1,
+                    headers: Vec::new(),
+                });
+                self.profiles.last_mut().expect("Just pushed")
+            }
+        }
+    }
+
+    /// The profile of one of the workers. Workers are spread over the profiles in
+    /// proportion to the weights, in order, so the split is the same on every run.
+    pub fn assign(&self, worker: usize, workers: usize) -> Option<&Profile> {
+        let total: u32 = self.profiles.iter().map(|p| p.weight).sum();
+        if total == 0 {
+            return None;
+        }
+        // Place the worker in the middle of its slice of the weights.
+        let point = (worker as f64 + 0.5) / workers as f64 * f64::from(total);
+        let mut cumulative = 0.;
+        for profile in &self.profiles {
+            cumulative += f64::from(profile.weight);
+            if point < cumulative {
+                return Some(profile);
+            }
+        }
+        self.profiles.last()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(name: &str, value: &str) -> (String, String) {
+        (name.to_string(), value.to_string())
+    }
+
+    fn assigned(profiles: &Profiles, workers: usize) -> Vec<&str> {
+        (0..workers)
+            .map(|worker| profiles.assign(worker, workers).unwrap().name())
+            .collect()
+    }
+
+    #[test]
+    fn assigns_workers_in_proportion_to_weights() {
+        let mut profiles = Profiles::new();
+        profiles.add_headers("mobile", vec![header("user-agent", "phone")]);
+        profiles.add_headers("desktop", vec![header("user-agent", "browser")]);
+        profiles.set_weight("mobile", 3).unwrap();
+        assert_eq!(
+            assigned(&profiles, 8),
+            vec!["mobile", "mobile", "mobile", "mobile", "mobile", "mobile", "desktop", "desktop"]
+        );
+        assert_eq!(assigned(&profiles, 1), vec!["mobile"]);
+    }
+
+    #[test]
+    fn collects_headers_by_name() {
+        let mut profiles = Profiles::new();
+        profiles.add_headers("bot", vec![header("user-agent", "crawler")]);
+        profiles.add_headers("bot", vec![header("accept", "*/*")]);
+        let bot = profiles.assign(0, 1).unwrap();
+        assert_eq!(
+            bot.headers(),
+            &[header("user-agent", "crawler"), header("accept", "*/*")]
+        );
+    }
+
+    #[test]
+    fn rejects_zero_weights() {
+        assert!(Profiles::new().set_weight("bot", 0).is_err());
+        assert_eq!(Profiles::new().assign(0, 1), None);
+    }
+}
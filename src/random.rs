@@ -0,0 +1,74 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A small xorshift* random number generator. It isn't cryptographically secure, but it is
+/// fast and good enough for generating payloads and picking values in the hot path.
+#[derive(Clone, Debug)]
+pub struct Rng(u64);
+
+impl Rng {
+    /// Creates a generator from a seed. The same seed always yields the same sequence.
+    pub fn new(seed: u64) -> Rng {
+        // Scramble the seed so that similar seeds don't produce similar sequences, and
+        // make sure the state is never zero which would only ever produce zeros.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        Rng(if z == 0 { 0x9E37_79B9_7F4A_7C15 } else { z })
+    }
+
+    /// Creates a generator seeded from the current time.
+    pub fn from_time() -> Rng {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Time to be after the epoch");
+        Rng::new(now.as_secs() ^ (u64::from(now.subsec_nanos()) << 32))
+    }
+
+    /// The next random number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A random number between low and high, inclusive of both.
+    pub fn range(&mut self, low: i64, high: i64) -> i64 {
+        // The span of the widest bounds only fits in a u64, so it's worked out wrapping.
+        let span = (high.wrapping_sub(low) as u64).wrapping_add(1);
+        if span == 0 {
+            // The full range of an i64, every value is fair game.
+            return self.next_u64() as i64;
+        }
+        low.wrapping_add((self.next_u64() % span) as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_same_seed_yields_the_same_sequence() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        for _ in 0..10 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+        assert_ne!(Rng::new(1).next_u64(), Rng::new(2).next_u64());
+    }
+
+    #[test]
+    fn ranges_are_inclusive() {
+        let mut rng = Rng::new(7);
+        let values: Vec<i64> = (0..1000).map(|_| rng.range(1, 3)).collect();
+        assert!(values.iter().all(|v| *v >= 1 && *v <= 3));
+        assert!(values.contains(&1));
+        assert!(values.contains(&3));
+
+        let (low, high) = (-9_000_000_000_000_000_000, 9_000_000_000_000_000_000);
+        assert!((0..1000).map(|_| rng.range(low, high)).all(|v| v >= low && v <= high));
+        assert!((0..1000).map(|_| rng.range(i64::MIN, i64::MAX)).any(|v| v < 0));
+    }
+}
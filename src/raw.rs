@@ -0,0 +1,446 @@
+use bind::{self, Binds, Family};
+use content_length;
+use engine::Version;
+use hyper::Uri;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+/// Where a request is sent, parsed once from its url.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Target {
+    addr: String,
+    host: String,
+    path: String,
+    tls: bool,
+}
+
+impl Target {
+    /// Parses a plain http url. The raw engine has no tls, so https urls are rejected.
+    pub fn parse(url: &str) -> Result<Target, String> {
+        let target = Target::parse_any(url)?;
+        if target.tls {
+            return Err(format!("The raw engine only supports http urls, got '{}'", url));
+        }
+        Ok(target)
+    }
+
+    /// Parses an http or an https url.
+    pub fn parse_any(url: &str) -> Result<Target, String> {
+        let uri: Uri = url
+            .parse()
+            .map_err(|e| format!("Invalid url '{}': {}", url, e))?;
+        let (tls, default_port) = match uri.scheme() {
+            Some("http") => (false, 80),
+            Some("https") => (true, 443),
+            _ => return Err(format!("Expected an http or https url, got '{}'", url)),
+        };
+        let host = uri
+            .host()
+            .ok_or_else(|| format!("Expected a host in '{}'", url))?;
+        let port = uri.port().unwrap_or(default_port);
+        Ok(Target {
+            addr: format!("{}:{}", host, port),
+            host: if port == default_port {
+                host.to_string()
+            } else {
+                format!("{}:{}", host, port)
+            },
+            tls,
+            path: match uri.query() {
+                Some(query) => format!("{}?{}", uri.path(), query),
+                None => uri.path().to_string(),
+            },
+        })
+    }
+
+    /// The host and port that the target's connections are opened to.
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    /// The host of the target without its port, which is what tls verifies.
+    pub fn domain(&self) -> &str {
+        match self.addr.rfind(':') {
+            Some(port) => &self.addr[..port],
+            None => &self.addr,
+        }
+    }
+
+    /// Whether the target is an https url.
+    pub fn is_tls(&self) -> bool {
+        self.tls
+    }
+}
+
+/// Writes the bytes of a request. Requests without a body are the same every time, so they
+/// can be built once and reused.
+pub fn request(method: &str, target: &Target, headers: &[(String, String)], body: Option<&[u8]>) -> Vec<u8> {
+    let mut request = format!("{} {} HTTP/1.1\r\n", method, target.path);
+    if !headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("host")) {
+        request.push_str(&format!("host: {}\r\n", target.host));
+    }
+    // The length of a body is always the one it's sent with.
+    let headers = headers
+        .iter()
+        .filter(|(name, _)| body.is_none() || !name.eq_ignore_ascii_case("content-length"));
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    match body {
+        Some(body) => {
+            request.push_str(&format!("content-length: {}\r\n\r\n", body.len()));
+            let mut request = request.into_bytes();
+            request.extend_from_slice(body);
+            request
+        }
+        None => {
+            request.push_str("\r\n");
+            request.into_bytes()
+        }
+    }
+}
+
+/// What was read of a response.
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    /// The http version of the status line, if it's one that responses are told apart by.
+    pub version: Option<Version>,
+    pub body_length: u64,
+    /// The length the content-length header declared for the body, if it had a body. The
+    /// body is shorter when the connection closed part way through it.
+    pub declared_length: Option<u64>,
+    pub download: Duration,
+    pub location: Option<String>,
+    pub retry_after: Option<String>,
+    /// The values of the `Server-Timing` headers.
+    pub server_timing: Vec<String>,
+    /// The family of the connection the response came over.
+    pub family: Option<Family>,
+    /// The status line, headers and body are only kept when the client was asked to keep
+    /// whole responses.
+    pub status_line: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    keep_alive: bool,
+}
+
+/// A client that keeps a connection open to each address it sends to and reuses it for as
+/// long as the server allows.
+///
+/// Its sockets are blocking, and a thread has one request in flight at a time. It saves the
+/// work of a full http client on each request, but it isn't an event loop over epoll or
+/// io_uring, so it takes as many threads as concurrent requests like reqwest does.
+pub struct Client {
+    connections: HashMap<String, (BufReader<TcpStream>, Instant)>,
+    idle_timeout: Option<Duration>,
+    keep_responses: bool,
+    binds: Option<Binds>,
+    family: Option<Family>,
+}
+
+impl Default for Client {
+    fn default() -> Client {
+        Client::new()
+    }
+}
+
+impl Client {
+    pub fn new() -> Client {
+        Client {
+            connections: HashMap::new(),
+            idle_timeout: None,
+            keep_responses: false,
+            binds: None,
+            family: None,
+        }
+    }
+
+    /// Opens connections from the local addresses in turn.
+    pub fn with_binds(mut self, binds: Binds) -> Client {
+        self.binds = Some(binds);
+        self
+    }
+
+    /// Only connects to the addresses of the family.
+    pub fn with_family(mut self, family: Family) -> Client {
+        self.family = Some(family);
+        self
+    }
+
+    /// Whether to keep the whole of each response rather than discarding it once its body
+    /// is counted.
+    pub fn keep_responses(&mut self, keep: bool) {
+        self.keep_responses = keep;
+    }
+
+    /// Closes connections that have been idle for at least the timeout instead of reusing
+    /// them.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Client {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Sends a request and reads the whole response. Also returns whether a connection had
+    /// to be opened for it. A kept-alive connection that the server already closed is
+    /// retried once on a new connection.
+    pub fn send(&mut self, target: &Target, request: &[u8], head: bool) -> io::Result<(Response, bool)> {
+        let idle_timeout = self.idle_timeout;
+        let keep = self.keep_responses;
+        let connection = self
+            .connections
+            .remove(&target.addr)
+            .filter(|(_, idle)| idle_timeout.is_none_or(|timeout| idle.elapsed() < timeout));
+        if let Some((mut connection, _)) = connection {
+            if let Ok(response) = exchange(&mut connection, request, head, keep) {
+                self.keep(target, connection, &response);
+                return Ok((response, false));
+            }
+        }
+        let stream = bind::connect(target.addr.as_str(), self.binds.as_ref(), self.family)?;
+        stream.set_nodelay(true)?;
+        let mut connection = BufReader::new(stream);
+        let response = exchange(&mut connection, request, head, keep)?;
+        self.keep(target, connection, &response);
+        Ok((response, true))
+    }
+
+    fn keep(&mut self, target: &Target, connection: BufReader<TcpStream>, response: &Response) {
+        if response.keep_alive {
+            self.connections
+                .insert(target.addr.clone(), (connection, Instant::now()));
+        }
+    }
+}
+
+fn exchange(connection: &mut BufReader<TcpStream>, request: &[u8], head: bool, keep: bool) -> io::Result<Response> {
+    connection.get_mut().write_all(request)?;
+    let response = read_response(connection, head, keep)?;
+    Ok(Response {
+        family: Family::of_peer(connection.get_ref()),
+        ..response
+    })
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Reads a response off the reader, counting the bytes of the body and only keeping the
+/// whole response if asked to.
+pub fn read_response<R: BufRead>(reader: &mut R, head: bool, keep: bool) -> io::Result<Response> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed"));
+    }
+    let mut parts = line.split_whitespace();
+    let version = parts.next().ok_or_else(|| invalid("Missing the http version"))?;
+    let status = parts
+        .next()
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or_else(|| invalid("Missing the status code"))?;
+    let mut keep_alive = version == "HTTP/1.1";
+    let version = Version::parse(version);
+    let status_line = if keep { line.trim_end().to_string() } else { String::new() };
+    let mut content_length = None;
+    let mut chunked = false;
+    let mut location = None;
+    let mut retry_after = None;
+    let mut server_timing = Vec::new();
+    let mut headers = Vec::new();
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("Connection closed in the headers"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(split) = header.find(':') {
+            let value = header[split + 1..].trim();
+            if keep {
+                headers.push((header[..split].to_string(), value.to_string()));
+            }
+            match header[..split].trim().to_lowercase().as_str() {
+                "content-length" => {
+                    content_length = Some(value.parse::<u64>().map_err(|_| invalid("Bad content-length"))?)
+                }
+                "location" => location = Some(value.to_string()),
+                "retry-after" => retry_after = Some(value.to_string()),
+                "server-timing" => server_timing.push(value.to_string()),
+                "transfer-encoding" => chunked = value.eq_ignore_ascii_case("chunked"),
+                "connection" if value.eq_ignore_ascii_case("close") => keep_alive = false,
+                "connection" if value.eq_ignore_ascii_case("keep-alive") => keep_alive = true,
+                _ => {}
+            }
+        }
+    }
+
+    let start = Instant::now();
+    let mut body = Vec::new();
+    let mut sink = io::sink();
+    let out: &mut dyn Write = if keep { &mut body } else { &mut sink };
+    let no_body = !content_length::has_body(head, status);
+    let body_length = if no_body {
+        0
+    } else if chunked {
+        read_chunked(reader, out)?
+    } else if let Some(length) = content_length {
+        let read = io::copy(&mut reader.take(length), out)?;
+        if read < length {
+            keep_alive = false;
+        }
+        read
+    } else {
+        keep_alive = false;
+        io::copy(reader, out)?
+    };
+
+    Ok(Response {
+        status,
+        version,
+        body_length,
+        declared_length: content_length.filter(|_| !no_body),
+        download: start.elapsed(),
+        location,
+        retry_after,
+        server_timing,
+        family: None,
+        status_line,
+        headers,
+        body,
+        keep_alive,
+    })
+}
+
+fn read_chunked<R: BufRead>(reader: &mut R, out: &mut dyn Write) -> io::Result<u64> {
+    let mut total = 0;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line)?;
+        let size = line.trim().split(';').next().unwrap_or("");
+        let size = u64::from_str_radix(size, 16).map_err(|_| invalid("Bad chunk size"))?;
+        if size == 0 {
+            // Skip any trailers up to the final empty line.
+            loop {
+                line.clear();
+                if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                    return Ok(total);
+                }
+            }
+        }
+        if io::copy(&mut reader.take(size), out)? < size {
+            return Err(invalid("Connection closed in a chunk"));
+        }
+        total += size;
+        line.clear();
+        reader.read_line(&mut line)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn read(response: &str, head: bool) -> io::Result<Response> {
+        read_response(&mut Cursor::new(response.as_bytes()), head, false)
+    }
+
+    #[test]
+    fn parses_targets() {
+        assert_eq!(
+            Target::parse("http://localhost:8080/a?b=c"),
+            Ok(Target {
+                addr: "localhost:8080".to_string(),
+                host: "localhost:8080".to_string(),
+                path: "/a?b=c".to_string(),
+                tls: false,
+            })
+        );
+        assert_eq!(Target::parse("http://localhost").unwrap().addr, "localhost:80");
+        assert!(Target::parse("https://localhost/").is_err());
+        let https = Target::parse_any("https://localhost/").unwrap();
+        assert_eq!((https.addr(), https.domain(), https.is_tls()), ("localhost:443", "localhost", true));
+    }
+
+    #[test]
+    fn writes_requests() {
+        let target = Target::parse("http://localhost/").unwrap();
+        let headers = vec![("x-key".to_string(), "1".to_string())];
+        assert_eq!(
+            String::from_utf8(request("POST", &target, &headers, Some(b"hi"))).unwrap(),
+            "POST / HTTP/1.1\r\nhost: localhost\r\nx-key: 1\r\ncontent-length: 2\r\n\r\nhi"
+        );
+        let headers = vec![
+            ("Host".to_string(), "tenant.example.com".to_string()),
+            ("content-length".to_string(), "5".to_string()),
+        ];
+        assert_eq!(
+            String::from_utf8(request("POST", &target, &headers, Some(b"hi"))).unwrap(),
+            "POST / HTTP/1.1\r\nHost: tenant.example.com\r\ncontent-length: 2\r\n\r\nhi"
+        );
+    }
+
+    #[test]
+    fn reads_sized_bodies() {
+        let response = read("HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello", false).unwrap();
+        assert_eq!((response.status, response.body_length, response.keep_alive), (200, 5, true));
+        assert_eq!(response.declared_length, Some(5));
+        assert_eq!(response.version, Some(Version::Http11));
+    }
+
+    #[test]
+    fn reads_what_there_is_of_truncated_bodies() {
+        let response = read("HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello", false).unwrap();
+        assert_eq!((response.body_length, response.declared_length), (5, Some(10)));
+        assert!(!response.keep_alive);
+    }
+
+    #[test]
+    fn reads_the_location_of_redirects() {
+        let response = read("HTTP/1.1 302 Found\r\nLocation: /next\r\nContent-Length: 0\r\n\r\n", false).unwrap();
+        assert_eq!(response.location, Some("/next".to_string()));
+    }
+
+    #[test]
+    fn reads_chunked_bodies() {
+        let response = read(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n",
+            false,
+        )
+        .unwrap();
+        assert_eq!(response.body_length, 11);
+        assert!(response.body.is_empty());
+    }
+
+    #[test]
+    fn keeps_whole_responses_when_asked() {
+        let chunked = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n";
+        let response = read_response(&mut Cursor::new(chunked.as_bytes()), false, true).unwrap();
+        assert_eq!(response.status_line, "HTTP/1.1 200 OK");
+        assert_eq!(
+            response.headers,
+            vec![("Transfer-Encoding".to_string(), "chunked".to_string())]
+        );
+        assert_eq!(response.body, b"hello world".to_vec());
+    }
+
+    #[test]
+    fn reads_to_the_end_without_a_length() {
+        let response = read("HTTP/1.0 404 Not Found\r\n\r\nmissing", false).unwrap();
+        assert_eq!((response.status, response.body_length, response.keep_alive), (404, 7, false));
+    }
+
+    #[test]
+    fn skips_bodies_of_head_requests() {
+        let response = read("HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\n", true).unwrap();
+        assert_eq!((response.body_length, response.keep_alive), (0, false));
+        assert_eq!(response.declared_length, None);
+    }
+}
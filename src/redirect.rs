@@ -0,0 +1,118 @@
+use hyper::Uri;
+use std::borrow::Cow;
+
+/// How many redirects are followed when `--follow-redirects` is given without a limit.
+pub const DEFAULT_MAX: u32 = 10;
+
+/// Whether the status asks the client to go elsewhere.
+pub fn is_redirect(status: u16) -> bool {
+    matches!(status, 301 | 302 | 303 | 307 | 308)
+}
+
+/// Whether the request that followed a redirect keeps its method and body. Browsers, and
+/// so most servers, expect a get after the older redirects.
+pub fn keeps_method(status: u16) -> bool {
+    status == 307 || status == 308
+}
+
+/// The headers that only go to the origin of the url they were given for, so that a
+/// redirect elsewhere doesn't hand over credentials or name the wrong host.
+const ORIGIN_HEADERS: &[&str] = &["authorization", "cookie", "proxy-authorization", "host"];
+
+/// The headers to send to `url` after following redirects from `original`. Credentials and
+/// the host aren't sent to a scheme, host or port other than the original's.
+pub fn headers_for<'a>(original: &str, url: &str, headers: &'a [(String, String)]) -> Cow<'a, [(String, String)]> {
+    if origin(original).is_some() && origin(original) == origin(url) {
+        return Cow::Borrowed(headers);
+    }
+    Cow::Owned(
+        headers
+            .iter()
+            .filter(|(name, _)| !ORIGIN_HEADERS.iter().any(|header| name.eq_ignore_ascii_case(header)))
+            .cloned()
+            .collect(),
+    )
+}
+
+/// The scheme, host and port of a url, with the port the scheme defaults to if it has none.
+fn origin(url: &str) -> Option<(String, String, u16)> {
+    let uri: Uri = url.parse().ok()?;
+    let scheme = uri.scheme()?.to_ascii_lowercase();
+    let port = match (uri.port(), scheme.as_str()) {
+        (Some(port), _) => port,
+        (None, "http") | (None, "ws") => 80,
+        (None, "https") | (None, "wss") => 443,
+        (None, _) => return None,
+    };
+    Some((scheme, uri.host()?.to_ascii_lowercase(), port))
+}
+
+/// Resolves the location of a redirect against the url that was redirected.
+pub fn resolve(base: &str, location: &str) -> Option<String> {
+    let base: Uri = base.parse().ok()?;
+    let scheme = base.scheme()?;
+    let authority = match base.port() {
+        Some(port) => format!("{}:{}", base.host()?, port),
+        None => base.host()?.to_string(),
+    };
+    let resolved = if location.contains("://") {
+        location.to_string()
+    } else if location.starts_with("//") {
+        format!("{}:{}", scheme, location)
+    } else if location.starts_with('/') {
+        format!("{}://{}{}", scheme, authority, location)
+    } else {
+        let path = base.path();
+        let directory = &path[..path.rfind('/').map_or(0, |slash| slash + 1)];
+        let directory = if directory.is_empty() { "/" } else { directory };
+        format!("{}://{}{}{}", scheme, authority, directory, location)
+    };
+    resolved.parse::<Uri>().ok().map(|_| resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_locations_against_the_base() {
+        let base = "http://localhost:8080/a/b?c=d";
+        let resolve = |location| resolve(base, location);
+        assert_eq!(resolve("https://example.com/"), Some("https://example.com/".to_string()));
+        assert_eq!(resolve("//example.com/x"), Some("http://example.com/x".to_string()));
+        assert_eq!(resolve("/x?y=z"), Some("http://localhost:8080/x?y=z".to_string()));
+        assert_eq!(resolve("x"), Some("http://localhost:8080/a/x".to_string()));
+        assert_eq!(
+            super::resolve("http://localhost", "x"),
+            Some("http://localhost/x".to_string())
+        );
+    }
+
+    #[test]
+    fn keeps_credentials_within_the_origin() {
+        let headers = vec![
+            ("Authorization".to_string(), "Bearer secret".to_string()),
+            ("cookie".to_string(), "a=b".to_string()),
+            ("host".to_string(), "tenant.example.com".to_string()),
+            ("x-key".to_string(), "val".to_string()),
+        ];
+        let names = |url| -> Vec<String> {
+            headers_for("http://a.com/x", url, &headers)
+                .iter()
+                .map(|(name, _)| name.clone())
+                .collect()
+        };
+        assert_eq!(names("http://A.com:80/y").len(), 4);
+        assert_eq!(names("https://a.com/x"), vec!["x-key"]);
+        assert_eq!(names("http://b.com/x"), vec!["x-key"]);
+        assert_eq!(names("http://a.com:8080/x"), vec!["x-key"]);
+    }
+
+    #[test]
+    fn only_the_newer_redirects_keep_the_method() {
+        assert!(is_redirect(302));
+        assert!(!is_redirect(304));
+        assert!(!keeps_method(303));
+        assert!(keeps_method(307));
+    }
+}
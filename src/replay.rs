@@ -0,0 +1,137 @@
+use hyper::{Method, Uri};
+use std::io::BufRead;
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// How many requests are read ahead of the workers, so a long log isn't read into memory
+/// all at once.
+const READ_AHEAD: usize = 1024;
+
+/// A request to replay, read from a line of input.
+#[derive(Debug)]
+pub struct Entry {
+    pub method: Method,
+    pub url: String,
+    pub uri: Uri,
+    pub body: Option<String>,
+}
+
+/// A line that is a json object rather than a bare url.
+#[derive(Deserialize)]
+struct Line {
+    method: Option<String>,
+    url: String,
+    body: Option<String>,
+}
+
+impl Entry {
+    /// Reads a request from a line, either a url to get or a json object with a `url` and
+    /// an optional `method` and `body`. Blank lines and lines starting with `#` are skipped.
+    pub fn parse(line: &str) -> Result<Option<Entry>, String> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+        let line = if line.starts_with('{') {
+            serde_json::from_str(line).map_err(|e| format!("Invalid request: {}", e))?
+        } else {
+            Line {
+                method: None,
+                url: line.to_string(),
+                body: None,
+            }
+        };
+        let method = match line.method {
+            Some(ref method) => method
+                .to_uppercase()
+                .parse::<Method>()
+                .map_err(|_| format!("Invalid method '{}'", method))?,
+            None => Method::Get,
+        };
+        let uri = line
+            .url
+            .parse::<Uri>()
+            .map_err(|_| format!("Invalid url '{}'", line.url))?;
+        if uri.scheme().is_none() || uri.host().is_none() {
+            return Err(format!("The url '{}' isn't absolute", line.url));
+        }
+        Ok(Some(Entry {
+            method,
+            url: line.url,
+            uri,
+            body: line.body,
+        }))
+    }
+}
+
+/// The requests read from the input as they arrive, shared between the workers so each
+/// takes the next one when it's free. Lines that aren't requests are reported and skipped.
+#[derive(Clone)]
+pub struct Feed {
+    entries: Arc<Mutex<Receiver<Entry>>>,
+}
+
+impl Feed {
+    pub fn read<R: BufRead + Send + 'static>(reader: R) -> Feed {
+        let (sender, entries) = sync_channel(READ_AHEAD);
+        thread::spawn(move || {
+            for (number, line) in reader.lines().enumerate() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(e) => {
+                        eprintln!("Stopped reading requests: {}", e);
+                        break;
+                    }
+                };
+                match Entry::parse(&line) {
+                    Ok(Some(entry)) => {
+                        if sender.send(entry).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => eprintln!("Skipping line {}: {}", number + 1, e),
+                }
+            }
+        });
+        Feed {
+            entries: Arc::new(Mutex::new(entries)),
+        }
+    }
+
+    /// The next request, waiting for it to arrive, or none once the input has ended.
+    pub fn next(&self) -> Option<Entry> {
+        self.entries.lock().expect("Expected the feed to be readable").recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn reads_urls_and_json_requests() {
+        let entry = Entry::parse("http://a/1").unwrap().unwrap();
+        assert_eq!((entry.method, entry.url.as_str()), (Method::Get, "http://a/1"));
+
+        let entry = Entry::parse(r#"{"method":"post","url":"http://a/2","body":"hi"}"#)
+            .unwrap()
+            .unwrap();
+        assert_eq!((entry.method, entry.body), (Method::Post, Some("hi".to_string())));
+
+        assert!(Entry::parse("  # comment").unwrap().is_none());
+        assert!(Entry::parse("/no/host").is_err());
+        assert!(Entry::parse(r#"{"method":"GET"}"#).is_err());
+    }
+
+    #[test]
+    fn feeds_every_request_until_the_input_ends() {
+        let feed = Feed::read(Cursor::new("http://a/1\nnot a url\n\nhttp://a/2\n"));
+        let shared = feed.clone();
+        assert_eq!(feed.next().unwrap().url, "http://a/1");
+        assert_eq!(shared.next().unwrap().url, "http://a/2");
+        assert!(feed.next().is_none());
+    }
+}
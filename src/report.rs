@@ -0,0 +1,771 @@
+use histogram::Histogram;
+use metadata::{self, Metadata};
+use stats::ToMilliseconds;
+use std::collections::BTreeMap;
+use warning::Warnings;
+
+/// The version of the JSON report's schema. Within a version fields are only ever added,
+/// never removed, renamed or given a new meaning, so a dashboard written against it keeps
+/// working as rench grows new metrics. Anything else bumps the version.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The machine-readable report of a run, printed by `--output json`. Durations are in
+/// milliseconds and sizes in bytes, and the unit is part of every field's name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    pub schema_version: u32,
+    pub run: Run,
+    pub warnings: Vec<String>,
+    /// Whether the client used nearly all of its cpu, so the results may not be the
+    /// server's.
+    pub client_saturated: bool,
+    pub took_seconds: f64,
+    /// How long the clients took to set up before the run was timed. Only present when
+    /// the run was local.
+    pub setup_seconds: Option<f64>,
+    /// How much sending the facts to the collector held the workers up. Missing from
+    /// reports of older versions.
+    #[serde(default)]
+    pub collector: Option<Collector>,
+    /// The connections opened before the run with `--preconnect`. Only present when the
+    /// run was local and preconnected.
+    #[serde(default)]
+    pub preconnect: Option<Preconnect>,
+    /// The cpu and memory the client itself used while the run went on. Only present when
+    /// the run was local and the platform reports them.
+    #[serde(default)]
+    pub client_usage: Option<ClientUsage>,
+    pub requests_per_second: f64,
+    pub results: Results,
+}
+
+/// How many facts the workers sent to the collector, how long they were held up sending
+/// them in all, and the most facts that waited in its queue at once. A queue that grows
+/// deep means the collector fell behind the workers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Collector {
+    pub sent: u64,
+    /// Facts that were dropped because the queue was full.
+    #[serde(default)]
+    pub dropped: u64,
+    pub blocked_seconds: f64,
+    pub deepest_queue: usize,
+}
+
+/// How many connections were opened before the run, how long they took on average and
+/// at the longest, and how many failed to open.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Preconnect {
+    pub opened: u64,
+    pub failed: u64,
+    pub average_ms: f64,
+    pub longest_ms: f64,
+}
+
+/// How much of the cpus of the client's host it used, as a percentage of a single cpu, on
+/// average and over its busiest half second, and how much memory it had resident.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClientUsage {
+    pub cpus: usize,
+    pub cpu_seconds: f64,
+    pub average_cpu_percent: f64,
+    pub busiest_cpu_percent: f64,
+    /// Only present where the platform reports it.
+    pub average_memory_bytes: Option<u64>,
+    pub peak_memory_bytes: Option<u64>,
+}
+
+/// What was run, against what, from where and when.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Run {
+    pub version: String,
+    pub command: String,
+    pub urls: Vec<String>,
+    pub engine: String,
+    pub concurrency: usize,
+    pub requests: usize,
+    /// An RFC 3339 timestamp in UTC.
+    pub started: String,
+    pub seed: u64,
+    pub host: Host,
+    /// Only present when the run was labelled.
+    pub label: Option<String>,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Host {
+    pub name: String,
+    pub os: String,
+    pub arch: String,
+    pub cpus: usize,
+}
+
+/// The statistics of the requests.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Results {
+    pub count: u32,
+    /// Requests that errored without receiving a response.
+    pub errors: u32,
+    /// Requests that didn't receive a 2xx response, errors included.
+    pub failures: u32,
+    /// Responses whose body failed the `--expect-body` checks, which are also failures.
+    pub body_mismatches: u32,
+    /// Responses that didn't echo the token of their request, which are also failures.
+    /// Missing from reports of older versions.
+    #[serde(default)]
+    pub echo_mismatches: u32,
+    /// Responses whose body wasn't the length their content-length declared, which are
+    /// also failures.
+    pub length_mismatches: u32,
+    pub latency_ms: Latency,
+    /// The latency at each percentile from 0 to 99. Empty when the run was streamed
+    /// without keeping a sample.
+    pub percentiles_ms: Vec<f64>,
+    /// The count of each bucket of the latency histogram that counted anything, by the
+    /// lowest latency in microseconds that the bucket holds. It's how `rench aggregate`
+    /// merges the percentiles of runs, and is missing from reports of older versions.
+    pub latency_buckets_us: Option<Vec<(u64, u64)>>,
+    pub content_length_bytes: u64,
+    /// The bytes of the bodies of each status class that had a response, such as `2xx`.
+    #[serde(default)]
+    pub content_length_bytes_by_class: BTreeMap<String, u64>,
+    pub status_codes: BTreeMap<u16, u32>,
+    /// Only present when the engine could tell new connections from reused ones.
+    pub connections: Option<Connections>,
+    /// How many requests went over IPv4 and IPv6. Only present when the engine could tell
+    /// the family of its connections.
+    #[serde(default)]
+    pub families: Option<Families>,
+    /// Only present when redirects were followed.
+    pub redirects: Option<Redirects>,
+    /// Only present when connections per host were limited.
+    pub pool_wait: Option<PoolWait>,
+    /// Only present when any request was throttled with a 429 or asked to retry after a
+    /// while.
+    pub throttling: Option<Throttling>,
+    /// The phases the server reported with `Server-Timing` headers. Only present when any
+    /// response had one.
+    #[serde(default)]
+    pub server_timing: Option<ServerTiming>,
+    /// Only present when the engine timed the download of the bodies.
+    pub transfer_rates_bytes_per_second: Option<TransferRates>,
+    /// The rates of the bodies over the whole time of their requests. Only present when
+    /// any response had a body.
+    pub throughput_bytes_per_second: Option<TransferRates>,
+    /// The sizes of the bodies of the responses. Only present when any request had a
+    /// response.
+    #[serde(default)]
+    pub response_sizes_bytes: Option<ResponseSizes>,
+    pub labels: BTreeMap<String, Label>,
+    /// How the requests to each url went, only present when more than one url was
+    /// requested.
+    pub targets: Option<BTreeMap<String, Target>>,
+    /// The variance of the bodies of each url, only present when they were checksummed.
+    pub checksums: Option<BTreeMap<String, Checksums>>,
+    /// Only present when the heatmap was asked for.
+    pub heatmap: Option<Heatmap>,
+    /// Only present when the latency by response size was asked for.
+    pub size_latency: Option<SizeLatency>,
+    /// Only present when latency spikes repeated on a schedule during the run.
+    pub periodicity: Option<Periodicity>,
+    /// Only present when the requests were paced.
+    pub schedule: Option<Schedule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Latency {
+    pub min: f64,
+    pub median: f64,
+    pub average: f64,
+    pub max: f64,
+    pub stddev: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Connections {
+    pub opened: u64,
+    pub tracked: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Families {
+    pub ipv4: u64,
+    pub ipv6: u64,
+}
+
+/// How many requests were redirected, how many redirects they followed in all and the
+/// most any one of them followed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Redirects {
+    pub redirected: u64,
+    pub hops: u64,
+    pub most: u32,
+}
+
+/// How many requests waited for a free connection and for how long.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PoolWait {
+    pub waited: u64,
+    pub average_ms: f64,
+    pub longest_ms: f64,
+}
+
+/// How many requests were throttled with a 429 and their average latency, and how long
+/// the responses with a `Retry-After` asked the client to wait. The workers only waited
+/// when they respected it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Throttling {
+    pub throttled: u64,
+    pub average_ms: f64,
+    pub asked: u64,
+    pub average_retry_after_ms: f64,
+    pub backed_off_ms: f64,
+}
+
+/// The phases the server reported spending its time in with `Server-Timing` headers,
+/// with the average latency the client saw of the same responses and how much of it the
+/// server accounted for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerTiming {
+    /// How many responses reported any phase.
+    pub timed: u64,
+    pub average_latency_ms: f64,
+    /// By the `total` phase of each response, or else the sum of its phases.
+    pub average_server_ms: f64,
+    pub phases: BTreeMap<String, ServerPhase>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerPhase {
+    pub count: u64,
+    pub average_ms: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransferRates {
+    pub slowest: f64,
+    pub p10: f64,
+    pub median: f64,
+    pub p90: f64,
+    pub fastest: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResponseSizes {
+    pub smallest: u64,
+    pub average: f64,
+    pub median: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub largest: u64,
+}
+
+/// How many distinct bodies a url served, and how many of its responses had the most
+/// common one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checksums {
+    pub responses: u64,
+    pub distinct: usize,
+    pub most_common: u64,
+}
+
+/// The requests counted by when they finished and how long they took. Each column covers
+/// `column_ms` of the run in order, and each of its counts is of the latencies under the
+/// bound of the row at the same index that weren't under the bound of the row before.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Heatmap {
+    pub column_ms: f64,
+    pub rows_under_ms: Vec<f64>,
+    pub counts: Vec<Vec<u64>>,
+}
+
+/// The latencies of the responses grouped by the size of their bodies, with the correlation
+/// of size and latency when the sizes and latencies varied. Each row counts the bodies
+/// under `under_bytes` that weren't under the bound of the row before.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SizeLatency {
+    pub correlation: Option<f64>,
+    pub rows: Vec<SizeRow>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SizeRow {
+    pub under_bytes: u64,
+    pub count: u64,
+    pub average_ms: f64,
+    pub p99_ms: f64,
+}
+
+/// How often latency spikes repeated during the run, and how strongly the latency
+/// correlated with itself that long later, from 0 to 1.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Periodicity {
+    pub period_seconds: f64,
+    pub autocorrelation: f64,
+}
+
+/// How closely a paced run kept to its target rate. Each point of the backlog covers
+/// `backlog_interval_ms` of the run in order, and is the most requests that were due but
+/// not yet started in it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Schedule {
+    pub target_rps: f64,
+    pub achieved_rps: f64,
+    pub late: u64,
+    pub late_percent: f64,
+    pub backlog_interval_ms: u64,
+    pub backlog: Vec<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Label {
+    pub count: u32,
+    pub average_ms: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Target {
+    pub count: u32,
+    pub failures: u32,
+    pub average_ms: f64,
+}
+
+impl Report {
+    pub fn new(metadata: &Metadata, warnings: &Warnings, took_seconds: f64, results: Results) -> Report {
+        Report {
+            schema_version: SCHEMA_VERSION,
+            run: Run {
+                version: metadata.version.clone(),
+                command: metadata.command.clone(),
+                urls: metadata.urls.clone(),
+                engine: metadata.engine.clone(),
+                concurrency: metadata.concurrency,
+                requests: metadata.requests,
+                started: metadata::timestamp(metadata.started),
+                seed: metadata.seed,
+                host: Host {
+                    name: metadata.host.name.clone(),
+                    os: metadata.host.os.clone(),
+                    arch: metadata.host.arch.clone(),
+                    cpus: metadata.host.cpus,
+                },
+                label: metadata.label.clone(),
+                tags: metadata.tags.clone(),
+            },
+            warnings: warnings.iter().map(|w| w.to_string()).collect(),
+            client_saturated: warnings.client_saturated(),
+            took_seconds,
+            setup_seconds: None,
+            collector: None,
+            preconnect: None,
+            client_usage: None,
+            requests_per_second: if took_seconds > 0. {
+                f64::from(results.count) / took_seconds
+            } else {
+                0.
+            },
+            results,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Reports always serialize")
+    }
+
+    /// Reads a report written by `--output json`, refusing one of another schema version.
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), String> {
+    /// let json = std::fs::read_to_string("run.json").map_err(|e| e.to_string())?;
+    /// let report = rench::Report::from_json(&json)?;
+    /// println!("{} requests at {:.0} rps", report.results.count, report.requests_per_second);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_json(json: &str) -> Result<Report, String> {
+        let report: Report = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        if report.schema_version != SCHEMA_VERSION {
+            return Err(format!(
+                "Version {} of the report can't be read, only version {}",
+                report.schema_version, SCHEMA_VERSION
+            ));
+        }
+        Ok(report)
+    }
+}
+
+impl Results {
+    /// The latency at the quantile, read from the histogram saved with the run, or from
+    /// its percentiles if it was saved by an older rench.
+    pub fn quantile_ms(&self, quantile: f64) -> Option<f64> {
+        match self.latency_buckets_us {
+            Some(ref buckets) => Some(Histogram::from_buckets(buckets).value_at_quantile(quantile).to_ms()),
+            None => self.percentiles_ms.get((quantile * 100.) as usize).cloned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metadata::Host as Machine;
+    use std::time::{Duration, UNIX_EPOCH};
+    use warning::Warning;
+
+    fn report() -> Report {
+        let metadata = Metadata {
+            version: "0.3.0".to_string(),
+            command: "rench http://a/".to_string(),
+            urls: vec!["http://a/".to_string()],
+            engine: "hyper".to_string(),
+            concurrency: 2,
+            requests: 10,
+            started: UNIX_EPOCH + Duration::from_secs(60),
+            seed: 42,
+            host: Machine {
+                name: "box".to_string(),
+                os: "linux".to_string(),
+                arch: "x86_64".to_string(),
+                cpus: 4,
+            },
+            label: Some("canary".to_string()),
+            tags: vec![("env".to_string(), "prod".to_string())].into_iter().collect(),
+        };
+        let mut warnings = Warnings::new();
+        warnings.push(Warning::FewRequests(10));
+        let results = Results {
+            count: 10,
+            errors: 0,
+            failures: 0,
+            body_mismatches: 0,
+            echo_mismatches: 0,
+            length_mismatches: 0,
+            latency_ms: Latency {
+                min: 1.,
+                median: 2.,
+                average: 2.,
+                max: 3.,
+                stddev: 0.5,
+            },
+            percentiles_ms: Vec::new(),
+            latency_buckets_us: Some(vec![(2_000, 10)]),
+            content_length_bytes: 100,
+            content_length_bytes_by_class: vec![("2xx".to_string(), 100)].into_iter().collect(),
+            status_codes: vec![(200, 10)].into_iter().collect(),
+            connections: None,
+            families: None,
+            redirects: None,
+            pool_wait: None,
+            throttling: None,
+            server_timing: None,
+            transfer_rates_bytes_per_second: None,
+            throughput_bytes_per_second: None,
+            response_sizes_bytes: None,
+            labels: BTreeMap::new(),
+            targets: None,
+            checksums: None,
+            heatmap: None,
+            size_latency: None,
+            periodicity: None,
+            schedule: None,
+        };
+        Report::new(&metadata, &warnings, 2., results)
+    }
+
+    #[test]
+    fn leads_with_the_schema_version() {
+        let json = report().to_json();
+        assert!(json.starts_with("{\n  \"schema_version\": 1,"));
+        assert!(json.contains("\"started\": \"1970-01-01T00:01:00Z\""));
+        assert!(json.contains("\"requests_per_second\": 5.0"));
+    }
+
+    /// The report with every section that's only present some of the time.
+    fn full_report() -> Report {
+        let mut report = report();
+        report.setup_seconds = Some(0.25);
+        report.collector = Some(Collector {
+            sent: 10,
+            dropped: 1,
+            blocked_seconds: 0.5,
+            deepest_queue: 3,
+        });
+        report.preconnect = Some(Preconnect {
+            opened: 2,
+            failed: 0,
+            average_ms: 1.5,
+            longest_ms: 2.,
+        });
+        report.client_usage = Some(ClientUsage {
+            cpus: 4,
+            cpu_seconds: 1.5,
+            average_cpu_percent: 150.,
+            busiest_cpu_percent: 210.,
+            average_memory_bytes: Some(2_048),
+            peak_memory_bytes: Some(4_096),
+        });
+        let results = &mut report.results;
+        results.percentiles_ms = (0..100).map(f64::from).collect();
+        results.connections = Some(Connections { opened: 2, tracked: 10 });
+        results.families = Some(Families { ipv4: 8, ipv6: 2 });
+        results.redirects = Some(Redirects {
+            redirected: 1,
+            hops: 2,
+            most: 2,
+        });
+        results.pool_wait = Some(PoolWait {
+            waited: 3,
+            average_ms: 0.5,
+            longest_ms: 1.25,
+        });
+        results.throttling = Some(Throttling {
+            throttled: 1,
+            average_ms: 3.,
+            asked: 1,
+            average_retry_after_ms: 1_000.,
+            backed_off_ms: 1_000.,
+        });
+        results.server_timing = Some(ServerTiming {
+            timed: 10,
+            average_latency_ms: 2.,
+            average_server_ms: 1.5,
+            phases: vec![("db".to_string(), ServerPhase { count: 10, average_ms: 1. })].into_iter().collect(),
+        });
+        let rates = TransferRates {
+            slowest: 1.,
+            p10: 2.,
+            median: 3.,
+            p90: 4.,
+            fastest: 5.,
+        };
+        results.transfer_rates_bytes_per_second = Some(rates.clone());
+        results.throughput_bytes_per_second = Some(rates);
+        results.response_sizes_bytes = Some(ResponseSizes {
+            smallest: 10,
+            average: 55.,
+            median: 10,
+            p90: 100,
+            p99: 100,
+            largest: 100,
+        });
+        results.labels = vec![("method=GET".to_string(), Label { count: 10, average_ms: 2. })].into_iter().collect();
+        results.targets = Some(
+            vec![(
+                "http://a/".to_string(),
+                Target {
+                    count: 10,
+                    failures: 0,
+                    average_ms: 2.,
+                },
+            )]
+            .into_iter()
+            .collect(),
+        );
+        results.checksums = Some(
+            vec![(
+                "http://a/".to_string(),
+                Checksums {
+                    responses: 10,
+                    distinct: 1,
+                    most_common: 10,
+                },
+            )]
+            .into_iter()
+            .collect(),
+        );
+        results.heatmap = Some(Heatmap {
+            column_ms: 100.,
+            rows_under_ms: vec![1., 10.],
+            counts: vec![vec![0, 10]],
+        });
+        results.size_latency = Some(SizeLatency {
+            correlation: Some(0.5),
+            rows: vec![SizeRow {
+                under_bytes: 1_024,
+                count: 10,
+                average_ms: 2.,
+                p99_ms: 3.,
+            }],
+        });
+        results.periodicity = Some(Periodicity {
+            period_seconds: 5.,
+            autocorrelation: 0.75,
+        });
+        results.schedule = Some(Schedule {
+            target_rps: 5.,
+            achieved_rps: 4.5,
+            late: 1,
+            late_percent: 10.,
+            backlog_interval_ms: 100,
+            backlog: vec![0., 1.],
+        });
+        report
+    }
+
+    /// Every field of the report by its path, like `results.latency_ms.median`. The
+    /// entries of maps are left out, since their keys are the data.
+    fn field_paths(value: &serde_json::Value, prefix: &str, paths: &mut Vec<String>) {
+        if let serde_json::Value::Object(ref fields) = *value {
+            for (name, value) in fields {
+                let path = if prefix.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}.{}", prefix, name)
+                };
+                if !MAPS.contains(&path.as_str()) {
+                    field_paths(value, &path, paths);
+                }
+                paths.push(path);
+            }
+        }
+    }
+
+    const MAPS: &[&str] = &[
+        "run.tags",
+        "results.content_length_bytes_by_class",
+        "results.status_codes",
+        "results.server_timing.phases",
+        "results.labels",
+        "results.targets",
+        "results.checksums",
+    ];
+
+    #[test]
+    fn reads_back_what_it_wrote() {
+        let report = report();
+        let read: Report = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(read, report);
+
+        let report = full_report();
+        let read: Report = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(read, report);
+    }
+
+    #[test]
+    fn only_reads_its_own_version() {
+        let mut report = report();
+        assert_eq!(Report::from_json(&report.to_json()), Ok(report.clone()));
+        report.schema_version = SCHEMA_VERSION + 1;
+        assert!(Report::from_json(&report.to_json()).is_err());
+        assert!(Report::from_json("{}").is_err());
+    }
+
+    /// The fields that the readers of version 1 of the report rely on. More can be added,
+    /// but one that's renamed or removed breaks them, which calls for a new version.
+    #[test]
+    fn keeps_the_names_of_its_fields() {
+        const RELEASED: &[&str] = &[
+            "schema_version",
+            "run.version",
+            "run.command",
+            "run.urls",
+            "run.engine",
+            "run.concurrency",
+            "run.requests",
+            "run.started",
+            "run.seed",
+            "run.host.name",
+            "run.host.os",
+            "run.host.arch",
+            "run.host.cpus",
+            "run.label",
+            "warnings",
+            "client_saturated",
+            "took_seconds",
+            "setup_seconds",
+            "requests_per_second",
+            "results.count",
+            "results.errors",
+            "results.failures",
+            "results.body_mismatches",
+            "results.length_mismatches",
+            "results.latency_ms.min",
+            "results.latency_ms.median",
+            "results.latency_ms.average",
+            "results.latency_ms.max",
+            "results.latency_ms.stddev",
+            "results.percentiles_ms",
+            "results.latency_buckets_us",
+            "results.content_length_bytes",
+            "results.status_codes",
+            "results.connections.opened",
+            "results.connections.tracked",
+            "results.redirects.redirected",
+            "results.redirects.hops",
+            "results.redirects.most",
+            "results.pool_wait.waited",
+            "results.pool_wait.average_ms",
+            "results.pool_wait.longest_ms",
+            "results.throttling.throttled",
+            "results.throttling.average_ms",
+            "results.throttling.asked",
+            "results.throttling.average_retry_after_ms",
+            "results.throttling.backed_off_ms",
+            "results.transfer_rates_bytes_per_second.slowest",
+            "results.transfer_rates_bytes_per_second.p10",
+            "results.transfer_rates_bytes_per_second.median",
+            "results.transfer_rates_bytes_per_second.p90",
+            "results.transfer_rates_bytes_per_second.fastest",
+            "results.throughput_bytes_per_second.median",
+            "results.labels",
+            "results.targets",
+            "results.checksums",
+            "results.heatmap.column_ms",
+            "results.heatmap.rows_under_ms",
+            "results.heatmap.counts",
+            "results.size_latency.correlation",
+            "results.size_latency.rows",
+            "results.periodicity.period_seconds",
+            "results.periodicity.autocorrelation",
+            "results.schedule.target_rps",
+            "results.schedule.achieved_rps",
+            "results.schedule.late",
+            "results.schedule.late_percent",
+            "results.schedule.backlog_interval_ms",
+            "results.schedule.backlog",
+        ];
+        let json: serde_json::Value = serde_json::from_str(&full_report().to_json()).unwrap();
+        let mut written = Vec::new();
+        field_paths(&json, "", &mut written);
+        let missing: Vec<&&str> = RELEASED.iter().filter(|path| !written.iter().any(|written| written == *path)).collect();
+        assert!(missing.is_empty(), "The report no longer writes {:?}", missing);
+    }
+
+    /// A report of the same version from an older rench lacks the fields added since,
+    /// and one from a newer rench has fields this one doesn't know. Both are read.
+    #[test]
+    fn reads_the_reports_of_other_releases_of_its_version() {
+        let json = r#"{
+            "schema_version": 1,
+            "run": {
+                "version": "0.2.0", "command": "rench http://a/", "urls": ["http://a/"],
+                "engine": "hyper", "concurrency": 1, "requests": 1,
+                "started": "1970-01-01T00:01:00Z", "seed": 1,
+                "host": {"name": "box", "os": "linux", "arch": "x86_64", "cpus": 1},
+                "label": null
+            },
+            "warnings": [],
+            "client_saturated": false,
+            "took_seconds": 1.0,
+            "setup_seconds": null,
+            "requests_per_second": 1.0,
+            "results": {
+                "count": 1, "errors": 0, "failures": 0, "body_mismatches": 0, "length_mismatches": 0,
+                "latency_ms": {"min": 1.0, "median": 1.0, "average": 1.0, "max": 1.0, "stddev": 0.0},
+                "percentiles_ms": [], "latency_buckets_us": null, "content_length_bytes": 0,
+                "status_codes": {"200": 1}, "connections": null, "redirects": null, "pool_wait": null,
+                "throttling": null, "transfer_rates_bytes_per_second": null,
+                "throughput_bytes_per_second": null, "labels": {}, "targets": null, "checksums": null,
+                "heatmap": null, "size_latency": null, "periodicity": null, "schedule": null,
+                "added_by_a_newer_rench_ms": 1.0
+            }
+        }"#;
+        let report: Report = serde_json::from_str(json).unwrap();
+        assert_eq!(report.results.status_codes.get(&200), Some(&1));
+        assert_eq!(report.results.echo_mismatches, 0);
+        assert_eq!(report.collector, None);
+        assert!(report.run.tags.is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+use random::Rng;
+
+/// A fixed size, uniformly random sample of a stream of values. Every value offered has the
+/// same chance of being in the sample no matter how many values are seen, which keeps the
+/// memory bounded while still giving a fair picture of the whole stream.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Reservoir<T> {
+    capacity: usize,
+    seen: u64,
+    values: Vec<T>,
+    #[serde(skip, default = "Rng::from_time")]
+    rng: Rng,
+}
+
+impl<T> Reservoir<T> {
+    /// Creates an empty reservoir that keeps at most `capacity` values.
+    pub fn new(capacity: usize, rng: Rng) -> Reservoir<T> {
+        Reservoir {
+            capacity,
+            seen: 0,
+            values: Vec::with_capacity(capacity),
+            rng,
+        }
+    }
+
+    /// Offers a value to the sample, which may or may not be kept.
+    pub fn offer(&mut self, value: T) {
+        self.seen += 1;
+        if self.values.len() < self.capacity {
+            self.values.push(value);
+        } else if self.capacity > 0 {
+            let index = self.rng.next_u64() % self.seen;
+            if index < self.capacity as u64 {
+                self.values[index as usize] = value;
+            }
+        }
+    }
+
+    /// Combines another reservoir into this one. Values are drawn from each reservoir in
+    /// proportion to how many values each has seen so the result stays uniform.
+    pub fn merge(&mut self, other: Reservoir<T>) {
+        let mut pools = [
+            Pool::new(std::mem::take(&mut self.values), self.seen),
+            Pool::new(other.values, other.seen),
+        ];
+        self.seen += other.seen;
+
+        while self.values.len() < self.capacity && pools.iter().any(|p| !p.values.is_empty()) {
+            let total = pools[0].mass + pools[1].mass;
+            let roll = self.rng.next_u64() as f64 / u64::MAX as f64 * total;
+            let take_ours = !pools[0].values.is_empty() && roll < pools[0].mass;
+            let pool = if take_ours || pools[1].values.is_empty() {
+                &mut pools[0]
+            } else {
+                &mut pools[1]
+            };
+            let index = (self.rng.next_u64() % pool.values.len() as u64) as usize;
+            self.values.push(pool.values.swap_remove(index));
+            pool.mass -= pool.weight;
+        }
+    }
+
+    /// The number of values that have been offered.
+    pub fn seen(&self) -> u64 {
+        self.seen
+    }
+
+    /// The values that were kept.
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+}
+
+/// One side of a merge. Every value left in the pool stands in for `weight` of the values
+/// that the reservoir saw.
+struct Pool<T> {
+    values: Vec<T>,
+    weight: f64,
+    mass: f64,
+}
+
+impl<T> Pool<T> {
+    fn new(values: Vec<T>, seen: u64) -> Pool<T> {
+        let weight = if values.is_empty() {
+            0.
+        } else {
+            seen as f64 / values.len() as f64
+        };
+        Pool {
+            values,
+            weight,
+            mass: seen as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_everything_until_full() {
+        let mut reservoir = Reservoir::new(10, Rng::new(0));
+        for n in 0..5 {
+            reservoir.offer(n);
+        }
+        assert_eq!(reservoir.values(), &[0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn never_grows_past_its_capacity() {
+        let mut reservoir = Reservoir::new(10, Rng::new(0));
+        for n in 0..10_000 {
+            reservoir.offer(n);
+        }
+        assert_eq!(reservoir.values().len(), 10);
+        assert_eq!(reservoir.seen(), 10_000);
+        // With 10 values out of 10,000 at least one should come from past the start.
+        assert!(reservoir.values().iter().any(|n| *n >= 10));
+    }
+
+    #[test]
+    fn samples_uniformly() {
+        let mut reservoir = Reservoir::new(1_000, Rng::new(3));
+        for n in 0..100_000 {
+            reservoir.offer(n);
+        }
+        let mean = reservoir.values().iter().sum::<u64>() as f64 / 1_000.;
+        assert!((mean - 50_000.).abs() < 5_000., "mean was {}", mean);
+    }
+
+    #[test]
+    fn merges_in_proportion_to_what_was_seen() {
+        let mut a = Reservoir::new(100, Rng::new(1));
+        let mut b = Reservoir::new(100, Rng::new(2));
+        for _ in 0..1_000 {
+            a.offer(0);
+        }
+        for _ in 0..9_000 {
+            b.offer(1);
+        }
+        a.merge(b);
+        assert_eq!(a.seen(), 10_000);
+        assert_eq!(a.values().len(), 100);
+        let ones = a.values().iter().filter(|n| **n == 1).count();
+        assert!(ones > 75, "only {} ones", ones);
+    }
+
+    #[test]
+    fn empty_reservoirs_keep_nothing() {
+        let mut reservoir = Reservoir::new(0, Rng::new(0));
+        reservoir.offer(1);
+        assert!(reservoir.values().is_empty());
+    }
+}
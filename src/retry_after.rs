@@ -0,0 +1,33 @@
+use hyper::header::HttpDate;
+use std::time::{Duration, SystemTime};
+
+/// The longest a worker backs off for however long the server asks, so that one response
+/// can't hold a run up for hours.
+pub const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// How long a `Retry-After` header asks the client to wait before trying again, given
+/// either as a number of seconds or as the date to wait until. A date that has already
+/// passed asks for no wait at all.
+pub fn parse(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date: HttpDate = value.parse().ok()?;
+    Some(SystemTime::from(date).duration_since(now).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn reads_seconds_or_a_date() {
+        let now = UNIX_EPOCH + Duration::from_secs(784_111_777);
+        assert_eq!(parse("120", now), Some(Duration::from_secs(120)));
+        assert_eq!(parse("Sun, 06 Nov 1994 08:49:47 GMT", now), Some(Duration::from_secs(10)));
+        assert_eq!(parse("Sun, 06 Nov 1994 08:49:00 GMT", now), Some(Duration::new(0, 0)));
+        assert_eq!(parse("soon", now), None);
+    }
+}
@@ -1,46 +1,235 @@
+use affinity::{self, Pinning};
+use collector::Sender;
 use engine::Engine;
 use plan::Plan;
+use profile::{Profile, Profiles};
 use message::Message;
 use stats::Fact;
-use std::{thread, sync::mpsc::Sender};
+use std::thread;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 /// The runner struct represents an ongoing run time of the engine.
 pub struct Runner {
     handles: Vec<thread::JoinHandle<()>>,
+    start_line: StartLine,
+    started: Instant,
+    unpinned: Arc<Mutex<Vec<String>>>,
 }
 
 impl Runner {
     /// Launches the runner with a plan. It will tell the engine to run and broadcast the
     /// facts that the engine produces. The plan tells the runner how many threads to run
     /// on and how to distribute the work.
-    pub fn start(plan: Plan, eng: &Engine, collector: &Sender<Message<Fact>>) -> Runner {
-        let handles = plan.distribute()
-            .into_iter()
-            .map(|work| {
+    ///
+    /// Each thread keeps its share of the plan's concurrency in flight.
+    ///
+    /// When there are profiles, each thread is assigned one for the whole run. Its headers
+    /// are sent with every request and its facts are labelled with its name.
+    ///
+    /// The threads wait for each other to set up their clients before any makes a request,
+    /// so that the setup of a slow thread doesn't overlap the run of the others.
+    ///
+    /// When there's a pinning, each thread pins itself to its core before it sets up. A
+    /// thread that can't be pinned runs unpinned rather than stopping the run.
+    pub fn start(
+        plan: Plan,
+        eng: &Engine,
+        profiles: &Profiles,
+        pinning: Option<&Pinning>,
+        collector: &Sender<Fact>,
+    ) -> Runner {
+        let started = Instant::now();
+        let unpinned = Arc::new(Mutex::new(Vec::new()));
+        let start_line = StartLine::new(plan.threads());
+        // Each thread numbers its requests on from those of the threads before it.
+        let work = plan.distribute();
+        let firsts = work.iter().scan(0, |first, work| {
+            let this = *first;
+            *first += work;
+            Some(this)
+        });
+        let handles = work
+            .iter()
+            .cloned()
+            .zip(firsts)
+            .zip(plan.in_flight())
+            .enumerate()
+            .map(|(thread, ((work, first), in_flight))| {
                 let collector = collector.clone();
-                let eng = eng.clone();
-                thread::spawn(move || Self::run(work, eng, &collector))
+                let profile = profiles.assign(thread, plan.threads()).cloned();
+                let ticket = start_line.ticket();
+                let eng = eng
+                    .clone()
+                    .for_worker(thread)
+                    .with_first_request(first)
+                    .with_in_flight(in_flight)
+                    .with_ready(move || ticket.punch());
+                let eng = match profile {
+                    Some(ref profile) => eng.with_headers(profile.headers()),
+                    None => eng,
+                };
+                let eng = match plan.interval() {
+                    Some(interval) => eng.with_interval(interval),
+                    None => eng,
+                };
+                let core = pinning.map(|pinning| pinning.worker(thread));
+                let unpinned = unpinned.clone();
+                thread::spawn(move || {
+                    if let Some(Err(e)) = core.map(affinity::pin) {
+                        unpinned.lock().expect("Expected the unpinned threads to be lockable").push(e);
+                    }
+                    Self::run(work, eng, profile, &collector)
+                })
             })
             .collect();
-        Runner { handles }
+        Runner {
+            handles,
+            start_line,
+            started,
+            unpinned,
+        }
+    }
+
+    /// Waits for every thread to set up its client, returning how long it took since the
+    /// runner was started. The run begins once this returns.
+    pub fn ready(&self) -> Duration {
+        self.start_line.wait().duration_since(self.started)
+    }
+
+    /// Why the threads that couldn't be pinned to their cores weren't, once the runner is
+    /// ready.
+    pub fn unpinned(&self) -> Vec<String> {
+        self.unpinned.lock().expect("Expected the unpinned threads to be lockable").clone()
     }
 
     /// After the runner has been started, it just be joined so that all of the work can
-    /// be finished.
-    pub fn join(self) {
+    /// be finished. Returns how many of the threads stopped early because they panicked.
+    pub fn join(self) -> usize {
         self.handles
             .into_iter()
-            .for_each(|h| h.join().expect("Sending thread to finish"));
+            .map(|h| h.join())
+            .filter(|result| result.is_err())
+            .count()
     }
 
-    fn run(work: usize, eng: Engine, collector: &Sender<Message<Fact>>) {
+    fn run(work: usize, eng: Engine, profile: Option<Profile>, collector: &Sender<Fact>) {
+        let _finished = Finished(collector);
+        // The batch is dropped first, sending what's left in it before the end of file.
+        let mut batch = collector.batch();
         eng.run(work, |fact| {
-            collector
-                .send(Message::Body(fact))
-                .expect("to send the fact correctly");
+            let fact = match profile {
+                Some(ref profile) => fact.with_label("profile", profile.name()),
+                None => fact,
+            };
+            batch.push(fact).expect("to send the fact correctly");
         });
-        collector
-            .send(Message::EOF)
-            .expect("to send None correctly");
+    }
+}
+
+/// Holds the threads back until every one of them is ready to make its first request. The
+/// run starts when the last of them is ready, which every thread times its requests from.
+#[derive(Clone)]
+struct StartLine {
+    ready: Arc<(Mutex<Arrivals>, Condvar)>,
+    threads: usize,
+}
+
+/// How many threads are ready, and when the last of them was.
+#[derive(Default)]
+struct Arrivals {
+    ready: usize,
+    started: Option<Instant>,
+}
+
+impl StartLine {
+    fn new(threads: usize) -> StartLine {
+        StartLine {
+            ready: Arc::new((Mutex::new(Arrivals::default()), Condvar::new())),
+            threads,
+        }
+    }
+
+    fn ticket(&self) -> Ticket {
+        Ticket {
+            start_line: self.clone(),
+            punched: AtomicBool::new(false),
+        }
+    }
+
+    /// Counts a thread as ready, or as gone.
+    fn arrive(&self) {
+        let (ref ready, ref all_ready) = *self.ready;
+        if let Ok(mut ready) = ready.lock() {
+            ready.ready += 1;
+            if ready.ready >= self.threads {
+                ready.started.get_or_insert_with(Instant::now);
+            }
+        }
+        all_ready.notify_all();
+    }
+
+    /// Waits for every thread to be ready, returning when the run started.
+    fn wait(&self) -> Instant {
+        let (ref ready, ref all_ready) = *self.ready;
+        let mut ready = ready.lock().expect("Expected the start line to be lockable");
+        while ready.ready < self.threads {
+            ready = all_ready.wait(ready).expect("Expected the start line to be lockable");
+        }
+        *ready.started.get_or_insert_with(Instant::now)
+    }
+}
+
+/// A thread's place at the start line. A thread that stops before it's ready, such as by
+/// panicking while setting up, gives up its place when the ticket is dropped so the others
+/// aren't held back forever.
+struct Ticket {
+    start_line: StartLine,
+    punched: AtomicBool,
+}
+
+impl Ticket {
+    /// Marks the thread ready and waits for the rest, returning when the run started.
+    /// Only the first call counts.
+    fn punch(&self) -> Instant {
+        if !self.punched.swap(true, Ordering::SeqCst) {
+            self.start_line.arrive();
+        }
+        self.start_line.wait()
+    }
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        if !self.punched.swap(true, Ordering::SeqCst) {
+            self.start_line.arrive();
+        }
+    }
+}
+
+/// Tells the collector that a thread is done when it's dropped, so the collector still
+/// finishes if the thread panics part way through its work.
+struct Finished<'a>(&'a Sender<Fact>);
+
+impl<'a> Drop for Finished<'a> {
+    fn drop(&mut self) {
+        let _ = self.0.send(Message::EOF);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starts_once_every_thread_is_ready_or_gone() {
+        let start_line = StartLine::new(2);
+        let ready = start_line.ticket();
+        let gone = start_line.ticket();
+        let waiting = thread::spawn(move || ready.punch());
+        drop(gone);
+        let started = start_line.wait();
+        assert_eq!(waiting.join().unwrap(), started);
     }
 }
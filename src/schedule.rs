@@ -0,0 +1,206 @@
+use chart;
+use clock::{self, Clock};
+use report;
+use stats::TimeUnit;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A request that starts within this long of when it was due is on time.
+const TOLERANCE_MS: u64 = 1;
+
+/// How much of the run each point of the backlog covers.
+const BACKLOG_INTERVAL_MS: u64 = 1_000;
+
+/// The most points of the backlog that are printed. Neighbouring points are merged down to
+/// fit, keeping the furthest behind of them.
+const PRINTED_POINTS: usize = 64;
+
+/// How closely a paced run kept to its target rate. When the client can't keep up the
+/// requests start late and the server sees less load than was asked for, so the latencies
+/// of the run say little about the target rate.
+pub struct Schedule {
+    target_rps: f64,
+    clock: Arc<dyn Clock>,
+    start: Instant,
+    paced: u64,
+    late: u64,
+    /// The furthest behind schedule a request started in each interval of the run.
+    behind: Vec<Duration>,
+    achieved_rps: f64,
+    time_unit: TimeUnit,
+}
+
+impl Schedule {
+    /// Starts a schedule for the target rate whose time starts now.
+    pub fn new(target_rps: f64) -> Schedule {
+        Schedule {
+            target_rps,
+            clock: clock::system(),
+            start: Instant::now(),
+            paced: 0,
+            late: 0,
+            behind: Vec::new(),
+            achieved_rps: 0.,
+            time_unit: TimeUnit::Auto,
+        }
+    }
+
+    pub fn with_time_unit(mut self, unit: TimeUnit) -> Self {
+        self.time_unit = unit;
+        self
+    }
+
+    /// Times the schedule by the clock instead, from now by it.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.start = clock.now();
+        self.clock = clock;
+        self
+    }
+
+    /// Notes the rate the run achieved over all of its requests.
+    pub fn with_achieved_rps(mut self, achieved_rps: f64) -> Self {
+        self.achieved_rps = achieved_rps;
+        self
+    }
+
+    /// Counts a request that has just finished, if it was paced.
+    pub fn record(&mut self, lag: Option<Duration>) {
+        if let Some(lag) = lag {
+            let elapsed = self.clock.elapsed(self.start);
+            self.record_at(elapsed, lag);
+        }
+    }
+
+    fn record_at(&mut self, elapsed: Duration, lag: Duration) {
+        self.paced += 1;
+        if lag > Duration::from_millis(TOLERANCE_MS) {
+            self.late += 1;
+        }
+        let point = (elapsed.as_millis() / u128::from(BACKLOG_INTERVAL_MS)) as usize;
+        if self.behind.len() <= point {
+            self.behind.resize(point + 1, Duration::new(0, 0));
+        }
+        self.behind[point] = self.behind[point].max(lag);
+    }
+
+    pub fn target_rps(&self) -> f64 {
+        self.target_rps
+    }
+
+    pub fn achieved_rps(&self) -> f64 {
+        self.achieved_rps
+    }
+
+    /// The share of the paced requests that started late, as a percentage.
+    pub fn late_percent(&self) -> f64 {
+        if self.paced == 0 {
+            0.
+        } else {
+            self.late as f64 / self.paced as f64 * 100.
+        }
+    }
+
+    /// How many requests were due but not yet started at worst in each interval of the run,
+    /// which is how far behind schedule the run was at the target rate.
+    pub fn backlog(&self) -> Vec<f64> {
+        self.behind
+            .iter()
+            .map(|behind| (behind.as_secs_f64() * self.target_rps).round())
+            .collect()
+    }
+
+    /// The schedule in the shape of the JSON report.
+    pub fn report(&self) -> report::Schedule {
+        report::Schedule {
+            target_rps: self.target_rps,
+            achieved_rps: self.achieved_rps,
+            late: self.late,
+            late_percent: self.late_percent(),
+            backlog_interval_ms: BACKLOG_INTERVAL_MS,
+            backlog: self.backlog(),
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Schedule")?;
+        writeln!(f, "  Target:    {:.2} rps", self.target_rps)?;
+        writeln!(
+            f,
+            "  Achieved:  {:.2} rps ({:.1}% of the target)",
+            self.achieved_rps,
+            self.achieved_rps / self.target_rps * 100.
+        )?;
+        writeln!(
+            f,
+            "  Late:      {} of {} requests ({:.2}%) started more than {} after they were due",
+            self.late,
+            self.paced,
+            self.late_percent(),
+            self.time_unit.format(Duration::from_millis(TOLERANCE_MS))
+        )?;
+        let backlog = self.backlog();
+        let most = backlog.iter().cloned().fold(0., f64::max);
+        if most > 0. {
+            let factor = backlog.len().div_ceil(PRINTED_POINTS);
+            let points: Vec<f64> = backlog
+                .chunks(factor)
+                .map(|chunk| chunk.iter().cloned().fold(0., f64::max))
+                .collect();
+            writeln!(
+                f,
+                "  Backlog:   {} at most {} requests behind",
+                chart::sparkline(&points),
+                most
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clock::Manual;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn counts_the_requests_that_started_late() {
+        let mut schedule = Schedule::new(100.).with_achieved_rps(90.);
+        schedule.record_at(ms(0), ms(0));
+        schedule.record_at(ms(500), ms(1));
+        schedule.record_at(ms(1_500), ms(50));
+        schedule.record_at(ms(1_600), ms(20));
+        assert_eq!(schedule.late, 2);
+        assert_eq!(schedule.late_percent(), 50.);
+        assert_eq!(schedule.backlog(), vec![0., 5.]);
+
+        let printed = schedule.to_string();
+        assert!(printed.contains("Achieved:  90.00 rps (90.0% of the target)"));
+        assert!(printed.contains("Backlog:   ▁█ at most 5 requests behind"));
+    }
+
+    #[test]
+    fn times_the_requests_by_its_clock() {
+        let clock = Manual::new();
+        let mut schedule = Schedule::new(100.).with_clock(Arc::new(clock.clone()));
+        schedule.record(Some(ms(0)));
+        clock.advance(ms(2_100));
+        schedule.record(Some(ms(30)));
+        assert_eq!(schedule.backlog(), vec![0., 0., 3.]);
+    }
+
+    #[test]
+    fn leaves_out_the_backlog_when_every_request_was_on_time() {
+        let mut schedule = Schedule::new(100.);
+        schedule.record(None);
+        schedule.record_at(ms(0), ms(0));
+        assert_eq!(schedule.paced, 1);
+        assert!(!schedule.to_string().contains("Backlog"));
+    }
+}
@@ -0,0 +1,275 @@
+use std::io::{self, BufRead, BufReader, Cursor, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+use tungstenite::{self, Message};
+
+/// Starts the test server on the address on a thread of its own, for the tests of the
+/// engines, and returns the address it's listening on so that a port of 0 can be given.
+#[allow(dead_code)]
+pub fn start(addr: &str) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    let addr = listener.local_addr()?;
+    thread::spawn(move || accept(listener));
+    Ok(addr)
+}
+
+/// Runs the test server on the address until the process is stopped. The url it's
+/// listening on is printed first, so that whatever started it can read the port it got.
+pub fn listen(addr: &str) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    println!("Listening on http://{}", listener.local_addr()?);
+    accept(listener);
+    Ok(())
+}
+
+/// Serves each connection on a thread of its own.
+fn accept(listener: TcpListener) {
+    for stream in listener.incoming().flatten() {
+        thread::spawn(move || serve(stream));
+    }
+}
+
+/// A tiny http/1.1 server that answers every engine the same way, so that they can be
+/// tested against it without the network. Its routes are:
+///
+/// * `/bytes/N` answers with a body of N bytes.
+/// * `/status/N` answers with the status and no body.
+/// * `/headers` answers with the request's `x-` headers and credentials, a line each in
+///   order of name.
+/// * `/host` answers with the request's `host` headers, a line each.
+/// * `/echo` answers with the body of the request.
+/// * `/redirect/N` redirects N times before answering.
+/// * `/redirect-to/URL` redirects to the url.
+/// * `/delay/MS` answers after waiting that long.
+/// * `/ws` upgrades to a websocket that echoes each message.
+///
+/// Anything else is answered with `ok`. Connections are kept alive unless the request asks
+/// for them to be closed or is http/1.0.
+fn serve(stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let mut parts = line.split_whitespace();
+        let (method, target, version) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(method), Some(target), Some(version)) => (method.to_string(), target.to_string(), version.to_string()),
+            _ => return Ok(()),
+        };
+        let mut head = line.clone();
+        let mut headers = Vec::new();
+        loop {
+            line.clear();
+            reader.read_line(&mut line)?;
+            head.push_str(&line);
+            match line.trim_end().find(':') {
+                Some(split) => headers.push((line[..split].trim().to_lowercase(), line[split + 1..].trim().to_string())),
+                None => break,
+            }
+        }
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(header, _)| header == name)
+                .map(|(_, value)| value.to_lowercase())
+        };
+        let body = if header("transfer-encoding").is_some_and(|encoding| encoding == "chunked") {
+            read_chunks(&mut reader)?
+        } else {
+            let length = header("content-length").and_then(|length| length.parse().ok()).unwrap_or(0);
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body)?;
+            body
+        };
+        let path = target.split('?').next().unwrap_or_default();
+        if path == "/ws" && header("upgrade").is_some_and(|upgrade| upgrade == "websocket") {
+            // The client waits for the handshake to be answered, so nothing past the
+            // request has been read.
+            return echo_messages(Replayed {
+                head: Cursor::new(head.into_bytes()),
+                stream: writer,
+            });
+        }
+        let close = header("connection").is_some_and(|connection| connection == "close") || version == "HTTP/1.0";
+
+        let (status, location, body) = respond(path, &headers, body);
+        let mut head = format!("HTTP/1.1 {} {}\r\ncontent-length: {}\r\n", status, reason(status), body.len());
+        if let Some(location) = location {
+            head.push_str(&format!("location: {}\r\n", location));
+        }
+        if close {
+            head.push_str("connection: close\r\n");
+        }
+        head.push_str("\r\n");
+        writer.write_all(head.as_bytes())?;
+        if method != "HEAD" {
+            writer.write_all(&body)?;
+        }
+        writer.flush()?;
+        if close {
+            return Ok(());
+        }
+    }
+}
+
+/// Answers the websocket handshake and echoes each message until the client closes.
+fn echo_messages(stream: Replayed) -> io::Result<()> {
+    let mut socket = tungstenite::accept(stream).map_err(|e| io::Error::other(e.to_string()))?;
+    loop {
+        match socket.read_message() {
+            Ok(message @ Message::Text(_)) | Ok(message @ Message::Binary(_)) => {
+                socket.write_message(message).map_err(|e| io::Error::other(e.to_string()))?;
+            }
+            Ok(_) => {}
+            Err(_) => return Ok(()),
+        }
+    }
+}
+
+/// A connection whose request has already been read, which reads it again before the
+/// rest of the connection.
+struct Replayed {
+    head: Cursor<Vec<u8>>,
+    stream: TcpStream,
+}
+
+impl Read for Replayed {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.head.read(buf)? {
+            0 => self.stream.read(buf),
+            read => Ok(read),
+        }
+    }
+}
+
+impl Write for Replayed {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/// Reads a chunked body, as hyper sends them, up to and past its last chunk.
+fn read_chunks<R: BufRead>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line)?;
+        let size = line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..])?;
+        line.clear();
+        reader.read_line(&mut line)?;
+        if size == 0 {
+            return Ok(body);
+        }
+    }
+}
+
+/// The status, where to redirect to and the body of the answer to a request for the path.
+fn respond(path: &str, headers: &[(String, String)], body: Vec<u8>) -> (u16, Option<String>, Vec<u8>) {
+    let mut segments = path.trim_start_matches('/').splitn(2, '/');
+    let (route, arg) = (segments.next().unwrap_or_default(), segments.next());
+    let number = arg.and_then(|arg| arg.parse::<u64>().ok());
+    match (route, number) {
+        ("bytes", Some(bytes)) => (200, None, vec![b'x'; bytes as usize]),
+        ("status", Some(status)) if (100..600).contains(&status) => (status as u16, None, Vec::new()),
+        ("headers", None) => {
+            let mut echoed: Vec<String> = headers
+                .iter()
+                .filter(|(name, _)| {
+                    name.starts_with("x-") || ["authorization", "cookie", "proxy-authorization"].contains(&name.as_str())
+                })
+                .map(|(name, value)| format!("{}: {}\n", name, value))
+                .collect();
+            echoed.sort();
+            (200, None, echoed.concat().into_bytes())
+        }
+        ("host", None) => {
+            let hosts: Vec<String> = headers
+                .iter()
+                .filter(|(name, _)| name == "host")
+                .map(|(_, value)| format!("{}\n", value))
+                .collect();
+            (200, None, hosts.concat().into_bytes())
+        }
+        ("echo", None) => (200, None, body),
+        ("redirect", Some(0)) => (200, None, b"ok".to_vec()),
+        ("redirect", Some(times)) => (302, Some(format!("/redirect/{}", times - 1)), Vec::new()),
+        ("redirect-to", None) if arg.is_some() => (302, arg.map(str::to_string), Vec::new()),
+        ("delay", Some(ms)) => {
+            thread::sleep(Duration::from_millis(ms));
+            (200, None, b"ok".to_vec())
+        }
+        _ => (200, None, b"ok".to_vec()),
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        302 => "Found",
+        404 => "Not Found",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sends the request on a connection of its own and reads the whole answer.
+    fn exchange(addr: SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut answer = String::new();
+        stream.read_to_string(&mut answer).unwrap();
+        answer
+    }
+
+    #[test]
+    fn answers_each_route() {
+        let addr = start("127.0.0.1:0").unwrap();
+        let get = |path: &str, headers: &str| {
+            exchange(addr, &format!("GET {} HTTP/1.1\r\nhost: a\r\n{}connection: close\r\n\r\n", path, headers))
+        };
+        assert!(get("/bytes/3", "").ends_with("content-length: 3\r\nconnection: close\r\n\r\nxxx"));
+        assert!(get("/status/404", "").starts_with("HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n"));
+        assert!(get("/redirect/2?a=1", "").contains("location: /redirect/1\r\n"));
+        assert!(get("/redirect-to/http://b/c", "").contains("location: http://b/c\r\n"));
+        assert!(get("/headers", "X-B: 2\r\nx-a: 1\r\nuser-agent: t\r\n").ends_with("\r\n\r\nx-a: 1\nx-b: 2\n"));
+        assert!(get("/host", "").ends_with("\r\n\r\na\n"));
+        let echoed = exchange(addr, "POST /echo HTTP/1.0\r\ncontent-length: 4\r\n\r\nbody");
+        assert!(echoed.ends_with("\r\n\r\nbody"));
+        let chunked = "POST /echo HTTP/1.0\r\ntransfer-encoding: chunked\r\n\r\n2\r\nbo\r\n2\r\ndy\r\n0\r\n\r\n";
+        assert!(exchange(addr, chunked).ends_with("content-length: 4\r\nconnection: close\r\n\r\nbody"));
+        let head = exchange(addr, "HEAD /bytes/3 HTTP/1.0\r\n\r\n");
+        assert!(head.ends_with("content-length: 3\r\nconnection: close\r\n\r\n"));
+    }
+
+    #[test]
+    fn keeps_the_connection_alive() {
+        let addr = start("127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /bytes/1 HTTP/1.1\r\nhost: a\r\n\r\nGET /bytes/2 HTTP/1.1\r\nconnection: close\r\n\r\n")
+            .unwrap();
+        let mut answers = String::new();
+        stream.read_to_string(&mut answers).unwrap();
+        assert_eq!(answers.matches("HTTP/1.1 200 OK").count(), 2);
+        assert!(answers.ends_with("\r\n\r\nxx"));
+    }
+}
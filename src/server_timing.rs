@@ -0,0 +1,247 @@
+use report;
+use stats::{Fact, ToMilliseconds};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// The phase whose duration is taken as the server's whole time when a response reports
+/// it, rather than the sum of its phases.
+const TOTAL: &str = "total";
+
+/// Reads the phases with a duration from the values of a response's `Server-Timing`
+/// headers, such as `db;dur=53, app;desc="Render";dur=47.2`. Phases without a duration,
+/// or with one too long to be a duration at all, are left out.
+pub fn parse(values: &[String]) -> Vec<(String, Duration)> {
+    values
+        .iter()
+        .flat_map(|value| split_outside_quotes(value, ','))
+        .filter_map(|metric| {
+            let mut params = split_outside_quotes(metric, ';').into_iter().map(str::trim);
+            let name = params.next().filter(|name| !name.is_empty())?;
+            let duration = params
+                .filter_map(|param| {
+                    let at = param.find('=')?;
+                    match param[..at].trim() {
+                        "dur" => param[at + 1..].trim().trim_matches('"').parse::<f64>().ok(),
+                        _ => None,
+                    }
+                })
+                .find_map(|ms| Duration::try_from_secs_f64(ms / 1_000.).ok())?;
+            Some((name.to_string(), duration))
+        })
+        .collect()
+}
+
+fn split_outside_quotes(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut start, mut quoted) = (0, false);
+    for (at, c) in value.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            parts.push(&value[start..at]);
+            start = at + 1;
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+/// The phases that the servers reported their time in with `Server-Timing` headers,
+/// beside the latency the client saw for the same responses. Whatever the latency has
+/// over the server's time was spent on the network and in the client.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ServerTiming {
+    phases: BTreeMap<String, Phase>,
+    /// How many responses reported any phase.
+    timed: u64,
+    /// The latency of the responses that reported any phase, in all.
+    latency: Duration,
+    /// The time the servers spent on those responses in all, by their `total` phase or
+    /// else the sum of their phases.
+    server: Duration,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+struct Phase {
+    count: u64,
+    total: Duration,
+}
+
+impl ServerTiming {
+    /// Counts the phases of a single fact, if its response reported any.
+    pub fn add(&mut self, fact: &Fact) {
+        let phases = fact.server_timing();
+        if phases.is_empty() {
+            return;
+        }
+        self.timed += 1;
+        self.latency += fact.duration();
+        let mut sum = Duration::new(0, 0);
+        let mut total = None;
+        for &(ref name, duration) in phases {
+            let phase = self.phases.entry(name.clone()).or_default();
+            phase.count += 1;
+            // The server says how long its phases took, so they may add up to anything.
+            phase.total = phase.total.saturating_add(duration);
+            sum = sum.saturating_add(duration);
+            if name == TOTAL {
+                total = Some(duration);
+            }
+        }
+        self.server = self.server.saturating_add(total.unwrap_or(sum));
+    }
+
+    pub fn merge(&mut self, other: &ServerTiming) {
+        for (name, other) in &other.phases {
+            let phase = self.phases.entry(name.clone()).or_default();
+            phase.count += other.count;
+            phase.total = phase.total.saturating_add(other.total);
+        }
+        self.timed += other.timed;
+        self.latency += other.latency;
+        self.server = self.server.saturating_add(other.server);
+    }
+
+    /// Whether any response reported its phases.
+    pub fn happened(&self) -> bool {
+        self.timed > 0
+    }
+
+    /// How many responses reported any phase.
+    pub fn timed(&self) -> u64 {
+        self.timed
+    }
+
+    /// Each phase by name, with how many responses reported it and its average duration.
+    pub fn phases(&self) -> Vec<(&str, u64, Duration)> {
+        self.phases
+            .iter()
+            .map(|(name, phase)| (name.as_str(), phase.count, phase.total / phase.count as u32))
+            .collect()
+    }
+
+    /// The average time the servers reported spending on a response.
+    pub fn average_server(&self) -> Duration {
+        self.average(self.server)
+    }
+
+    /// The average latency the client saw of the responses that reported their phases.
+    pub fn average_latency(&self) -> Duration {
+        self.average(self.latency)
+    }
+
+    fn average(&self, total: Duration) -> Duration {
+        if self.timed == 0 {
+            Duration::new(0, 0)
+        } else {
+            total / self.timed as u32
+        }
+    }
+
+    /// The phases in the shape of the JSON report.
+    pub fn report(&self) -> report::ServerTiming {
+        report::ServerTiming {
+            timed: self.timed,
+            average_latency_ms: self.average_latency().to_ms(),
+            average_server_ms: self.average_server().to_ms(),
+            phases: self
+                .phases
+                .iter()
+                .map(|(name, phase)| {
+                    (
+                        name.clone(),
+                        report::ServerPhase {
+                            count: phase.count,
+                            average_ms: (phase.total / phase.count as u32).to_ms(),
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// The phases of a saved report, to merge with others.
+    pub fn from_report(report: &report::ServerTiming) -> ServerTiming {
+        let total = |average_ms: f64, count: u64| {
+            Duration::try_from_secs_f64(average_ms * count as f64 / 1_000.).unwrap_or(Duration::MAX)
+        };
+        ServerTiming {
+            phases: report
+                .phases
+                .iter()
+                .map(|(name, phase)| {
+                    (
+                        name.clone(),
+                        Phase {
+                            count: phase.count,
+                            total: total(phase.average_ms, phase.count),
+                        },
+                    )
+                })
+                .collect(),
+            timed: report.timed,
+            latency: total(report.average_latency_ms, report.timed),
+            server: total(report.average_server_ms, report.timed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use content_length::ContentLength;
+
+    fn values(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn reads_the_phases_with_a_duration() {
+        let phases = parse(&values(&["db;dur=53, app;desc=\"Render, then send\";dur=47.2", "miss, cache;dur=bad"]));
+        assert_eq!(
+            phases,
+            vec![
+                ("db".to_string(), Duration::from_millis(53)),
+                ("app".to_string(), Duration::from_micros(47_200)),
+            ]
+        );
+        assert_eq!(parse(&[]), Vec::new());
+        assert_eq!(parse(&values(&["huge;dur=1e30, negative;dur=-1"])), Vec::new());
+
+        let mut timing = ServerTiming::default();
+        let huge = Duration::from_secs(u64::MAX);
+        let fact = Fact::record(ContentLength::zero(), 200, Duration::from_millis(1))
+            .with_server_timing(vec![("a".to_string(), huge), ("b".to_string(), huge)]);
+        timing.add(&fact);
+        timing.add(&fact);
+        assert_eq!(timing.average_server(), Duration::MAX / 2);
+    }
+
+    #[test]
+    fn splits_the_latency_between_the_server_and_the_rest() {
+        let fact = |ms: u64, phases: &[(&str, u64)]| {
+            Fact::record(ContentLength::zero(), 200, Duration::from_millis(ms)).with_server_timing(
+                phases
+                    .iter()
+                    .map(|&(name, ms)| (name.to_string(), Duration::from_millis(ms)))
+                    .collect(),
+            )
+        };
+        let mut timing = ServerTiming::default();
+        timing.add(&fact(30, &[("db", 10), ("app", 10)]));
+        timing.add(&fact(50, &[("db", 20), ("total", 40)]));
+        timing.add(&fact(500, &[]));
+        assert_eq!(timing.timed(), 2);
+        assert_eq!(timing.average_latency(), Duration::from_millis(40));
+        assert_eq!(timing.average_server(), Duration::from_millis(30));
+        assert_eq!(
+            timing.phases(),
+            vec![
+                ("app", 1, Duration::from_millis(10)),
+                ("db", 2, Duration::from_millis(15)),
+                ("total", 1, Duration::from_millis(40)),
+            ]
+        );
+        assert_eq!(ServerTiming::from_report(&timing.report()), timing);
+    }
+}
@@ -0,0 +1,221 @@
+use content_length::{ContentLength, SizeUnit};
+use histogram::Histogram;
+use report;
+use stats::{Fact, TimeUnit};
+use std::fmt;
+use std::time::Duration;
+
+/// The latencies of the responses grouped by the size of their bodies, so that slow
+/// responses that are only slow because they're big can be told apart from slow responses
+/// of any size. Each row covers twice the sizes of the one below it, and the requests that
+/// got no response aren't counted.
+pub struct SizeLatency {
+    /// The latencies of each row, where row `n` counts the bodies under `2^n` bytes that
+    /// weren't counted by a lower row.
+    rows: Vec<Histogram>,
+    totals: Vec<Duration>,
+    /// The sums that the correlation of size and latency is worked out from.
+    n: f64,
+    sum_bytes: f64,
+    sum_ms: f64,
+    sum_bytes_ms: f64,
+    sum_bytes_squared: f64,
+    sum_ms_squared: f64,
+    time_unit: TimeUnit,
+    size_unit: SizeUnit,
+}
+
+impl Default for SizeLatency {
+    fn default() -> SizeLatency {
+        SizeLatency::new()
+    }
+}
+
+impl SizeLatency {
+    pub fn new() -> SizeLatency {
+        SizeLatency {
+            rows: Vec::new(),
+            totals: Vec::new(),
+            n: 0.,
+            sum_bytes: 0.,
+            sum_ms: 0.,
+            sum_bytes_ms: 0.,
+            sum_bytes_squared: 0.,
+            sum_ms_squared: 0.,
+            time_unit: TimeUnit::Auto,
+            size_unit: SizeUnit::Binary,
+        }
+    }
+
+    pub fn with_time_unit(mut self, unit: TimeUnit) -> Self {
+        self.time_unit = unit;
+        self
+    }
+
+    pub fn with_size_unit(mut self, unit: SizeUnit) -> Self {
+        self.size_unit = unit;
+        self
+    }
+
+    /// Counts a request that has just finished, if it got a response.
+    pub fn record(&mut self, fact: &Fact) {
+        if !fact.is_error() {
+            self.record_size(fact.content_length().bytes(), fact.duration());
+        }
+    }
+
+    fn record_size(&mut self, bytes: u64, duration: Duration) {
+        let row = row_of(bytes);
+        if self.rows.len() <= row {
+            self.rows.resize(row + 1, Histogram::new());
+            self.totals.resize(row + 1, Duration::new(0, 0));
+        }
+        self.rows[row].record(duration);
+        self.totals[row] += duration;
+
+        let (x, y) = (bytes as f64, duration.as_secs_f64() * 1_000.);
+        self.n += 1.;
+        self.sum_bytes += x;
+        self.sum_ms += y;
+        self.sum_bytes_ms += x * y;
+        self.sum_bytes_squared += x * x;
+        self.sum_ms_squared += y * y;
+    }
+
+    /// The rows that counted anything, with their count, average and 99th percentile.
+    fn counted(&self) -> Vec<(usize, u64, Duration, Duration)> {
+        self.rows
+            .iter()
+            .zip(&self.totals)
+            .enumerate()
+            .filter(|&(_, (histogram, _))| histogram.count() > 0)
+            .map(|(row, (histogram, &total))| {
+                let count = histogram.count();
+                (row, count, total / count as u32, histogram.value_at_quantile(0.99))
+            })
+            .collect()
+    }
+
+    /// The Pearson correlation of the size of each body with the latency of its request,
+    /// from -1 to 1. Nothing when every body was the same size, or every latency the same.
+    pub fn correlation(&self) -> Option<f64> {
+        let covariance = self.n * self.sum_bytes_ms - self.sum_bytes * self.sum_ms;
+        let bytes_spread = self.n * self.sum_bytes_squared - self.sum_bytes.powi(2);
+        let ms_spread = self.n * self.sum_ms_squared - self.sum_ms.powi(2);
+        if bytes_spread <= 0. || ms_spread <= 0. {
+            None
+        } else {
+            Some((covariance / (bytes_spread.sqrt() * ms_spread.sqrt())).clamp(-1., 1.))
+        }
+    }
+
+    /// The latency by size in the shape of the JSON report.
+    pub fn report(&self) -> report::SizeLatency {
+        report::SizeLatency {
+            correlation: self.correlation(),
+            rows: self
+                .counted()
+                .into_iter()
+                .map(|(row, count, average, p99)| report::SizeRow {
+                    under_bytes: upper_bound(row),
+                    count,
+                    average_ms: average.as_secs_f64() * 1_000.,
+                    p99_ms: p99.as_secs_f64() * 1_000.,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The row that counts the body, which is the number of bits in its bytes.
+fn row_of(bytes: u64) -> usize {
+    (64 - bytes.leading_zeros()) as usize
+}
+
+/// The size that every body counted by the row is under.
+fn upper_bound(row: usize) -> u64 {
+    1u64.checked_shl(row as u32).unwrap_or(u64::MAX)
+}
+
+impl fmt::Display for SizeLatency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let counted = self.counted();
+        if counted.is_empty() {
+            return Ok(());
+        }
+        let labels: Vec<String> = counted
+            .iter()
+            .map(|&(row, _, _, _)| match row {
+                0 => "0 B".to_string(),
+                row => format!("< {}", self.size_unit.format(&ContentLength::new(upper_bound(row)))),
+            })
+            .collect();
+        let width = labels.iter().map(|label| label.len()).max().unwrap_or(0).max("Size".len());
+        let time = |duration: Duration| self.time_unit.format(duration);
+
+        writeln!(f, "Latency by response size:")?;
+        writeln!(
+            f,
+            "  {:<width$}  {:>10}  {:>12}  {:>12}",
+            "Size",
+            "Requests",
+            "Average",
+            "99%",
+            width = width
+        )?;
+        for (label, &(_, count, average, p99)) in labels.iter().zip(&counted) {
+            writeln!(
+                f,
+                "  {:<width$}  {:>10}  {:>12}  {:>12}",
+                label,
+                count,
+                time(average),
+                time(p99),
+                width = width
+            )?;
+        }
+        if let Some(correlation) = self.correlation() {
+            writeln!(f, "  Correlation of size and latency: {:.2}", correlation)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn groups_the_latencies_by_size() {
+        let mut sizes = SizeLatency::new().with_time_unit(TimeUnit::Millis);
+        sizes.record_size(0, ms(1));
+        sizes.record_size(1_000, ms(2));
+        sizes.record_size(1_020, ms(4));
+        sizes.record_size(100_000, ms(40));
+        sizes.record(&Fact::error(ms(500)));
+
+        let printed = sizes.to_string();
+        assert!(printed.starts_with("Latency by response size:\n"));
+        assert!(printed.contains("  0 B                   1       1.00 ms       1.00 ms\n"));
+        assert!(printed.contains("  < 1024 B              2       3.00 ms       4.00 ms\n"));
+        assert!(printed.contains("  < 128.00 KB           1      40.00 ms      39.94 ms\n"));
+        assert!(printed.ends_with("  Correlation of size and latency: 1.00\n"));
+
+        let report = sizes.report();
+        assert_eq!(report.rows.len(), 3);
+        assert_eq!((report.rows[1].under_bytes, report.rows[1].count), (1_024, 2));
+    }
+
+    #[test]
+    fn has_no_correlation_when_every_body_is_the_same_size() {
+        let mut sizes = SizeLatency::new();
+        sizes.record_size(10, ms(1));
+        sizes.record_size(10, ms(5));
+        assert_eq!(sizes.correlation(), None);
+        assert!(!sizes.to_string().contains("Correlation"));
+    }
+}
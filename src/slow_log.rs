@@ -0,0 +1,89 @@
+use metadata;
+use stats::{Fact, TimeUnit};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// Appends a line to a file for every request slower than a threshold, so that the outliers
+/// of a run can be looked into without recording every fact. Every worker shares the file.
+#[derive(Clone)]
+pub struct SlowLog {
+    threshold: Duration,
+    file: Arc<Mutex<File>>,
+}
+
+impl SlowLog {
+    /// Appends to the file, creating it if needed.
+    pub fn open(threshold: Duration, path: &str) -> io::Result<SlowLog> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(SlowLog {
+            threshold,
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    /// Logs the request if it was slower than the threshold.
+    pub fn record(&self, method: &str, url: &str, fact: &Fact) {
+        if fact.duration() <= self.threshold {
+            return;
+        }
+        let line = line(SystemTime::now(), method, url, fact);
+        let mut file = self.file.lock().expect("Expected the slow log to be writable");
+        // A line that fails to write is lost rather than stopping the run.
+        let _ = writeln!(file, "{}", line);
+    }
+}
+
+/// The tab separated line of a slow request: when it finished, the request, its status,
+/// how long it took and its labels, such as the captured headers, after its trace id.
+fn line(time: SystemTime, method: &str, url: &str, fact: &Fact) -> String {
+    let status = match fact.status() {
+        Some(status) => status.to_string(),
+        None if fact.is_error() => "error".to_string(),
+        None => "ok".to_string(),
+    };
+    let labels: Vec<String> = fact
+        .trace()
+        .into_iter()
+        .chain(fact.labels())
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    format!(
+        "{}\t{} {}\t{}\t{}\t{}",
+        metadata::timestamp(time),
+        method,
+        url,
+        status,
+        TimeUnit::Millis.format(fact.duration()),
+        labels.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use content_length::ContentLength;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn writes_a_line_for_each_request() {
+        let fact = Fact::record(ContentLength::new(10), 200, Duration::from_millis(512))
+            .with_label("x-cache", "MISS");
+        assert_eq!(
+            line(UNIX_EPOCH, "GET", "http://a/", &fact),
+            "1970-01-01T00:00:00Z\tGET http://a/\t200\t512.00 ms\tx-cache=MISS"
+        );
+        let traced = Fact::error(Duration::from_secs(1))
+            .with_trace(Some(("x-request-id".to_string(), "abc".to_string())))
+            .with_label("x-cache", "MISS");
+        assert_eq!(
+            line(UNIX_EPOCH, "GET", "http://a/", &traced),
+            "1970-01-01T00:00:00Z\tGET http://a/\terror\t1000.00 ms\tx-request-id=abc, x-cache=MISS"
+        );
+        assert_eq!(
+            line(UNIX_EPOCH, "GET", "http://a/", &Fact::error(Duration::from_secs(1))),
+            "1970-01-01T00:00:00Z\tGET http://a/\terror\t1000.00 ms\t"
+        );
+    }
+}
@@ -1,10 +1,17 @@
 use std::time::Duration;
 use std::{cmp, fmt};
+use accumulator::Accumulator;
 use chart::Chart;
-use content_length::ContentLength;
-use std::collections::HashMap;
+use checksum::{Checksum, Checksums};
+use content_length::{ContentLength, SizeUnit};
+use bind::Family;
+use engine::{Method, Version};
+use histogram::Histogram;
+use report;
+use server_timing::ServerTiming;
+use std::collections::{BTreeMap, HashMap};
 
-trait ToMilliseconds {
+pub trait ToMilliseconds {
     fn to_ms(&self) -> f64;
 }
 
@@ -36,6 +43,15 @@ impl From<MS> for Duration {
 mod millisecond_tests {
     use super::*;
 
+    #[test]
+    fn scales_time_units_to_the_duration() {
+        assert_eq!(TimeUnit::Auto.format(Duration::from_micros(850)), "850.00 µs");
+        assert_eq!(TimeUnit::Auto.format(Duration::from_micros(12_345)), "12.35 ms");
+        assert_eq!(TimeUnit::Auto.format(Duration::from_millis(2_500)), "2.50 s");
+        assert_eq!(TimeUnit::Millis.format(Duration::from_micros(850)), "0.85 ms");
+        assert_eq!(TimeUnit::Seconds.format(Duration::from_millis(20)), "0.02 s");
+    }
+
     #[test]
     fn exchange_duration_to_ms() {
         assert_eq!(Duration::new(1, 500000).to_ms(), 1000.5f64);
@@ -54,66 +70,496 @@ mod millisecond_tests {
     }
 }
 
-/// A single datum or "fact" about the requests
-#[derive(Debug)]
+/// A single datum or "fact" about the requests. A fact without a status is a request
+/// that errored before any response was received, unless it's a round trip that has no
+/// status to begin with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Fact {
-    status: u16,
+    status: Option<u16>,
     duration: Duration,
     content_length: ContentLength,
+    new_connection: Option<bool>,
+    download: Option<Duration>,
+    redirects: u32,
+    pool_wait: Option<Duration>,
+    lag: Option<Duration>,
+    /// When the request started, since the run did.
+    started: Option<Duration>,
+    retry_after: Option<Duration>,
+    backoff: Option<Duration>,
+    body_mismatch: bool,
+    echo_mismatch: bool,
+    declared_length: Option<u64>,
+    checksum: Option<Checksum>,
+    labels: Vec<(String, String)>,
+    /// The index of the url the request was sent to.
+    url: Option<usize>,
+    /// The method the request was sent with, which isn't known of websockets and raw tcp.
+    method: Option<Method>,
+    /// The family of the connection the request was sent over, if the engine could tell.
+    family: Option<Family>,
+    /// The phases the response reported with its `Server-Timing` headers.
+    server_timing: Vec<(String, Duration)>,
+    /// The name and value of the header that carried the request's trace id.
+    trace: Option<(String, String)>,
+    /// The http version the response came back over, if the engine could tell.
+    version: Option<Version>,
+    /// Whether the fact is a round trip that got its answer but has no status of its own,
+    /// such as a websocket message or a tcp reply.
+    #[serde(default)]
+    round_trip: bool,
 }
 
 impl Fact {
     pub fn record(content_length: ContentLength, status: u16, duration: Duration) -> Fact {
         Fact {
             duration,
-            status,
+            status: Some(status),
             content_length,
+            new_connection: None,
+            download: None,
+            redirects: 0,
+            pool_wait: None,
+            lag: None,
+            started: None,
+            retry_after: None,
+            backoff: None,
+            body_mismatch: false,
+            echo_mismatch: false,
+            declared_length: None,
+            checksum: None,
+            labels: Vec::new(),
+            url: None,
+            method: None,
+            family: None,
+            server_timing: Vec::new(),
+            trace: None,
+            version: None,
+            round_trip: false,
+        }
+    }
+
+    /// Records a round trip that got its answer but has no status of its own, such as a
+    /// websocket message or a tcp reply.
+    pub fn round_trip(content_length: ContentLength, duration: Duration) -> Fact {
+        Fact {
+            status: None,
+            round_trip: true,
+            ..Fact::record(content_length, 0, duration)
+        }
+    }
+
+    /// Records a request that failed without a response, such as a refused connection.
+    pub fn error(duration: Duration) -> Fact {
+        Fact {
+            duration,
+            status: None,
+            content_length: ContentLength::zero(),
+            new_connection: None,
+            download: None,
+            redirects: 0,
+            pool_wait: None,
+            lag: None,
+            started: None,
+            retry_after: None,
+            backoff: None,
+            body_mismatch: false,
+            echo_mismatch: false,
+            declared_length: None,
+            checksum: None,
+            labels: Vec::new(),
+            url: None,
+            method: None,
+            family: None,
+            server_timing: Vec::new(),
+            trace: None,
+            version: None,
+            round_trip: false,
+        }
+    }
+
+    /// Notes whether the request had to open a new connection rather than reuse one. Not
+    /// every engine can tell, so this is unknown unless set.
+    pub fn with_new_connection(mut self, new_connection: bool) -> Fact {
+        self.new_connection = Some(new_connection);
+        self
+    }
+
+    /// Notes how long the body took to download once the response headers arrived.
+    pub fn with_download(mut self, download: Duration) -> Fact {
+        self.download = Some(download);
+        self
+    }
+
+    /// Notes how many redirects were followed to reach the response.
+    pub fn with_redirects(mut self, redirects: u32) -> Fact {
+        self.redirects = redirects;
+        self
+    }
+
+    /// How many redirects were followed to reach the response.
+    pub fn redirects(&self) -> u32 {
+        self.redirects
+    }
+
+    /// Notes how long the request waited for a free connection slot before it was sent.
+    pub fn with_pool_wait(mut self, wait: Duration) -> Fact {
+        self.pool_wait = Some(wait);
+        self
+    }
+
+    /// Notes how long after it was due the request started when the requests were paced,
+    /// which is zero when it started on time.
+    pub fn with_lag(mut self, lag: Option<Duration>) -> Fact {
+        self.lag = lag;
+        self
+    }
+
+    /// How long after it was due the request started, if the requests were paced.
+    pub fn lag(&self) -> Option<Duration> {
+        self.lag
+    }
+
+    /// Notes when the request started, since the run did.
+    pub fn with_started(mut self, started: Duration) -> Fact {
+        self.started = Some(started);
+        self
+    }
+
+    /// When the request started, since the run did, if the engine timed it.
+    pub fn started(&self) -> Option<Duration> {
+        self.started
+    }
+
+    /// When the request ended, since the run did, if the engine timed it.
+    pub fn ended(&self) -> Option<Duration> {
+        self.started.map(|started| started + self.duration)
+    }
+
+    /// Notes how long the response asked for the client to wait before trying again, in
+    /// its `Retry-After` header.
+    pub fn with_retry_after(mut self, retry_after: Option<Duration>) -> Fact {
+        self.retry_after = retry_after;
+        self
+    }
+
+    /// How long the response asked for the client to wait before trying again, if it did.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.retry_after
+    }
+
+    /// Notes how long the worker waits before its next request, when it respects the
+    /// `Retry-After` of the response.
+    pub fn with_backoff(mut self, backoff: Option<Duration>) -> Fact {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Notes whether the body failed the checks it was expected to pass, which makes the
+    /// request a failure whatever its status.
+    pub fn with_body_mismatch(mut self, mismatch: bool) -> Fact {
+        self.body_mismatch = mismatch;
+        self
+    }
+
+    /// Whether the body failed the checks it was expected to pass.
+    pub fn body_mismatch(&self) -> bool {
+        self.body_mismatch
+    }
+
+    /// Notes whether the response failed to echo the token its request was sent with,
+    /// which makes the request a failure whatever its status.
+    pub fn with_echo_mismatch(mut self, mismatch: bool) -> Fact {
+        self.echo_mismatch = mismatch;
+        self
+    }
+
+    /// Whether the response failed to echo the token its request was sent with.
+    pub fn echo_mismatch(&self) -> bool {
+        self.echo_mismatch
+    }
+
+    /// Notes the length the response declared for its body in its content-length header,
+    /// if it had one and was meant to have a body.
+    pub fn with_declared_length(mut self, length: Option<u64>) -> Fact {
+        self.declared_length = length;
+        self
+    }
+
+    /// Whether the body that was read wasn't the length its response declared, such as when
+    /// the server closed the connection part way through the body.
+    pub fn length_mismatch(&self) -> bool {
+        self.declared_length
+            .is_some_and(|declared| declared != self.content_length.bytes())
+    }
+
+    /// Notes the checksum of the body.
+    pub fn with_checksum(mut self, checksum: Checksum) -> Fact {
+        self.checksum = Some(checksum);
+        self
+    }
+
+    /// The checksum of the body, if it was checksummed.
+    pub fn checksum(&self) -> Option<Checksum> {
+        self.checksum
+    }
+
+    /// Attaches a label to the fact, such as a business dimension derived from the request,
+    /// so that it can be broken out in the reports.
+    pub fn with_label(mut self, name: &str, value: &str) -> Fact {
+        self.labels.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Notes the header that the request was sent with to trace it, such as
+    /// `x-request-id`, and its id.
+    pub fn with_trace(mut self, trace: Option<(String, String)>) -> Fact {
+        self.trace = trace;
+        self
+    }
+
+    /// The name and value of the header that carried the request's trace id, if it had one.
+    pub fn trace(&self) -> Option<&(String, String)> {
+        self.trace.as_ref()
+    }
+
+    /// The labels attached to the fact.
+    pub fn labels(&self) -> &[(String, String)] {
+        &self.labels
+    }
+
+    /// Notes which of the urls the request was sent to, by its index.
+    pub fn with_url(mut self, index: usize) -> Fact {
+        self.url = Some(index);
+        self
+    }
+
+    /// The index of the url the request was sent to, if the engine noted it.
+    pub fn url(&self) -> Option<usize> {
+        self.url
+    }
+
+    /// Notes the method the request was sent with.
+    pub fn with_method(mut self, method: Method) -> Fact {
+        self.method = Some(method);
+        self
+    }
+
+    /// The method the request was sent with, if the engine noted it.
+    pub fn method(&self) -> Option<Method> {
+        self.method
+    }
+
+    /// Notes the http version the response came back over.
+    pub fn with_version(mut self, version: Option<Version>) -> Fact {
+        self.version = version;
+        self
+    }
+
+    pub fn version(&self) -> Option<Version> {
+        self.version
+    }
+
+    /// Notes the family of the connection the request was sent over.
+    pub fn with_family(mut self, family: Option<Family>) -> Fact {
+        self.family = family;
+        self
+    }
+
+    pub fn family(&self) -> Option<Family> {
+        self.family
+    }
+
+    /// Notes the phases the response reported with its `Server-Timing` headers.
+    pub fn with_server_timing(mut self, phases: Vec<(String, Duration)>) -> Fact {
+        self.server_timing = phases;
+        self
+    }
+
+    pub fn server_timing(&self) -> &[(String, Duration)] {
+        &self.server_timing
+    }
+
+    /// The status of the response, or nothing if the request errored or was a round trip
+    /// without one.
+    pub fn status(&self) -> Option<u16> {
+        self.status
+    }
+
+    /// Whether the request failed without an answer.
+    pub fn is_error(&self) -> bool {
+        self.status.is_none() && !self.round_trip
+    }
+
+    /// How long the request took.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Whether the request opened a new connection, if the engine could tell.
+    pub fn new_connection(&self) -> Option<bool> {
+        self.new_connection
+    }
+
+    /// The number of bytes in the response body.
+    pub fn content_length(&self) -> &ContentLength {
+        &self.content_length
+    }
+
+    /// The rate the body was downloaded at in bytes per second, if the download was timed
+    /// and had a body to measure.
+    pub fn transfer_rate(&self) -> Option<f64> {
+        let seconds = self.download?.as_secs_f64();
+        if seconds > 0. && self.content_length.bytes() > 0 {
+            Some(self.content_length.bytes() as f64 / seconds)
+        } else {
+            None
+        }
+    }
+
+    /// The rate of the body over the whole of the request in bytes per second, which is
+    /// what a client fetching it sees, if there was a body.
+    pub fn throughput(&self) -> Option<f64> {
+        let seconds = self.duration.as_secs_f64();
+        if seconds > 0. && self.content_length.bytes() > 0 {
+            Some(self.content_length.bytes() as f64 / seconds)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the request received a 2xx response, or was a round trip that got its
+    /// answer, with a whole body that passed its checks.
+    pub fn is_success(&self) -> bool {
+        if self.body_mismatch || self.echo_mismatch || self.length_mismatch() {
+            return false;
+        }
+        match self.status {
+            Some(status) => (200..300).contains(&status),
+            None => self.round_trip,
         }
     }
 }
 
-struct DurationStats {
-    sorted: Vec<Duration>,
+/// How many latencies a run can have before its statistics are read from a histogram
+/// rather than from every latency sorted, since sorting hundreds of millions of them takes
+/// longer and more memory than the run itself.
+const EXACT_LIMIT: usize = 1_000_000;
+
+/// The latencies that the statistics of a run are read from. Runs of up to `EXACT_LIMIT`
+/// requests keep every latency sorted, so every statistic is exact. Bigger runs only keep
+/// a histogram and the running moments, so the quantiles are within 1% of the exact ones
+/// while the minimum, maximum, average and deviation stay exact.
+enum DurationStats {
+    Exact(Vec<Duration>),
+    Streamed(Streamed),
+}
+
+struct Streamed {
+    histogram: Histogram,
+    count: u64,
+    mean_ms: f64,
+    squared_distance_ms: f64,
+    min: Option<Duration>,
+    max: Option<Duration>,
 }
 
 impl DurationStats {
     fn from_facts(facts: &[Fact]) -> DurationStats {
-        let mut sorted: Vec<Duration> = facts.iter().map(|f| f.duration).collect();
+        if facts.len() > EXACT_LIMIT {
+            Self::streamed(facts.iter().map(|f| f.duration))
+        } else {
+            Self::from_durations(facts.iter().map(|f| f.duration).collect())
+        }
+    }
+
+    fn from_durations(mut sorted: Vec<Duration>) -> DurationStats {
         sorted.sort();
-        Self { sorted }
+        DurationStats::Exact(sorted)
+    }
+
+    fn streamed<I>(durations: I) -> DurationStats
+    where
+        I: Iterator<Item = Duration>,
+    {
+        let mut streamed = Streamed {
+            histogram: Histogram::new(),
+            count: 0,
+            mean_ms: 0.,
+            squared_distance_ms: 0.,
+            min: None,
+            max: None,
+        };
+        for duration in durations {
+            // Welford's online algorithm for the mean and variance.
+            let ms = duration.to_ms();
+            streamed.count += 1;
+            let delta = ms - streamed.mean_ms;
+            streamed.mean_ms += delta / streamed.count as f64;
+            streamed.squared_distance_ms += delta * (ms - streamed.mean_ms);
+            streamed.min = Some(streamed.min.map_or(duration, |min| min.min(duration)));
+            streamed.max = Some(streamed.max.map_or(duration, |max| max.max(duration)));
+            streamed.histogram.record(duration);
+        }
+        DurationStats::Streamed(streamed)
+    }
+
+    fn len(&self) -> u64 {
+        match *self {
+            DurationStats::Exact(ref sorted) => sorted.len() as u64,
+            DurationStats::Streamed(ref streamed) => streamed.count,
+        }
     }
 
     fn max(&self) -> Option<Duration> {
-        self.sorted.last().cloned()
+        match *self {
+            DurationStats::Exact(ref sorted) => sorted.last().cloned(),
+            DurationStats::Streamed(ref streamed) => streamed.max,
+        }
     }
 
     fn min(&self) -> Option<Duration> {
-        self.sorted.first().cloned()
+        match *self {
+            DurationStats::Exact(ref sorted) => sorted.first().cloned(),
+            DurationStats::Streamed(ref streamed) => streamed.min,
+        }
     }
 
     fn median(&self) -> Duration {
-        let mid = self.sorted.len() / 2;
-        if self.sorted.len() % 2 == 0 {
-            // even
-            (self.sorted[mid - 1] + self.sorted[mid]) / 2
-        } else {
-            // odd
-            self.sorted[mid]
+        match *self {
+            DurationStats::Exact(ref sorted) => {
+                let mid = sorted.len() / 2;
+                if sorted.len().is_multiple_of(2) {
+                    // even
+                    (sorted[mid - 1] + sorted[mid]) / 2
+                } else {
+                    // odd
+                    sorted[mid]
+                }
+            }
+            DurationStats::Streamed(ref streamed) => streamed.histogram.value_at_quantile(0.5),
         }
     }
 
     fn average(&self) -> Duration {
-        self.total() / (self.sorted.len() as u32)
+        match *self {
+            DurationStats::Exact(ref sorted) => sorted.iter().sum::<Duration>() / (sorted.len() as u32),
+            DurationStats::Streamed(ref streamed) => MS(streamed.mean_ms).into(),
+        }
     }
 
     fn stddev(&self) -> Duration {
-        let mean = self.average();
-        let MS(mean) = mean.into();
-        let summed_squares = self.sorted.iter().fold(0f64, |acc, duration| {
-            let MS(ms) = (*duration).into();
-            acc + (ms - mean).powi(2)
-        });
-        let ratio = summed_squares / (self.sorted.len() - 1) as f64;
+        let summed_squares = match *self {
+            DurationStats::Exact(ref sorted) => {
+                let MS(mean) = self.average().into();
+                sorted.iter().fold(0f64, |acc, duration| {
+                    let MS(ms) = (*duration).into();
+                    acc + (ms - mean).powi(2)
+                })
+            }
+            DurationStats::Streamed(ref streamed) => streamed.squared_distance_ms,
+        };
+        let ratio = summed_squares / (self.len() - 1) as f64;
         let std_ms = ratio.sqrt();
         MS(std_ms).into()
     }
@@ -123,32 +569,68 @@ impl DurationStats {
 
         if let Some(max) = self.max() {
             let bin_size = max.to_ms() / 100.;
+            let bin = |duration: Duration| cmp::min((duration.to_ms() / bin_size) as usize, 49);
 
-            for duration in &self.sorted {
-                let index = (duration.to_ms() / bin_size) as usize;
-                latency_histogram[cmp::min(index, 49)] += 1;
+            match *self {
+                DurationStats::Exact(ref sorted) => {
+                    for duration in sorted {
+                        latency_histogram[bin(*duration)] += 1;
+                    }
+                }
+                DurationStats::Streamed(ref streamed) => {
+                    for (micros, count) in streamed.histogram.buckets() {
+                        latency_histogram[bin(Duration::from_micros(micros))] += count as u32;
+                    }
+                }
             }
         }
         latency_histogram
     }
 
-    fn percentiles(&self) -> Vec<Duration> {
-        (0..100)
-            .map(|n| {
-                let mut index = ((f64::from(n) / 100.0) * (self.sorted.len() as f64)) as usize;
-                index = cmp::max(index, 0);
-                index = cmp::min(index, self.sorted.len() - 1);
-                self.sorted[index]
-            })
-            .collect()
+    /// The nearest-rank value at the quantile, so every value is one that was measured.
+    /// Streamed runs read it from the histogram, apart from the maximum.
+    fn quantile(&self, quantile: f64) -> Duration {
+        match *self {
+            DurationStats::Exact(ref sorted) => {
+                let rank = (quantile * sorted.len() as f64).ceil() as usize;
+                sorted[cmp::min(rank.max(1), sorted.len()) - 1]
+            }
+            DurationStats::Streamed(ref streamed) if quantile >= 1. => {
+                streamed.max.expect("Expected a streamed run to have a maximum")
+            }
+            DurationStats::Streamed(ref streamed) => streamed.histogram.value_at_quantile(quantile),
+        }
     }
 
-    fn total(&self) -> Duration {
-        self.sorted.iter().sum()
+    fn percentiles(&self) -> Vec<Duration> {
+        match *self {
+            DurationStats::Exact(ref sorted) => (0..100)
+                .map(|n| {
+                    let mut index = ((f64::from(n) / 100.0) * (sorted.len() as f64)) as usize;
+                    index = cmp::max(index, 0);
+                    index = cmp::min(index, sorted.len() - 1);
+                    sorted[index]
+                })
+                .collect(),
+            DurationStats::Streamed(ref streamed) => (0..100)
+                .map(|n| streamed.histogram.value_at_quantile(f64::from(n) / 100.))
+                .collect(),
+        }
     }
 }
 
-#[derive(Debug, Eq, PartialEq)]
+/// The percentiles printed as exact numbers in the summary, for pasting into reports.
+const PERCENTILE_TABLE: [(&str, f64); 7] = [
+    ("p50", 0.5),
+    ("p75", 0.75),
+    ("p90", 0.9),
+    ("p95", 0.95),
+    ("p99", 0.99),
+    ("p99.9", 0.999),
+    ("max", 1.),
+];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum ChartSize {
     None,
     Small,
@@ -156,6 +638,470 @@ pub enum ChartSize {
     Large,
 }
 
+impl ChartSize {
+    /// The height of a chart of this size, and every how many points of its data it draws
+    /// a column for. Charts of no size aren't drawn.
+    pub fn dimensions(self) -> Option<(u32, usize)> {
+        match self {
+            ChartSize::None => None,
+            ChartSize::Small => Some((7, 3)),
+            ChartSize::Medium => Some((10, 2)),
+            ChartSize::Large => Some((20, 1)),
+        }
+    }
+}
+
+/// How many facts carried a label and how long they took in total.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct LabelStats {
+    pub count: u32,
+    pub total: Duration,
+}
+
+impl LabelStats {
+    /// Counts a fact with the label.
+    pub fn add(&mut self, duration: Duration) {
+        self.count += 1;
+        self.total += duration;
+    }
+
+    /// Combines the stats of the same label from elsewhere.
+    pub fn merge(&mut self, other: &LabelStats) {
+        self.count += other.count;
+        self.total += other.total;
+    }
+
+    pub fn average(&self) -> Duration {
+        if self.count == 0 {
+            Duration::new(0, 0)
+        } else {
+            self.total / self.count
+        }
+    }
+}
+
+/// How the requests to one of the urls went.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TargetStats {
+    pub count: u32,
+    pub failures: u32,
+    pub total: Duration,
+}
+
+impl TargetStats {
+    /// Counts a request to the url.
+    pub fn add(&mut self, fact: &Fact) {
+        self.count += 1;
+        if !fact.is_success() {
+            self.failures += 1;
+        }
+        self.total += fact.duration;
+    }
+
+    /// Combines the stats of the same url from elsewhere.
+    pub fn merge(&mut self, other: &TargetStats) {
+        self.count += other.count;
+        self.failures += other.failures;
+        self.total += other.total;
+    }
+
+    pub fn average(&self) -> Duration {
+        if self.count == 0 {
+            Duration::new(0, 0)
+        } else {
+            self.total / self.count
+        }
+    }
+}
+
+/// The unit that durations are printed in. `Auto` picks microseconds for anything under a
+/// millisecond, seconds for anything over a second and milliseconds for the rest.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TimeUnit {
+    Auto,
+    Micros,
+    Millis,
+    Seconds,
+}
+
+impl TimeUnit {
+    /// The unit a duration is printed in, which only depends on the duration when auto
+    /// scaling.
+    pub fn resolve(self, duration: Duration) -> TimeUnit {
+        match self {
+            TimeUnit::Auto if duration < Duration::from_millis(1) => TimeUnit::Micros,
+            TimeUnit::Auto if duration >= Duration::from_secs(1) => TimeUnit::Seconds,
+            TimeUnit::Auto => TimeUnit::Millis,
+            unit => unit,
+        }
+    }
+
+    /// The duration as a number of this unit.
+    pub fn value(self, duration: Duration) -> f64 {
+        match self.resolve(duration) {
+            TimeUnit::Micros => duration.as_secs_f64() * 1_000_000.,
+            TimeUnit::Seconds => duration.as_secs_f64(),
+            _ => duration.to_ms(),
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            TimeUnit::Micros => "µs",
+            TimeUnit::Seconds => "s",
+            _ => "ms",
+        }
+    }
+
+    /// Formats the duration with its unit, such as `12.35 ms`.
+    pub fn format(self, duration: Duration) -> String {
+        let unit = self.resolve(duration);
+        format!("{:.2} {}", unit.value(duration), unit.symbol())
+    }
+}
+
+/// How many requests were sent over each family of connection, of those whose engine could
+/// tell.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Families {
+    pub ipv4: u64,
+    pub ipv6: u64,
+}
+
+impl Families {
+    pub fn add(&mut self, fact: &Fact) {
+        match fact.family() {
+            Some(Family::V4) => self.ipv4 += 1,
+            Some(Family::V6) => self.ipv6 += 1,
+            None => {}
+        }
+    }
+
+    pub fn merge(&mut self, other: &Families) {
+        self.ipv4 += other.ipv4;
+        self.ipv6 += other.ipv6;
+    }
+
+    pub fn happened(&self) -> bool {
+        self.ipv4 + self.ipv6 > 0
+    }
+}
+
+/// How many connections were opened for the requests that an engine could track.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Connections {
+    pub opened: u64,
+    pub tracked: u64,
+}
+
+impl Connections {
+    /// Counts the connection of a single fact, if it was tracked.
+    pub fn add(&mut self, fact: &Fact) {
+        if let Some(new_connection) = fact.new_connection {
+            self.tracked += 1;
+            if new_connection {
+                self.opened += 1;
+            }
+        }
+    }
+
+    /// The fraction of tracked requests that reused a connection.
+    pub fn reuse_ratio(&self) -> f64 {
+        if self.tracked == 0 {
+            0.
+        } else {
+            self.tracked.saturating_sub(self.opened) as f64 / self.tracked as f64
+        }
+    }
+}
+
+/// How many requests were redirected and how many redirects they followed in all.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Redirects {
+    pub redirected: u64,
+    pub hops: u64,
+    pub most: u32,
+}
+
+impl Redirects {
+    /// Counts the redirects of a single fact.
+    pub fn add(&mut self, fact: &Fact) {
+        if fact.redirects > 0 {
+            self.redirected += 1;
+            self.hops += u64::from(fact.redirects);
+            self.most = self.most.max(fact.redirects);
+        }
+    }
+
+    pub fn merge(&mut self, other: &Redirects) {
+        self.redirected += other.redirected;
+        self.hops += other.hops;
+        self.most = self.most.max(other.most);
+    }
+}
+
+/// How long requests waited for a free connection slot when connections were limited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PoolWaits {
+    pub limited: u64,
+    pub waited: u64,
+    pub total: Duration,
+    pub longest: Duration,
+}
+
+impl PoolWaits {
+    /// Counts the wait of a single fact, if its connections were limited.
+    pub fn add(&mut self, fact: &Fact) {
+        if let Some(wait) = fact.pool_wait {
+            self.limited += 1;
+            if wait > Duration::new(0, 0) {
+                self.waited += 1;
+            }
+            self.total += wait;
+            self.longest = self.longest.max(wait);
+        }
+    }
+
+    pub fn merge(&mut self, other: &PoolWaits) {
+        self.limited += other.limited;
+        self.waited += other.waited;
+        self.total += other.total;
+        self.longest = self.longest.max(other.longest);
+    }
+
+    /// The average wait of the requests that were limited.
+    pub fn average(&self) -> Duration {
+        if self.limited == 0 {
+            Duration::new(0, 0)
+        } else {
+            self.total / self.limited as u32
+        }
+    }
+}
+
+/// The latency of the requests without the slowest of them, so that a few pathological
+/// requests, such as one that waited out a DNS timeout, don't hide how the rest went in
+/// the longest latency and the deviation. It's read from the histogram of the run, so
+/// each latency is within 1% of the one that was measured.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Trimmed {
+    /// The fraction of the requests that was left out.
+    pub fraction: f64,
+    pub left_out: u64,
+    pub average: Duration,
+    pub stddev: Duration,
+    pub median: Duration,
+    pub max: Duration,
+}
+
+impl Trimmed {
+    /// Leaves out the slowest fraction of the latencies, rounded up to a whole request.
+    /// There's nothing to show when no latency or every latency would be left out.
+    pub fn of(histogram: &Histogram, fraction: f64) -> Option<Trimmed> {
+        let count = histogram.count();
+        let left_out = ((count as f64 * fraction).ceil() as u64).min(count);
+        let kept = count - left_out;
+        if left_out == 0 || kept == 0 {
+            return None;
+        }
+        let mut remaining = kept;
+        let buckets: Vec<(f64, u64)> = histogram
+            .buckets()
+            .into_iter()
+            .map(|(micros, count)| {
+                let taken = count.min(remaining);
+                remaining -= taken;
+                (micros as f64, taken)
+            })
+            .filter(|&(_, taken)| taken > 0)
+            .collect();
+        let mean = buckets.iter().map(|&(micros, n)| micros * n as f64).sum::<f64>() / kept as f64;
+        let squares: f64 = buckets.iter().map(|&(micros, n)| (micros - mean).powi(2) * n as f64).sum();
+        let stddev = if kept < 2 { 0. } else { (squares / (kept - 1) as f64).sqrt() };
+        // The nearest-rank median, as the histogram reads its quantiles.
+        let mut below = 0;
+        let middle = kept.div_ceil(2);
+        let median = buckets
+            .iter()
+            .find(|&&(_, n)| {
+                below += n;
+                below >= middle
+            })
+            .map_or(0., |&(micros, _)| micros);
+        let micros = |micros: f64| Duration::from_nanos((micros * 1_000.) as u64);
+        Some(Trimmed {
+            fraction,
+            left_out,
+            average: micros(mean),
+            stddev: micros(stddev),
+            median: micros(median),
+            max: micros(buckets.last().map_or(0., |&(micros, _)| micros)),
+        })
+    }
+}
+
+/// How many requests were throttled with a 429, and how long the servers asked the
+/// client to back off with their `Retry-After` headers, so that fast refusals can be
+/// told apart from fast responses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Throttling {
+    pub throttled: u64,
+    /// The latency of the throttled requests in all.
+    pub latency: Duration,
+    /// How many responses had a `Retry-After`, whatever their status.
+    pub asked: u64,
+    pub retry_after: Duration,
+    /// How long the workers waited in all, when they respected the `Retry-After`.
+    pub backed_off: Duration,
+}
+
+impl Throttling {
+    /// Counts a single fact, if it was throttled or asked for a backoff.
+    pub fn add(&mut self, fact: &Fact) {
+        if fact.status == Some(429) {
+            self.throttled += 1;
+            self.latency += fact.duration;
+        }
+        if let Some(retry_after) = fact.retry_after {
+            self.asked += 1;
+            self.retry_after += retry_after;
+        }
+        if let Some(backoff) = fact.backoff {
+            self.backed_off += backoff;
+        }
+    }
+
+    pub fn merge(&mut self, other: &Throttling) {
+        self.throttled += other.throttled;
+        self.latency += other.latency;
+        self.asked += other.asked;
+        self.retry_after += other.retry_after;
+        self.backed_off += other.backed_off;
+    }
+
+    /// Whether anything was throttled or asked for a backoff.
+    pub fn happened(&self) -> bool {
+        self.throttled > 0 || self.asked > 0
+    }
+
+    /// The average latency of the throttled requests.
+    pub fn average_latency(&self) -> Duration {
+        if self.throttled == 0 {
+            Duration::new(0, 0)
+        } else {
+            self.latency / self.throttled as u32
+        }
+    }
+
+    /// The average wait that the responses with a `Retry-After` asked for.
+    pub fn average_retry_after(&self) -> Duration {
+        if self.asked == 0 {
+            Duration::new(0, 0)
+        } else {
+            self.retry_after / self.asked as u32
+        }
+    }
+}
+
+/// The distribution of the rates, in bytes per second, that each request moved its body at,
+/// over either the download of the body or the whole of the request. The slow end shows
+/// requests stuck behind slow readers or writers, which neither the total throughput nor
+/// the latency percentiles reveal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransferRates {
+    pub slowest: f64,
+    pub p10: f64,
+    pub median: f64,
+    pub p90: f64,
+    pub fastest: f64,
+}
+
+impl TransferRates {
+    /// Builds the distribution from a set of rates, or nothing if there are none.
+    pub fn from_rates(mut rates: Vec<f64>) -> Option<TransferRates> {
+        if rates.is_empty() {
+            return None;
+        }
+        rates.sort_by(|a, b| a.partial_cmp(b).expect("Rates are never NaN"));
+        let at = |quantile: f64| rates[((rates.len() - 1) as f64 * quantile).round() as usize];
+        Some(TransferRates {
+            slowest: at(0.),
+            p10: at(0.1),
+            median: at(0.5),
+            p90: at(0.9),
+            fastest: at(1.),
+        })
+    }
+
+    /// The distribution in the shape of the JSON report.
+    pub fn report(&self) -> report::TransferRates {
+        report::TransferRates {
+            slowest: self.slowest,
+            p10: self.p10,
+            median: self.median,
+            p90: self.p90,
+            fastest: self.fastest,
+        }
+    }
+
+    /// Writes the distribution under the title, with the rates in the size unit.
+    fn write(&self, f: &mut fmt::Formatter, title: &str, size_unit: SizeUnit) -> fmt::Result {
+        let rate = |bytes: f64| format!("{}/s", size_unit.format(&ContentLength::new(bytes as u64)));
+        writeln!(f)?;
+        writeln!(f, "{}:", title)?;
+        writeln!(f, "  Slowest:   {}", rate(self.slowest))?;
+        writeln!(f, "  10%:       {}", rate(self.p10))?;
+        writeln!(f, "  Median:    {}", rate(self.median))?;
+        writeln!(f, "  90%:       {}", rate(self.p90))?;
+        writeln!(f, "  Fastest:   {}", rate(self.fastest))
+    }
+}
+
+/// The distribution of the sizes of the response bodies. Responses that come in distinct
+/// sizes, such as cache hits and misses, show up here where the total bytes hide them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResponseSizes {
+    pub smallest: u64,
+    pub average: f64,
+    pub median: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub largest: u64,
+    /// How many of the sizes fall into each hundredth of the way up to the largest.
+    pub histogram: Vec<u32>,
+}
+
+impl ResponseSizes {
+    /// Builds the distribution from a set of sizes, or nothing if there are none.
+    pub fn from_sizes(mut sizes: Vec<u64>) -> Option<ResponseSizes> {
+        if sizes.is_empty() {
+            return None;
+        }
+        sizes.sort_unstable();
+        let at = |quantile: f64| sizes[((sizes.len() - 1) as f64 * quantile).round() as usize];
+        let largest = sizes[sizes.len() - 1];
+        let mut histogram = vec![0; 100];
+        for &size in &sizes {
+            histogram[cmp::min((size * 100 / largest.max(1)) as usize, 99)] += 1;
+        }
+        Some(ResponseSizes {
+            smallest: sizes[0],
+            average: sizes.iter().sum::<u64>() as f64 / sizes.len() as f64,
+            median: at(0.5),
+            p90: at(0.9),
+            p99: at(0.99),
+            largest,
+            histogram,
+        })
+    }
+
+    /// Whether the responses came in more than one size, without which there's nothing to
+    /// the distribution.
+    pub fn varied(&self) -> bool {
+        self.smallest != self.largest
+    }
+}
+
 /// Represents the statistics around a given set of facts.
 #[derive(Debug)]
 pub struct Summary {
@@ -165,40 +1111,369 @@ pub struct Summary {
     min: Duration,
     stddev: Duration,
     count: u32,
+    errors: u32,
+    failures: u32,
+    body_mismatches: u32,
+    echo_mismatches: u32,
+    length_mismatches: u32,
+    connections: Option<Connections>,
+    families: Option<Families>,
+    redirects: Option<Redirects>,
+    pool_waits: Option<PoolWaits>,
+    throttling: Option<Throttling>,
+    server_timing: Option<ServerTiming>,
     content_length: ContentLength,
+    /// The bytes of the bodies of each status class, keyed by its first digit.
+    class_bytes: BTreeMap<u16, u64>,
+    transfer_rates: Option<TransferRates>,
+    throughputs: Option<TransferRates>,
+    response_sizes: Option<ResponseSizes>,
     percentiles: Vec<Duration>,
+    /// The latency at each of the `PERCENTILE_TABLE` quantiles.
+    percentile_table: Vec<Duration>,
     latency_histogram: Vec<u32>,
+    /// Every latency, kept so that the run can be merged with others later.
+    histogram: Histogram,
+    latency_by_order: Vec<Duration>,
+    chart_sample: Option<usize>,
+    /// The fraction of the slowest requests to also show the latency without.
+    trim_outliers: Option<f64>,
     status_counts: HashMap<u16, u32>,
+    labels: BTreeMap<String, LabelStats>,
+    /// How the requests to each url went, keyed by its index.
+    targets: BTreeMap<usize, TargetStats>,
+    checksums: Checksums,
+    /// The urls that were requested, to name the ones that facts refer to by index.
+    urls: Vec<String>,
     chart_size: ChartSize,
+    time_unit: TimeUnit,
+    size_unit: SizeUnit,
 }
 
 impl Summary {
-    /// From a set of facts, calculate the statistics.
+    /// From a set of facts, calculate the statistics. Runs fold their facts into an
+    /// accumulator as they arrive instead, so this is for facts that are already at hand.
+    #[allow(dead_code)]
     pub fn from_facts(facts: &[Fact]) -> Summary {
         if facts.is_empty() {
             return Summary::zero();
         }
-        let content_length = Self::total_content_length(&facts);
+        let content_length = Self::total_content_length(facts);
         let count = facts.len() as u32;
-        let status_counts = facts.iter().fold(
+        let errors = facts.iter().filter(|f| f.is_error()).count() as u32;
+        let failures = facts.iter().filter(|f| !f.is_success()).count() as u32;
+        let body_mismatches = facts.iter().filter(|f| f.body_mismatch()).count() as u32;
+        let echo_mismatches = facts.iter().filter(|f| f.echo_mismatch()).count() as u32;
+        let length_mismatches = facts.iter().filter(|f| f.length_mismatch()).count() as u32;
+        let status_counts = facts.iter().filter_map(|f| f.status).fold(
             HashMap::with_capacity(699),
-            |mut acc: HashMap<u16, u32>, fact| {
-                let count = if let Some(current) = acc.get(&fact.status) {
+            |mut acc: HashMap<u16, u32>, status| {
+                let count = if let Some(current) = acc.get(&status) {
                     current + 1
                 } else {
                     1
                 };
-                acc.insert(fact.status, count);
+                acc.insert(status, count);
+                acc
+            },
+        );
+
+        let mut class_bytes = BTreeMap::new();
+        for fact in facts {
+            if let Some(status) = fact.status {
+                *class_bytes.entry(status / 100).or_insert(0) += fact.content_length.bytes();
+            }
+        }
+
+        let mut labels: BTreeMap<String, LabelStats> = BTreeMap::new();
+        for fact in facts {
+            for (name, value) in &fact.labels {
+                labels
+                    .entry(format!("{}={}", name, value))
+                    .or_default()
+                    .add(fact.duration);
+            }
+        }
+
+        let mut targets: BTreeMap<usize, TargetStats> = BTreeMap::new();
+        for fact in facts {
+            if let Some(url) = fact.url {
+                targets.entry(url).or_default().add(fact);
+            }
+        }
+
+        let connections = facts.iter().fold(Connections::default(), |mut acc, fact| {
+            acc.add(fact);
+            acc
+        });
+        let families = facts.iter().fold(Families::default(), |mut acc, fact| {
+            acc.add(fact);
+            acc
+        });
+        let redirects = facts.iter().fold(Redirects::default(), |mut acc, fact| {
+            acc.add(fact);
+            acc
+        });
+        let pool_waits = facts.iter().fold(PoolWaits::default(), |mut acc, fact| {
+            acc.add(fact);
+            acc
+        });
+        let throttling = facts.iter().fold(Throttling::default(), |mut acc, fact| {
+            acc.add(fact);
+            acc
+        });
+        let server_timing = facts.iter().fold(ServerTiming::default(), |mut acc, fact| {
+            acc.add(fact);
+            acc
+        });
+        let checksums = facts.iter().filter_map(|f| f.checksum()).fold(
+            Checksums::default(),
+            |mut acc, checksum| {
+                acc.add(checksum);
                 acc
             },
         );
 
-        Summary {
+        Summary {
+            count,
+            errors,
+            failures,
+            body_mismatches,
+            echo_mismatches,
+            length_mismatches,
+            connections: Some(connections).filter(|c| c.tracked > 0),
+            families: Some(families).filter(Families::happened),
+            redirects: Some(redirects).filter(|r| r.redirected > 0),
+            pool_waits: Some(pool_waits).filter(|w| w.limited > 0),
+            throttling: Some(throttling).filter(Throttling::happened),
+            server_timing: Some(server_timing).filter(ServerTiming::happened),
+            content_length,
+            class_bytes,
+            transfer_rates: TransferRates::from_rates(
+                facts.iter().filter_map(|f| f.transfer_rate()).collect(),
+            ),
+            throughputs: TransferRates::from_rates(facts.iter().filter_map(|f| f.throughput()).collect()),
+            response_sizes: Self::response_sizes(facts),
+            status_counts,
+            labels,
+            targets,
+            checksums,
+            histogram: facts.iter().fold(Histogram::new(), |mut acc, fact| {
+                acc.record(fact.duration);
+                acc
+            }),
+            latency_by_order: latency_by_order(facts),
+            ..Summary::from_durations(&DurationStats::from_facts(facts))
+        }
+    }
+
+    /// Builds the statistics from an accumulator that was folded as facts arrived, so that
+    /// little is left to work out once the run ends. The charts, the median and the
+    /// transfer rates are read from the raw facts if they were recorded, so that they're
+    /// the same as from `from_facts`, otherwise the charts are drawn from the sample if one
+    /// was kept.
+    pub fn from_accumulator(acc: &Accumulator) -> Summary {
+        if acc.count() == 0 {
+            return Summary::zero();
+        }
+        let (stats, chart_sample) = match (acc.facts(), acc.sample()) {
+            (Some(facts), _) => (Some(DurationStats::from_facts(facts)), None),
+            (None, Some(sample)) if !sample.is_empty() => (
+                Some(DurationStats::from_durations(sample.to_vec())),
+                Some(sample.len()),
+            ),
+            _ => (None, None),
+        };
+        let (percentiles, latency_histogram) = match stats {
+            Some(ref stats) => (stats.percentiles(), stats.latency_histogram()),
+            None => (Vec::new(), Vec::new()),
+        };
+
+        let rates = |rates: &[f64], rate: fn(&Fact) -> Option<f64>| match acc.facts() {
+            Some(facts) => TransferRates::from_rates(facts.iter().filter_map(rate).collect()),
+            None => TransferRates::from_rates(rates.to_vec()),
+        };
+
+        Summary {
+            average: acc.average(),
+            stddev: acc.stddev(),
+            median: match stats {
+                Some(ref stats) if chart_sample.is_none() => stats.median(),
+                _ => acc.histogram().value_at_quantile(0.5),
+            },
+            min: acc.min().expect("Returned early if empty"),
+            max: acc.max().expect("Returned early if empty"),
+            count: acc.count() as u32,
+            errors: acc.errors() as u32,
+            failures: acc.failures() as u32,
+            body_mismatches: acc.body_mismatches() as u32,
+            echo_mismatches: acc.echo_mismatches() as u32,
+            length_mismatches: acc.length_mismatches() as u32,
+            connections: Some(*acc.connections()).filter(|c| c.tracked > 0),
+            families: Some(*acc.families()).filter(Families::happened),
+            redirects: Some(*acc.redirects()).filter(|r| r.redirected > 0),
+            pool_waits: Some(*acc.pool_waits()).filter(|w| w.limited > 0),
+            throttling: Some(*acc.throttling()).filter(Throttling::happened),
+            server_timing: Some(acc.server_timing().clone()).filter(ServerTiming::happened),
+            content_length: ContentLength::new(acc.content_length().bytes()),
+            class_bytes: acc.class_bytes().clone(),
+            transfer_rates: rates(acc.transfer_rates(), Fact::transfer_rate),
+            throughputs: rates(acc.throughputs(), Fact::throughput),
+            response_sizes: match acc.facts() {
+                Some(facts) => Self::response_sizes(facts),
+                None => ResponseSizes::from_sizes(acc.response_sizes().to_vec()),
+            },
+            percentiles,
+            percentile_table: match stats {
+                Some(ref stats) if chart_sample.is_none() => Self::percentile_table(stats),
+                // The histogram saw every request, where a sample might miss the tail.
+                _ => PERCENTILE_TABLE
+                    .iter()
+                    .map(|&(_, quantile)| match quantile {
+                        q if q >= 1. => acc.max().expect("Returned early if empty"),
+                        q => acc.histogram().value_at_quantile(q),
+                    })
+                    .collect(),
+            },
+            latency_histogram,
+            histogram: acc.histogram().clone(),
+            latency_by_order: acc.facts().map(latency_by_order).unwrap_or_default(),
+            chart_sample,
+            status_counts: acc.status_counts().clone(),
+            labels: acc.labels().clone(),
+            targets: acc.targets().clone(),
+            checksums: acc.checksums().clone(),
+            ..Summary::zero()
+        }
+    }
+
+    /// Merges the results of runs saved as json into the statistics of them all, as if they
+    /// had been one run. The latencies are merged through the histograms saved with each
+    /// run, so the percentiles are of every request rather than averages of each run's.
+    /// Transfer rates, response sizes, pool waits and checksums can't be merged from their
+    /// summaries, so they're left out, as is the breakdown by url since each run may have
+    /// been given different urls.
+    pub fn from_results(runs: &[&report::Results]) -> Result<Summary, String> {
+        let runs: Vec<&report::Results> = runs.iter().cloned().filter(|run| run.count > 0).collect();
+        if runs.is_empty() {
+            return Ok(Summary::zero());
+        }
+        let mut histogram = Histogram::new();
+        for run in &runs {
+            let buckets = run
+                .latency_buckets_us
+                .as_ref()
+                .ok_or("A run was saved without its latency histogram by an older rench, so it can't be merged")?;
+            histogram.merge(&Histogram::from_buckets(buckets));
+        }
+
+        let count: u32 = runs.iter().map(|run| run.count).sum();
+        let mean_ms = runs
+            .iter()
+            .map(|run| f64::from(run.count) * run.latency_ms.average)
+            .sum::<f64>()
+            / f64::from(count);
+        // The squared distances of each run from its own mean, moved to the mean of them all.
+        let squared_distance_ms: f64 = runs
+            .iter()
+            .map(|run| {
+                let n = f64::from(run.count);
+                run.latency_ms.stddev.powi(2) * (n - 1.) + n * (run.latency_ms.average - mean_ms).powi(2)
+            })
+            .sum();
+        let stddev_ms = if count < 2 {
+            0.
+        } else {
+            (squared_distance_ms / f64::from(count - 1)).sqrt()
+        };
+        let min = runs.iter().map(|run| run.latency_ms.min).fold(f64::INFINITY, f64::min);
+        let max = runs.iter().map(|run| run.latency_ms.max).fold(0., f64::max);
+        let at = |quantile: f64| {
+            if quantile >= 1. {
+                MS(max).into()
+            } else {
+                histogram.value_at_quantile(quantile)
+            }
+        };
+
+        let mut status_counts = HashMap::new();
+        let mut class_bytes = BTreeMap::new();
+        let mut labels: BTreeMap<String, LabelStats> = BTreeMap::new();
+        let mut connections = Connections::default();
+        let mut families = Families::default();
+        let mut redirects = Redirects::default();
+        let mut throttling = Throttling::default();
+        let mut server_timing = ServerTiming::default();
+        for run in &runs {
+            for (&status, &n) in &run.status_codes {
+                *status_counts.entry(status).or_insert(0) += n;
+            }
+            for (class, &bytes) in &run.content_length_bytes_by_class {
+                if let Ok(class) = class.trim_end_matches("xx").parse::<u16>() {
+                    *class_bytes.entry(class).or_insert(0) += bytes;
+                }
+            }
+            for (name, label) in &run.labels {
+                labels.entry(name.clone()).or_default().merge(&LabelStats {
+                    count: label.count,
+                    total: MS(label.average_ms * f64::from(label.count)).into(),
+                });
+            }
+            if let Some(ref c) = run.connections {
+                connections.opened += c.opened;
+                connections.tracked += c.tracked;
+            }
+            if let Some(ref f) = run.families {
+                families.merge(&Families {
+                    ipv4: f.ipv4,
+                    ipv6: f.ipv6,
+                });
+            }
+            if let Some(ref r) = run.redirects {
+                redirects.redirected += r.redirected;
+                redirects.hops += r.hops;
+                redirects.most = redirects.most.max(r.most);
+            }
+            if let Some(ref t) = run.throttling {
+                throttling.merge(&Throttling {
+                    throttled: t.throttled,
+                    latency: MS(t.average_ms * t.throttled as f64).into(),
+                    asked: t.asked,
+                    retry_after: MS(t.average_retry_after_ms * t.asked as f64).into(),
+                    backed_off: MS(t.backed_off_ms).into(),
+                });
+            }
+            if let Some(ref timing) = run.server_timing {
+                server_timing.merge(&ServerTiming::from_report(timing));
+            }
+        }
+
+        Ok(Summary {
+            average: MS(mean_ms).into(),
+            stddev: MS(stddev_ms).into(),
+            median: histogram.value_at_quantile(0.5),
+            min: MS(min).into(),
+            max: MS(max).into(),
             count,
-            content_length,
+            errors: runs.iter().map(|run| run.errors).sum(),
+            failures: runs.iter().map(|run| run.failures).sum(),
+            body_mismatches: runs.iter().map(|run| run.body_mismatches).sum(),
+            echo_mismatches: runs.iter().map(|run| run.echo_mismatches).sum(),
+            length_mismatches: runs.iter().map(|run| run.length_mismatches).sum(),
+            connections: Some(connections).filter(|c| c.tracked > 0),
+            families: Some(families).filter(Families::happened),
+            redirects: Some(redirects).filter(|r| r.redirected > 0),
+            throttling: Some(throttling).filter(Throttling::happened),
+            server_timing: Some(server_timing).filter(ServerTiming::happened),
+            content_length: ContentLength::new(runs.iter().map(|run| run.content_length_bytes).sum()),
+            class_bytes,
+            percentiles: (0..100).map(|n| at(f64::from(n) / 100.)).collect(),
+            percentile_table: PERCENTILE_TABLE.iter().map(|&(_, quantile)| at(quantile)).collect(),
+            histogram,
             status_counts,
-            ..Summary::from_durations(&DurationStats::from_facts(&facts))
-        }
+            labels,
+            ..Summary::zero()
+        })
     }
 
     #[allow(dead_code)]
@@ -206,11 +1481,209 @@ impl Summary {
         self.content_length
     }
 
+    /// The mean latency of the requests.
+    pub fn average(&self) -> Duration {
+        self.average
+    }
+
+    /// The number of requests.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// The number of requests that errored without receiving a response.
+    pub fn errors(&self) -> u32 {
+        self.errors
+    }
+
+    /// The fraction of requests that did not receive a 2xx response, errors included.
+    pub fn failure_rate(&self) -> f64 {
+        if self.count == 0 {
+            0.
+        } else {
+            f64::from(self.failures) / f64::from(self.count)
+        }
+    }
+
+    pub fn chart_size(&self) -> ChartSize {
+        self.chart_size
+    }
+
     pub fn with_chart_size(mut self, size: ChartSize) -> Self {
         self.chart_size = size;
         self
     }
 
+    pub fn with_time_unit(mut self, unit: TimeUnit) -> Self {
+        self.time_unit = unit;
+        self
+    }
+
+    pub fn with_size_unit(mut self, unit: SizeUnit) -> Self {
+        self.size_unit = unit;
+        self
+    }
+
+    /// Shows the latency without the slowest fraction of the requests as well.
+    pub fn with_trimmed_outliers(mut self, fraction: f64) -> Self {
+        self.trim_outliers = Some(fraction);
+        self
+    }
+
+    /// The latency without the slowest of the requests, if they're to be trimmed.
+    pub fn trimmed(&self) -> Option<Trimmed> {
+        self.trim_outliers
+            .and_then(|fraction| Trimmed::of(&self.histogram, fraction))
+    }
+
+    /// Names the urls that facts refer to by index.
+    pub fn with_urls(mut self, urls: &[String]) -> Self {
+        self.urls = urls.to_vec();
+        self
+    }
+
+    /// The name of the url at the index, or the index if the urls weren't given.
+    fn url(&self, index: usize) -> String {
+        self.urls
+            .get(index)
+            .cloned()
+            .unwrap_or_else(|| format!("url #{}", index))
+    }
+
+    /// The percentiles and the latency histogram as whitespace separated columns in
+    /// milliseconds, for plotting with gnuplot or loading as csv. The two are separate
+    /// datasets, which gnuplot picks between with `index 0` and `index 1`. Both are empty
+    /// when the run was streamed without keeping a sample.
+    pub fn chart_data(&self) -> String {
+        let mut data = String::from("# percentile latency_ms\n");
+        for (n, duration) in self.percentiles.iter().enumerate() {
+            data.push_str(&format!("{} {:.3}\n", n, duration.to_ms()));
+        }
+        data.push_str("\n\n# bin_start_ms bin_end_ms count\n");
+        if !self.percentiles.is_empty() {
+            let bin_size = self.max.to_ms() / self.latency_histogram.len() as f64;
+            for (n, count) in self.latency_histogram.iter().enumerate() {
+                let start = n as f64 * bin_size;
+                data.push_str(&format!("{:.3} {:.3} {}\n", start, start + bin_size, count));
+            }
+        }
+        data
+    }
+
+    /// The statistics in the shape of the JSON report.
+    pub fn results(&self) -> report::Results {
+        report::Results {
+            count: self.count,
+            errors: self.errors,
+            failures: self.failures,
+            body_mismatches: self.body_mismatches,
+            echo_mismatches: self.echo_mismatches,
+            length_mismatches: self.length_mismatches,
+            latency_ms: report::Latency {
+                min: self.min.to_ms(),
+                median: self.median.to_ms(),
+                average: self.average.to_ms(),
+                max: self.max.to_ms(),
+                stddev: self.stddev.to_ms(),
+            },
+            percentiles_ms: self.percentiles.iter().map(|p| p.to_ms()).collect(),
+            latency_buckets_us: Some(self.histogram.buckets()),
+            content_length_bytes: self.content_length.bytes(),
+            content_length_bytes_by_class: self
+                .class_bytes
+                .iter()
+                .map(|(class, &bytes)| (format!("{}xx", class), bytes))
+                .collect(),
+            status_codes: self.status_counts.iter().map(|(&s, &c)| (s, c)).collect(),
+            connections: self.connections.map(|c| report::Connections {
+                opened: c.opened,
+                tracked: c.tracked,
+            }),
+            families: self.families.map(|f| report::Families {
+                ipv4: f.ipv4,
+                ipv6: f.ipv6,
+            }),
+            redirects: self.redirects.map(|r| report::Redirects {
+                redirected: r.redirected,
+                hops: r.hops,
+                most: r.most,
+            }),
+            pool_wait: self.pool_waits.map(|w| report::PoolWait {
+                waited: w.waited,
+                average_ms: w.average().to_ms(),
+                longest_ms: w.longest.to_ms(),
+            }),
+            throttling: self.throttling.map(|t| report::Throttling {
+                throttled: t.throttled,
+                average_ms: t.average_latency().to_ms(),
+                asked: t.asked,
+                average_retry_after_ms: t.average_retry_after().to_ms(),
+                backed_off_ms: t.backed_off.to_ms(),
+            }),
+            server_timing: self.server_timing.as_ref().map(ServerTiming::report),
+            transfer_rates_bytes_per_second: self.transfer_rates.as_ref().map(TransferRates::report),
+            throughput_bytes_per_second: self.throughputs.as_ref().map(TransferRates::report),
+            response_sizes_bytes: self.response_sizes.as_ref().map(|s| report::ResponseSizes {
+                smallest: s.smallest,
+                average: s.average,
+                median: s.median,
+                p90: s.p90,
+                p99: s.p99,
+                largest: s.largest,
+            }),
+            labels: self
+                .labels
+                .iter()
+                .map(|(name, stats)| {
+                    let label = report::Label {
+                        count: stats.count,
+                        average_ms: stats.average().to_ms(),
+                    };
+                    (name.clone(), label)
+                })
+                .collect(),
+            targets: if self.targets.len() > 1 {
+                Some(
+                    self.targets
+                        .iter()
+                        .map(|(&url, stats)| {
+                            let target = report::Target {
+                                count: stats.count,
+                                failures: stats.failures,
+                                average_ms: stats.average().to_ms(),
+                            };
+                            (self.url(url), target)
+                        })
+                        .collect(),
+                )
+            } else {
+                None
+            },
+            checksums: if self.checksums.is_empty() {
+                None
+            } else {
+                Some(
+                    self.checksums
+                        .variances()
+                        .into_iter()
+                        .map(|(url, v)| {
+                            let variance = report::Checksums {
+                                responses: v.responses,
+                                distinct: v.distinct,
+                                most_common: v.most_common,
+                            };
+                            (self.url(url), variance)
+                        })
+                        .collect(),
+                )
+            },
+            heatmap: None,
+            size_latency: None,
+            periodicity: None,
+            schedule: None,
+        }
+    }
+
     fn from_durations(stats: &DurationStats) -> Summary {
         let average = stats.average();
         let stddev = stats.stddev();
@@ -227,11 +1700,19 @@ impl Summary {
             min,
             max,
             percentiles,
+            percentile_table: Self::percentile_table(stats),
             latency_histogram,
             ..Summary::zero()
         }
     }
 
+    fn percentile_table(stats: &DurationStats) -> Vec<Duration> {
+        PERCENTILE_TABLE
+            .iter()
+            .map(|&(_, quantile)| stats.quantile(quantile))
+            .collect()
+    }
+
     fn zero() -> Summary {
         Summary {
             average: Duration::new(0, 0),
@@ -240,36 +1721,96 @@ impl Summary {
             max: Duration::new(0, 0),
             min: Duration::new(0, 0),
             count: 0,
+            errors: 0,
+            failures: 0,
+            body_mismatches: 0,
+            echo_mismatches: 0,
+            length_mismatches: 0,
+            connections: None,
+            families: None,
+            redirects: None,
+            pool_waits: None,
+            throttling: None,
+            server_timing: None,
             content_length: ContentLength::zero(),
+            class_bytes: BTreeMap::new(),
+            transfer_rates: None,
+            throughputs: None,
+            response_sizes: None,
             percentiles: vec![Duration::new(0, 0); 100],
+            percentile_table: Vec::new(),
             latency_histogram: vec![0; 0],
+            histogram: Histogram::new(),
+            latency_by_order: Vec::new(),
+            chart_sample: None,
+            trim_outliers: None,
             status_counts: HashMap::new(),
+            labels: BTreeMap::new(),
+            targets: BTreeMap::new(),
+            checksums: Checksums::default(),
+            urls: Vec::new(),
             chart_size: ChartSize::Medium,
+            time_unit: TimeUnit::Auto,
+            size_unit: SizeUnit::Binary,
         }
     }
 
+    /// The distribution of the sizes of the bodies of the facts that had a response.
+    fn response_sizes(facts: &[Fact]) -> Option<ResponseSizes> {
+        ResponseSizes::from_sizes(
+            facts
+                .iter()
+                .filter(|fact| !fact.is_error())
+                .map(|fact| fact.content_length.bytes())
+                .collect(),
+        )
+    }
+
     fn total_content_length(facts: &[Fact]) -> ContentLength {
         facts.iter().fold(ContentLength::zero(), |len, fact| {
             len + &fact.content_length
         })
     }
 
-    fn chart<T>(&self, vec: &[T]) -> String
+    /// The width of the longest bar of a bar chart at the size of the charts, if they're drawn.
+    fn bar_width(&self) -> Option<u32> {
+        match self.chart_size {
+            ChartSize::None => None,
+            ChartSize::Small => Some(20),
+            ChartSize::Medium => Some(40),
+            ChartSize::Large => Some(60),
+        }
+    }
+
+    fn chart<T>(&self, vec: &[T], unit: Option<&str>) -> String
     where
         T: Copy + Into<f64>,
     {
-        let (height, scale) = match self.chart_size {
-            ChartSize::None => return String::new(),
-            ChartSize::Small => (7, 3),
-            ChartSize::Medium => (10, 2),
-            ChartSize::Large => (20, 1),
+        let (height, scale) = match self.chart_size.dimensions() {
+            Some(dimensions) => dimensions,
+            None => return String::new(),
+        };
+        let chart = Chart::new().height(height);
+        let chart = match unit {
+            Some(unit) => chart.unit(unit),
+            None => chart,
         };
-        use stats::scale_array;
-        Chart::new().height(height).make(&scale_array(&vec, scale))
+        chart.make(&scale_array(vec, scale))
     }
 }
 
-fn scale_array<T>(vec: &[T], scale_array: usize) -> Vec<T>
+/// The average latency of each hundredth of the facts in the order they were collected,
+/// which shows warm-up, periodic spikes and degradation over a run.
+fn latency_by_order(facts: &[Fact]) -> Vec<Duration> {
+    const BUCKETS: usize = 100;
+    let size = facts.len().div_ceil(BUCKETS).max(1);
+    facts
+        .chunks(size)
+        .map(|chunk| chunk.iter().map(|f| f.duration).sum::<Duration>() / chunk.len() as u32)
+        .collect()
+}
+
+pub fn scale_array<T>(vec: &[T], scale_array: usize) -> Vec<T>
 where
     T: Copy,
 {
@@ -292,35 +1833,273 @@ mod scale_array_tests {
     }
 }
 
+/// The numbers of a summary laid out for a terminal, without its charts.
+pub struct SummaryText<'a>(&'a Summary);
+
+/// The charts of a summary, which are left out at the `None` chart size.
+pub struct SummaryCharts<'a>(&'a Summary);
+
+impl Summary {
+    pub fn text(&self) -> SummaryText<'_> {
+        SummaryText(self)
+    }
+
+    pub fn charts(&self) -> SummaryCharts<'_> {
+        SummaryCharts(self)
+    }
+}
+
 impl fmt::Display for Summary {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.text(), self.charts())
+    }
+}
+
+impl<'a> fmt::Display for SummaryText<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let summary = self.0;
         writeln!(f, "Summary")?;
+        let time = |duration: Duration| summary.time_unit.format(duration);
         writeln!(
             f,
-            "  Average:   {} ms (std: {} ms)",
-            self.average.to_ms(),
-            self.stddev.to_ms()
+            "  Average:   {} (std: {})",
+            time(summary.average),
+            time(summary.stddev)
         )?;
-        writeln!(f, "  Median:    {} ms", self.median.to_ms())?;
-        writeln!(f, "  Longest:   {} ms", self.max.to_ms())?;
-        writeln!(f, "  Shortest:  {} ms", self.min.to_ms())?;
-        writeln!(f, "  Requests:  {}", self.count)?;
-        writeln!(f, "  Data:      {}", self.content_length)?;
+        writeln!(f, "  Median:    {}", time(summary.median))?;
+        writeln!(f, "  Longest:   {}", time(summary.max))?;
+        writeln!(f, "  Shortest:  {}", time(summary.min))?;
+        if let Some(trimmed) = summary.trimmed() {
+            writeln!(
+                f,
+                "  Without the slowest {:.2}% of requests ({} left out):",
+                trimmed.fraction * 100.,
+                trimmed.left_out
+            )?;
+            writeln!(f, "    Average: {} (std: {})", time(trimmed.average), time(trimmed.stddev))?;
+            writeln!(f, "    Median:  {}", time(trimmed.median))?;
+            writeln!(f, "    Longest: {}", time(trimmed.max))?;
+        }
+        writeln!(f, "  Requests:  {}", summary.count)?;
+        writeln!(f, "  Errors:    {}", summary.errors)?;
+        if summary.body_mismatches > 0 {
+            writeln!(f, "  Mismatch:  {} bodies failed the expected checks", summary.body_mismatches)?;
+        }
+        if summary.echo_mismatches > 0 {
+            writeln!(
+                f,
+                "  Echo:      {} responses didn't echo the token of their request",
+                summary.echo_mismatches
+            )?;
+        }
+        if summary.length_mismatches > 0 {
+            writeln!(
+                f,
+                "  Length:    {} bodies weren't the length their content-length declared",
+                summary.length_mismatches
+            )?;
+        }
+        let size = |bytes: u64| summary.size_unit.format(&ContentLength::new(bytes));
+        write!(f, "  Data:      {}", size(summary.content_length.bytes()))?;
+        if summary.class_bytes.len() > 1 {
+            let classes: Vec<String> = summary
+                .class_bytes
+                .iter()
+                .map(|(class, &bytes)| format!("{}xx: {}", class, size(bytes)))
+                .collect();
+            write!(f, " ({})", classes.join(", "))?;
+        }
+        writeln!(f)?;
+        if let Some(connections) = summary.connections {
+            writeln!(
+                f,
+                "  Conns:     {} opened ({:.2}% of requests reused a connection)",
+                connections.opened,
+                connections.reuse_ratio() * 100.
+            )?;
+        }
+        if let Some(families) = summary.families {
+            writeln!(
+                f,
+                "  Family:    {} requests over IPv4, {} over IPv6",
+                families.ipv4, families.ipv6
+            )?;
+        }
+        if let Some(redirects) = summary.redirects {
+            writeln!(
+                f,
+                "  Redirects: {} requests followed {} redirects (at most {})",
+                redirects.redirected, redirects.hops, redirects.most
+            )?;
+        }
+        if let Some(waits) = summary.pool_waits {
+            writeln!(
+                f,
+                "  Pool wait: {} requests waited for a connection (average {}, longest {})",
+                waits.waited,
+                time(waits.average()),
+                time(waits.longest)
+            )?;
+        }
+        if let Some(throttling) = summary.throttling {
+            if throttling.throttled > 0 {
+                writeln!(
+                    f,
+                    "  Throttled: {} requests got a 429 ({:.2}% of requests, average {})",
+                    throttling.throttled,
+                    throttling.throttled as f64 / f64::from(summary.count) * 100.,
+                    time(throttling.average_latency())
+                )?;
+            }
+            if throttling.asked > 0 {
+                write!(
+                    f,
+                    "  Backoff:   {} responses asked to retry after {} on average",
+                    throttling.asked,
+                    time(throttling.average_retry_after())
+                )?;
+                if throttling.backed_off > Duration::new(0, 0) {
+                    write!(f, ", and the workers waited {} in all", time(throttling.backed_off))?;
+                }
+                writeln!(f)?;
+            }
+        }
+        if let Some(ref timing) = summary.server_timing {
+            writeln!(
+                f,
+                "  Server:    {} of the {} average latency by the Server-Timing of {} responses, leaving {} for the network and client",
+                time(timing.average_server()),
+                time(timing.average_latency()),
+                timing.timed(),
+                time(timing.average_latency().saturating_sub(timing.average_server()))
+            )?;
+            for (name, count, average) in timing.phases() {
+                writeln!(f, "    {:<18} {} average over {} responses", name, time(average), count)?;
+            }
+        }
+        if !summary.percentile_table.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "Latency percentiles:")?;
+            for (&(name, _), latency) in PERCENTILE_TABLE.iter().zip(&summary.percentile_table) {
+                writeln!(f, "  {:<10} {}", format!("{}:", name), time(*latency))?;
+            }
+        }
+        if let Some(ref rates) = summary.transfer_rates {
+            rates.write(f, "Transfer rates (body bytes / download time)", summary.size_unit)?;
+        }
+        if let Some(ref rates) = summary.throughputs {
+            rates.write(f, "Throughput (body bytes / request time)", summary.size_unit)?;
+        }
+        if let Some(sizes) = summary.response_sizes.as_ref().filter(|sizes| sizes.varied()) {
+            writeln!(f)?;
+            writeln!(f, "Response sizes:")?;
+            writeln!(f, "  Average:   {}", size(sizes.average.round() as u64))?;
+            writeln!(f, "  Smallest:  {}", size(sizes.smallest))?;
+            writeln!(f, "  Median:    {}", size(sizes.median))?;
+            writeln!(f, "  90%:       {}", size(sizes.p90))?;
+            writeln!(f, "  99%:       {}", size(sizes.p99))?;
+            writeln!(f, "  Largest:   {}", size(sizes.largest))?;
+        }
         writeln!(f)?;
         writeln!(f, "Status codes:")?;
-        let mut status_counts: Vec<(&u16, &u32)> = self.status_counts.iter().collect();
-        status_counts.sort_by(|&(&code_a, _), &(&code_b, _)| code_a.cmp(&code_b));
-        for (k, v) in status_counts {
-            writeln!(f, "  {}: {}", k, v)?;
+        let mut status_counts: Vec<(&u16, &u32)> = summary.status_counts.iter().collect();
+        status_counts.sort_by_key(|&(&code, _)| code);
+        match summary.bar_width() {
+            Some(width) => {
+                let bars: Vec<(String, u32)> = status_counts.iter().map(|&(k, &v)| (k.to_string(), v)).collect();
+                for line in Chart::new().width(width).bars(&bars).lines() {
+                    writeln!(f, "  {}", line)?;
+                }
+            }
+            None => {
+                for (k, v) in status_counts {
+                    writeln!(f, "  {}: {}", k, v)?;
+                }
+            }
+        }
+        if !summary.labels.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "Labels:")?;
+            for (label, stats) in &summary.labels {
+                writeln!(
+                    f,
+                    "  {}: {} (average {})",
+                    label,
+                    stats.count,
+                    summary.time_unit.format(stats.average())
+                )?;
+            }
         }
-        if self.chart_size != ChartSize::None {
+        if summary.targets.len() > 1 {
+            writeln!(f)?;
+            writeln!(f, "Urls:")?;
+            for (&url, stats) in &summary.targets {
+                writeln!(
+                    f,
+                    "  {}: {} ({} failed, average {})",
+                    summary.url(url),
+                    stats.count,
+                    stats.failures,
+                    summary.time_unit.format(stats.average())
+                )?;
+            }
+        }
+        if !summary.checksums.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "Checksums (distinct bodies per url):")?;
+            for (url, variance) in summary.checksums.variances() {
+                write!(
+                    f,
+                    "  {}: {} distinct in {} responses",
+                    summary.url(url),
+                    variance.distinct,
+                    variance.responses
+                )?;
+                if variance.distinct > 1 {
+                    write!(
+                        f,
+                        " (the most common in {:.2}%)",
+                        variance.most_common as f64 * 100. / variance.responses as f64
+                    )?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Display for SummaryCharts<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let summary = self.0;
+        if summary.chart_size != ChartSize::None && !summary.percentiles.is_empty() {
             writeln!(f)?;
             writeln!(f, "Latency Percentiles (2% of requests per bar):")?;
-            let percentiles: Vec<f64> = self.percentiles.iter().map(|d| d.to_ms()).collect();
-            writeln!(f, "{}", self.chart(&percentiles))?;
+            let unit = summary.time_unit.resolve(summary.max);
+            let percentiles: Vec<f64> = summary.percentiles.iter().map(|d| unit.value(*d)).collect();
+            writeln!(f, "{}", summary.chart(&percentiles, Some(unit.symbol())))?;
             writeln!(f)?;
             writeln!(f, "Latency Histogram (each bar is 2% of max latency)")?;
-            writeln!(f, "{}", self.chart(&self.latency_histogram))?;
+            writeln!(f, "{}", summary.chart(&summary.latency_histogram, None))?;
+            if let Some(sizes) = summary.response_sizes.as_ref().filter(|sizes| sizes.varied()) {
+                // The buckets are added up rather than sampled, so that no response is left out.
+                let (height, scale) = summary.chart_size.dimensions().expect("Charts are drawn");
+                let histogram: Vec<u32> = sizes.histogram.chunks(scale).map(|sizes| sizes.iter().sum()).collect();
+                writeln!(f)?;
+                writeln!(f, "Response Size Histogram (each bar is {}% of the largest response)", scale)?;
+                writeln!(f, "{}", Chart::new().height(height).make(&histogram))?;
+            }
+            if summary.chart_size == ChartSize::Large && !summary.latency_by_order.is_empty() {
+                writeln!(f)?;
+                writeln!(f, "Latency by Request Order (average of each 1% of requests, first to last)")?;
+                let max = summary.latency_by_order.iter().max().cloned().unwrap_or_default();
+                let unit = summary.time_unit.resolve(max);
+                let averages: Vec<f64> = summary.latency_by_order.iter().map(|d| unit.value(*d)).collect();
+                writeln!(f, "{}", summary.chart(&averages, Some(unit.symbol())))?;
+            }
+            if let Some(sample) = summary.chart_sample {
+                writeln!(f, "Charts are drawn from a random sample of {} requests", sample)?;
+            }
         }
         Ok(())
     }
@@ -332,26 +2111,185 @@ mod summary_tests {
 
     fn ok_zero_length_fact(duration: Duration) -> Fact {
         Fact {
-            status: 200,
-            duration: duration,
+            status: Some(200),
+            duration,
             content_length: ContentLength::zero(),
+            new_connection: None,
+            download: None,
+            redirects: 0,
+            pool_wait: None,
+            lag: None,
+            started: None,
+            retry_after: None,
+            backoff: None,
+            body_mismatch: false,
+            echo_mismatch: false,
+            declared_length: None,
+            checksum: None,
+            labels: Vec::new(),
+            url: None,
+            method: None,
+            family: None,
+            server_timing: Vec::new(),
+            trace: None,
+            version: None,
+            round_trip: false,
         }
     }
 
     fn ok_instant_fact(content_length: ContentLength) -> Fact {
         Fact {
-            status: 200,
+            status: Some(200),
             duration: Duration::new(0, 0),
             content_length,
+            new_connection: None,
+            download: None,
+            redirects: 0,
+            pool_wait: None,
+            lag: None,
+            started: None,
+            retry_after: None,
+            backoff: None,
+            body_mismatch: false,
+            echo_mismatch: false,
+            declared_length: None,
+            checksum: None,
+            labels: Vec::new(),
+            url: None,
+            method: None,
+            family: None,
+            server_timing: Vec::new(),
+            trace: None,
+            version: None,
+            round_trip: false,
         }
     }
 
     fn zero_length_instant_fact(status: u16) -> Fact {
         Fact {
-            status,
+            status: Some(status),
             duration: Duration::new(0, 0),
             content_length: ContentLength::zero(),
+            new_connection: None,
+            download: None,
+            redirects: 0,
+            pool_wait: None,
+            lag: None,
+            started: None,
+            retry_after: None,
+            backoff: None,
+            body_mismatch: false,
+            echo_mismatch: false,
+            declared_length: None,
+            checksum: None,
+            labels: Vec::new(),
+            url: None,
+            method: None,
+            family: None,
+            server_timing: Vec::new(),
+            trace: None,
+            version: None,
+            round_trip: false,
+        }
+    }
+
+    #[test]
+    fn averages_latency_in_the_order_of_the_requests() {
+        let facts: Vec<Fact> = (0..200)
+            .map(|n| ok_zero_length_fact(Duration::from_millis(n)))
+            .collect();
+        let by_order = latency_by_order(&facts);
+        assert_eq!(by_order.len(), 100);
+        assert_eq!(by_order[0], Duration::from_micros(500));
+        assert_eq!(by_order[99], Duration::from_micros(198_500));
+        assert_eq!(latency_by_order(&facts[..3]).len(), 3);
+    }
+
+    #[test]
+    fn sums_pool_waits() {
+        let facts = [
+            zero_length_instant_fact(200).with_pool_wait(Duration::from_millis(3)),
+            zero_length_instant_fact(200).with_pool_wait(Duration::new(0, 0)),
+        ];
+        let waits = Summary::from_facts(&facts).pool_waits.unwrap();
+        assert_eq!((waits.limited, waits.waited), (2, 1));
+        assert_eq!(waits.average(), Duration::from_micros(1500));
+        assert_eq!(waits.longest, Duration::from_millis(3));
+    }
+
+    #[test]
+    fn reports_the_throttled_requests() {
+        let facts = [
+            zero_length_instant_fact(429)
+                .with_retry_after(Some(Duration::from_secs(2)))
+                .with_backoff(Some(Duration::from_secs(2))),
+            zero_length_instant_fact(429),
+            zero_length_instant_fact(503).with_retry_after(Some(Duration::from_secs(4))),
+            zero_length_instant_fact(200),
+        ];
+        let summary = Summary::from_facts(&facts).with_time_unit(TimeUnit::Millis);
+        let text = summary.text().to_string();
+        assert!(text.contains("  Throttled: 2 requests got a 429 (50.00% of requests, average 0.00 ms)\n"));
+        assert!(text.contains(
+            "  Backoff:   2 responses asked to retry after 3000.00 ms on average, and the workers waited 2000.00 ms in all\n"
+        ));
+
+        let results = summary.results();
+        let merged = Summary::from_results(&[&results, &results]).unwrap();
+        assert_eq!(merged.throttling.map(|t| (t.throttled, t.asked)), Some((4, 4)));
+        assert_eq!(Summary::from_facts(&facts[3..]).throttling, None);
+    }
+
+    #[test]
+    fn streams_the_statistics_of_big_runs() {
+        let durations: Vec<Duration> = (1..=10_000).map(|n| Duration::from_micros(n * 37 % 20_011)).collect();
+        let exact = DurationStats::from_durations(durations.clone());
+        let streamed = DurationStats::streamed(durations.into_iter());
+        assert_eq!((streamed.min(), streamed.max()), (exact.min(), exact.max()));
+        assert!((streamed.average().to_ms() - exact.average().to_ms()).abs() < 1e-6);
+        assert!((streamed.stddev().to_ms() - exact.stddev().to_ms()).abs() < 1e-3);
+        for &(_, quantile) in PERCENTILE_TABLE.iter() {
+            let (s, e) = (streamed.quantile(quantile).to_ms(), exact.quantile(quantile).to_ms());
+            assert!((s - e).abs() <= e * 0.01, "{} is {} streamed but {} exactly", quantile, s, e);
+        }
+        assert_eq!(
+            streamed.latency_histogram().iter().sum::<u32>(),
+            exact.latency_histogram().iter().sum::<u32>()
+        );
+    }
+
+    #[test]
+    fn breaks_the_requests_down_by_url() {
+        let facts = [
+            zero_length_instant_fact(200).with_url(0).with_method(Method::Get),
+            zero_length_instant_fact(500).with_url(1).with_method(Method::Post),
+            zero_length_instant_fact(200).with_url(1).with_method(Method::Post),
+        ];
+        let urls = ["http://a/".to_string(), "http://b/".to_string()];
+        let summary = Summary::from_facts(&facts).with_urls(&urls).with_time_unit(TimeUnit::Millis);
+        let text = summary.text().to_string();
+        assert!(text.contains("Urls:\n  http://a/: 1 (0 failed, average 0.00 ms)\n  http://b/: 2 (1 failed, average 0.00 ms)\n"));
+
+        let mut acc = Accumulator::new();
+        for fact in &facts {
+            acc.add(fact.clone());
         }
+        assert_eq!(Summary::from_accumulator(&acc).targets, summary.targets);
+        let targets = summary.results().targets.unwrap();
+        assert_eq!((targets["http://b/"].count, targets["http://b/"].failures), (2, 1));
+        assert_eq!(Summary::from_facts(&facts[..1]).results().targets, None);
+    }
+
+    #[test]
+    fn counts_redirects() {
+        let facts = [
+            zero_length_instant_fact(200).with_redirects(2),
+            zero_length_instant_fact(200),
+            zero_length_instant_fact(200).with_redirects(1),
+        ];
+        let redirects = Summary::from_facts(&facts).redirects.unwrap();
+        assert_eq!((redirects.redirected, redirects.hops, redirects.most), (2, 3, 2));
+        assert!(Summary::from_facts(&facts[1..2]).redirects.is_none());
     }
 
     #[test]
@@ -386,6 +2324,28 @@ mod summary_tests {
         assert_eq!(summary.stddev, Duration::new(1, 290994448));
     }
 
+    #[test]
+    fn trims_the_slowest_outliers() {
+        let mut facts: Vec<Fact> = (0..99)
+            .map(|n| ok_zero_length_fact(Duration::from_millis(if n < 50 { 1 } else { 2 })))
+            .collect();
+        facts.push(ok_zero_length_fact(Duration::from_secs(5)));
+        let summary = Summary::from_facts(&facts).with_time_unit(TimeUnit::Millis);
+        assert_eq!(summary.trimmed(), None);
+
+        let summary = summary.with_trimmed_outliers(0.001);
+        let trimmed = summary.trimmed().unwrap();
+        assert_eq!(trimmed.left_out, 1);
+        assert_eq!(trimmed.median, Duration::from_millis(1));
+        assert_eq!(trimmed.max, Duration::from_millis(2));
+        assert!((trimmed.average.to_ms() - 148. / 99.).abs() < 1e-3);
+        assert!(trimmed.stddev < Duration::from_millis(1));
+        assert!(summary.to_string().contains(
+            "  Without the slowest 0.10% of requests (1 left out):\n    Average: 1.49 ms (std: 0.50 ms)\n    Median:  1.00 ms\n    Longest: 2.00 ms\n"
+        ));
+        assert_eq!(summary.with_trimmed_outliers(1.).trimmed(), None);
+    }
+
     #[test]
     fn counts_the_facts() {
         let facts = [
@@ -464,6 +2424,41 @@ mod summary_tests {
         assert_eq!(summary.percentiles[50], Duration::new(250, 0));
     }
 
+    #[test]
+    fn tabulates_the_percentiles_by_nearest_rank() {
+        let facts: Vec<Fact> = (1..=1000)
+            .map(|n| ok_zero_length_fact(Duration::from_millis(n)))
+            .collect();
+        let summary = Summary::from_facts(&facts);
+
+        let ms = |table: &[Duration]| table.iter().map(|d| d.as_millis()).collect::<Vec<_>>();
+        assert_eq!(ms(&summary.percentile_table), vec![500, 750, 900, 950, 990, 999, 1000]);
+        let printed = summary.to_string();
+        assert!(printed.contains("  p99.9:     999.00 ms"), "{}", printed);
+        assert!(printed.contains("  max:       1.00 s"), "{}", printed);
+    }
+
+    #[test]
+    fn writes_the_chart_data_in_columns() {
+        let facts: Vec<Fact> = (1..=100)
+            .map(|n| ok_zero_length_fact(Duration::from_millis(n)))
+            .collect();
+        let data = Summary::from_facts(&facts).chart_data();
+        let blocks: Vec<&str> = data.split("\n\n\n").collect();
+        assert_eq!(blocks.len(), 2);
+
+        let percentiles: Vec<&str> = blocks[0].lines().collect();
+        assert_eq!(percentiles[0], "# percentile latency_ms");
+        assert_eq!(percentiles[1], "0 1.000");
+        assert_eq!(percentiles[100], "99 100.000");
+
+        let bins: Vec<&str> = blocks[1].lines().collect();
+        assert_eq!(bins[0], "# bin_start_ms bin_end_ms count");
+        assert_eq!(bins[1], "0.000 1.000 0");
+        assert_eq!(bins[2], "1.000 2.000 1");
+        assert_eq!(bins.len(), 101);
+    }
+
     #[test]
     fn sums_up_the_content_lengths() {
         let facts: Vec<Fact> = (0..500)
@@ -488,4 +2483,241 @@ mod summary_tests {
         let summary = Summary::from_facts(&facts);
         assert_eq!(summary.status_counts.get(&200), Some(&4));
     }
+
+    #[test]
+    fn prints_the_status_codes_as_bars_with_the_charts() {
+        let facts = vec![zero_length_instant_fact(200), zero_length_instant_fact(200), zero_length_instant_fact(503)];
+        let printed = Summary::from_facts(&facts).with_chart_size(ChartSize::Small).to_string();
+        assert!(printed.contains("Status codes:\n  200 │████████████████████ 2\n  503 │██████████           1\n"));
+        let printed = Summary::from_facts(&facts).with_chart_size(ChartSize::None).to_string();
+        assert!(printed.contains("Status codes:\n  200: 2\n  503: 1\n"));
+    }
+
+    #[test]
+    fn counts_errors_separately_from_status_codes() {
+        let facts: Vec<Fact> = vec![
+            zero_length_instant_fact(200),
+            Fact::error(Duration::new(0, 0)),
+        ];
+        let summary = Summary::from_facts(&facts);
+        assert_eq!(summary.errors(), 1);
+        assert_eq!(summary.status_counts.len(), 1);
+    }
+
+    #[test]
+    fn calculates_the_failure_rate() {
+        let facts: Vec<Fact> = vec![
+            zero_length_instant_fact(200),
+            zero_length_instant_fact(201),
+            zero_length_instant_fact(500),
+            Fact::error(Duration::new(0, 0)),
+        ];
+        let summary = Summary::from_facts(&facts);
+        assert_eq!(summary.failure_rate(), 0.5);
+        assert_eq!(Summary::from_facts(&[]).failure_rate(), 0.);
+    }
+
+    #[test]
+    fn counts_round_trips_without_a_status_as_successes() {
+        let facts: Vec<Fact> = vec![
+            Fact::round_trip(ContentLength::new(5), Duration::new(0, 0)),
+            zero_length_instant_fact(101),
+            Fact::error(Duration::new(0, 0)),
+        ];
+        let summary = Summary::from_facts(&facts);
+        assert_eq!(summary.errors(), 1);
+        assert_eq!(summary.failure_rate(), 2. / 3.);
+        assert_eq!(summary.status_counts.len(), 1);
+    }
+
+    #[test]
+    fn summarizes_an_accumulator() {
+        let mut acc = Accumulator::new();
+        for n in 1..5 {
+            acc.add(ok_zero_length_fact(Duration::from_micros(n)));
+        }
+        acc.add(zero_length_instant_fact(500));
+        let summary = Summary::from_accumulator(&acc);
+        assert_eq!(summary.count, 5);
+        assert_eq!(summary.median, Duration::from_micros(2));
+        assert_eq!(summary.max, Duration::from_micros(4));
+        assert_eq!(summary.min, Duration::new(0, 0));
+        assert_eq!(summary.failure_rate(), 0.2);
+        assert!(summary.percentiles.is_empty());
+    }
+
+    #[test]
+    fn charts_an_accumulator_from_its_sample() {
+        let mut acc = Accumulator::new().with_sample(50);
+        for n in 0..500 {
+            acc.add(ok_zero_length_fact(Duration::new(n, 0)));
+        }
+        let summary = Summary::from_accumulator(&acc);
+        assert_eq!(summary.percentiles.len(), 100);
+        assert_eq!(summary.chart_sample, Some(50));
+    }
+
+    #[test]
+    fn charts_an_accumulator_that_recorded_facts() {
+        let mut acc = Accumulator::recording(500);
+        for n in 0..500 {
+            acc.add(ok_zero_length_fact(Duration::new(n, 0)));
+        }
+        let summary = Summary::from_accumulator(&acc);
+        assert_eq!(summary.percentiles.len(), 100);
+        assert_eq!(summary.latency_histogram.len(), 100);
+
+        let from_facts = Summary::from_facts(acc.facts().unwrap());
+        assert_eq!(summary.median, from_facts.median);
+        assert_eq!(summary.percentile_table, from_facts.percentile_table);
+        assert_eq!(summary.results().latency_ms.average, from_facts.results().latency_ms.average);
+    }
+
+    #[test]
+    fn counts_connections_when_tracked() {
+        let facts: Vec<Fact> = vec![
+            zero_length_instant_fact(200).with_new_connection(true),
+            zero_length_instant_fact(200).with_new_connection(false),
+            zero_length_instant_fact(200).with_new_connection(false),
+            zero_length_instant_fact(200).with_new_connection(false),
+            zero_length_instant_fact(200),
+        ];
+        let summary = Summary::from_facts(&facts);
+        let connections = summary.connections.unwrap();
+        assert_eq!(connections.opened, 1);
+        assert_eq!(connections.tracked, 4);
+        assert_eq!(connections.reuse_ratio(), 0.75);
+
+        let summary = Summary::from_facts(&[zero_length_instant_fact(200)]);
+        assert!(summary.connections.is_none());
+    }
+
+    #[test]
+    fn counts_the_family_of_each_connection() {
+        let facts: Vec<Fact> = vec![
+            zero_length_instant_fact(200).with_family(Some(Family::V4)),
+            zero_length_instant_fact(200).with_family(Some(Family::V6)),
+            zero_length_instant_fact(200).with_family(Some(Family::V6)),
+            zero_length_instant_fact(200),
+        ];
+        let summary = Summary::from_facts(&facts);
+        assert_eq!(summary.families, Some(Families { ipv4: 1, ipv6: 2 }));
+        assert!(summary.to_string().contains("  Family:    1 requests over IPv4, 2 over IPv6\n"));
+        let mut accumulator = Accumulator::new();
+        for fact in facts {
+            accumulator.add(fact);
+        }
+        assert_eq!(accumulator.families(), &Families { ipv4: 1, ipv6: 2 });
+
+        let summary = Summary::from_facts(&[zero_length_instant_fact(200)]);
+        assert!(summary.families.is_none());
+    }
+
+    #[test]
+    fn measures_transfer_rates_of_timed_downloads() {
+        let facts: Vec<Fact> = vec![
+            ok_instant_fact(ContentLength::new(1000)).with_download(Duration::from_secs(1)),
+            ok_instant_fact(ContentLength::new(4000)).with_download(Duration::from_secs(2)),
+            ok_instant_fact(ContentLength::new(3000)).with_download(Duration::from_secs(1)),
+            ok_instant_fact(ContentLength::new(1000)),
+            zero_length_instant_fact(200).with_download(Duration::from_secs(1)),
+        ];
+        let rates = Summary::from_facts(&facts).transfer_rates.unwrap();
+        assert_eq!(rates.slowest, 1000.);
+        assert_eq!(rates.median, 2000.);
+        assert_eq!(rates.fastest, 3000.);
+
+        let summary = Summary::from_facts(&[ok_instant_fact(ContentLength::new(1000))]);
+        assert!(summary.transfer_rates.is_none());
+    }
+
+    #[test]
+    fn measures_the_throughput_of_each_request() {
+        let fact = |bytes, ms| Fact::record(ContentLength::new(bytes), 200, Duration::from_millis(ms));
+        let facts = vec![fact(1000, 500), fact(1000, 1000), fact(3000, 1000), fact(0, 1000)];
+        let summary = Summary::from_facts(&facts);
+        let throughputs = summary.throughputs.unwrap();
+        assert_eq!(throughputs.slowest, 1000.);
+        assert_eq!(throughputs.median, 2000.);
+        assert_eq!(throughputs.fastest, 3000.);
+        assert!(summary.to_string().contains("Throughput (body bytes / request time):\n  Slowest:   1000 B/s\n"));
+    }
+
+    #[test]
+    fn distributes_the_sizes_of_the_responses() {
+        let fact = |bytes| Fact::record(ContentLength::new(bytes), 200, Duration::from_millis(1));
+        let mut facts: Vec<Fact> = (0..8).map(|_| fact(1_000)).collect();
+        facts.extend(vec![fact(10_000), fact(10_000), Fact::error(Duration::from_millis(1))]);
+        let summary = Summary::from_facts(&facts).with_size_unit(SizeUnit::Si);
+        let sizes = summary.response_sizes.clone().unwrap();
+        assert_eq!((sizes.smallest, sizes.median, sizes.p90, sizes.largest), (1_000, 1_000, 10_000, 10_000));
+        assert_eq!(sizes.average, 2_800.);
+        assert_eq!((sizes.histogram[10], sizes.histogram[99]), (8, 2));
+        let text = summary.to_string();
+        assert!(text.contains("Response sizes:\n  Average:   2.80 kB\n  Smallest:  1000 B\n"));
+        assert!(text.contains("Response Size Histogram (each bar is 2% of the largest response)"));
+        assert_eq!(summary.results().response_sizes_bytes.unwrap().p99, 10_000);
+
+        let mut acc = Accumulator::new().with_sample(5);
+        for fact in facts {
+            acc.add(fact);
+        }
+        assert_eq!(Summary::from_accumulator(&acc).response_sizes, Some(sizes));
+
+        let same = Summary::from_facts(&[fact(1_000), fact(1_000)]).to_string();
+        assert!(!same.contains("Response sizes:"));
+    }
+
+    #[test]
+    fn totals_the_bytes_of_each_status_class() {
+        let fact = |bytes, status| Fact::record(ContentLength::new(bytes), status, Duration::from_millis(1));
+        let facts = vec![fact(2_000, 200), fact(1_000, 204), fact(500, 404), Fact::error(Duration::from_millis(1))];
+        let summary = Summary::from_facts(&facts).with_size_unit(SizeUnit::Si);
+        assert!(summary.to_string().contains("  Data:      3.50 kB (2xx: 3.00 kB, 4xx: 500 B)\n"));
+        let results = summary.results();
+        assert_eq!(results.content_length_bytes_by_class.get("2xx"), Some(&3_000));
+        assert_eq!(results.content_length_bytes_by_class.get("4xx"), Some(&500));
+    }
+
+    #[test]
+    fn averages_label_latencies() {
+        let facts: Vec<Fact> = vec![
+            ok_zero_length_fact(Duration::from_millis(2)).with_label("profile", "mobile"),
+            ok_zero_length_fact(Duration::from_millis(4)).with_label("profile", "mobile"),
+        ];
+        let summary = Summary::from_facts(&facts);
+        let mobile = summary.labels["profile=mobile"];
+        assert_eq!(mobile.average(), Duration::from_millis(3));
+    }
+
+    #[test]
+    fn counts_distinct_checksums_per_url() {
+        let facts: Vec<Fact> = ["a", "a", "b"]
+            .iter()
+            .map(|body| zero_length_instant_fact(200).with_checksum(Checksum::of(0, body.as_bytes())))
+            .collect();
+        let summary = Summary::from_facts(&facts).with_urls(&["http://a/".to_string()]);
+
+        let printed = summary.to_string();
+        assert!(
+            printed.contains("  http://a/: 2 distinct in 3 responses (the most common in 66.67%)"),
+            "{}",
+            printed
+        );
+        assert_eq!(summary.results().checksums.unwrap()["http://a/"].distinct, 2);
+    }
+
+    #[test]
+    fn counts_labels() {
+        let facts: Vec<Fact> = vec![
+            zero_length_instant_fact(200).with_label("tier", "gold"),
+            zero_length_instant_fact(200).with_label("tier", "gold"),
+            zero_length_instant_fact(200).with_label("tier", "free"),
+            zero_length_instant_fact(200),
+        ];
+        let summary = Summary::from_facts(&facts);
+        assert_eq!(summary.labels.get("tier=gold").map(|l| l.count), Some(2));
+        assert_eq!(summary.labels.get("tier=free").map(|l| l.count), Some(1));
+        assert_eq!(summary.labels.len(), 2);
+    }
 }
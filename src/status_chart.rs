@@ -0,0 +1,125 @@
+use chart::Chart;
+use columns::Columns;
+use std::fmt;
+use std::time::Duration;
+
+const HEIGHT: u32 = 8;
+
+/// The families of responses from the bottom of the chart up, with how each is drawn. Errors
+/// are the requests that got no response.
+const FAMILIES: [(&str, char); 6] = [
+    ("2xx", '░'),
+    ("3xx", '▒'),
+    ("1xx", '·'),
+    ("4xx", '▓'),
+    ("5xx", '█'),
+    ("errors", 'x'),
+];
+
+/// Counts the responses of each status family by when they finished, so that a burst of
+/// server errors part way through a run stands out where the status counts of the run as a
+/// whole would hide it.
+pub struct StatusChart {
+    /// The count of each family in each column, in the order of `FAMILIES`.
+    columns: Columns<[u64; 6]>,
+}
+
+impl Default for StatusChart {
+    fn default() -> StatusChart {
+        StatusChart::new()
+    }
+}
+
+impl StatusChart {
+    /// Starts a chart whose time starts now.
+    pub fn new() -> StatusChart {
+        StatusChart {
+            columns: Columns::default(),
+        }
+    }
+
+    /// Counts a request that has just finished, without a status if it errored.
+    pub fn record(&mut self, status: Option<u16>) {
+        let elapsed = self.columns.elapsed();
+        self.record_at(elapsed, status);
+    }
+
+    fn record_at(&mut self, elapsed: Duration, status: Option<u16>) {
+        self.columns.at(elapsed)[family_of(status)] += 1;
+    }
+}
+
+/// The index in `FAMILIES` of the family of the status.
+fn family_of(status: Option<u16>) -> usize {
+    match status {
+        Some(200..=299) => 0,
+        Some(300..=399) => 1,
+        Some(400..=499) => 3,
+        Some(500..=599) => 4,
+        Some(_) => 2,
+        None => 5,
+    }
+}
+
+impl fmt::Display for StatusChart {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.columns.columns().is_empty() {
+            return Ok(());
+        }
+        let (columns, took) = self.columns.printed();
+        // Only the families that were seen are drawn.
+        let layers: Vec<(&str, char, Vec<f64>)> = FAMILIES
+            .iter()
+            .enumerate()
+            .filter(|&(family, _)| columns.iter().any(|counts| counts[family] > 0))
+            .map(|(family, &(label, fill))| {
+                (
+                    label,
+                    fill,
+                    columns.iter().map(|counts| counts[family] as f64).collect(),
+                )
+            })
+            .collect();
+        writeln!(f, "Responses over 0 s to {:.2} s:", took.as_secs_f64())?;
+        write!(f, "{}", Chart::new().height(HEIGHT).stacked(&layers))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use columns::{FIRST_COLUMN_MS, MAX_COLUMNS};
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn stacks_the_families_seen_in_each_column() {
+        let mut chart = StatusChart::new();
+        for _ in 0..4 {
+            chart.record_at(ms(0), Some(200));
+        }
+        chart.record_at(ms(10), Some(204));
+        chart.record_at(ms(10), Some(503));
+        chart.record_at(ms(10), Some(500));
+        chart.record_at(ms(10), None);
+        assert_eq!(chart.columns.columns(), &[[4, 0, 0, 0, 0, 0], [1, 0, 0, 0, 2, 1]][..]);
+
+        let printed = chart.to_string();
+        assert!(printed.starts_with("Responses over 0 s to 0.02 s:\n"));
+        assert!(printed.ends_with("░ 2xx  █ 5xx  x errors\n"));
+        assert!(printed.contains("░x 4\n"));
+        assert!(printed.contains("░░ 0\n"));
+    }
+
+    #[test]
+    fn doubles_the_columns_once_the_run_outgrows_them() {
+        let mut chart = StatusChart::new();
+        chart.record_at(ms(0), Some(200));
+        chart.record_at(ms(FIRST_COLUMN_MS * MAX_COLUMNS as u64), Some(500));
+        assert_eq!(chart.columns.width(), ms(FIRST_COLUMN_MS * 2));
+        assert_eq!(chart.columns.columns().len(), MAX_COLUMNS / 2 + 1);
+        assert_eq!(chart.columns.columns()[MAX_COLUMNS / 2][4], 1);
+    }
+}
@@ -0,0 +1,80 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Stops a run part way through. The collector decides to stop as the facts arrive, and every
+/// worker checks before each request, so a stopped run finishes the requests already in
+/// flight and makes no more.
+#[derive(Clone, Default)]
+pub struct Stop {
+    stopped: Arc<AtomicBool>,
+    aborted: Arc<AtomicBool>,
+    reason: Arc<Mutex<Option<String>>>,
+}
+
+impl Stop {
+    pub fn new() -> Stop {
+        Stop::default()
+    }
+
+    /// Stops the run, keeping the reason it was first stopped for.
+    pub fn stop(&self, reason: String) {
+        let mut first = self.reason.lock().expect("Expected the stop reason to be lockable");
+        if first.is_none() {
+            *first = Some(reason);
+        }
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    /// Stops the run because it went wrong, so that it fails once its results are written.
+    pub fn abort(&self, reason: String) {
+        if !self.is_stopped() {
+            self.aborted.store(true, Ordering::Relaxed);
+        }
+        self.stop(reason);
+    }
+
+    /// Lets the workers make requests again, for the next run of an interactive session.
+    pub fn reset(&self) {
+        *self.reason.lock().expect("Expected the stop reason to be lockable") = None;
+        self.aborted.store(false, Ordering::Relaxed);
+        self.stopped.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    /// Whether the run was first stopped because it went wrong.
+    pub fn is_aborted(&self) -> bool {
+        self.aborted.load(Ordering::Relaxed)
+    }
+
+    /// Why the run was stopped, if it was.
+    pub fn reason(&self) -> Option<String> {
+        self.reason.lock().expect("Expected the stop reason to be lockable").clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_first_reason() {
+        let stop = Stop::new();
+        let worker = stop.clone();
+        assert!(!worker.is_stopped());
+        stop.stop("first".to_string());
+        stop.stop("second".to_string());
+        assert!(worker.is_stopped());
+        assert_eq!(worker.reason(), Some("first".to_string()));
+        assert!(!worker.is_aborted());
+
+        let stop = Stop::new();
+        stop.abort("failing".to_string());
+        assert!(stop.is_aborted());
+        stop.reset();
+        assert!(!stop.is_stopped() && !stop.is_aborted());
+        assert_eq!(stop.reason(), None);
+    }
+}
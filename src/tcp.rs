@@ -0,0 +1,118 @@
+use std::io::{self, Read, Write};
+
+/// How much of the reply the tcp engine reads before an exchange is done.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reply {
+    /// A fixed number of bytes, after which the connection is used for the next exchange.
+    Bytes(u64),
+    /// Everything until the server closes the connection, so each exchange connects anew.
+    UntilClose,
+}
+
+impl Reply {
+    /// Whether the connection can be used again once the reply has been read.
+    pub fn keeps_alive(self) -> bool {
+        match self {
+            Reply::Bytes(_) => true,
+            Reply::UntilClose => false,
+        }
+    }
+}
+
+/// Parses a `tcp://host:port` url into the address to connect to.
+pub fn address(url: &str) -> Result<String, String> {
+    let address = url
+        .strip_prefix("tcp://")
+        .ok_or_else(|| format!("The tcp engine only supports tcp urls, got '{}'", url))?
+        .trim_end_matches('/');
+    match address.rfind(':') {
+        Some(colon) if colon > 0 && address[colon + 1..].parse::<u16>().is_ok() => {
+            Ok(address.to_string())
+        }
+        _ => Err(format!("Expected a host and port in '{}'", url)),
+    }
+}
+
+/// Writes the payload and reads the reply, returning how many bytes were read.
+pub fn exchange<S: Read + Write>(stream: &mut S, payload: &[u8], reply: Reply) -> io::Result<u64> {
+    stream.write_all(payload)?;
+    stream.flush()?;
+    match reply {
+        Reply::Bytes(length) => {
+            let read = io::copy(&mut stream.take(length), &mut io::sink())?;
+            if read < length {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("The connection closed after {} of {} bytes", read, length),
+                ));
+            }
+            Ok(read)
+        }
+        Reply::UntilClose => io::copy(stream, &mut io::sink()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// A stream that replies with fixed bytes and records what was written to it.
+    struct Fake {
+        written: Vec<u8>,
+        reply: Cursor<Vec<u8>>,
+    }
+
+    impl Fake {
+        fn new(reply: &str) -> Fake {
+            Fake {
+                written: Vec::new(),
+                reply: Cursor::new(reply.as_bytes().to_vec()),
+            }
+        }
+    }
+
+    impl Read for Fake {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.reply.read(buf)
+        }
+    }
+
+    impl Write for Fake {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn parses_addresses() {
+        assert_eq!(address("tcp://localhost:11211"), Ok("localhost:11211".to_string()));
+        assert_eq!(address("tcp://[::1]:80/"), Ok("[::1]:80".to_string()));
+        assert!(address("http://localhost:80").is_err());
+        assert!(address("tcp://localhost").is_err());
+    }
+
+    #[test]
+    fn reads_a_fixed_number_of_bytes() {
+        let mut stream = Fake::new("STORED\r\nEND\r\n");
+        assert_eq!(exchange(&mut stream, b"set k\r\n", Reply::Bytes(8)).unwrap(), 8);
+        assert_eq!(stream.written, b"set k\r\n");
+        assert_eq!(exchange(&mut stream, b"", Reply::Bytes(5)).unwrap(), 5);
+    }
+
+    #[test]
+    fn fails_when_the_reply_is_short() {
+        let mut stream = Fake::new("END");
+        assert!(exchange(&mut stream, b"get k\r\n", Reply::Bytes(5)).is_err());
+    }
+
+    #[test]
+    fn reads_until_close() {
+        let mut stream = Fake::new("hello world");
+        assert_eq!(exchange(&mut stream, b"hi", Reply::UntilClose).unwrap(), 11);
+    }
+}
@@ -0,0 +1,192 @@
+use echo;
+use random::Rng;
+use std::fmt::Write;
+
+/// A string with placeholders that are filled in for each request. Placeholders are
+/// wrapped in double braces:
+///
+/// * `{{n}}` is the number of the request in the run, counted from zero across the workers.
+/// * `{{uuid}}` is a random v4 uuid.
+/// * `{{rand_int(1,100)}}` is a random integer between the bounds, inclusive.
+/// * `{{token}}` is a random token for the response to echo, checked with `--echo`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+    Literal(String),
+    Number,
+    Uuid,
+    RandInt(i64, i64),
+    Token,
+}
+
+impl Template {
+    /// Parses the template, failing if a placeholder is unknown or never closed.
+    pub fn parse(template: &str) -> Result<Template, String> {
+        Template::parse_with_columns(template, &[])
+    }
+
+    /// Parses the template, keeping the placeholders that name a column of the csv feed
+    /// as they are for the feed to fill in.
+    pub fn parse_with_columns(template: &str, columns: &[String]) -> Result<Template, String> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find("}}")
+                .ok_or_else(|| format!("Unclosed placeholder in '{}'", template))?;
+            let placeholder = rest[start + 2..start + end].trim();
+            if columns.iter().any(|column| column == placeholder) {
+                parts.push(Part::Literal(rest[start..start + end + 2].to_string()));
+            } else {
+                parts.push(Part::parse(placeholder)?);
+            }
+            rest = &rest[start + end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        Ok(Template { parts })
+    }
+
+    /// Whether the template has no placeholders and so always renders the same string.
+    pub fn is_static(&self) -> bool {
+        self.parts.iter().all(|part| matches!(*part, Part::Literal(_)))
+    }
+
+    /// Whether the template puts a token in each body for the response to echo.
+    pub fn has_token(&self) -> bool {
+        self.parts.iter().any(|part| matches!(*part, Part::Token))
+    }
+
+    /// Renders the template for the nth request.
+    pub fn render(&self, n: usize, rng: &mut Rng) -> String {
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match *part {
+                Part::Literal(ref literal) => rendered.push_str(literal),
+                Part::Number => write!(rendered, "{}", n).expect("Writing to a string"),
+                Part::Uuid => write_uuid(&mut rendered, rng),
+                Part::RandInt(low, high) => {
+                    write!(rendered, "{}", rng.range(low, high)).expect("Writing to a string")
+                }
+                Part::Token => echo::write_token(&mut rendered, rng),
+            }
+        }
+        rendered
+    }
+}
+
+impl Part {
+    fn parse(placeholder: &str) -> Result<Part, String> {
+        match placeholder {
+            "n" => return Ok(Part::Number),
+            "uuid" => return Ok(Part::Uuid),
+            "token" => return Ok(Part::Token),
+            _ => {}
+        }
+        if placeholder.starts_with("rand_int(") && placeholder.ends_with(')') {
+            let args: Vec<&str> = placeholder["rand_int(".len()..placeholder.len() - 1]
+                .split(',')
+                .map(|arg| arg.trim())
+                .collect();
+            if args.len() == 2 {
+                if let (Ok(low), Ok(high)) = (args[0].parse::<i64>(), args[1].parse::<i64>()) {
+                    if low <= high {
+                        return Ok(Part::RandInt(low, high));
+                    }
+                }
+            }
+            return Err(format!(
+                "Expected rand_int(low,high) with low <= high, got '{}'",
+                placeholder
+            ));
+        }
+        Err(format!("Unknown placeholder '{{{{{}}}}}'", placeholder))
+    }
+}
+
+/// Writes a random v4 uuid.
+pub fn write_uuid(out: &mut String, rng: &mut Rng) {
+    let (a, b) = (rng.next_u64(), rng.next_u64());
+    write!(
+        out,
+        "{:08x}-{:04x}-4{:03x}-{:04x}-{:012x}",
+        a >> 32,
+        (a >> 16) & 0xffff,
+        a & 0x0fff,
+        ((b >> 48) & 0x3fff) | 0x8000,
+        b & 0xffff_ffff_ffff
+    )
+    .expect("Writing to a string");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_literals_untouched() {
+        let template = Template::parse("{\"hello\": \"world\"}").unwrap();
+        assert!(template.is_static());
+        assert_eq!(template.render(0, &mut Rng::new(0)), "{\"hello\": \"world\"}");
+    }
+
+    #[test]
+    fn renders_the_request_number() {
+        let template = Template::parse("id={{n}}&again={{ n }}").unwrap();
+        assert!(!template.is_static());
+        assert_eq!(template.render(7, &mut Rng::new(0)), "id=7&again=7");
+    }
+
+    #[test]
+    fn renders_random_integers_within_bounds() {
+        let template = Template::parse("{{rand_int(5, 6)}}").unwrap();
+        let mut rng = Rng::new(0);
+        for n in 0..100 {
+            let value: i64 = template.render(n, &mut rng).parse().unwrap();
+            assert!(value == 5 || value == 6);
+        }
+    }
+
+    #[test]
+    fn renders_uuids() {
+        let template = Template::parse("{{uuid}}").unwrap();
+        let uuid = template.render(0, &mut Rng::new(0));
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+        assert_ne!(uuid, template.render(0, &mut Rng::new(1)));
+    }
+
+    #[test]
+    fn renders_tokens() {
+        let template = Template::parse("{\"id\":\"{{token}}\"}").unwrap();
+        assert!(template.has_token());
+        assert!(!Template::parse("{{uuid}}").unwrap().has_token());
+        let body = template.render(0, &mut Rng::new(0));
+        assert!(body.starts_with("{\"id\":\"rench-"));
+        assert_eq!(body.len(), 38 + 9);
+    }
+
+    #[test]
+    fn rejects_bad_placeholders() {
+        assert!(Template::parse("{{nope}}").is_err());
+        assert!(Template::parse("{{n").is_err());
+        assert!(Template::parse("{{rand_int(5)}}").is_err());
+        assert!(Template::parse("{{rand_int(6,5)}}").is_err());
+    }
+
+    #[test]
+    fn keeps_the_columns_of_the_feed() {
+        let columns = vec!["user".to_string()];
+        let template = Template::parse_with_columns("{{ user }}-{{n}}", &columns).unwrap();
+        assert_eq!(template.render(3, &mut Rng::new(0)), "{{ user }}-3");
+    }
+}
@@ -0,0 +1,75 @@
+use header;
+use middleware::{Middleware, Request};
+use random::Rng;
+use std::sync::atomic::{AtomicU64, Ordering};
+use template;
+
+/// Sends every request with a header that carries an id of its own, such as
+/// `x-request-id`, and notes the id on the request's fact. The slow log and the emitted
+/// facts then name the id of each request, so that the outliers the client measured can be
+/// found among the server's traces.
+pub struct TraceHeader {
+    name: String,
+    /// Each id is drawn from a generator seeded with this and the number of ids drawn
+    /// before it, so that the workers don't have to share a generator.
+    seed: u64,
+    drawn: AtomicU64,
+}
+
+impl TraceHeader {
+    /// Parses a header like `x-request-id=uuid`, whose value is the kind of id to send.
+    pub fn parse(arg: &str) -> Result<TraceHeader, String> {
+        let (name, kind) = header::parse_header(arg)?;
+        if kind != "uuid" {
+            return Err(format!("Expected the trace header '{}' to be sent a 'uuid', got '{}'", name, kind));
+        }
+        Ok(TraceHeader {
+            name,
+            seed: Rng::from_time().next_u64(),
+            drawn: AtomicU64::new(0),
+        })
+    }
+
+    /// The next id, a random version 4 uuid.
+    fn next_id(&self) -> String {
+        let drawn = self.drawn.fetch_add(1, Ordering::Relaxed);
+        let mut id = String::with_capacity(36);
+        template::write_uuid(&mut id, &mut Rng::new(self.seed.wrapping_add(drawn)));
+        id
+    }
+}
+
+impl Middleware for TraceHeader {
+    fn on_request(&self, request: &mut Request) {
+        let id = self.next_id();
+        request.set_trace_header(&self.name, &id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sends_each_request_a_uuid_of_its_own() {
+        let trace = TraceHeader::parse("X-Request-Id=uuid").unwrap();
+        let headers = Vec::new();
+        let mut ids = Vec::new();
+        for _ in 0..2 {
+            let mut request = Request::new("http://a/", &headers, None);
+            trace.on_request(&mut request);
+            let (name, id) = request.trace_header().unwrap().clone();
+            assert_eq!(name, "x-request-id");
+            assert_eq!(request.headers(), &[(name, id.clone())][..]);
+            ids.push(id);
+        }
+        assert_ne!(ids[0], ids[1]);
+        let parts: Vec<usize> = ids[0].split('-').map(str::len).collect();
+        assert_eq!(parts, vec![8, 4, 4, 4, 12]);
+        assert_eq!(&ids[0][14..15], "4");
+        assert!("89ab".contains(&ids[0][19..20]));
+
+        assert!(TraceHeader::parse("x-request-id=counter").is_err());
+        assert!(TraceHeader::parse("uuid").is_err());
+    }
+}
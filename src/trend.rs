@@ -0,0 +1,87 @@
+use chart;
+use histogram::Histogram;
+use stats::TimeUnit;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// How many intervals the sparkline covers.
+const HISTORY: usize = 20;
+
+/// Tracks the p99 latency of each interval of a run so that the recent trend can be printed
+/// on the end of each progress line, which gives plain logs a rough view of how latency
+/// moved during the run.
+pub struct Trend {
+    interval: Histogram,
+    history: VecDeque<Duration>,
+    time_unit: TimeUnit,
+}
+
+impl Default for Trend {
+    fn default() -> Trend {
+        Trend::new()
+    }
+}
+
+impl Trend {
+    pub fn new() -> Trend {
+        Trend {
+            interval: Histogram::new(),
+            history: VecDeque::with_capacity(HISTORY),
+            time_unit: TimeUnit::Auto,
+        }
+    }
+
+    pub fn with_time_unit(mut self, unit: TimeUnit) -> Self {
+        self.time_unit = unit;
+        self
+    }
+
+    /// Records the latency of a request in the current interval.
+    pub fn record(&mut self, duration: Duration) {
+        self.interval.record(duration);
+    }
+
+    /// Closes the current interval and renders its p99 alongside a sparkline of the p99 of
+    /// the recent intervals.
+    pub fn tick(&mut self) -> String {
+        let p99 = self.interval.value_at_quantile(0.99);
+        self.interval = Histogram::new();
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(p99);
+        let history: Vec<f64> = self.history.iter().map(|d| d.as_secs_f64()).collect();
+        format!(
+            "p99 {} {}",
+            self.time_unit.format(p99),
+            chart::sparkline(&history)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_the_p99_of_each_interval() {
+        let mut trend = Trend::new();
+        for ms in &[1, 2, 4] {
+            for _ in 0..100 {
+                trend.record(Duration::from_millis(*ms));
+            }
+            trend.tick();
+        }
+        trend.record(Duration::from_millis(2));
+        assert_eq!(trend.tick(), "p99 2.00 ms ▁▃█▃");
+    }
+
+    #[test]
+    fn only_keeps_recent_intervals() {
+        let mut trend = Trend::new();
+        for _ in 0..HISTORY * 2 {
+            trend.tick();
+        }
+        assert_eq!(trend.history.len(), HISTORY);
+    }
+}
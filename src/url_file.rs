@@ -0,0 +1,34 @@
+use std::fs;
+
+/// Reads the urls to request from a file, one per line. Blank lines and lines starting with
+/// `#` are skipped, so a list can be annotated and parts of it commented out.
+pub fn read(path: &str) -> Result<Vec<String>, String> {
+    let contents =
+        fs::read_to_string(path).map_err(|e| format!("Could not read urls from '{}': {}", path, e))?;
+    let urls = parse(&contents);
+    if urls.is_empty() {
+        return Err(format!("There are no urls in '{}'", path));
+    }
+    Ok(urls)
+}
+
+fn parse(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_blank_lines_and_comments() {
+        let contents = "# products\nhttp://a/1\n\n  http://a/2  \n# http://a/3\n";
+        assert_eq!(parse(contents), vec!["http://a/1", "http://a/2"]);
+        assert!(read("/does/not/exist").is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use content_length::{ContentLength, SizeUnit};
+use report;
+use std::fmt;
+use std::fs;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// The clock ticks per second that /proc reports cpu time in. It's 100 on every common
+/// Linux build.
+const CLOCK_TICKS: u64 = 100;
+
+/// How often the sampler looks at the cpu time and memory of the process. Much shorter and
+/// the cpu time, which /proc counts in hundredths of a second, is too coarse to go by.
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The cpu time, user and system, that this process has used so far. Only Linux exposes it
+/// without extra dependencies, so it's nothing elsewhere.
+pub fn cpu_time() -> Option<Duration> {
+    fs::read_to_string("/proc/self/stat")
+        .ok()
+        .and_then(|stat| parse_cpu_time(&stat))
+}
+
+fn parse_cpu_time(stat: &str) -> Option<Duration> {
+    // The name in the second field can hold spaces, so count the fields after it.
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+    let utime = fields.get(11)?.parse::<u64>().ok()?;
+    let stime = fields.get(12)?.parse::<u64>().ok()?;
+    let ticks = utime + stime;
+    Some(Duration::from_millis(ticks * 1_000 / CLOCK_TICKS))
+}
+
+/// How much memory the process has resident, from /proc like its cpu time.
+pub fn resident_memory() -> Option<u64> {
+    fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| parse_resident_memory(&status))
+}
+
+fn parse_resident_memory(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb = line["VmRSS:".len()..].trim().trim_end_matches("kB").trim();
+    Some(kb.parse::<u64>().ok()? * 1_024)
+}
+
+/// Samples the cpu time and memory the process uses while a run goes on, on a thread of its
+/// own, so that the report can show whether the client kept up or was itself the bottleneck.
+pub struct Sampler {
+    stop: Sender<()>,
+    handle: JoinHandle<Option<Usage>>,
+}
+
+impl Sampler {
+    /// Starts sampling right away. The cpus are those of the host, to show the usage against.
+    pub fn start(cpus: usize) -> Sampler {
+        let (stop, stopped) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            let mut usage = Usage::new(cpus);
+            let (mut cpu, mut at) = (cpu_time()?, Instant::now());
+            let (started_cpu, started_at) = (cpu, at);
+            loop {
+                let done = !matches!(stopped.recv_timeout(SAMPLE_INTERVAL), Err(RecvTimeoutError::Timeout));
+                let (now_cpu, now) = (cpu_time()?, Instant::now());
+                usage.sample(now_cpu - cpu, now - at, resident_memory());
+                cpu = now_cpu;
+                at = now;
+                if done {
+                    usage.cpu = now_cpu - started_cpu;
+                    usage.wall = now - started_at;
+                    return Some(usage);
+                }
+            }
+        });
+        Sampler { stop, handle }
+    }
+
+    /// Stops sampling and returns what was used, or nothing where the platform doesn't
+    /// report it.
+    pub fn finish(self) -> Option<Usage> {
+        let _ = self.stop.send(());
+        self.handle.join().expect("The sampler to finish")
+    }
+}
+
+/// The cpu time and resident memory that the client used over a run. Cpu is shown as a
+/// percentage of a single cpu, so a client that kept four cpus busy used 400%.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Usage {
+    cpus: usize,
+    cpu: Duration,
+    wall: Duration,
+    /// The most cpus' worth that was used over any one interval.
+    busiest: f64,
+    memory_samples: u64,
+    memory_total: u64,
+    peak_memory: Option<u64>,
+    size_unit: SizeUnit,
+}
+
+impl Usage {
+    fn new(cpus: usize) -> Usage {
+        Usage {
+            cpus,
+            cpu: Duration::new(0, 0),
+            wall: Duration::new(0, 0),
+            busiest: 0.,
+            memory_samples: 0,
+            memory_total: 0,
+            peak_memory: None,
+            size_unit: SizeUnit::Binary,
+        }
+    }
+
+    /// Adds the cpu time used over an interval and the memory resident at its end.
+    fn sample(&mut self, cpu: Duration, wall: Duration, memory: Option<u64>) {
+        if wall > Duration::new(0, 0) {
+            self.busiest = self.busiest.max(cpu.as_secs_f64() / wall.as_secs_f64());
+        }
+        if let Some(memory) = memory {
+            self.memory_samples += 1;
+            self.memory_total += memory;
+            self.peak_memory = Some(self.peak_memory.map_or(memory, |peak| peak.max(memory)));
+        }
+    }
+
+    pub fn with_size_unit(mut self, size_unit: SizeUnit) -> Usage {
+        self.size_unit = size_unit;
+        self
+    }
+
+    /// The cpu time used over the run.
+    pub fn cpu(&self) -> Duration {
+        self.cpu
+    }
+
+    /// How many cpus' worth was used on average over the run.
+    fn average(&self) -> f64 {
+        if self.wall == Duration::new(0, 0) {
+            0.
+        } else {
+            self.cpu.as_secs_f64() / self.wall.as_secs_f64()
+        }
+    }
+
+    fn average_memory(&self) -> Option<u64> {
+        self.memory_total.checked_div(self.memory_samples)
+    }
+
+    /// The usage in the shape of the JSON report.
+    pub fn report(&self) -> report::ClientUsage {
+        report::ClientUsage {
+            cpus: self.cpus,
+            cpu_seconds: self.cpu.as_secs_f64(),
+            average_cpu_percent: self.average() * 100.,
+            busiest_cpu_percent: self.busiest * 100.,
+            average_memory_bytes: self.average_memory(),
+            peak_memory_bytes: self.peak_memory,
+        }
+    }
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Client resource usage (of {} cpus):", self.cpus)?;
+        writeln!(f, "  CPU time:  {:.2} s", self.cpu.as_secs_f64())?;
+        writeln!(
+            f,
+            "  CPU:       {:.0}% on average, {:.0}% at the busiest",
+            self.average() * 100.,
+            self.busiest * 100.
+        )?;
+        if let (Some(average), Some(peak)) = (self.average_memory(), self.peak_memory) {
+            let size = |bytes: u64| self.size_unit.format(&ContentLength::new(bytes));
+            writeln!(f, "  Memory:    {} on average, {} at the peak", size(average), size(peak))?;
+        }
+        Ok(())
+    }
+}
+
+/// The fraction of the cpus that the workers could have kept busy which the process used
+/// over the run. Each worker runs on one thread, so fewer workers than cpus can only use
+/// that many.
+pub fn cpu_utilization(cpu: Duration, wall: Duration, cpus: usize, workers: usize) -> f64 {
+    let usable = cpus.min(workers).max(1) as f64;
+    if wall == Duration::new(0, 0) {
+        0.
+    } else {
+        cpu.as_secs_f64() / (wall.as_secs_f64() * usable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_user_and_system_time() {
+        let stat = "1234 (rench (1)) S 1 1234 1234 0 -1 4194560 100 0 0 0 250 50 0 0 20 0 5 0";
+        assert_eq!(parse_cpu_time(stat), Some(Duration::from_secs(3)));
+        assert_eq!(parse_cpu_time("garbage"), None);
+    }
+
+    #[test]
+    fn reads_the_resident_memory() {
+        let status = "Name:\trench\nVmPeak:\t  20000 kB\nVmRSS:\t   1500 kB\nThreads:\t4\n";
+        assert_eq!(parse_resident_memory(status), Some(1_536_000));
+        assert_eq!(parse_resident_memory("Name:\trench\n"), None);
+    }
+
+    #[test]
+    fn keeps_the_busiest_interval_and_the_peak_memory() {
+        let mut usage = Usage::new(4);
+        let second = Duration::from_secs(1);
+        usage.sample(Duration::from_millis(500), second, Some(1_024));
+        usage.sample(Duration::from_millis(3_000), second, Some(3_072));
+        usage.sample(Duration::from_millis(500), second, None);
+        usage.cpu = Duration::from_secs(4);
+        usage.wall = Duration::from_secs(3);
+        let report = usage.report();
+        assert_eq!(report.busiest_cpu_percent, 300.);
+        assert_eq!(report.average_memory_bytes, Some(2_048));
+        assert_eq!(report.peak_memory_bytes, Some(3_072));
+        assert!(usage.to_string().contains("  CPU:       133% on average, 300% at the busiest\n"));
+        assert!(usage.to_string().contains("  Memory:    2.00 KB on average, 3.00 KB at the peak\n"));
+    }
+
+    #[test]
+    fn only_counts_the_cpus_the_workers_could_use() {
+        let second = Duration::from_secs(1);
+        assert_eq!(cpu_utilization(second, second, 8, 1), 1.);
+        assert_eq!(cpu_utilization(second, second, 8, 16), 0.125);
+        assert_eq!(cpu_utilization(second, Duration::new(0, 0), 8, 1), 0.);
+    }
+}
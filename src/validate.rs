@@ -0,0 +1,87 @@
+use hyper::Uri;
+use raw;
+use reqwest;
+use tcp;
+
+/// Checks that a flag is a whole number, for clap to report with the value at fault before
+/// anything is started.
+pub fn whole_number(value: String) -> Result<(), String> {
+    match value.parse::<u64>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err(format!("expected a whole number, got '{}'", value)),
+    }
+}
+
+/// Checks that the concurrency is a whole number or 'auto', for one request per cpu.
+pub fn concurrency(value: String) -> Result<(), String> {
+    match value.as_str() {
+        "auto" => Ok(()),
+        _ => whole_number(value),
+    }
+}
+
+/// Checks that a flag is a number that isn't negative, such as a rate or some seconds.
+pub fn number(value: String) -> Result<(), String> {
+    match value.parse::<f64>() {
+        Ok(number) if number.is_finite() && number >= 0. => Ok(()),
+        _ => Err(format!("expected a number, got '{}'", value)),
+    }
+}
+
+/// Checks that the engine can request the url, so that a typo is reported before the run
+/// rather than as every request of the run failing.
+pub fn url(engine: &str, url: &str) -> Result<(), String> {
+    match engine {
+        "raw" => raw::Target::parse(url).map(|_| ()),
+        "websocket" if !url.starts_with("ws://") => {
+            Err(format!("The websocket engine only supports ws urls, got '{}'", url))
+        }
+        "websocket" => Ok(()),
+        "tcp" => tcp::address(url).map(|_| ()),
+        _ => http_url(url),
+    }
+}
+
+/// Both the hyper and reqwest engines request the url, and each parses it its own way.
+fn http_url(url: &str) -> Result<(), String> {
+    let uri: Uri = url
+        .parse()
+        .map_err(|e| format!("Invalid url '{}': {}", url, e))?;
+    match uri.scheme() {
+        Some("http") | Some("https") => {}
+        _ => return Err(format!("Expected an http or https url, got '{}'", url)),
+    }
+    if uri.host().is_none_or(|host| host.is_empty()) {
+        return Err(format!("Expected a host in '{}'", url));
+    }
+    reqwest::Url::parse(url).map_err(|e| format!("Invalid url '{}': {}", url, e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_numbers() {
+        assert!(whole_number("10".to_string()).is_ok());
+        assert_eq!(whole_number("1e3".to_string()), Err("expected a whole number, got '1e3'".to_string()));
+        assert!(number("0.5".to_string()).is_ok());
+        assert!(number("-1".to_string()).is_err());
+        assert!(number("NaN".to_string()).is_err());
+        assert!(concurrency("auto".to_string()).is_ok());
+        assert!(concurrency("12".to_string()).is_ok());
+        assert!(concurrency("many".to_string()).is_err());
+    }
+
+    #[test]
+    fn checks_urls_for_the_engine() {
+        assert!(url("hyper", "https://localhost:8080/a?b=c").is_ok());
+        assert!(url("hyper", "localhost:8080").is_err());
+        assert!(url("reqwest", "http:///path").is_err());
+        assert!(url("reqwest", "ws://localhost/").is_err());
+        assert!(url("raw", "https://localhost/").is_err());
+        assert!(url("websocket", "ws://localhost/").is_ok());
+        assert!(url("tcp", "tcp://localhost").is_err());
+    }
+}
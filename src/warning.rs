@@ -0,0 +1,294 @@
+use std::fmt;
+
+/// Fewer requests than this make the percentiles and charts unreliable.
+const FEW_REQUESTS: u64 = 100;
+
+/// Past this fraction of its usable cpu the client is likely the bottleneck.
+const SATURATED: f64 = 0.95;
+
+/// A paced run that achieved less than this fraction of its target rate fell short of it.
+const RATE_ACHIEVED: f64 = 0.95;
+
+/// A caveat about a run that whoever reads the results should know about. Warnings are
+/// gathered from every part of the run and printed together with the results rather than
+/// mid-run where they scroll away.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    /// Workers stopped early so fewer requests completed than were planned.
+    Truncated { completed: u64, planned: u64 },
+    /// Worker threads panicked part way through their work.
+    WorkersStopped { stopped: usize, workers: usize },
+    /// Requests errored without receiving a response.
+    Errors { errors: u64, total: u64 },
+    /// Too few requests completed for the distribution to mean much.
+    FewRequests(u64),
+    /// Headers given more than once were folded into a single header by the engine.
+    FoldedHeaders(Vec<String>),
+    /// An option was given that the engine can't honor.
+    IgnoredOption { option: String, engine: String },
+    /// The client used nearly all of the cpu it could, so it may have limited the results.
+    ClientSaturated { utilization: f64 },
+    /// The run was stopped before it made all of its requests, and why.
+    Stopped(String),
+    /// Facts were dropped because the collector's queue was full, so they're missing from
+    /// the results.
+    DroppedFacts { dropped: u64, total: u64 },
+    /// The run was forced to use more threads than the cpus can keep busy.
+    Oversubscribed { threads: usize, cpus: usize },
+    /// A paced run fell short of its target rate, so the server saw less load than was
+    /// asked for.
+    RateNotAchieved { achieved_rps: f64, target_rps: f64 },
+    /// The responses came back over more than one http version, whose latencies differ.
+    MixedVersions(Vec<String>),
+    /// The engine can't tell when it opens a connection, so the connections opened and the
+    /// reuse ratio are left out of the results.
+    UntrackedConnections(String),
+    /// Threads couldn't be pinned to their cores, so the scheduler moving them around may
+    /// have added noise to the latencies.
+    Unpinned(Vec<String>),
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Warning::Truncated { completed, planned } => write!(
+                f,
+                "The run was truncated: {} of {} planned requests completed",
+                completed, planned
+            ),
+            Warning::WorkersStopped { stopped, workers } => write!(
+                f,
+                "{} of {} workers stopped early",
+                stopped, workers
+            ),
+            Warning::Errors { errors, total } => write!(
+                f,
+                "{} of {} requests errored without a response",
+                errors, total
+            ),
+            Warning::FewRequests(count) => write!(
+                f,
+                "Only {} requests completed, too few for reliable percentiles",
+                count
+            ),
+            Warning::FoldedHeaders(ref names) => write!(
+                f,
+                "The engine joined repeated headers into one: {}",
+                names.join(", ")
+            ),
+            Warning::IgnoredOption {
+                ref option,
+                ref engine,
+            } => write!(f, "{} has no effect with the {} engine", option, engine),
+            Warning::ClientSaturated { utilization } => write!(
+                f,
+                "THE CLIENT WAS SATURATED: it used {:.0}% of the cpu its workers could, so the results may measure rench rather than the server",
+                utilization * 100.
+            ),
+            Warning::Stopped(ref reason) => write!(f, "The run was stopped early: {}", reason),
+            Warning::DroppedFacts { dropped, total } => write!(
+                f,
+                "{} of {} requests were left out of the results because the collector fell behind",
+                dropped, total
+            ),
+            Warning::Oversubscribed { threads, cpus } => write!(
+                f,
+                "{} threads shared {} cpus, so the latency includes the time they waited to be scheduled",
+                threads, cpus
+            ),
+            Warning::RateNotAchieved { achieved_rps, target_rps } => write!(
+                f,
+                "The run achieved {:.2} of the {:.2} requests per second it targeted ({:.1}%), so the server saw less load than was asked for",
+                achieved_rps,
+                target_rps,
+                achieved_rps / target_rps * 100.
+            ),
+            Warning::MixedVersions(ref versions) => write!(
+                f,
+                "The responses came back over more than one http version: {}",
+                versions.join(", ")
+            ),
+            Warning::UntrackedConnections(ref engine) => write!(
+                f,
+                "The {} engine can't tell when it opens a connection, so the connections opened and the reuse ratio are left out",
+                engine
+            ),
+            Warning::Unpinned(ref errors) => write!(
+                f,
+                "{} of the threads ran unpinned: {}",
+                errors.len(),
+                errors.join("; ")
+            ),
+        }
+    }
+}
+
+/// The warnings raised during a run.
+#[derive(Debug, Clone, Default)]
+pub struct Warnings {
+    warnings: Vec<Warning>,
+}
+
+impl Warnings {
+    pub fn new() -> Warnings {
+        Warnings::default()
+    }
+
+    pub fn push(&mut self, warning: Warning) {
+        self.warnings.push(warning);
+    }
+
+    /// Raises the warnings that follow from how many requests completed.
+    pub fn check_counts(&mut self, completed: u64, planned: u64, errors: u64) {
+        if completed < planned {
+            self.push(Warning::Truncated { completed, planned });
+        }
+        if errors > 0 {
+            self.push(Warning::Errors {
+                errors,
+                total: completed,
+            });
+        }
+        if completed < FEW_REQUESTS {
+            self.push(Warning::FewRequests(completed));
+        }
+    }
+
+    /// Raises a warning if the client used nearly all of the cpu it could.
+    pub fn check_saturation(&mut self, utilization: f64) {
+        if utilization > SATURATED {
+            self.push(Warning::ClientSaturated { utilization });
+        }
+    }
+
+    /// Raises a warning if a paced run fell short of its target rate.
+    pub fn check_rate(&mut self, achieved_rps: f64, target_rps: f64) {
+        if achieved_rps < target_rps * RATE_ACHIEVED {
+            self.push(Warning::RateNotAchieved { achieved_rps, target_rps });
+        }
+    }
+
+    /// Raises a warning if the responses came back over more than one http version.
+    pub fn check_versions(&mut self, versions: &[&str]) {
+        if versions.len() > 1 {
+            self.push(Warning::MixedVersions(versions.iter().map(|version| version.to_string()).collect()));
+        }
+    }
+
+    /// Whether the client was saturated during the run.
+    pub fn client_saturated(&self) -> bool {
+        self.iter()
+            .any(|warning| matches!(*warning, Warning::ClientSaturated { .. }))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.warnings.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Warning> {
+        self.warnings.iter()
+    }
+}
+
+impl fmt::Display for Warnings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return Ok(());
+        }
+        writeln!(f, "Warnings")?;
+        for warning in self.iter() {
+            writeln!(f, "  ! {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warns_about_truncated_and_errored_runs() {
+        let mut warnings = Warnings::new();
+        warnings.check_counts(900, 1000, 3);
+        assert_eq!(
+            warnings.iter().cloned().collect::<Vec<_>>(),
+            vec![
+                Warning::Truncated {
+                    completed: 900,
+                    planned: 1000
+                },
+                Warning::Errors {
+                    errors: 3,
+                    total: 900
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn warns_about_small_runs() {
+        let mut warnings = Warnings::new();
+        warnings.check_counts(10, 10, 0);
+        assert_eq!(warnings.iter().next(), Some(&Warning::FewRequests(10)));
+    }
+
+    #[test]
+    fn warns_about_a_saturated_client() {
+        let mut warnings = Warnings::new();
+        warnings.check_saturation(0.5);
+        assert!(!warnings.client_saturated());
+        warnings.check_saturation(0.98);
+        assert!(warnings.client_saturated());
+        assert!(warnings.to_string().contains("used 98% of the cpu"));
+    }
+
+    #[test]
+    fn warns_about_a_missed_rate() {
+        let mut warnings = Warnings::new();
+        warnings.check_rate(98., 100.);
+        assert!(warnings.is_empty());
+        warnings.check_rate(50., 100.);
+        assert_eq!(
+            warnings.iter().next(),
+            Some(&Warning::RateNotAchieved {
+                achieved_rps: 50.,
+                target_rps: 100.
+            })
+        );
+        assert!(warnings.to_string().contains("achieved 50.00 of the 100.00 requests per second"));
+    }
+
+    #[test]
+    fn warns_about_mixed_versions() {
+        let mut warnings = Warnings::new();
+        warnings.check_versions(&["HTTP/1.1"]);
+        assert!(warnings.is_empty());
+        warnings.check_versions(&["HTTP/1.1", "HTTP/2"]);
+        assert!(warnings.to_string().contains("more than one http version: HTTP/1.1, HTTP/2"));
+    }
+
+    #[test]
+    fn names_the_engine_that_cant_track_connections() {
+        let mut warnings = Warnings::new();
+        warnings.push(Warning::UntrackedConnections("reqwest".to_string()));
+        assert!(warnings.to_string().contains("The reqwest engine can't tell when it opens a connection"));
+    }
+
+    #[test]
+    fn says_why_threads_ran_unpinned() {
+        let mut warnings = Warnings::new();
+        warnings.push(Warning::Unpinned(vec![
+            "Could not pin a thread to core 3: Invalid argument".to_string(),
+        ]));
+        assert!(warnings.to_string().contains("1 of the threads ran unpinned: Could not pin a thread to core 3"));
+    }
+
+    #[test]
+    fn prints_nothing_without_warnings() {
+        let mut warnings = Warnings::new();
+        warnings.check_counts(1000, 1000, 0);
+        assert!(warnings.is_empty());
+        assert_eq!(warnings.to_string(), "");
+    }
+}
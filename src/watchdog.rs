@@ -0,0 +1,153 @@
+use clock::{self, Clock};
+use stats::Fact;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use {parse_duration, parse_ratio};
+
+/// How much of the run each bucket of the window covers.
+const BUCKET_MS: u64 = 100;
+
+/// Watches the facts as they arrive for a run that has gone wrong, such as one where every
+/// request is refused, so that it can be stopped rather than hammer the server for the rest
+/// of its requests. A rule is broken once the failure rate of the requests that finished
+/// within its window has stayed above its threshold for the whole of the window.
+pub struct Watchdog {
+    threshold: f64,
+    window: Duration,
+    clock: Arc<dyn Clock>,
+    start: Instant,
+    /// The requests and failures of each bucket of the window, oldest first, along with
+    /// the bucket's number into the run.
+    buckets: VecDeque<(u64, u64, u64)>,
+    rule: String,
+}
+
+impl Watchdog {
+    /// Parses a rule such as 'error-rate>50% for 10s'. The error rate counts every request
+    /// that wasn't a success, as the summary's failures do.
+    pub fn parse(rule: &str) -> Result<Watchdog, String> {
+        let invalid = || format!("Expected a rule like 'error-rate>50% for 10s', got '{}'", rule);
+        let (condition, window) = match rule.find(" for ") {
+            Some(at) => (&rule[..at], &rule[at + " for ".len()..]),
+            None => return Err(invalid()),
+        };
+        let mut parts = condition.splitn(2, '>');
+        let metric = parts.next().unwrap_or("").trim();
+        if metric != "error-rate" {
+            return Err(format!("Unknown metric '{}' in '{}', expected error-rate", metric, rule));
+        }
+        let threshold = parts.next().and_then(parse_ratio).ok_or_else(invalid)?;
+        let window = parse_duration(window.trim())?;
+        if window == Duration::new(0, 0) {
+            return Err(format!("Expected a window above zero in '{}'", rule));
+        }
+        Ok(Watchdog {
+            threshold,
+            window,
+            clock: clock::system(),
+            start: Instant::now(),
+            buckets: VecDeque::new(),
+            rule: rule.trim().to_string(),
+        })
+    }
+
+    /// Times the windows by the clock instead, from now by it.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.start = clock.now();
+        self.clock = clock;
+        self
+    }
+
+    /// Counts a request that has just finished, returning why the run should stop if the
+    /// rule is broken.
+    pub fn record(&mut self, fact: &Fact) -> Option<String> {
+        let elapsed = self.clock.elapsed(self.start);
+        self.record_at(elapsed, fact.is_success())
+    }
+
+    fn record_at(&mut self, elapsed: Duration, success: bool) -> Option<String> {
+        let bucket = (elapsed.as_millis() / u128::from(BUCKET_MS)) as u64;
+        match self.buckets.back_mut() {
+            Some(&mut (number, ref mut requests, ref mut failures)) if number == bucket => {
+                *requests += 1;
+                *failures += u64::from(!success);
+            }
+            _ => self.buckets.push_back((bucket, 1, u64::from(!success))),
+        }
+        let buckets = (self.window.as_millis() / u128::from(BUCKET_MS)).max(1) as u64;
+        while self.buckets.front().is_some_and(|&(number, _, _)| number + buckets <= bucket) {
+            self.buckets.pop_front();
+        }
+        // The run has to have lasted the whole window before the rule can be broken.
+        if elapsed < self.window {
+            return None;
+        }
+        let (requests, failures) = self
+            .buckets
+            .iter()
+            .fold((0, 0), |(requests, failures), &(_, r, f)| (requests + r, failures + f));
+        let rate = failures as f64 / requests as f64;
+        if rate > self.threshold {
+            Some(format!("{} ({:.1}% of requests failed)", self.rule, rate * 100.))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clock::Manual;
+    use content_length::ContentLength;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn parses_a_rule() {
+        let watchdog = Watchdog::parse("error-rate>50% for 10s").unwrap();
+        assert_eq!((watchdog.threshold, watchdog.window), (0.5, Duration::from_secs(10)));
+        assert!(Watchdog::parse("error-rate>0.1 for 500ms").is_ok());
+        assert!(Watchdog::parse("error-rate>50%").is_err());
+        assert!(Watchdog::parse("p99>50% for 10s").is_err());
+        assert!(Watchdog::parse("error-rate>150% for 10s").is_err());
+    }
+
+    #[test]
+    fn stops_once_the_rate_has_stayed_above_the_threshold_for_the_window() {
+        let mut watchdog = Watchdog::parse("error-rate>50% for 1s").unwrap();
+        // Failing from the start, but the run hasn't lasted the window yet.
+        assert_eq!(watchdog.record_at(ms(0), false), None);
+        assert_eq!(watchdog.record_at(ms(900), false), None);
+        assert_eq!(
+            watchdog.record_at(ms(1_000), false),
+            Some("error-rate>50% for 1s (100.0% of requests failed)".to_string())
+        );
+
+        // Only the requests within the window count.
+        let mut watchdog = Watchdog::parse("error-rate>50% for 1s").unwrap();
+        for _ in 0..10 {
+            watchdog.record_at(ms(0), false);
+        }
+        assert_eq!(watchdog.record_at(ms(1_500), true), None);
+        assert!(watchdog.record_at(ms(1_600), false).is_none());
+        assert!(watchdog.record_at(ms(1_700), false).is_some());
+    }
+
+    #[test]
+    fn times_the_window_by_its_clock() {
+        let clock = Manual::new();
+        let mut watchdog = Watchdog::parse("error-rate>50% for 1s")
+            .unwrap()
+            .with_clock(Arc::new(clock.clone()));
+        let failed = Fact::record(ContentLength::zero(), 500, ms(1));
+        assert_eq!(watchdog.record(&failed), None);
+        clock.advance(ms(999));
+        assert_eq!(watchdog.record(&failed), None);
+        clock.advance(ms(1));
+        assert!(watchdog.record(&failed).is_some());
+    }
+}
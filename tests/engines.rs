@@ -0,0 +1,195 @@
+extern crate serde_json;
+
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::process::{self, Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The engines that make http requests, which are all expected to see the same facts.
+const ENGINES: &[&str] = &["reqwest", "hyper", "raw"];
+
+/// The fields of the results that every engine is expected to report the same.
+const RESULTS: &[&str] = &[
+    "count",
+    "errors",
+    "failures",
+    "content_length_bytes",
+    "content_length_bytes_by_class",
+    "status_codes",
+    "redirects",
+];
+
+/// Numbers the reports of the runs, which the tests make in parallel, so that each has a
+/// file of its own.
+static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+/// The test server of the rench under test, running for as long as it's kept.
+struct Server {
+    child: Child,
+    url: String,
+}
+
+impl Server {
+    fn start() -> Server {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_rench"))
+            .arg("selftest-server")
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Starting the test server");
+        let mut line = String::new();
+        BufReader::new(child.stdout.as_mut().expect("Stdout is piped"))
+            .read_line(&mut line)
+            .expect("Reading the address of the test server");
+        let url = line.trim().trim_start_matches("Listening on ").to_string();
+        Server { child, url }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.url, path)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// A run with one of the engines: the status and size of each of its facts in order, and
+/// its JSON report.
+struct Run {
+    engine: &'static str,
+    facts: Vec<(String, u64)>,
+    report: Value,
+}
+
+fn run(engine: &'static str, args: &[&str]) -> Run {
+    let run = RUNS.fetch_add(1, Ordering::Relaxed);
+    let path = env::temp_dir().join(format!("rench-engines-{}-{}-{}.json", process::id(), engine, run));
+    let output = Command::new(env!("CARGO_BIN_EXE_rench"))
+        .args(["--engine", engine, "--quiet", "--output", "csv", "--output"])
+        .arg(format!("json={}", path.display()))
+        .args(args)
+        .output()
+        .expect("Running rench");
+    assert!(
+        output.status.success(),
+        "The {} engine failed: {}",
+        engine,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let mut facts: Vec<(String, u64)> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .skip(1)
+        .map(|line| {
+            let fields: Vec<&str> = line.split(',').collect();
+            (fields[0].to_string(), fields[2].parse().expect("A size in bytes"))
+        })
+        .collect();
+    facts.sort();
+    let report = serde_json::from_str(&fs::read_to_string(&path).expect("Reading the report")).expect("A report");
+    let _ = fs::remove_file(&path);
+    Run { engine, facts, report }
+}
+
+/// Runs every engine with the same flags and checks that they saw the same facts and
+/// reported the same results.
+fn run_every_engine(args: &[&str]) -> Vec<Run> {
+    let runs: Vec<Run> = ENGINES.iter().map(|engine| run(engine, args)).collect();
+    let first = &runs[0];
+    for run in &runs[1..] {
+        assert_eq!(run.facts, first.facts, "The {} and {} engines saw different facts", first.engine, run.engine);
+        for field in RESULTS {
+            assert_eq!(
+                run.report["results"][field], first.report["results"][field],
+                "The {} and {} engines reported different {}",
+                first.engine, run.engine, field
+            );
+        }
+    }
+    runs
+}
+
+fn facts(count: usize, status: &str, bytes: u64) -> Vec<(String, u64)> {
+    vec![(status.to_string(), bytes); count]
+}
+
+#[test]
+fn engines_see_the_same_responses() {
+    let server = Server::start();
+    let urls = [server.url("/bytes/10"), server.url("/bytes/2000"), server.url("/status/404")];
+    let runs = run_every_engine(&["-n", "30", "-c", "3", &urls[0], &urls[1], &urls[2]]);
+    // Each of the three workers goes round the urls from the first, ten requests apiece.
+    let expected = [facts(12, "200", 10), facts(9, "200", 2000), facts(9, "404", 0)].concat();
+    assert_eq!(runs[0].facts, expected);
+    assert_eq!(runs[0].report["results"]["failures"], 9);
+}
+
+#[test]
+fn engines_fill_in_extracted_values() {
+    let server = Server::start();
+    let (echo, bytes) = (server.url("/echo"), server.url("/bytes/{{id}}"));
+    // The one worker takes the id from the echoed body and asks for that many bytes next.
+    let args = ["-n", "4", "-c", "1", "--body", "{\"id\":12}", "--extract", "id=json:/id", &echo, &bytes];
+    let runs = run_every_engine(&args);
+    let expected = [facts(2, "200", 9), facts(2, "200", 12)].concat();
+    assert_eq!(runs[0].facts, expected);
+
+    let args = ["-n", "4", "-c", "1", "--body", "\"id\":3", "--extract", "id=regex:\"id\":(\\d+)", &echo, &bytes];
+    let runs = run_every_engine(&args);
+    let expected = [facts(2, "200", 3), facts(2, "200", 6)].concat();
+    assert_eq!(runs[0].facts, expected);
+}
+
+#[test]
+fn engines_send_the_same_headers() {
+    let server = Server::start();
+    let runs = run_every_engine(&["-n", "5", "--header", "x-key:val", &server.url("/headers")]);
+    // The test server echoes the `x-` headers it was sent, one a line.
+    assert_eq!(runs[0].facts, facts(5, "200", "x-key: val\n".len() as u64));
+
+    // The test server answers with each host header it was sent, so a second would show.
+    let runs = run_every_engine(&["-n", "5", "--header", "host:tenant.example.com", &server.url("/host")]);
+    assert_eq!(runs[0].facts, facts(5, "200", "tenant.example.com\n".len() as u64));
+}
+
+#[test]
+fn engines_post_the_same_bodies() {
+    let server = Server::start();
+    let runs = run_every_engine(&["-n", "12", "-c", "3", "--unique-bodies", "--body", "body {{n}}", &server.url("/echo")]);
+    // The requests are numbered from zero across the workers, so the last two have two digits.
+    let expected = [facts(10, "200", "body 0".len() as u64), facts(2, "200", "body 10".len() as u64)].concat();
+    assert_eq!(runs[0].facts, expected);
+}
+
+#[test]
+fn engines_follow_the_same_redirects() {
+    let server = Server::start();
+    let runs = run_every_engine(&["-n", "4", "--follow-redirects=3", &server.url("/redirect/2")]);
+    assert_eq!(runs[0].facts, facts(4, "200", 2));
+    assert_eq!(runs[0].report["results"]["redirects"]["hops"], 8);
+
+    let runs = run_every_engine(&["-n", "4", &server.url("/redirect/2")]);
+    assert_eq!(runs[0].facts, facts(4, "302", 0));
+}
+
+#[test]
+fn engines_keep_credentials_to_their_origin() {
+    let (server, elsewhere) = (Server::start(), Server::start());
+    let headers = ["--header", "x-key:val", "--header", "authorization:Bearer secret", "--header", "cookie:a=b"];
+    let follow = |to: String| {
+        let url = server.url(&format!("/redirect-to/{}", to));
+        run_every_engine(&[&["-n", "2", "--follow-redirects=1"], &headers[..], &[url.as_str()]].concat())
+    };
+    // The test server echoes the credentials it was sent too.
+    let runs = follow(server.url("/headers"));
+    let sent = "authorization: Bearer secret\ncookie: a=b\nx-key: val\n";
+    assert_eq!(runs[0].facts, facts(2, "200", sent.len() as u64));
+
+    // The other server listens on another port, which is another origin.
+    let runs = follow(elsewhere.url("/headers"));
+    assert_eq!(runs[0].facts, facts(2, "200", "x-key: val\n".len() as u64));
+}